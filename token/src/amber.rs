@@ -1,17 +1,98 @@
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
 
-use cosmwasm_std::{CanonicalAddr, Env, StdError, StdResult, Storage};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{
+    to_binary, Addr, Api, Binary, BlockInfo, CanonicalAddr, Env, StdError, StdResult, Storage,
+};
+use secret_toolkit::crypto::secp256k1::PrivateKey;
 use secret_toolkit::crypto::{sha_256, ContractPrng};
-use secret_toolkit::storage::{Keymap, Keyset};
+use secret_toolkit::storage::{Item, Keymap, Keyset};
+
+use crate::hooks::{MembershipChange, MembershipHooksStore};
+use crate::notifications::NotificationsStore;
+use crate::state::{BalancesStore, CheckpointsStore};
+
+/// The original membership threshold of 1 AMBER, used until the admin configures tiers.
+pub const DEFAULT_TIER_THRESHOLD: u128 = 1_000_000;
+/// Kept small, because each tier boundary tells whoever sees a member's tier more about its balance.
+pub const MAX_TIERS: usize = 5;
 
-use crate::state::BalancesStore;
+const MEMBERS_NAMESPACE: &[u8] = b"members";
+const INVITE_CODES_NAMESPACE: &[u8] = b"invite_codes";
 
-/// A set of accounts with 1+ AMBER.
-pub static OAC_MEMBERS: Keyset<CanonicalAddr> = Keyset::new(b"members");
-/// A set of valid codes used to access something.
-pub static OAC_INVITE_CODES: Keyset<[u8; 32]> = Keyset::new(b"invite_codes");
-/// A map of accounts with 1+ AMBER to a unique code used to access something.
+/// A set of accounts in tier 0 (1+ AMBER by default).
+pub static OAC_MEMBERS: Keyset<CanonicalAddr> = Keyset::new(MEMBERS_NAMESPACE);
+/// A set of valid tier 0 codes used to access something.
+pub static OAC_INVITE_CODES: Keyset<[u8; 32]> = Keyset::new(INVITE_CODES_NAMESPACE);
+/// A map of members to a unique code used to access something.
 pub static OAC_MEMBER_CODES: Keymap<CanonicalAddr, [u8; 32]> = Keymap::new(b"member_codes");
+/// A map of members to their tier. Members enrolled before tiers existed are in tier 0.
+pub static OAC_MEMBER_TIERS: Keymap<CanonicalAddr, u8> = Keymap::new(b"member_tiers");
+/// The minimum balance for each tier, in ascending order.
+pub static OAC_TIER_THRESHOLDS: Item<Vec<u128>> = Item::new(b"tier_thresholds");
+/// The secp256k1 private key used to sign membership attestations.
+pub static OAC_ATTESTATION_KEY: Item<[u8; 32]> = Item::new(b"attestation_key");
+
+/// Additional invite codes minted by members, keyed by code.
+pub static OAC_EXTRA_CODES: Keymap<[u8; 32], InviteCode> = Keymap::new(b"extra_codes");
+/// The set of additional invite codes minted by a member. Suffixed by the member's address.
+pub static OAC_OWNED_CODES: Keyset<[u8; 32]> = Keyset::new(b"owned_codes");
+/// Incremented for every additional invite code, so codes minted in the same block differ.
+pub static OAC_CODE_NONCE: Item<u64> = Item::new(b"code_nonce");
+/// Contracts or addresses allowed to consume additional invite codes.
+pub static OAC_CODE_CONSUMERS: Keyset<Addr> = Keyset::new(b"code_consumers");
+
+/// How long a membership attestation remains valid, in seconds.
+pub const ATTESTATION_TTL: u64 = 60 * 60;
+/// The maximum number of outstanding additional invite codes per member.
+pub const MAX_CODES_PER_MEMBER: u32 = 20;
+
+/// An additional invite code, valid for a limited number of uses.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct InviteCode {
+    pub owner: CanonicalAddr,
+    pub remaining_uses: u32,
+    pub expiration_height: Option<u64>,
+    pub expiration_time: Option<u64>,
+}
+
+impl InviteCode {
+    pub fn is_expired_at(&self, block: &BlockInfo) -> bool {
+        let height_expired = self
+            .expiration_height
+            .map_or(false, |height| block.height >= height);
+        let time_expired = self
+            .expiration_time
+            .map_or(false, |time| block.time.seconds() >= time);
+
+        height_expired || time_expired
+    }
+}
+
+/// Decode a base64 code, returning `None` if it is not a well-formed code.
+fn decode_code(encoded_code: &str) -> Option<[u8; 32]> {
+    let decoded_bytes = URL_SAFE_NO_PAD.decode(encoded_code).ok()?;
+    decoded_bytes.try_into().ok()
+}
+
+/// The set of members in a tier. Tier 0 keeps the original namespace so existing members
+/// carry over.
+fn tier_members(tier: u8) -> Keyset<'static, CanonicalAddr> {
+    match tier {
+        0 => Keyset::new(MEMBERS_NAMESPACE),
+        _ => OAC_MEMBERS.add_suffix(&[tier]),
+    }
+}
+
+/// The set of valid codes for a tier.
+fn tier_invite_codes(tier: u8) -> Keyset<'static, [u8; 32]> {
+    match tier {
+        0 => Keyset::new(INVITE_CODES_NAMESPACE),
+        _ => OAC_INVITE_CODES.add_suffix(&[tier]),
+    }
+}
 
 #[allow(unused)]
 pub mod special {
@@ -43,6 +124,48 @@ impl OneAmberStore {
     //     OAC_MEMBERS.get_len(storage).unwrap_or_default()
     // }
 
+    pub fn load_tiers(storage: &dyn Storage) -> Vec<u128> {
+        OAC_TIER_THRESHOLDS
+            .may_load(storage)
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| vec![DEFAULT_TIER_THRESHOLD])
+    }
+
+    /// Replace the tier thresholds. Existing members are moved to their new tier on their next
+    /// balance change, or when re-evaluated by the admin.
+    pub fn save_tiers(storage: &mut dyn Storage, thresholds: Vec<u128>) -> StdResult<()> {
+        if thresholds.is_empty() || thresholds.len() > MAX_TIERS {
+            return Err(StdError::generic_err(format!(
+                "Number of tiers must be between 1 and {MAX_TIERS}"
+            )));
+        }
+        if thresholds[0] == 0 || thresholds.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(StdError::generic_err(
+                "Tier thresholds must be non-zero and strictly ascending",
+            ));
+        }
+
+        OAC_TIER_THRESHOLDS.save(storage, &thresholds)
+    }
+
+    /// Returns the highest tier a balance qualifies for.
+    pub fn tier_for_balance(storage: &dyn Storage, balance: u128) -> Option<u8> {
+        Self::load_tiers(storage)
+            .iter()
+            .rposition(|threshold| balance >= *threshold)
+            .map(|tier| tier as u8)
+    }
+
+    /// Returns the tier the account is currently enrolled in, if any.
+    pub fn get_tier(storage: &dyn Storage, account: &CanonicalAddr) -> Option<u8> {
+        match OAC_MEMBER_TIERS.get(storage, account) {
+            Some(tier) => Some(tier),
+            None if OAC_MEMBERS.contains(storage, account) => Some(0),
+            None => None,
+        }
+    }
+
     pub fn get_code(storage: &dyn Storage, account: &CanonicalAddr) -> String {
         OAC_MEMBER_CODES
             .get(storage, account)
@@ -50,87 +173,314 @@ impl OneAmberStore {
             .unwrap_or_default()
     }
 
-    /// Given a list of codes, return only the ones that are valid.
-    pub fn validate_codes(storage: &dyn Storage, codes: Vec<String>) -> Vec<String> {
+    /// Given a list of codes, return only the ones that are valid, along with their tier.
+    /// Additional invite codes are valid while unexpired and their owner is still a member.
+    ///
+    /// Every tier is checked, not only the configured ones: after the tiers are reduced, members
+    /// of a removed tier keep their code until they are moved on re-evaluation.
+    pub fn validate_codes(
+        storage: &dyn Storage,
+        block: &BlockInfo,
+        codes: Vec<String>,
+    ) -> Vec<(String, u8)> {
         codes
             .into_iter()
             .filter_map(|encoded_code| {
-                let decoded_bytes = match URL_SAFE_NO_PAD.decode(encoded_code).ok() {
-                    Some(bytes) => bytes,
-                    None => return None, // Skip if decoding fails.
-                };
-
-                let code_bytes: [u8; 32] = match decoded_bytes.try_into().ok() {
-                    Some(array) => array,
-                    None => return None, // Skip if conversion fails.
-                };
-
-                match OAC_INVITE_CODES.contains(storage, &code_bytes) {
-                    true => Some(URL_SAFE_NO_PAD.encode(code_bytes)),
-                    false => None,
-                }
+                // Skip if decoding fails.
+                let code_bytes = decode_code(&encoded_code)?;
+
+                (0..MAX_TIERS as u8)
+                    .find(|tier| tier_invite_codes(*tier).contains(storage, &code_bytes))
+                    .or_else(|| Self::extra_code_tier(storage, block, &code_bytes))
+                    .map(|tier| (URL_SAFE_NO_PAD.encode(code_bytes), tier))
             })
-            .collect::<Vec<String>>()
+            .collect::<Vec<(String, u8)>>()
     }
 
-    pub fn update_member(
-        store: &mut dyn Storage,
+    /// Returns the owner's tier if the additional invite code can still be used.
+    fn extra_code_tier(storage: &dyn Storage, block: &BlockInfo, code: &[u8; 32]) -> Option<u8> {
+        let invite = OAC_EXTRA_CODES.get(storage, code)?;
+        if invite.is_expired_at(block) {
+            return None;
+        }
+
+        Self::get_tier(storage, &invite.owner)
+    }
+
+    pub fn add_consumers(storage: &mut dyn Storage, consumers: Vec<Addr>) -> StdResult<()> {
+        for consumer in consumers {
+            OAC_CODE_CONSUMERS.insert(storage, &consumer)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn remove_consumers(storage: &mut dyn Storage, consumers: Vec<Addr>) -> StdResult<()> {
+        for consumer in consumers {
+            if OAC_CODE_CONSUMERS.contains(storage, &consumer) {
+                OAC_CODE_CONSUMERS.remove(storage, &consumer)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn is_consumer(storage: &dyn Storage, account: &Addr) -> bool {
+        OAC_CODE_CONSUMERS.contains(storage, account)
+    }
+
+    pub fn load_consumers(storage: &dyn Storage) -> StdResult<Vec<Addr>> {
+        OAC_CODE_CONSUMERS.iter(storage)?.collect()
+    }
+
+    /// Mint an additional invite code for a member.
+    pub fn create_invite_code(
+        storage: &mut dyn Storage,
         account: &CanonicalAddr,
-        previous_balance: u128,
-        balance: u128,
+        max_uses: u32,
+        expiration_height: Option<u64>,
+        expiration_time: Option<u64>,
         env: &Env,
-    ) -> StdResult<()> {
-        match (previous_balance >= 1_000_000, balance >= 1_000_000) {
-            (false, false) | (true, false) => {
-                Self::remove_member(store, account)?;
+    ) -> StdResult<String> {
+        if Self::get_tier(storage, account).is_none() {
+            return Err(StdError::generic_err(
+                "Only OAC members can create invite codes",
+            ));
+        }
+        if max_uses == 0 {
+            return Err(StdError::generic_err("max_uses must be at least 1"));
+        }
+
+        let invite = InviteCode {
+            owner: account.clone(),
+            remaining_uses: max_uses,
+            expiration_height,
+            expiration_time,
+        };
+        if invite.is_expired_at(&env.block) {
+            return Err(StdError::generic_err(
+                "Invite code would already be expired",
+            ));
+        }
+
+        Self::prune_invite_codes(storage, account, &env.block)?;
+        let owned_codes = OAC_OWNED_CODES.add_suffix(account.as_slice());
+        if owned_codes.get_len(storage)? >= MAX_CODES_PER_MEMBER {
+            return Err(StdError::generic_err(format!(
+                "Cannot have more than {MAX_CODES_PER_MEMBER} outstanding invite codes",
+            )));
+        }
+
+        let nonce = OAC_CODE_NONCE.may_load(storage)?.unwrap_or_default() + 1;
+        OAC_CODE_NONCE.save(storage, &nonce)?;
+
+        let entropy = [account.as_slice(), &nonce.to_be_bytes()].concat();
+        let (code_string, code_bytes) = Self::generate_code(env, &entropy);
+        OAC_EXTRA_CODES.insert(storage, &code_bytes, &invite)?;
+        owned_codes.insert(storage, &code_bytes)?;
+
+        Ok(code_string)
+    }
+
+    /// Use up one use of an additional invite code. Returns the owner's tier and the uses left.
+    pub fn consume_invite_code(
+        storage: &mut dyn Storage,
+        block: &BlockInfo,
+        code: &str,
+    ) -> StdResult<(u8, u32)> {
+        let invalid = || StdError::generic_err("Invalid invite code");
+
+        let code_bytes = decode_code(code).ok_or_else(invalid)?;
+        let mut invite = OAC_EXTRA_CODES
+            .get(storage, &code_bytes)
+            .ok_or_else(invalid)?;
+        let tier = Self::extra_code_tier(storage, block, &code_bytes).ok_or_else(invalid)?;
+
+        invite.remaining_uses -= 1;
+        if invite.remaining_uses == 0 {
+            Self::remove_invite_code(storage, &invite.owner, &code_bytes)?;
+        } else {
+            OAC_EXTRA_CODES.insert(storage, &code_bytes, &invite)?;
+        }
+
+        Ok((tier, invite.remaining_uses))
+    }
+
+    /// Returns a member's outstanding additional invite codes.
+    pub fn invite_codes(
+        storage: &dyn Storage,
+        account: &CanonicalAddr,
+        block: &BlockInfo,
+    ) -> StdResult<Vec<(String, InviteCode)>> {
+        let owned_codes = OAC_OWNED_CODES.add_suffix(account.as_slice());
+
+        let mut codes = vec![];
+        for code in owned_codes.iter(storage)? {
+            let code = code?;
+            if let Some(invite) = OAC_EXTRA_CODES.get(storage, &code) {
+                if !invite.is_expired_at(block) {
+                    codes.push((URL_SAFE_NO_PAD.encode(code), invite));
+                }
             }
-            (true, true) | (false, true) => {
-                Self::add_member(store, account, env)?;
+        }
+
+        Ok(codes)
+    }
+
+    /// Remove a member's expired additional invite codes.
+    fn prune_invite_codes(
+        storage: &mut dyn Storage,
+        account: &CanonicalAddr,
+        block: &BlockInfo,
+    ) -> StdResult<()> {
+        let owned_codes = OAC_OWNED_CODES.add_suffix(account.as_slice());
+
+        let mut expired = vec![];
+        for code in owned_codes.iter(storage)? {
+            let code = code?;
+            let is_expired = OAC_EXTRA_CODES
+                .get(storage, &code)
+                .map_or(true, |invite| invite.is_expired_at(block));
+            if is_expired {
+                expired.push(code);
             }
         }
 
+        for code in expired {
+            Self::remove_invite_code(storage, account, &code)?;
+        }
+
         Ok(())
     }
 
-    /// Add account to the set of `OAC_MEMBERS` and generate a new code for them.
-    /// The code is stored in separate set for ease of checking.
-    /// The code is also stored in a map so it can be retrieved when removing the account.
-    fn add_member(storage: &mut dyn Storage, account: &CanonicalAddr, env: &Env) -> StdResult<()> {
-        if OAC_MEMBERS.contains(storage, account) {
-            return Ok(());
+    fn remove_invite_code(
+        storage: &mut dyn Storage,
+        account: &CanonicalAddr,
+        code: &[u8; 32],
+    ) -> StdResult<()> {
+        if OAC_EXTRA_CODES.contains(storage, code) {
+            OAC_EXTRA_CODES.remove(storage, code)?;
         }
 
-        OAC_MEMBERS.insert(storage, account)?;
-
-        let (_, code) = Self::generate_code(env, account);
-        OAC_INVITE_CODES.insert(storage, &code)?;
-        OAC_MEMBER_CODES.insert(storage, account, &code)?;
+        let owned_codes = OAC_OWNED_CODES.add_suffix(account.as_slice());
+        if owned_codes.contains(storage, code) {
+            owned_codes.remove(storage, code)?;
+        }
 
         Ok(())
     }
 
-    /// Remove account from the set of `OAC_MEMBERS`, and remove their associated code.
-    fn remove_member(storage: &mut dyn Storage, account: &CanonicalAddr) -> StdResult<()> {
-        if !OAC_MEMBERS.contains(storage, account) {
-            return Ok(());
+    /// Move the account into the tier its balance qualifies for, if it is not there already.
+    /// Returns `true` if the account's membership changed.
+    pub fn update_member(
+        store: &mut dyn Storage,
+        account: &CanonicalAddr,
+        balance: u128,
+        env: &Env,
+    ) -> StdResult<bool> {
+        let current_tier = Self::get_tier(store, account);
+        let qualified_tier = Self::tier_for_balance(store, balance);
+
+        if current_tier == qualified_tier {
+            return Ok(false);
+        }
+
+        if let Some(tier) = current_tier {
+            Self::remove_member(store, account, tier)?;
+        }
+        if let Some(tier) = qualified_tier {
+            Self::add_member(store, account, tier, env)?;
         }
 
+        Ok(true)
+    }
+
+    /// Re-evaluate the account's tier against its current balance, and notify the account of its
+    /// tier whether or not it changed. Returns `true` if the account's membership changed.
+    pub fn reevaluate_member(
+        storage: &mut dyn Storage,
+        account: &CanonicalAddr,
+        env: &Env,
+    ) -> StdResult<bool> {
+        let balance = BalancesStore::load(storage, account)?;
+        let changed = Self::update_member(storage, account, balance, env)?;
+        if changed {
+            CheckpointsStore::settle_and_record(storage, account, env.block.height)?;
+        }
+        NotificationsStore::notify_membership(storage, account, Self::get_tier(storage, account))?;
+
+        Ok(changed)
+    }
+
+    /// Enroll an account whose balance predates the membership sets.
+    ///
+    /// Accounts carried over from the code-563 deployment only become members on their next
+    /// balance change. This lets the admin enroll them up front. It is idempotent, so batches can
+    /// be resubmitted safely. Returns `true` if the account was newly enrolled.
+    pub fn backfill_member(
+        storage: &mut dyn Storage,
+        account: &CanonicalAddr,
+        env: &Env,
+    ) -> StdResult<bool> {
+        if let Some(tier) = Self::get_tier(storage, account) {
+            NotificationsStore::notify_membership(storage, account, Some(tier))?;
+            return Ok(false);
+        }
+
+        Self::reevaluate_member(storage, account, env)
+    }
+
+    /// Add account to the set of members for `tier` and generate a new code for them.
+    /// The code is stored in separate set for ease of checking.
+    /// The code is also stored in a map so it can be retrieved when removing the account.
+    fn add_member(
+        storage: &mut dyn Storage,
+        account: &CanonicalAddr,
+        tier: u8,
+        env: &Env,
+    ) -> StdResult<()> {
+        tier_members(tier).insert(storage, account)?;
+        OAC_MEMBER_TIERS.insert(storage, account, &tier)?;
+
+        let (_, code) = Self::generate_code(env, account.as_slice());
+        tier_invite_codes(tier).insert(storage, &code)?;
+        OAC_MEMBER_CODES.insert(storage, account, &code)?;
+
+        MembershipHooksStore::record(storage, account, MembershipChange::Joined, tier)
+    }
+
+    /// Remove account from the set of members for `tier`, and remove their associated code.
+    fn remove_member(
+        storage: &mut dyn Storage,
+        account: &CanonicalAddr,
+        tier: u8,
+    ) -> StdResult<()> {
         // there should always be a code here, but safety first
         if let Some(code) = OAC_MEMBER_CODES.get(storage, account) {
-            OAC_INVITE_CODES.remove(storage, &code)?;
+            let invite_codes = tier_invite_codes(tier);
+            if invite_codes.contains(storage, &code) {
+                invite_codes.remove(storage, &code)?;
+            }
             OAC_MEMBER_CODES.remove(storage, account)?;
-            OAC_MEMBERS.remove(storage, account)?
         }
 
-        Ok(())
+        let members = tier_members(tier);
+        if members.contains(storage, account) {
+            members.remove(storage, account)?;
+        }
+        if OAC_MEMBER_TIERS.contains(storage, account) {
+            OAC_MEMBER_TIERS.remove(storage, account)?;
+        }
+
+        MembershipHooksStore::record(storage, account, MembershipChange::Left, tier)
     }
 
     /// Generate a unique code per OAC member, using Secret VRF.
     ///
     /// Example base64 encoded string: `"Lx8NsS2V9HOJstXp321Fh4wI4i9fqSSfb85utUEWos"`.
-    fn generate_code(env: &Env, account: &CanonicalAddr) -> (String, [u8; 32]) {
+    fn generate_code(env: &Env, entropy: &[u8]) -> (String, [u8; 32]) {
         let seed = env.block.random.as_ref().unwrap();
-        let mut rng = ContractPrng::new(seed.as_slice(), account.as_slice());
+        let mut rng = ContractPrng::new(seed.as_slice(), entropy);
         let rand_slice = rng.rand_bytes();
 
         let code_bytes = sha_256(&rand_slice);
@@ -140,30 +490,95 @@ impl OneAmberStore {
     }
 
     /// Allow a user to regenerate their code if it has been compromised.
+    /// This also moves them to the tier their balance currently qualifies for.
     pub fn regenerate_code(
         storage: &mut dyn Storage,
         account: &CanonicalAddr,
         env: &Env,
     ) -> StdResult<String> {
-        let balance = BalancesStore::load(storage, account);
+        let balance = BalancesStore::load(storage, account)?;
 
-        if balance < 1_000_000 {
-            return Err(StdError::generic_err("Not enough AMBER"));
-        }
+        let tier = match Self::tier_for_balance(storage, balance) {
+            Some(tier) => tier,
+            None => return Err(StdError::generic_err("Not enough AMBER")),
+        };
 
-        if let Some(code) = OAC_MEMBER_CODES.get(storage, account) {
-            // Ignore any errors related to removing the code from OAC_INVITE_CODES.
-            // If a user's code is not there, they need to be able to make a new one.
-            // Also, serialization errors should not be possible here.
-            let _ = OAC_INVITE_CODES.remove(storage, &code);
+        let current_tier = Self::get_tier(storage, account);
+        if let Some(current_tier) = current_tier {
+            if let Some(code) = OAC_MEMBER_CODES.get(storage, account) {
+                // Ignore any errors related to removing the code from the tier's codes.
+                // If a user's code is not there, they need to be able to make a new one.
+                // Also, serialization errors should not be possible here.
+                let _ = tier_invite_codes(current_tier).remove(storage, &code);
+            }
+            if current_tier != tier {
+                let _ = tier_members(current_tier).remove(storage, account);
+                MembershipHooksStore::record(
+                    storage,
+                    account,
+                    MembershipChange::Left,
+                    current_tier,
+                )?;
+            }
+        }
+        if current_tier != Some(tier) {
+            MembershipHooksStore::record(storage, account, MembershipChange::Joined, tier)?;
         }
 
-        OAC_MEMBERS.insert(storage, account)?;
+        tier_members(tier).insert(storage, account)?;
+        OAC_MEMBER_TIERS.insert(storage, account, &tier)?;
 
-        let (code_string, code_bytes) = Self::generate_code(env, account);
-        OAC_INVITE_CODES.insert(storage, &code_bytes)?;
+        let (code_string, code_bytes) = Self::generate_code(env, account.as_slice());
+        tier_invite_codes(tier).insert(storage, &code_bytes)?;
         OAC_MEMBER_CODES.insert(storage, account, &code_bytes)?;
 
+        if current_tier != Some(tier) {
+            CheckpointsStore::settle_and_record(storage, account, env.block.height)?;
+        }
+        NotificationsStore::notify_membership(storage, account, Some(tier))?;
+
         Ok(code_string)
     }
+
+    /// Generate the attestation signing key using Secret VRF, unless one already exists.
+    pub fn init_attestation_key(storage: &mut dyn Storage, env: &Env) -> StdResult<()> {
+        if OAC_ATTESTATION_KEY.may_load(storage)?.is_some() {
+            return Ok(());
+        }
+
+        let seed = env.block.random.as_ref().unwrap();
+        let mut rng = ContractPrng::new(seed.as_slice(), b"attestation_key");
+        let key = sha_256(&rng.rand_bytes());
+
+        // Only fails if the hash is not a valid secp256k1 scalar, which is vanishingly unlikely.
+        PrivateKey::parse(&key)?;
+
+        OAC_ATTESTATION_KEY.save(storage, &key)
+    }
+
+    /// Returns the compressed public key that attestations can be verified against.
+    pub fn attestation_pubkey(storage: &dyn Storage) -> StdResult<Binary> {
+        let key = OAC_ATTESTATION_KEY.load(storage)?;
+        let pubkey = PrivateKey::parse(&key)?.pubkey();
+
+        Ok(Binary::from(pubkey.serialize_compressed().to_vec()))
+    }
+
+    /// Sign an attestation with the contract's key.
+    ///
+    /// Returns the exact bytes that were signed along with the signature, so verifiers do not
+    /// need to reproduce the serialization. The signature is over `sha256(data)`.
+    pub fn sign_attestation<T: Serialize>(
+        storage: &dyn Storage,
+        api: &dyn Api,
+        attestation: &T,
+    ) -> StdResult<(Binary, Binary)> {
+        let key = OAC_ATTESTATION_KEY.load(storage)?;
+        let data = to_binary(attestation)?;
+        let signature = api
+            .secp256k1_sign(data.as_slice(), &key)
+            .map_err(|err| StdError::generic_err(format!("Failed to sign attestation: {err}")))?;
+
+        Ok((data, Binary::from(signature)))
+    }
 }
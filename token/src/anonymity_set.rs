@@ -0,0 +1,128 @@
+//! A rolling set of recently active accounts that the contract picks decoys from.
+//!
+//! The set is a ring of keyed slots, so recording an account writes the same three keys whether
+//! or not it was already in the set, and costs the same however large the set is.
+
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{CanonicalAddr, StdError, StdResult, Storage};
+use secret_toolkit::crypto::ContractPrng;
+use secret_toolkit::serialization::Bincode2;
+use secret_toolkit::storage::{Item, Keymap, KeymapBuilder, WithoutIter};
+
+/// The number of recently active accounts to remember.
+pub const ANONYMITY_SET_LEN: u32 = 128;
+/// The maximum number of decoys per message, or per batch action.
+pub const MAX_DECOYS: u8 = 16;
+
+/// The accounts in the set, keyed by slot.
+pub static ANONYMITY_SET_SLOTS: Keymap<u32, CanonicalAddr, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"anonymity_set_slots")
+        .without_iter()
+        .build();
+/// The slot each account was last recorded in. Stale once its slot has been reused.
+pub static ANONYMITY_SET_POSITIONS: Keymap<CanonicalAddr, u32, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"anonymity_set_positions")
+        .without_iter()
+        .build();
+pub static ANONYMITY_SET_CURSOR: Item<AnonymitySet> = Item::new(b"anonymity_set_cursor");
+
+/// The set as stored before schema 11, rewritten in full on every update.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+struct LegacyAnonymitySet {
+    accounts: Vec<CanonicalAddr>,
+    next: u32,
+}
+
+static LEGACY_ANONYMITY_SET: Item<LegacyAnonymitySet> = Item::new(b"anonymity_set");
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct AnonymitySet {
+    /// The number of slots in use
+    len: u32,
+    /// The slot to write next, which holds the oldest account once the set is full
+    next: u32,
+}
+
+impl AnonymitySet {
+    pub fn load(storage: &dyn Storage) -> StdResult<Self> {
+        Ok(ANONYMITY_SET_CURSOR.may_load(storage)?.unwrap_or_default())
+    }
+
+    /// Add an account that has just transacted, replacing the oldest one once the set is full.
+    /// An account already in the set keeps its slot, which is rewritten so the writes look the
+    /// same either way.
+    pub fn record(storage: &mut dyn Storage, account: &CanonicalAddr) -> StdResult<()> {
+        let mut set = Self::load(storage)?;
+        let slot = match ANONYMITY_SET_POSITIONS.get(storage, account) {
+            Some(slot) if ANONYMITY_SET_SLOTS.get(storage, &slot).as_ref() == Some(account) => slot,
+            _ => {
+                let slot = set.next;
+                set.next = (set.next + 1) % ANONYMITY_SET_LEN;
+                set.len = (set.len + 1).min(ANONYMITY_SET_LEN);
+                slot
+            }
+        };
+
+        ANONYMITY_SET_SLOTS.insert(storage, &slot, account)?;
+        ANONYMITY_SET_POSITIONS.insert(storage, account, &slot)?;
+        ANONYMITY_SET_CURSOR.save(storage, &set)
+    }
+
+    /// Pick `count` distinct accounts at random, never one in `exclude`.
+    /// Fails if the set does not have that many to offer, rather than quietly picking fewer.
+    pub fn pick(
+        &self,
+        storage: &dyn Storage,
+        rng: &mut ContractPrng,
+        count: u8,
+        exclude: &[CanonicalAddr],
+    ) -> StdResult<Vec<CanonicalAddr>> {
+        let mut slots: Vec<u32> = (0..self.len).collect();
+
+        let mut picked = Vec::with_capacity(count as usize);
+        while picked.len() < count as usize && !slots.is_empty() {
+            let index = rng.rng.next_u64() as usize % slots.len();
+            let slot = slots.swap_remove(index);
+            let account = ANONYMITY_SET_SLOTS
+                .get(storage, &slot)
+                .ok_or_else(|| StdError::generic_err("Anonymity set slot not found"))?;
+            if !exclude.contains(&account) {
+                picked.push(account);
+            }
+        }
+
+        if picked.len() < count as usize {
+            return Err(StdError::generic_err(format!(
+                "Only {} recently active accounts can be decoys, fewer than the {count} asked for",
+                picked.len()
+            )));
+        }
+
+        Ok(picked)
+    }
+
+    /// Move a set stored before schema 11 into slots, oldest first.
+    pub fn upgrade(storage: &mut dyn Storage) -> StdResult<()> {
+        let legacy = match LEGACY_ANONYMITY_SET.may_load(storage)? {
+            Some(legacy) => legacy,
+            None => return Ok(()),
+        };
+
+        let len = legacy.accounts.len() as u32;
+        // Until the set was full, its accounts were in the order they were added
+        let oldest = if len < ANONYMITY_SET_LEN {
+            0
+        } else {
+            legacy.next
+        };
+        for offset in 0..len {
+            let account = &legacy.accounts[((oldest + offset) % len) as usize];
+            Self::record(storage, account)?;
+        }
+
+        LEGACY_ANONYMITY_SET.remove(storage);
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+//! A public log of privileged actions, recording who changed the admin, the minters, the
+//! supported denoms or the contract status, and when.
+
+use cosmwasm_std::{Addr, Env, StdResult, Storage};
+use secret_toolkit::storage::AppendStore;
+
+use crate::msg::{AdminAction, AdminHistoryEntry};
+
+pub static ADMIN_HISTORY: AppendStore<AdminHistoryEntry> = AppendStore::new(b"admin_history");
+
+pub struct AdminHistoryStore {}
+impl AdminHistoryStore {
+    pub fn record(
+        storage: &mut dyn Storage,
+        env: &Env,
+        actor: &Addr,
+        action: AdminAction,
+    ) -> StdResult<()> {
+        ADMIN_HISTORY.push(
+            storage,
+            &AdminHistoryEntry {
+                actor: actor.clone(),
+                action,
+                block_time: env.block.time.seconds(),
+                block_height: env.block.height,
+            },
+        )
+    }
+
+    /// A page of the log, newest first, and the number of entries in the whole log.
+    pub fn load_page(
+        storage: &dyn Storage,
+        page: u32,
+        page_size: u32,
+    ) -> StdResult<(Vec<AdminHistoryEntry>, u64)> {
+        let total = ADMIN_HISTORY.get_len(storage)?;
+        let entries = ADMIN_HISTORY
+            .iter(storage)?
+            .rev()
+            .skip((page as usize).saturating_mul(page_size as usize))
+            .take(page_size as usize)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        Ok((entries, total as u64))
+    }
+}
@@ -7,6 +7,9 @@ use cosmwasm_std::{Addr, Binary, Uint128};
 
 pub trait HasDecoy {
     fn decoys(&self) -> &Option<Vec<Addr>>;
+    fn decoys_mut(&mut self) -> &mut Option<Vec<Addr>>;
+    /// The accounts whose balances the action changes.
+    fn accounts(&self) -> Vec<&str>;
 }
 
 #[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
@@ -70,18 +73,26 @@ pub struct BurnFromAction {
 }
 
 macro_rules! impl_decoyable {
-    ($struct:ty) => {
+    ($struct:ty, $($account:ident),+) => {
         impl HasDecoy for $struct {
             fn decoys(&self) -> &Option<Vec<Addr>> {
                 &self.decoys
             }
+
+            fn decoys_mut(&mut self) -> &mut Option<Vec<Addr>> {
+                &mut self.decoys
+            }
+
+            fn accounts(&self) -> Vec<&str> {
+                vec![$(self.$account.as_str()),+]
+            }
         }
     };
 }
 
-impl_decoyable!(BurnFromAction);
-impl_decoyable!(MintAction);
-impl_decoyable!(SendFromAction);
-impl_decoyable!(TransferFromAction);
-impl_decoyable!(TransferAction);
-impl_decoyable!(SendAction);
+impl_decoyable!(BurnFromAction, owner);
+impl_decoyable!(MintAction, recipient);
+impl_decoyable!(SendFromAction, owner, recipient);
+impl_decoyable!(TransferFromAction, owner, recipient);
+impl_decoyable!(TransferAction, recipient);
+impl_decoyable!(SendAction, recipient);
@@ -0,0 +1,211 @@
+//! Bitwise trie of bucketed entries (BTBE), where settled balances live.
+//!
+//! Each account is placed in a bucket by walking a binary trie along the bits of a keyed hash of
+//! its address. A balance update rewrites the whole bucket, so storage access only reveals which
+//! bucket changed, not which of the accounts in it.
+
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{CanonicalAddr, Env, StdError, StdResult, Storage};
+use secret_toolkit::crypto::{sha_256, ContractPrng};
+use secret_toolkit::storage::{Item, Keymap};
+
+/// The maximum number of accounts in a bucket before it is split.
+pub const BTBE_BUCKET_LEN: usize = 64;
+
+/// The id of the root node. Ids start at 1 so that 0 can mean "none".
+const ROOT_NODE_ID: u64 = 1;
+
+/// Secret mixed into every address before hashing, so bucket placement cannot be predicted.
+pub static INTERNAL_SECRET: Item<[u8; 32]> = Item::new(b"internal_secret");
+pub static BTBE_NODES: Keymap<u64, BitwiseTrieNode> = Keymap::new(b"btbe_nodes");
+pub static BTBE_BUCKETS: Keymap<u64, BtbeBucket> = Keymap::new(b"btbe_buckets");
+pub static BTBE_NODE_COUNT: Item<u64> = Item::new(b"btbe_node_count");
+pub static BTBE_BUCKET_COUNT: Item<u64> = Item::new(b"btbe_bucket_count");
+
+/// A trie node is either a leaf pointing at a bucket, or an inner node with two children.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BitwiseTrieNode {
+    pub left: u64,
+    pub right: u64,
+    /// Non-zero for leaves only
+    pub bucket: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BtbeEntry {
+    pub address: CanonicalAddr,
+    pub balance: u128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BtbeBucket {
+    pub entries: Vec<BtbeEntry>,
+}
+
+/// Create the internal secret and an empty trie, unless they already exist.
+pub fn initialize_btbe(storage: &mut dyn Storage, env: &Env) -> StdResult<()> {
+    if INTERNAL_SECRET.may_load(storage)?.is_some() {
+        return Ok(());
+    }
+
+    let seed = env.block.random.as_ref().unwrap();
+    let mut rng = ContractPrng::new(seed.as_slice(), b"internal_secret");
+    INTERNAL_SECRET.save(storage, &sha_256(&rng.rand_bytes()))?;
+
+    BTBE_BUCKETS.insert(storage, &1, &BtbeBucket::default())?;
+    BTBE_BUCKET_COUNT.save(storage, &1)?;
+    BTBE_NODES.insert(
+        storage,
+        &ROOT_NODE_ID,
+        &BitwiseTrieNode {
+            bucket: 1,
+            ..Default::default()
+        },
+    )?;
+    BTBE_NODE_COUNT.save(storage, &ROOT_NODE_ID)
+}
+
+fn address_key(storage: &dyn Storage, address: &CanonicalAddr) -> StdResult<[u8; 32]> {
+    let secret = INTERNAL_SECRET.load(storage)?;
+    Ok(sha_256(&[secret.as_slice(), address.as_slice()].concat()))
+}
+
+/// Returns the bit at `depth`, counting from the most significant bit of the first byte.
+fn bit_at(key: &[u8; 32], depth: usize) -> bool {
+    key[depth / 8] & (0x80 >> (depth % 8)) != 0
+}
+
+/// Walk the trie to the leaf for `key`. Returns the leaf's id, the node, and its depth.
+fn locate_leaf(storage: &dyn Storage, key: &[u8; 32]) -> StdResult<(u64, BitwiseTrieNode, usize)> {
+    let mut node_id = ROOT_NODE_ID;
+    let mut depth = 0;
+    loop {
+        let node = BTBE_NODES
+            .get(storage, &node_id)
+            .ok_or_else(|| StdError::generic_err("Balance trie node not found"))?;
+        if node.bucket != 0 {
+            return Ok((node_id, node, depth));
+        }
+        node_id = if bit_at(key, depth) {
+            node.right
+        } else {
+            node.left
+        };
+        depth += 1;
+    }
+}
+
+fn load_bucket(storage: &dyn Storage, bucket_id: u64) -> StdResult<BtbeBucket> {
+    BTBE_BUCKETS
+        .get(storage, &bucket_id)
+        .ok_or_else(|| StdError::generic_err("Balance bucket not found"))
+}
+
+/// Returns the settled balance of `address`, or `None` if it has no entry in the trie.
+pub fn stored_balance(storage: &dyn Storage, address: &CanonicalAddr) -> StdResult<Option<u128>> {
+    let key = address_key(storage, address)?;
+    let (_, leaf, _) = locate_leaf(storage, &key)?;
+    let bucket = load_bucket(storage, leaf.bucket)?;
+
+    Ok(bucket
+        .entries
+        .iter()
+        .find(|entry| &entry.address == address)
+        .map(|entry| entry.balance))
+}
+
+/// Sets the settled balance of `address`, adding an entry if needed.
+/// Returns `true` if the entry is new.
+pub fn set_balance(
+    storage: &mut dyn Storage,
+    address: &CanonicalAddr,
+    balance: u128,
+) -> StdResult<bool> {
+    let key = address_key(storage, address)?;
+    loop {
+        let (node_id, leaf, depth) = locate_leaf(storage, &key)?;
+        let mut bucket = load_bucket(storage, leaf.bucket)?;
+
+        if let Some(entry) = bucket
+            .entries
+            .iter_mut()
+            .find(|entry| &entry.address == address)
+        {
+            entry.balance = balance;
+            BTBE_BUCKETS.insert(storage, &leaf.bucket, &bucket)?;
+            return Ok(false);
+        }
+
+        if bucket.entries.len() < BTBE_BUCKET_LEN {
+            bucket.entries.push(BtbeEntry {
+                address: address.clone(),
+                balance,
+            });
+            BTBE_BUCKETS.insert(storage, &leaf.bucket, &bucket)?;
+            return Ok(true);
+        }
+
+        // The bucket is full, so split it and try again one level deeper.
+        split_leaf(storage, node_id, leaf, bucket, depth)?;
+    }
+}
+
+/// Turn a full leaf into an inner node whose children divide its entries by the next bit.
+fn split_leaf(
+    storage: &mut dyn Storage,
+    node_id: u64,
+    leaf: BitwiseTrieNode,
+    bucket: BtbeBucket,
+    depth: usize,
+) -> StdResult<()> {
+    if depth >= 256 {
+        return Err(StdError::generic_err("Balance trie is too deep"));
+    }
+
+    let mut left = BtbeBucket::default();
+    let mut right = BtbeBucket::default();
+    for entry in bucket.entries {
+        if bit_at(&address_key(storage, &entry.address)?, depth) {
+            right.entries.push(entry);
+        } else {
+            left.entries.push(entry);
+        }
+    }
+
+    // The left child reuses the old bucket id
+    let right_bucket_id = BTBE_BUCKET_COUNT.load(storage)? + 1;
+    BTBE_BUCKETS.insert(storage, &leaf.bucket, &left)?;
+    BTBE_BUCKETS.insert(storage, &right_bucket_id, &right)?;
+    BTBE_BUCKET_COUNT.save(storage, &right_bucket_id)?;
+
+    let left_node_id = BTBE_NODE_COUNT.load(storage)? + 1;
+    let right_node_id = left_node_id + 1;
+    BTBE_NODES.insert(
+        storage,
+        &left_node_id,
+        &BitwiseTrieNode {
+            bucket: leaf.bucket,
+            ..Default::default()
+        },
+    )?;
+    BTBE_NODES.insert(
+        storage,
+        &right_node_id,
+        &BitwiseTrieNode {
+            bucket: right_bucket_id,
+            ..Default::default()
+        },
+    )?;
+    BTBE_NODE_COUNT.save(storage, &right_node_id)?;
+
+    BTBE_NODES.insert(
+        storage,
+        &node_id,
+        &BitwiseTrieNode {
+            left: left_node_id,
+            right: right_node_id,
+            bucket: 0,
+        },
+    )
+}
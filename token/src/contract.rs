@@ -4,41 +4,230 @@
 use rand::RngCore;
 
 use cosmwasm_std::{
-    entry_point, to_binary, Addr, Api, BankMsg, Binary, CanonicalAddr, Coin, CosmosMsg, Deps,
-    DepsMut, Env, MessageInfo, Response, StdError, StdResult, Storage, Uint128,
+    entry_point, from_binary, to_binary, Addr, Api, BankMsg, Binary, CanonicalAddr, Coin,
+    CosmosMsg, Deps, DepsMut, Env, MessageInfo, Reply, Response, StdError, StdResult, Storage,
+    Uint128, Uint64,
 };
 
 use secret_toolkit::crypto::{sha_256, ContractPrng, SHA256_HASH_SIZE};
-use secret_toolkit::permit::{Permit, RevokedPermits, TokenPermissions};
+use secret_toolkit::permit::{Permit, RevokedPermits};
 use secret_toolkit::utils::{pad_handle_result, pad_query_result};
 
-use crate::amber::OneAmberStore;
+use crate::amber::{OneAmberStore, ATTESTATION_TTL};
+use crate::anonymity_set::{AnonymitySet, MAX_DECOYS};
+use crate::audit::AdminHistoryStore;
 use crate::batch;
+use crate::btbe::initialize_btbe;
+use crate::dwb::{
+    initialize_dwb, settle_legacy_buffer, settle_untiered_buffer, DelayedWriteBuffer,
+};
+use crate::hooks::{
+    HookErrorPolicy, MembershipHook, MembershipHooksStore, MEMBERSHIP_HOOK_REPLY_ID,
+};
 use crate::legacy_support::{ViewingKey, ViewingKeyStore};
 use crate::msg::{
-    AllowanceGivenResult, AllowanceReceivedResult, ContractStatusLevel, Decoyable, ExecuteAnswer,
-    ExecuteMsg, InstantiateMsg, MigrateAnswer, MigrateMsg, QueryAnswer, QueryMsg, QueryWithPermit,
-    ResponseStatus::Success,
+    AdminAction, AllowanceGivenResult, AllowanceReceivedResult, AmberPermissions, Attestation,
+    BalanceAttestation, ChannelInfoData, ContractStatusLevel, Decoyable, ExecuteAnswer, ExecuteMsg,
+    InstantiateMsg, InviteCodeInfo, MembershipHookInfo, MigrateAnswer, MigrateMsg, MintPeriod,
+    MintRate, MinterQuotaInfo, PauseFlags, QueryAnswer, QueryMsg, QueryWithPermit,
+    QueuedOperationInfo, ResponseStatus::Success, RoleHoldersInfo, ValidCode,
+};
+use crate::notifications::{
+    cddl, notification_id, notification_seed, NotificationsStore, CHANNELS,
 };
 use crate::receiver::Snip20ReceiveMsg;
+use crate::roles::{Role, RolesStore, ROLES};
 use crate::state::{
-    safe_add, AllowancesStore, BalancesStore, ConfigStore, Constants, MintersStore, PrngStore,
-    ReceiverHashStore,
+    safe_add, AdminProposal, AllowancesStore, BalancesStore, Checkpoint, CheckpointsStore,
+    ConfigStore, Constants, ContractVersion, ContractVersionStore, MintersStore, PrngStore,
+    ReceiverHashStore, ADMIN_PROPOSAL,
 };
+use crate::timelock::{QueuedOperation, TimelockStore, MAX_TIMELOCK_DELAY};
 use crate::transaction_history::{
-    store_burn, store_deposit, store_mint, store_redeem, store_transfer, StoredExtendedTx,
-    StoredLegacyTransfer,
+    history_pruned_through, prune_history, record_legacy_transfers_end, set_tx_label, store_burn,
+    store_deposit, store_mint, store_redeem, store_transfer, HistoryDirection, StoredExtendedTx,
+    StoredLegacyTransfer, TxFilter, TxLabel,
 };
 
 /// We make sure that responses from `handle` are padded to a multiple of this size.
 pub const RESPONSE_BLOCK_SIZE: usize = 256;
 pub const PREFIX_REVOKED_PERMITS: &str = "revoked_permits";
 
+pub const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
+pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Bump this and append a step to `MIGRATIONS` whenever the storage layout changes.
+pub const STORAGE_SCHEMA: u32 = 11;
+
+type MigrationStep = fn(&mut dyn Storage, &Env) -> StdResult<()>;
+
+/// Storage upgrade steps, in order. `MIGRATIONS[n]` upgrades storage from schema `n` to `n + 1`.
+/// Steps must be idempotent, because a code-563 instance has no record of which ones have run.
+const MIGRATIONS: [MigrationStep; STORAGE_SCHEMA as usize] = [
+    migrate_to_schema_1,
+    migrate_to_schema_2,
+    migrate_to_schema_3,
+    migrate_to_schema_4,
+    migrate_to_schema_5,
+    migrate_to_schema_6,
+    migrate_to_schema_7,
+    migrate_to_schema_8,
+    migrate_to_schema_9,
+    migrate_to_schema_10,
+    migrate_to_schema_11,
+];
+
+/// Schema 1 introduces the contract version record. Nothing else needs to change.
+fn migrate_to_schema_1(_storage: &mut dyn Storage, _env: &Env) -> StdResult<()> {
+    Ok(())
+}
+
+/// Schema 2 adds the key used to sign membership attestations.
+fn migrate_to_schema_2(storage: &mut dyn Storage, env: &Env) -> StdResult<()> {
+    OneAmberStore::init_attestation_key(storage, env)
+}
+
+/// Schema 3 moves balances into the balance trie behind a delayed write buffer.
+/// Balances in `PREFIX_BALANCES` stay readable and move over when each account next settles.
+fn migrate_to_schema_3(storage: &mut dyn Storage, env: &Env) -> StdResult<()> {
+    initialize_btbe(storage, env)?;
+    initialize_dwb(storage)
+}
+
+/// Schema 4 stops storing transfers a second time in `PREFIX_TRANSFERS`. The transfer history is
+/// read from the tx history instead, after the legacy transfers recorded up to this point.
+fn migrate_to_schema_4(storage: &mut dyn Storage, _env: &Env) -> StdResult<()> {
+    settle_legacy_buffer(storage)?;
+    record_legacy_transfers_end(storage)
+}
+
+/// Schema 5 adds an optional max supply to the constants, which are rewritten in the layout of
+/// `ConstantsV5` with no cap.
+fn migrate_to_schema_5(storage: &mut dyn Storage, _env: &Env) -> StdResult<()> {
+    ConfigStore::upgrade_constants_to_v5(storage)
+}
+
+/// Schema 6 splits the admin's powers into roles. The admin becomes the role admin and is
+/// granted every role, so it keeps the powers it had. The constants keep the layout of schema 5.
+fn migrate_to_schema_6(storage: &mut dyn Storage, _env: &Env) -> StdResult<()> {
+    match ConfigStore::load_admin_v5(storage)? {
+        Some(admin) => RolesStore::grant_all(storage, &admin),
+        None => Ok(()),
+    }
+}
+
+/// Schema 7 makes the admin optional, so it can be renounced. The constants are rewritten in the
+/// current layout.
+fn migrate_to_schema_7(storage: &mut dyn Storage, _env: &Env) -> StdResult<()> {
+    ConfigStore::upgrade_constants_to_v7(storage)
+}
+
+/// Schema 8 replaces the contract status level with a pause flag per class of operation.
+fn migrate_to_schema_8(storage: &mut dyn Storage, _env: &Env) -> StdResult<()> {
+    ConfigStore::upgrade_contract_status(storage)
+}
+
+/// Schema 9 enforces `supported_denoms` on deposits and redeems. Instances that took deposits or
+/// paid out redeems without any supported denoms only ever handled uscrt, so they get that.
+fn migrate_to_schema_9(storage: &mut dyn Storage, _env: &Env) -> StdResult<()> {
+    let mut constants = ConfigStore::load_constants(storage)?;
+    if !constants.supported_denoms.is_empty()
+        || !(constants.deposit_is_enabled || constants.redeem_is_enabled)
+    {
+        return Ok(());
+    }
+
+    constants.supported_denoms = vec!["uscrt".to_string()];
+    ConfigStore::set_constants(storage, &constants)
+}
+
+/// Schema 10 keeps each pending tx's OAC tier in the delayed write buffer, so checkpoints get the
+/// tier in force when the credit was made. The buffer is settled so no node keeps the old layout.
+fn migrate_to_schema_10(storage: &mut dyn Storage, _env: &Env) -> StdResult<()> {
+    settle_untiered_buffer(storage)
+}
+
+/// Schema 11 stores the anonymity set as a ring of keyed slots instead of a single item.
+fn migrate_to_schema_11(storage: &mut dyn Storage, _env: &Env) -> StdResult<()> {
+    AnonymitySet::upgrade(storage)
+}
+
+fn current_version() -> ContractVersion {
+    ContractVersion {
+        name: CONTRACT_NAME.to_string(),
+        version: CONTRACT_VERSION.to_string(),
+        schema: STORAGE_SCHEMA,
+    }
+}
+
+/// Run every upgrade step between the stored schema and `STORAGE_SCHEMA`, then record the
+/// current version. Refuses to migrate to an older version or storage schema.
+fn run_migrations(storage: &mut dyn Storage, env: &Env) -> StdResult<()> {
+    let stored = ContractVersionStore::may_load(storage)?;
+
+    // Instances without a version record predate versioning entirely.
+    let from_schema = stored.as_ref().map_or(0, |stored| stored.schema);
+    if from_schema > STORAGE_SCHEMA {
+        return Err(StdError::generic_err(format!(
+            "Cannot migrate from storage schema {from_schema} to older schema {STORAGE_SCHEMA}",
+        )));
+    }
+
+    if let Some(stored) = stored {
+        if stored.name != CONTRACT_NAME {
+            return Err(StdError::generic_err(format!(
+                "Cannot migrate from {} to {CONTRACT_NAME}",
+                stored.name
+            )));
+        }
+        if parse_semver(&stored.version)? > parse_semver(CONTRACT_VERSION)? {
+            return Err(StdError::generic_err(format!(
+                "Cannot migrate from version {} to older version {CONTRACT_VERSION}",
+                stored.version
+            )));
+        }
+    }
+
+    for step in MIGRATIONS.iter().skip(from_schema as usize) {
+        step(storage, env)?;
+    }
+
+    ContractVersionStore::save(storage, &current_version())
+}
+
+/// Parses a plain `major.minor.patch` version into something comparable.
+fn parse_semver(version: &str) -> StdResult<(u64, u64, u64)> {
+    let invalid = || StdError::generic_err(format!("Invalid contract version: {version}"));
+
+    let mut parts = version
+        .split('.')
+        .map(|part| part.parse::<u64>().map_err(|_| invalid()));
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(major), Some(minor), Some(patch), None) => Ok((major?, minor?, patch?)),
+        _ => Err(invalid()),
+    }
+}
+
 #[entry_point]
-pub fn migrate(_deps: DepsMut, _env: Env, msg: MigrateMsg) -> StdResult<Response> {
+pub fn migrate(mut deps: DepsMut, env: Env, msg: MigrateMsg) -> StdResult<Response> {
     match msg {
-        MigrateMsg::Migrate {} => {
-            Ok(Response::new().set_data(to_binary(&MigrateAnswer::Migrate { status: Success })?))
+        MigrateMsg::Migrate {
+            accounts,
+            max_supply,
+        } => {
+            run_migrations(deps.storage, &env)?;
+            if let Some(max_supply) = max_supply {
+                set_max_supply_impl(deps.storage, max_supply.u128())?;
+            }
+
+            let enrolled =
+                backfill_members_impl(deps.branch(), &env, accounts.unwrap_or_default())?;
+
+            let response = Response::new().set_data(to_binary(&MigrateAnswer::Migrate {
+                status: Success,
+                enrolled,
+            })?);
+            let response = MembershipHooksStore::dispatch(deps.storage, deps.api, response)?;
+            NotificationsStore::dispatch(deps.storage, &env, response)
         }
     }
 }
@@ -76,6 +265,9 @@ pub fn instantiate(
 
     let prng_seed_hashed = sha_256(&msg.prng_seed.0);
 
+    initialize_btbe(deps.storage, &env)?;
+    initialize_dwb(deps.storage)?;
+
     {
         let admin = deps.api.addr_canonicalize(admin.as_str())?;
         let initial_balances = msg.initial_balances.unwrap_or_default();
@@ -102,6 +294,14 @@ pub fn instantiate(
                     "The sum of all initial balances exceeds the maximum possible total supply",
                 ));
             }
+            if init_config
+                .max_supply()
+                .map_or(false, |max_supply| total_supply > max_supply)
+            {
+                return Err(StdError::generic_err(
+                    "The sum of all initial balances exceeds the max supply",
+                ));
+            }
 
             store_mint(
                 deps.storage,
@@ -121,12 +321,17 @@ pub fn instantiate(
         None => vec![],
         Some(x) => x,
     };
+    if supported_denoms.len() > 1 {
+        return Err(StdError::generic_err(
+            "Only one denom can be supported at instantiation, add pegged denoms with AddSupportedDenoms",
+        ));
+    }
 
     ConfigStore::set_constants(
         deps.storage,
         &Constants {
             name: msg.name,
-            admin: admin.clone(),
+            admin: Some(admin.clone()),
             symbol: msg.symbol,
             decimals: msg.decimals,
             prng_seed: prng_seed_hashed.to_vec(),
@@ -135,12 +340,16 @@ pub fn instantiate(
             redeem_is_enabled: init_config.redeem_enabled(),
             mint_is_enabled: init_config.mint_enabled(),
             burn_is_enabled: init_config.burn_enabled(),
-            contract_address: env.contract.address,
+            contract_address: env.contract.address.clone(),
             supported_denoms,
+            max_supply: init_config.max_supply(),
         },
     )?;
     ConfigStore::set_total_supply(deps.storage, &total_supply)?;
     ConfigStore::set_contract_status(deps.storage, &ContractStatusLevel::NormalRun)?;
+    ContractVersionStore::save(deps.storage, &current_version())?;
+    OneAmberStore::init_attestation_key(deps.storage, &env)?;
+    RolesStore::grant_all(deps.storage, &admin)?;
 
     let minters = if init_config.mint_enabled() {
         Vec::from([admin])
@@ -151,7 +360,7 @@ pub fn instantiate(
 
     ViewingKey::set_seed(deps.storage, &prng_seed_hashed);
 
-    Ok(Response::default())
+    NotificationsStore::dispatch(deps.storage, &env, Response::default())
 }
 
 fn get_address_position(
@@ -172,8 +381,64 @@ fn get_address_position(
 }
 
 #[entry_point]
-pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> StdResult<Response> {
-    let contract_status = ConfigStore::load_contract_status(deps.storage)?;
+pub fn execute(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> StdResult<Response> {
+    let gas_target = msg.get_gas_target();
+
+    let response = execute_impl(deps.branch(), env.clone(), info, msg, false)
+        .and_then(|response| MembershipHooksStore::dispatch(deps.storage, deps.api, response))
+        .and_then(|response| NotificationsStore::dispatch(deps.storage, &env, response));
+
+    let padded_result = pad_handle_result(response, RESPONSE_BLOCK_SIZE);
+    evaporate_to_target(deps.api, gas_target)?;
+
+    padded_result
+}
+
+/// Burn the gas left between what the execution has used and the sender's `gas_target`, so
+/// messages of the same kind cost the same whatever they ended up doing.
+///
+/// Membership hooks and notifications are dispatched before this, so building them counts
+/// toward the target. The hook submessages themselves run after the contract returns, and the
+/// gas they use is not covered.
+fn evaporate_to_target(api: &dyn Api, gas_target: Option<u32>) -> StdResult<()> {
+    if let Some(gas_target) = gas_target {
+        let evaporate = gas_to_evaporate(api.check_gas()?, gas_target);
+        if evaporate > 0 {
+            api.gas_evaporate(evaporate)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The gas still to burn to reach `gas_target`, none once it has been reached.
+fn gas_to_evaporate(gas_used: u64, gas_target: u32) -> u32 {
+    (gas_target as u64).saturating_sub(gas_used) as u32
+}
+
+fn execute_impl(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    mut msg: ExecuteMsg,
+    bypass_timelock: bool,
+) -> StdResult<Response> {
+    let pause_flags = ConfigStore::load_pause_flags(deps.storage)?;
+
+    // Sensitive admin messages wait out the timelock, if there is one
+    if !bypass_timelock {
+        if let Some(authority) = timelocked_authority(&pause_flags, &msg) {
+            let delay = TimelockStore::delay(deps.storage)?;
+            if delay > 0 {
+                return queue_operation(deps, &env, info, msg, authority, delay);
+            }
+        }
+    }
 
     let mut account_random_pos: Option<usize> = None;
 
@@ -182,35 +447,52 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
         Some(e) => sha_256(&e.0),
     };
 
+    let decoy_count = msg.get_decoy_count();
+    if msg.get_maximal_decoys_size() > MAX_DECOYS as usize
+        || decoy_count.unwrap_or_default() > MAX_DECOYS
+    {
+        return Err(StdError::generic_err(format!(
+            "Too many decoys, the maximum is {MAX_DECOYS}"
+        )));
+    }
+
+    // Let the contract pick decoys from recently active accounts, if asked to
+    if let Some(count) = decoy_count {
+        let anonymity_set = AnonymitySet::load(deps.storage)?;
+        // None of the accounts the message really updates can stand in as a decoy for another.
+        // Invalid addresses are left for the handler to reject.
+        let mut exclude = vec![deps.api.addr_canonicalize(info.sender.as_str())?];
+        exclude.extend(
+            msg.get_accounts()
+                .into_iter()
+                .filter_map(|account| deps.api.addr_canonicalize(account).ok()),
+        );
+        let mut rng = ContractPrng::new(
+            &PrngStore::load(deps.storage)?,
+            &sha_256(&[entropy.as_slice(), b"decoys".as_slice()].concat()),
+        );
+        let storage: &dyn Storage = deps.storage;
+        msg.fill_decoys(|| {
+            anonymity_set
+                .pick(storage, &mut rng, count, &exclude)?
+                .iter()
+                .map(|decoy| deps.api.addr_humanize(decoy))
+                .collect()
+        })?;
+    }
+
     let decoys_size = msg.get_minimal_decoys_size();
     if decoys_size != 0 {
         account_random_pos = Some(get_address_position(deps.storage, decoys_size, &entropy)?);
     }
 
-    match contract_status {
-        ContractStatusLevel::StopAll | ContractStatusLevel::StopAllButRedeems => {
-            let response = match msg {
-                ExecuteMsg::SetContractStatus { level, .. } => {
-                    set_contract_status(deps, info, level)
-                }
-                ExecuteMsg::Redeem {
-                    amount,
-                    denom,
-                    decoys,
-                    ..
-                } if contract_status == ContractStatusLevel::StopAllButRedeems => {
-                    try_redeem(deps, env, info, amount, denom, decoys, account_random_pos)
-                }
-                _ => Err(StdError::generic_err(
-                    "This contract is stopped and this action is not allowed",
-                )),
-            };
-            return pad_handle_result(response, RESPONSE_BLOCK_SIZE);
-        }
-        ContractStatusLevel::NormalRun => {} // If it's a normal run just continue
+    if pause_flags.pauses(&msg) {
+        return Err(StdError::generic_err(
+            "This contract is stopped and this action is not allowed",
+        ));
     }
 
-    let response = match msg.clone() {
+    match msg.clone() {
         // Native
         ExecuteMsg::Deposit { decoys, .. } => {
             try_deposit(deps, env, info, decoys, account_random_pos)
@@ -378,40 +660,128 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
         }
 
         // Other
-        ExecuteMsg::ChangeAdmin { address, .. } => change_admin(deps, info, address),
-        ExecuteMsg::SetContractStatus { level, .. } => set_contract_status(deps, info, level),
-        ExecuteMsg::AddMinters { minters, .. } => add_minters(deps, info, minters),
-        ExecuteMsg::RemoveMinters { minters, .. } => remove_minters(deps, info, minters),
-        ExecuteMsg::SetMinters { minters, .. } => set_minters(deps, info, minters),
+        ExecuteMsg::ChangeAdmin { address, .. } => change_admin(deps, env, info, address),
+        ExecuteMsg::ProposeAdmin {
+            address,
+            expiration,
+            ..
+        } => propose_admin(deps, env, info, address, expiration),
+        ExecuteMsg::AcceptAdmin { .. } => accept_admin(deps, env, info),
+        ExecuteMsg::CancelAdminProposal { .. } => cancel_admin_proposal(deps, env, info),
+        ExecuteMsg::RenounceAdmin { .. } => renounce_admin(deps, env, info),
+        ExecuteMsg::SetTimelock { delay, .. } => set_timelock(deps, env, info, delay),
+        ExecuteMsg::ExecuteQueued { id, .. } => execute_queued(deps, env, info, id),
+        ExecuteMsg::CancelQueued { id, .. } => cancel_queued(deps, env, info, id),
+        ExecuteMsg::GrantRole { role, account, .. } => grant_role(deps, env, info, role, account),
+        ExecuteMsg::RevokeRole { role, account, .. } => revoke_role(deps, env, info, role, account),
+        ExecuteMsg::RenounceRole { role, .. } => renounce_role(deps, env, info, role),
+        ExecuteMsg::SetContractStatus { level, .. } => set_contract_status(deps, env, info, level),
+        ExecuteMsg::SetPauseFlags { flags, .. } => set_pause_flags(deps, env, info, flags),
+        ExecuteMsg::SetMaxSupply { max_supply, .. } => set_max_supply(deps, env, info, max_supply),
+        ExecuteMsg::AddMinters { minters, .. } => add_minters(deps, env, info, minters),
+        ExecuteMsg::RemoveMinters { minters, .. } => remove_minters(deps, env, info, minters),
+        ExecuteMsg::SetMinters { minters, .. } => set_minters(deps, env, info, minters),
+        ExecuteMsg::SetMinterQuota {
+            minter,
+            allowance,
+            rate,
+            ..
+        } => set_minter_quota(deps, env, info, minter, allowance, rate),
         ExecuteMsg::RevokePermit { permit_name, .. } => revoke_permit(deps, info, permit_name),
-        ExecuteMsg::AddSupportedDenoms { denoms, .. } => add_supported_denoms(deps, info, denoms),
+        ExecuteMsg::AddSupportedDenoms { denoms, pegged } => {
+            add_supported_denoms(deps, env, info, denoms, pegged.unwrap_or(false))
+        }
         ExecuteMsg::RemoveSupportedDenoms { denoms, .. } => {
-            remove_supported_denoms(deps, info, denoms)
+            remove_supported_denoms(deps, env, info, denoms)
         }
+        ExecuteMsg::EnableCheckpoints { .. } => enable_checkpoints(deps, env, info),
+        ExecuteMsg::PruneHistory { keep_last, .. } => try_prune_history(deps, info, keep_last),
+        ExecuteMsg::ClearHistory { .. } => try_clear_history(deps, info),
+        ExecuteMsg::SetTxLabel {
+            tx_id, note, tags, ..
+        } => try_set_tx_label(deps, info, tx_id, note, tags),
         ExecuteMsg::RegenerateCode {} => regenerate_code(deps, env, info),
-    };
+        ExecuteMsg::BackfillMembers { accounts, .. } => backfill_members(deps, env, info, accounts),
+        ExecuteMsg::SetMemberTiers { thresholds, .. } => set_member_tiers(deps, info, thresholds),
+        ExecuteMsg::ReevaluateMembers { accounts, .. } => {
+            reevaluate_members(deps, env, info, accounts)
+        }
+        ExecuteMsg::CreateInviteCode {
+            max_uses,
+            expiration_height,
+            expiration_time,
+            ..
+        } => create_invite_code(
+            deps,
+            env,
+            info,
+            max_uses,
+            expiration_height,
+            expiration_time,
+        ),
+        ExecuteMsg::ConsumeInviteCode { code, .. } => consume_invite_code(deps, env, info, code),
+        ExecuteMsg::AddCodeConsumers { consumers, .. } => add_code_consumers(deps, info, consumers),
+        ExecuteMsg::RemoveCodeConsumers { consumers, .. } => {
+            remove_code_consumers(deps, info, consumers)
+        }
+        ExecuteMsg::AddMembershipHook {
+            contract,
+            code_hash,
+            on_error,
+            ..
+        } => add_membership_hook(deps, info, contract, code_hash, on_error),
+        ExecuteMsg::RemoveMembershipHook { contract, .. } => {
+            remove_membership_hook(deps, info, contract)
+        }
+    }
+}
 
-    pad_handle_result(response, RESPONSE_BLOCK_SIZE)
+#[entry_point]
+pub fn reply(_deps: DepsMut, _env: Env, msg: Reply) -> StdResult<Response> {
+    match msg.id {
+        // Hooks registered with `HookErrorPolicy::Ignore` are not allowed to fail the transfer
+        MEMBERSHIP_HOOK_REPLY_ID => Ok(Response::default()),
+        id => Err(StdError::generic_err(format!("Unknown reply id: {id}"))),
+    }
 }
 
 #[entry_point]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     pad_query_result(
         match msg {
             QueryMsg::TokenInfo {} => query_token_info(deps.storage),
             QueryMsg::TokenConfig {} => query_token_config(deps.storage),
             QueryMsg::ContractStatus {} => query_contract_status(deps.storage),
+            QueryMsg::ContractVersion {} => query_contract_version(deps.storage),
             QueryMsg::ExchangeRate {} => query_exchange_rate(deps.storage),
-            QueryMsg::Minters { .. } => query_minters(deps),
-            QueryMsg::WithPermit { permit, query } => permit_queries(deps, permit, query),
-            QueryMsg::ValidCodes { codes } => query_valid_codes(deps.storage, codes),
-            _ => viewing_keys_queries(deps, msg),
+            QueryMsg::Minters { .. } => query_minters(deps, &env),
+            QueryMsg::RoleHolders {} => query_role_holders(deps.storage),
+            QueryMsg::AdminProposal {} => query_admin_proposal(deps.storage),
+            QueryMsg::QueuedOperations { page, page_size } => {
+                query_queued_operations(deps.storage, page.unwrap_or(0), page_size)
+            }
+            QueryMsg::AdminHistory { page, page_size } => {
+                query_admin_history(deps.storage, page.unwrap_or(0), page_size)
+            }
+            QueryMsg::WithPermit { permit, query } => permit_queries(deps, &env, permit, query),
+            QueryMsg::ValidCodes { codes } => query_valid_codes(deps.storage, &env, codes),
+            QueryMsg::MemberTiers {} => query_member_tiers(deps.storage),
+            QueryMsg::AttestationKey {} => query_attestation_key(deps.storage),
+            QueryMsg::CodeConsumers {} => query_code_consumers(deps.storage),
+            QueryMsg::MembershipHooks {} => query_membership_hooks(deps.storage),
+            QueryMsg::ListChannels {} => query_list_channels(),
+            _ => viewing_keys_queries(deps, &env, msg),
         },
         RESPONSE_BLOCK_SIZE,
     )
 }
 
-fn permit_queries(deps: Deps, permit: Permit, query: QueryWithPermit) -> Result<Binary, StdError> {
+fn permit_queries(
+    deps: Deps,
+    env: &Env,
+    permit: Permit<AmberPermissions>,
+    query: QueryWithPermit,
+) -> Result<Binary, StdError> {
     // Validate permit content
     let token_address = ConfigStore::load_constants(deps.storage)?.contract_address;
 
@@ -426,7 +796,7 @@ fn permit_queries(deps: Deps, permit: Permit, query: QueryWithPermit) -> Result<
     // Permit validated! We can now execute the query.
     match query {
         QueryWithPermit::Balance {} => {
-            if !permit.check_permission(&TokenPermissions::Balance) {
+            if !permit.check_permission(&AmberPermissions::Balance) {
                 return Err(StdError::generic_err(format!(
                     "No permission to query balance, got permissions {:?}",
                     permit.params.permissions
@@ -435,12 +805,35 @@ fn permit_queries(deps: Deps, permit: Permit, query: QueryWithPermit) -> Result<
 
             query_balance(deps, account)
         }
+        QueryWithPermit::BalanceAt { height } => {
+            if !permit.check_permission(&AmberPermissions::Balance) {
+                return Err(StdError::generic_err(format!(
+                    "No permission to query balance, got permissions {:?}",
+                    permit.params.permissions
+                )));
+            }
+
+            query_balance_at(deps, account, height)
+        }
+        QueryWithPermit::WasMemberAt { height } => {
+            if !permit.check_permission(&AmberPermissions::Balance) {
+                return Err(StdError::generic_err(format!(
+                    "No permission to query balance, got permissions {:?}",
+                    permit.params.permissions
+                )));
+            }
+
+            query_was_member_at(deps, account, height)
+        }
         QueryWithPermit::TransferHistory {
             page,
             page_size,
             should_filter_decoys,
+            start_after_id,
+            direction,
+            filter,
         } => {
-            if !permit.check_permission(&TokenPermissions::History) {
+            if !permit.check_permission(&AmberPermissions::History) {
                 return Err(StdError::generic_err(format!(
                     "No permission to query history, got permissions {:?}",
                     permit.params.permissions
@@ -453,14 +846,20 @@ fn permit_queries(deps: Deps, permit: Permit, query: QueryWithPermit) -> Result<
                 page.unwrap_or(0),
                 page_size,
                 should_filter_decoys,
+                start_after_id,
+                direction,
+                filter,
             )
         }
         QueryWithPermit::TransactionHistory {
             page,
             page_size,
             should_filter_decoys,
+            start_after_id,
+            direction,
+            filter,
         } => {
-            if !permit.check_permission(&TokenPermissions::History) {
+            if !permit.check_permission(&AmberPermissions::History) {
                 return Err(StdError::generic_err(format!(
                     "No permission to query history, got permissions {:?}",
                     permit.params.permissions
@@ -473,10 +872,13 @@ fn permit_queries(deps: Deps, permit: Permit, query: QueryWithPermit) -> Result<
                 page.unwrap_or(0),
                 page_size,
                 should_filter_decoys,
+                start_after_id,
+                direction,
+                filter,
             )
         }
         QueryWithPermit::Allowance { owner, spender } => {
-            if !permit.check_permission(&TokenPermissions::Allowance) {
+            if !permit.check_permission(&AmberPermissions::Allowance) {
                 return Err(StdError::generic_err(format!(
                     "No permission to query allowance, got permissions {:?}",
                     permit.params.permissions
@@ -505,8 +907,8 @@ fn permit_queries(deps: Deps, permit: Permit, query: QueryWithPermit) -> Result<
 
             // we really should add a check_permission(s) function.. an owner permit should
             // just give you permissions to do everything
-            if !permit.check_permission(&TokenPermissions::Allowance)
-                && !permit.check_permission(&TokenPermissions::Owner)
+            if !permit.check_permission(&AmberPermissions::Allowance)
+                && !permit.check_permission(&AmberPermissions::Owner)
             {
                 return Err(StdError::generic_err(format!(
                     "No permission to query all allowances, got permissions {:?}",
@@ -526,8 +928,8 @@ fn permit_queries(deps: Deps, permit: Permit, query: QueryWithPermit) -> Result<
                 ));
             }
 
-            if !permit.check_permission(&TokenPermissions::Allowance)
-                && !permit.check_permission(&TokenPermissions::Owner)
+            if !permit.check_permission(&AmberPermissions::Allowance)
+                && !permit.check_permission(&AmberPermissions::Owner)
             {
                 return Err(StdError::generic_err(format!(
                     "No permission to query all allowed, got permissions {:?}",
@@ -537,7 +939,7 @@ fn permit_queries(deps: Deps, permit: Permit, query: QueryWithPermit) -> Result<
             query_allowances_received(deps, account, page.unwrap_or(0), page_size)
         }
         QueryWithPermit::MemberCode {} => {
-            if !permit.check_permission(&TokenPermissions::Balance) {
+            if !permit.check_permission(&AmberPermissions::Balance) {
                 return Err(StdError::generic_err(format!(
                     "No permission to query balance, got permissions {:?}",
                     permit.params.permissions
@@ -546,10 +948,56 @@ fn permit_queries(deps: Deps, permit: Permit, query: QueryWithPermit) -> Result<
 
             query_member_code(deps, account)
         }
+        QueryWithPermit::MembershipAttestation {} => {
+            if !permit.check_permission(&AmberPermissions::Balance) {
+                return Err(StdError::generic_err(format!(
+                    "No permission to query balance, got permissions {:?}",
+                    permit.params.permissions
+                )));
+            }
+
+            query_membership_attestation(deps, env, account)
+        }
+        QueryWithPermit::InviteCodes {} => {
+            if !permit.check_permission(&AmberPermissions::Balance) {
+                return Err(StdError::generic_err(format!(
+                    "No permission to query balance, got permissions {:?}",
+                    permit.params.permissions
+                )));
+            }
+
+            query_invite_codes(deps, env, account)
+        }
+        QueryWithPermit::ChannelInfo { channels, txhash } => {
+            if !permit.check_permission(&AmberPermissions::Owner) {
+                return Err(StdError::generic_err(format!(
+                    "No permission to query channel info, got permissions {:?}",
+                    permit.params.permissions
+                )));
+            }
+
+            query_channel_info(deps, env, account, channels, txhash)
+        }
+        QueryWithPermit::HasAtLeast { amount, attest } => {
+            if !permits_has_at_least(&permit, amount) {
+                return Err(StdError::generic_err(format!(
+                    "No permission to query has_at_least, got permissions {:?}",
+                    permit.params.permissions
+                )));
+            }
+
+            query_has_at_least(deps, env, account, amount, attest.unwrap_or_default())
+        }
     }
 }
 
-pub fn viewing_keys_queries(deps: Deps, msg: QueryMsg) -> StdResult<Binary> {
+/// A balance permit allows any threshold, a `HasAtLeast` permit only the amount it was signed for.
+fn permits_has_at_least(permit: &Permit<AmberPermissions>, amount: Uint128) -> bool {
+    permit.check_permission(&AmberPermissions::Balance)
+        || permit.check_permission(&AmberPermissions::HasAtLeast { amount })
+}
+
+pub fn viewing_keys_queries(deps: Deps, env: &Env, msg: QueryMsg) -> StdResult<Binary> {
     let (addresses, key) = msg.get_validation_params(deps.api)?;
 
     for address in addresses {
@@ -560,11 +1008,20 @@ pub fn viewing_keys_queries(deps: Deps, msg: QueryMsg) -> StdResult<Binary> {
             return match msg {
                 // Base
                 QueryMsg::Balance { address, .. } => query_balance(deps, address),
+                QueryMsg::BalanceAt {
+                    address, height, ..
+                } => query_balance_at(deps, address, height),
+                QueryMsg::WasMemberAt {
+                    address, height, ..
+                } => query_was_member_at(deps, address, height),
                 QueryMsg::TransferHistory {
                     address,
                     page,
                     page_size,
                     should_filter_decoys,
+                    start_after_id,
+                    direction,
+                    filter,
                     ..
                 } => query_transfers(
                     deps,
@@ -572,12 +1029,18 @@ pub fn viewing_keys_queries(deps: Deps, msg: QueryMsg) -> StdResult<Binary> {
                     page.unwrap_or(0),
                     page_size,
                     should_filter_decoys,
+                    start_after_id,
+                    direction,
+                    filter,
                 ),
                 QueryMsg::TransactionHistory {
                     address,
                     page,
                     page_size,
                     should_filter_decoys,
+                    start_after_id,
+                    direction,
+                    filter,
                     ..
                 } => query_transactions(
                     deps,
@@ -585,6 +1048,9 @@ pub fn viewing_keys_queries(deps: Deps, msg: QueryMsg) -> StdResult<Binary> {
                     page.unwrap_or(0),
                     page_size,
                     should_filter_decoys,
+                    start_after_id,
+                    direction,
+                    filter,
                 ),
                 QueryMsg::Allowance { owner, spender, .. } => query_allowance(deps, owner, spender),
                 QueryMsg::AllowancesGiven {
@@ -600,6 +1066,13 @@ pub fn viewing_keys_queries(deps: Deps, msg: QueryMsg) -> StdResult<Binary> {
                     ..
                 } => query_allowances_received(deps, spender, page.unwrap_or(0), page_size),
                 QueryMsg::MemberCode { address, .. } => query_member_code(deps, address),
+                QueryMsg::InviteCodes { address, .. } => query_invite_codes(deps, env, address),
+                QueryMsg::ChannelInfo {
+                    address,
+                    channels,
+                    txhash,
+                    ..
+                } => query_channel_info(deps, env, address, channels, txhash),
                 _ => panic!("This query type does not require authentication"),
             };
         }
@@ -647,6 +1120,7 @@ fn query_token_info(storage: &dyn Storage) -> StdResult<Binary> {
         symbol: constants.symbol,
         decimals: constants.decimals,
         total_supply,
+        max_supply: constants.max_supply.map(Uint128::new),
     })
 }
 
@@ -660,23 +1134,36 @@ fn query_token_config(storage: &dyn Storage) -> StdResult<Binary> {
         mint_enabled: constants.mint_is_enabled,
         burn_enabled: constants.burn_is_enabled,
         supported_denoms: constants.supported_denoms,
+        max_decoys: MAX_DECOYS,
+        gas_evaporation: true,
+        max_supply: constants.max_supply.map(Uint128::new),
     })
 }
 
 fn query_contract_status(storage: &dyn Storage) -> StdResult<Binary> {
-    let contract_status = ConfigStore::load_contract_status(storage)?;
+    let flags = ConfigStore::load_pause_flags(storage)?;
 
     to_binary(&QueryAnswer::ContractStatus {
-        status: contract_status,
+        status: flags.level(),
+        flags,
     })
 }
 
+fn query_contract_version(storage: &dyn Storage) -> StdResult<Binary> {
+    let version = ContractVersionStore::may_load(storage)?;
+
+    to_binary(&QueryAnswer::ContractVersion { version })
+}
+
 pub fn query_transfers(
     deps: Deps,
     account: String,
     page: u32,
     page_size: u32,
     should_filter_decoys: Option<bool>,
+    start_after_id: Option<u64>,
+    direction: Option<HistoryDirection>,
+    filter: Option<TxFilter>,
 ) -> StdResult<Binary> {
     // Notice that if query_transfers() was called by a viewking-key call, the address of 'account'
     // has already been validated.
@@ -685,18 +1172,29 @@ pub fn query_transfers(
     let account = Addr::unchecked(account);
     let account = deps.api.addr_canonicalize(account.as_str())?;
 
-    let (txs, total) = StoredLegacyTransfer::get_transfers(
+    let filter = match filter {
+        Some(filter) => Some(filter.into_stored(deps.api)?),
+        None => None,
+    };
+
+    let pruned_through = history_pruned_through(deps.storage, &account);
+    let (txs, total, next_cursor) = StoredLegacyTransfer::get_transfers(
         deps.api,
         deps.storage,
         account,
         page,
         page_size,
         should_filter_decoys.unwrap_or_default(),
+        start_after_id,
+        direction.unwrap_or_default(),
+        filter.as_ref(),
     )?;
 
     let result = QueryAnswer::TransferHistory {
         txs,
-        total: Some(total),
+        total,
+        next_cursor,
+        pruned_through,
     };
     to_binary(&result)
 }
@@ -707,6 +1205,9 @@ pub fn query_transactions(
     page: u32,
     page_size: u32,
     should_filter_decoys: Option<bool>,
+    start_after_id: Option<u64>,
+    direction: Option<HistoryDirection>,
+    filter: Option<TxFilter>,
 ) -> StdResult<Binary> {
     // Notice that if query_transactions() was called by a viewing-key call, the address of
     // 'account' has already been validated.
@@ -715,18 +1216,29 @@ pub fn query_transactions(
     let account = Addr::unchecked(account);
     let account = deps.api.addr_canonicalize(account.as_str())?;
 
-    let (txs, total) = StoredExtendedTx::get_txs(
+    let filter = match filter {
+        Some(filter) => Some(filter.into_stored(deps.api)?),
+        None => None,
+    };
+
+    let pruned_through = history_pruned_through(deps.storage, &account);
+    let (txs, total, next_cursor) = StoredExtendedTx::get_txs(
         deps.api,
         deps.storage,
         account,
         page,
         page_size,
         should_filter_decoys.unwrap_or_default(),
+        start_after_id,
+        direction.unwrap_or_default(),
+        filter.as_ref(),
     )?;
 
     let result = QueryAnswer::TransactionHistory {
         txs,
-        total: Some(total),
+        total,
+        next_cursor,
+        pruned_through,
     };
     to_binary(&result)
 }
@@ -739,18 +1251,119 @@ pub fn query_balance(deps: Deps, account: String) -> StdResult<Binary> {
     let account = Addr::unchecked(account);
     let account = deps.api.addr_canonicalize(account.as_str())?;
 
-    let amount = Uint128::new(BalancesStore::load(deps.storage, &account));
+    let amount = Uint128::new(BalancesStore::load(deps.storage, &account)?);
     let response = QueryAnswer::Balance { amount };
     to_binary(&response)
 }
 
-fn query_minters(deps: Deps) -> StdResult<Binary> {
+fn load_checkpoint_at(deps: Deps, account: String, height: u64) -> StdResult<Checkpoint> {
+    // Same as query_balance(), the address has either been validated or belongs to a permit.
+    let account = Addr::unchecked(account);
+    let account = deps.api.addr_canonicalize(account.as_str())?;
+
+    CheckpointsStore::load_at(deps.storage, &account, height)?
+        .ok_or_else(|| StdError::generic_err(format!("No checkpoint at or before height {height}")))
+}
+
+pub fn query_balance_at(deps: Deps, account: String, height: u64) -> StdResult<Binary> {
+    let checkpoint = load_checkpoint_at(deps, account, height)?;
+
+    let response = QueryAnswer::BalanceAt {
+        amount: Uint128::new(checkpoint.balance),
+        checkpoint_height: checkpoint.height,
+    };
+    to_binary(&response)
+}
+
+pub fn query_was_member_at(deps: Deps, account: String, height: u64) -> StdResult<Binary> {
+    let checkpoint = load_checkpoint_at(deps, account, height)?;
+
+    let response = QueryAnswer::WasMemberAt {
+        member: checkpoint.tier.is_some(),
+        tier: checkpoint.tier,
+        checkpoint_height: checkpoint.height,
+    };
+    to_binary(&response)
+}
+
+fn query_minters(deps: Deps, env: &Env) -> StdResult<Binary> {
     let minters = MintersStore::load(deps.storage)?;
 
-    let response = QueryAnswer::Minters { minters };
+    let quotas = minters
+        .iter()
+        .map(|minter| {
+            let quota = MintersStore::load_quota(deps.storage, minter).unwrap_or_default();
+            let period = quota.remaining_in_period(&env.block);
+            MinterQuotaInfo {
+                minter: minter.clone(),
+                allowance: quota.allowance.map(Uint128::new),
+                minted: Uint128::new(quota.minted),
+                remaining: quota.remaining().map(Uint128::new),
+                rate: quota.rate,
+                remaining_in_period: period.map(|(remaining, _)| Uint128::new(remaining)),
+                next_period_at: period.map(|(_, next_period_at)| next_period_at),
+            }
+        })
+        .collect();
+
+    let response = QueryAnswer::Minters { minters, quotas };
     to_binary(&response)
 }
 
+fn query_role_holders(storage: &dyn Storage) -> StdResult<Binary> {
+    let admin = ConfigStore::load_constants(storage)?.admin;
+    let roles = ROLES
+        .iter()
+        .map(|role| {
+            Ok(RoleHoldersInfo {
+                role: *role,
+                holders: RolesStore::load_holders(storage, *role)?,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    to_binary(&QueryAnswer::RoleHolders { admin, roles })
+}
+
+fn query_admin_proposal(storage: &dyn Storage) -> StdResult<Binary> {
+    let admin = ConfigStore::load_constants(storage)?.admin;
+    let proposal = ADMIN_PROPOSAL.may_load(storage)?;
+
+    to_binary(&QueryAnswer::AdminProposal {
+        admin,
+        proposed_admin: proposal.as_ref().map(|proposal| proposal.address.clone()),
+        expiration: proposal.and_then(|proposal| proposal.expiration),
+    })
+}
+
+fn query_admin_history(storage: &dyn Storage, page: u32, page_size: u32) -> StdResult<Binary> {
+    let (entries, total) = AdminHistoryStore::load_page(storage, page, page_size)?;
+
+    to_binary(&QueryAnswer::AdminHistory { entries, total })
+}
+
+fn query_queued_operations(storage: &dyn Storage, page: u32, page_size: u32) -> StdResult<Binary> {
+    let (operations, total) = TimelockStore::load_page(storage, page, page_size)?;
+    let operations = operations
+        .into_iter()
+        .map(|(id, operation)| {
+            Ok(QueuedOperationInfo {
+                id,
+                sender: operation.sender.clone(),
+                msg: from_binary(&operation.msg)?,
+                not_before: operation.not_before,
+                expires_at: operation.expires_at(),
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    to_binary(&QueryAnswer::QueuedOperations {
+        delay: TimelockStore::delay(storage)?,
+        operations,
+        total,
+    })
+}
+
 // Removed for privacy concerns.
 // fn query_member_count(storage: &dyn Storage, key: String) -> StdResult<Binary> {
 //     super::amber::special::check_special_key(storage, key)?;
@@ -769,562 +1382,941 @@ fn query_member_code(deps: Deps, account: String) -> StdResult<Binary> {
     let account = deps.api.addr_canonicalize(account.as_str())?;
 
     let code = OneAmberStore::get_code(deps.storage, &account);
-    let response = QueryAnswer::MemberCode { code };
+    let tier = OneAmberStore::get_tier(deps.storage, &account);
+    let response = QueryAnswer::MemberCode { code, tier };
     to_binary(&response)
 }
 
-fn query_valid_codes(storage: &dyn Storage, codes: Vec<String>) -> StdResult<Binary> {
-    let valid_codes = OneAmberStore::validate_codes(storage, codes);
+fn query_valid_codes(storage: &dyn Storage, env: &Env, codes: Vec<String>) -> StdResult<Binary> {
+    let valid_codes = OneAmberStore::validate_codes(storage, &env.block, codes)
+        .into_iter()
+        .map(|(code, tier)| ValidCode { code, tier })
+        .collect();
     let response = QueryAnswer::ValidCodes { codes: valid_codes };
     to_binary(&response)
 }
 
-fn change_admin(deps: DepsMut, info: MessageInfo, address: String) -> StdResult<Response> {
-    let address = deps.api.addr_validate(address.as_str())?;
+fn query_invite_codes(deps: Deps, env: &Env, account: String) -> StdResult<Binary> {
+    // Notice that if query_invite_codes() was called by a viewing-key call, the address of
+    // 'account' has already been validated.
+    // The address of 'account' should not be validated if query_invite_codes() was called by a
+    // permit call, for compatibility with non-Secret addresses.
+    let account = Addr::unchecked(account);
+    let account = deps.api.addr_canonicalize(account.as_str())?;
 
-    let mut constants = ConfigStore::load_constants(deps.storage)?;
-    check_if_admin(&constants.admin, &info.sender)?;
+    let codes = OneAmberStore::invite_codes(deps.storage, &account, &env.block)?
+        .into_iter()
+        .map(|(code, invite)| InviteCodeInfo {
+            code,
+            remaining_uses: invite.remaining_uses,
+            expiration_height: invite.expiration_height,
+            expiration_time: invite.expiration_time,
+        })
+        .collect();
+    let response = QueryAnswer::InviteCodes { codes };
+    to_binary(&response)
+}
 
-    constants.admin = address;
-    ConfigStore::set_constants(deps.storage, &constants)?;
+fn query_code_consumers(storage: &dyn Storage) -> StdResult<Binary> {
+    let consumers = OneAmberStore::load_consumers(storage)?;
+    let response = QueryAnswer::CodeConsumers { consumers };
+    to_binary(&response)
+}
 
-    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::ChangeAdmin { status: Success })?))
+fn query_membership_hooks(storage: &dyn Storage) -> StdResult<Binary> {
+    let hooks = MembershipHooksStore::load_all(storage)?
+        .into_iter()
+        .map(|(contract, hook)| MembershipHookInfo {
+            contract,
+            code_hash: hook.code_hash,
+            on_error: hook.on_error,
+        })
+        .collect();
+    let response = QueryAnswer::MembershipHooks { hooks };
+    to_binary(&response)
 }
 
-fn add_supported_denoms(
-    deps: DepsMut,
-    info: MessageInfo,
-    denoms: Vec<String>,
-) -> StdResult<Response> {
-    let mut config = ConfigStore::load_constants(deps.storage)?;
+fn query_list_channels() -> StdResult<Binary> {
+    let response = QueryAnswer::ListChannels {
+        channels: CHANNELS.iter().map(|channel| channel.to_string()).collect(),
+    };
+    to_binary(&response)
+}
 
-    check_if_admin(&config.admin, &info.sender)?;
-    // if !config.can_modify_denoms {
-    //     return Err(StdError::generic_err(
-    //         "Cannot modify denoms for this contract",
-    //     ));
-    // }
+fn query_channel_info(
+    deps: Deps,
+    env: &Env,
+    account: String,
+    channels: Vec<String>,
+    txhash: Option<String>,
+) -> StdResult<Binary> {
+    // Same as query_balance(), the address has either been validated or belongs to a permit.
+    let account = Addr::unchecked(account);
+    let account = deps.api.addr_canonicalize(account.as_str())?;
+    let seed = notification_seed(deps.storage, &account)?;
 
-    for denom in denoms.iter() {
-        if !config.supported_denoms.contains(denom) {
-            config.supported_denoms.push(denom.clone());
-        }
-    }
+    let mut channels_data = Vec::with_capacity(channels.len());
+    for channel in channels {
+        let cddl = match cddl(&channel) {
+            Some(cddl) => cddl,
+            None => {
+                return Err(StdError::generic_err(format!(
+                    "Unknown notification channel: {channel}"
+                )))
+            }
+        };
+        let answer_id = match &txhash {
+            Some(txhash) => {
+                Some(Binary::from(notification_id(&seed, &channel, txhash)?).to_base64())
+            }
+            None => None,
+        };
 
-    ConfigStore::set_constants(deps.storage, &config)?;
+        channels_data.push(ChannelInfoData {
+            channel,
+            mode: "txhash".to_string(),
+            answer_id,
+            cddl: Some(cddl.to_string()),
+        });
+    }
 
-    Ok(
-        Response::new().set_data(to_binary(&ExecuteAnswer::AddSupportedDenoms {
-            status: Success,
-        })?),
-    )
+    let response = QueryAnswer::ChannelInfo {
+        as_of_block: Uint64::from(env.block.height),
+        channels: channels_data,
+        seed: Binary::from(seed.to_vec()),
+    };
+    to_binary(&response)
 }
 
-fn remove_supported_denoms(
-    deps: DepsMut,
-    info: MessageInfo,
-    denoms: Vec<String>,
-) -> StdResult<Response> {
-    let mut config = ConfigStore::load_constants(deps.storage)?;
+fn query_attestation_key(storage: &dyn Storage) -> StdResult<Binary> {
+    let pubkey = OneAmberStore::attestation_pubkey(storage)?;
+    let response = QueryAnswer::AttestationKey { pubkey };
+    to_binary(&response)
+}
 
-    check_if_admin(&config.admin, &info.sender)?;
-    // if !config.can_modify_denoms {
-    //     return Err(StdError::generic_err(
-    //         "Cannot modify denoms for this contract",
-    //     ));
-    // }
+fn query_membership_attestation(deps: Deps, env: &Env, account: String) -> StdResult<Binary> {
+    // The address of 'account' should not be validated since query_membership_attestation() is
+    // only called by a permit call, for compatibility with non-Secret addresses.
+    let canonical = deps.api.addr_canonicalize(account.as_str())?;
 
-    for denom in denoms.iter() {
-        config.supported_denoms.retain(|x| x != denom);
-    }
+    let tier = match OneAmberStore::get_tier(deps.storage, &canonical) {
+        Some(tier) => tier,
+        None => return Err(StdError::generic_err("Account is not an OAC member")),
+    };
 
-    ConfigStore::set_constants(deps.storage, &config)?;
+    let attestation = Attestation {
+        contract: env.contract.address.clone(),
+        address_hash: Binary::from(sha_256(account.as_bytes()).to_vec()),
+        tier,
+        block_height: env.block.height,
+        expires_at: env.block.time.seconds() + ATTESTATION_TTL,
+    };
+    let (data, signature) = OneAmberStore::sign_attestation(deps.storage, deps.api, &attestation)?;
 
-    Ok(
-        Response::new().set_data(to_binary(&ExecuteAnswer::RemoveSupportedDenoms {
-            status: Success,
-        })?),
-    )
+    let response = QueryAnswer::MembershipAttestation {
+        attestation,
+        data,
+        signature,
+    };
+    to_binary(&response)
 }
 
-fn regenerate_code(deps: DepsMut, env: Env, info: MessageInfo) -> StdResult<Response> {
-    let account = deps.api.addr_canonicalize(info.sender.as_str())?;
-    let code = OneAmberStore::regenerate_code(deps.storage, &account, &env)?;
+fn query_has_at_least(
+    deps: Deps,
+    env: &Env,
+    account: String,
+    amount: Uint128,
+    attest: bool,
+) -> StdResult<Binary> {
+    // Same as query_membership_attestation(), the address belongs to a permit.
+    let canonical = deps.api.addr_canonicalize(account.as_str())?;
+    let result = BalancesStore::load(deps.storage, &canonical)? >= amount.u128();
+
+    let (attestation, data, signature) = if attest {
+        let attestation = BalanceAttestation {
+            contract: env.contract.address.clone(),
+            address_hash: Binary::from(sha_256(account.as_bytes()).to_vec()),
+            amount,
+            has_at_least: result,
+            block_height: env.block.height,
+            expires_at: env.block.time.seconds() + ATTESTATION_TTL,
+        };
+        let (data, signature) =
+            OneAmberStore::sign_attestation(deps.storage, deps.api, &attestation)?;
+        (Some(attestation), Some(data), Some(signature))
+    } else {
+        (None, None, None)
+    };
 
-    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::RegenerateCode { code })?))
+    let response = QueryAnswer::HasAtLeast {
+        result,
+        attestation,
+        data,
+        signature,
+    };
+    to_binary(&response)
 }
 
-#[allow(clippy::too_many_arguments)]
-fn try_mint_impl(
-    deps: &mut DepsMut,
+fn query_member_tiers(storage: &dyn Storage) -> StdResult<Binary> {
+    let thresholds = OneAmberStore::load_tiers(storage)
+        .into_iter()
+        .map(Uint128::new)
+        .collect();
+    let response = QueryAnswer::MemberTiers { thresholds };
+    to_binary(&response)
+}
+
+fn propose_admin_impl(
+    deps: DepsMut,
     env: &Env,
-    minter: Addr,
-    recipient: Addr,
-    amount: Uint128,
-    denom: String,
-    memo: Option<String>,
-    block: &cosmwasm_std::BlockInfo,
-    decoys: Option<Vec<Addr>>,
-    account_random_pos: Option<usize>,
+    info: MessageInfo,
+    address: String,
+    expiration: Option<u64>,
 ) -> StdResult<()> {
-    let raw_amount = amount.u128();
-    let minter = deps.api.addr_canonicalize(minter.as_str())?;
-    let recipient = deps.api.addr_canonicalize(recipient.as_str())?;
-
-    let decoys = convert_decoys(&decoys, deps.api)?;
+    let constants = ConfigStore::load_constants(deps.storage)?;
+    check_if_admin(&constants.admin, &info.sender)?;
 
-    BalancesStore::update_balance(
+    let address = deps.api.addr_validate(address.as_str())?;
+    ADMIN_PROPOSAL.save(
         deps.storage,
-        env,
-        &recipient,
-        raw_amount,
-        true,
-        "",
-        &decoys,
-        &account_random_pos,
+        &AdminProposal {
+            address: address.clone(),
+            expiration,
+        },
     )?;
 
-    store_mint(
+    AdminHistoryStore::record(
         deps.storage,
-        minter,
-        recipient,
-        amount,
-        denom,
-        memo,
-        block,
-        &decoys,
-        &account_random_pos,
-    )?;
+        env,
+        &info.sender,
+        AdminAction::ProposeAdmin {
+            address,
+            expiration,
+        },
+    )
+}
 
-    Ok(())
+fn change_admin(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    address: String,
+) -> StdResult<Response> {
+    propose_admin_impl(deps, &env, info, address, None)?;
+
+    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::ChangeAdmin { status: Success })?))
 }
 
-#[allow(clippy::too_many_arguments)]
-fn try_mint(
-    mut deps: DepsMut,
+fn propose_admin(
+    deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    recipient: String,
-    amount: Uint128,
-    memo: Option<String>,
-    decoys: Option<Vec<Addr>>,
-    account_random_pos: Option<usize>,
+    address: String,
+    expiration: Option<u64>,
 ) -> StdResult<Response> {
-    let recipient = deps.api.addr_validate(recipient.as_str())?;
+    propose_admin_impl(deps, &env, info, address, expiration)?;
 
-    let constants = ConfigStore::load_constants(deps.storage)?;
+    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::ProposeAdmin { status: Success })?))
+}
 
-    if !constants.mint_is_enabled {
-        return Err(StdError::generic_err(
-            "Mint functionality is not enabled for this token.",
-        ));
+fn accept_admin(deps: DepsMut, env: Env, info: MessageInfo) -> StdResult<Response> {
+    let proposal = match ADMIN_PROPOSAL.may_load(deps.storage)? {
+        Some(proposal) if proposal.address == info.sender => proposal,
+        _ => {
+            return Err(StdError::generic_err(
+                "There is no pending admin proposal for this address",
+            ))
+        }
+    };
+    if proposal.is_expired_at(&env.block) {
+        return Err(StdError::generic_err("The admin proposal has expired"));
     }
 
-    let minters = MintersStore::load(deps.storage)?;
-    if !minters.contains(&info.sender) {
-        return Err(StdError::generic_err(
-            "Minting is allowed to minter accounts only",
-        ));
+    let mut constants = ConfigStore::load_constants(deps.storage)?;
+    // The new admin takes over the roles the old one still held
+    if let Some(old_admin) = &constants.admin {
+        RolesStore::transfer_all(deps.storage, old_admin, &proposal.address)?;
     }
+    let previous_admin = constants.admin.replace(proposal.address);
+    ConfigStore::set_constants(deps.storage, &constants)?;
+    ADMIN_PROPOSAL.remove(deps.storage);
 
-    let mut total_supply = ConfigStore::load_total_supply(deps.storage)?;
-    let minted_amount = safe_add(&mut total_supply, amount.u128());
-    ConfigStore::set_total_supply(deps.storage, &total_supply)?;
-
-    // Note that even when minted_amount is equal to 0 we still want to perform the operations for logic consistency
-    try_mint_impl(
-        &mut deps,
+    AdminHistoryStore::record(
+        deps.storage,
         &env,
-        info.sender,
-        recipient,
-        Uint128::new(minted_amount),
-        constants.symbol,
-        memo,
-        &env.block,
-        decoys,
-        account_random_pos,
+        &info.sender,
+        AdminAction::AcceptAdmin { previous_admin },
     )?;
 
-    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::Mint { status: Success })?))
+    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::AcceptAdmin { status: Success })?))
 }
 
-fn try_batch_mint(
-    mut deps: DepsMut,
-    env: Env,
-    info: MessageInfo,
-    actions: Vec<batch::MintAction>,
-    account_random_pos: Option<usize>,
-) -> StdResult<Response> {
+fn cancel_admin_proposal(deps: DepsMut, env: Env, info: MessageInfo) -> StdResult<Response> {
     let constants = ConfigStore::load_constants(deps.storage)?;
+    check_if_admin(&constants.admin, &info.sender)?;
 
-    if !constants.mint_is_enabled {
-        return Err(StdError::generic_err(
-            "Mint functionality is not enabled for this token.",
-        ));
-    }
-
-    let minters = MintersStore::load(deps.storage)?;
-    if !minters.contains(&info.sender) {
-        return Err(StdError::generic_err(
-            "Minting is allowed to minter accounts only",
-        ));
-    }
-
-    let mut total_supply = ConfigStore::load_total_supply(deps.storage)?;
-
-    // Quick loop to check that the total of amounts is valid
-    for action in actions {
-        let actual_amount = safe_add(&mut total_supply, action.amount.u128());
-
-        let recipient = deps.api.addr_validate(action.recipient.as_str())?;
-        try_mint_impl(
-            &mut deps,
-            &env,
-            info.sender.clone(),
-            recipient,
-            Uint128::new(actual_amount),
-            constants.symbol.clone(),
-            action.memo,
-            &env.block,
-            action.decoys,
-            account_random_pos,
-        )?;
+    if ADMIN_PROPOSAL.may_load(deps.storage)?.is_none() {
+        return Err(StdError::generic_err("There is no pending admin proposal"));
     }
+    ADMIN_PROPOSAL.remove(deps.storage);
 
-    ConfigStore::set_total_supply(deps.storage, &total_supply)?;
-
-    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::BatchMint { status: Success })?))
-}
+    AdminHistoryStore::record(
+        deps.storage,
+        &env,
+        &info.sender,
+        AdminAction::CancelAdminProposal {},
+    )?;
 
-pub fn try_set_key(deps: DepsMut, info: MessageInfo, key: String) -> StdResult<Response> {
-    let canonical_addr = deps.api.addr_canonicalize(info.sender.as_str())?;
-    ViewingKey::set(deps.storage, canonical_addr.as_slice(), key.as_str());
     Ok(
-        Response::new().set_data(to_binary(&ExecuteAnswer::SetViewingKey {
+        Response::new().set_data(to_binary(&ExecuteAnswer::CancelAdminProposal {
             status: Success,
         })?),
     )
 }
 
-pub fn try_create_key(
+/// Who may send a message that waits out the timelock.
+enum Authority {
+    Admin,
+    Role(Role),
+}
+
+/// The admin messages that wait out the timelock, and who may queue them. Messages that only
+/// take powers away, and pausing operations in an emergency, always take effect right away.
+fn timelocked_authority(pause_flags: &PauseFlags, msg: &ExecuteMsg) -> Option<Authority> {
+    match msg {
+        ExecuteMsg::ChangeAdmin { .. }
+        | ExecuteMsg::ProposeAdmin { .. }
+        | ExecuteMsg::GrantRole { .. }
+        | ExecuteMsg::SetTimelock { .. } => Some(Authority::Admin),
+        ExecuteMsg::SetContractStatus { level, .. } if pause_flags.resumed_by(&level.flags()) => {
+            Some(Authority::Role(Role::Pauser))
+        }
+        ExecuteMsg::SetPauseFlags { flags, .. } if pause_flags.resumed_by(flags) => {
+            Some(Authority::Role(Role::Pauser))
+        }
+        ExecuteMsg::AddMinters { .. }
+        | ExecuteMsg::SetMinters { .. }
+        | ExecuteMsg::SetMinterQuota { .. } => Some(Authority::Role(Role::MinterManager)),
+        ExecuteMsg::AddSupportedDenoms { .. } | ExecuteMsg::RemoveSupportedDenoms { .. } => {
+            Some(Authority::Role(Role::DenomManager))
+        }
+        ExecuteMsg::SetMemberTiers { .. } | ExecuteMsg::AddCodeConsumers { .. } => {
+            Some(Authority::Role(Role::OacManager))
+        }
+        ExecuteMsg::AddMembershipHook { .. } => Some(Authority::Role(Role::HookManager)),
+        _ => None,
+    }
+}
+
+fn queue_operation(
     deps: DepsMut,
-    env: Env,
+    env: &Env,
     info: MessageInfo,
-    entropy: String,
+    msg: ExecuteMsg,
+    authority: Authority,
+    delay: u64,
 ) -> StdResult<Response> {
-    let canonical_addr = deps.api.addr_canonicalize(info.sender.as_str())?;
-    let key = ViewingKey::create(
+    match authority {
+        Authority::Admin => {
+            let constants = ConfigStore::load_constants(deps.storage)?;
+            check_if_admin(&constants.admin, &info.sender)?;
+        }
+        Authority::Role(role) => RolesStore::check_role(deps.storage, role, &info.sender)?,
+    }
+
+    let not_before = env.block.height.saturating_add(delay);
+    let id = TimelockStore::queue(
         deps.storage,
-        &info,
-        &env,
-        canonical_addr.as_slice(),
-        entropy.as_ref(),
-    );
+        &QueuedOperation {
+            sender: info.sender,
+            msg: to_binary(&msg)?,
+            not_before,
+        },
+    )?;
 
-    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::CreateViewingKey { key })?))
+    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::Queued { id, not_before })?))
 }
 
-fn set_contract_status(
-    deps: DepsMut,
-    info: MessageInfo,
-    status_level: ContractStatusLevel,
-) -> StdResult<Response> {
+fn set_timelock(deps: DepsMut, env: Env, info: MessageInfo, delay: u64) -> StdResult<Response> {
     let constants = ConfigStore::load_constants(deps.storage)?;
     check_if_admin(&constants.admin, &info.sender)?;
+    if delay > MAX_TIMELOCK_DELAY {
+        return Err(StdError::generic_err(format!(
+            "The timelock delay cannot be more than {MAX_TIMELOCK_DELAY} blocks"
+        )));
+    }
 
-    ConfigStore::set_contract_status(deps.storage, &status_level)?;
-
-    Ok(
-        Response::new().set_data(to_binary(&ExecuteAnswer::SetContractStatus {
-            status: Success,
-        })?),
-    )
-}
+    TimelockStore::set_delay(deps.storage, delay)?;
 
-pub fn query_allowance(deps: Deps, owner: String, spender: String) -> StdResult<Binary> {
-    // Notice that if query_allowance() was called by a viewing-key call, the addresses of 'owner'
-    // and 'spender' have already been validated.
-    // The addresses of 'owner' and 'spender' should not be validated if query_allowance() was
-    // called by a permit call, for compatibility with non-Secret addresses.
-    let owner = Addr::unchecked(owner);
-    let spender = Addr::unchecked(spender);
+    AdminHistoryStore::record(
+        deps.storage,
+        &env,
+        &info.sender,
+        AdminAction::SetTimelock { delay },
+    )?;
 
-    let allowance = AllowancesStore::load(deps.storage, &owner, &spender);
+    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::SetTimelock { status: Success })?))
+}
 
-    let response = QueryAnswer::Allowance {
-        owner,
-        spender,
-        allowance: Uint128::new(allowance.amount),
-        expiration: allowance.expiration,
-    };
-    to_binary(&response)
+/// Run a queued operation as if its sender had just sent it, checking its permissions again.
+fn execute_queued(deps: DepsMut, env: Env, info: MessageInfo, id: u64) -> StdResult<Response> {
+    let operation = TimelockStore::load(deps.storage, id)?;
+    if operation.sender != info.sender {
+        return Err(StdError::generic_err(
+            "Only the account that queued an operation can execute it",
+        ));
+    }
+    if env.block.height < operation.not_before {
+        return Err(StdError::generic_err(format!(
+            "Operation {id} cannot be executed before height {}",
+            operation.not_before
+        )));
+    }
+    if env.block.height >= operation.expires_at() {
+        return Err(StdError::generic_err(format!(
+            "Operation {id} expired at height {}, queue it again",
+            operation.expires_at()
+        )));
+    }
+
+    TimelockStore::remove(deps.storage, id)?;
+    let msg = from_binary(&operation.msg)?;
+    execute_impl(deps, env, info, msg, true)
 }
 
-pub fn query_allowances_given(
-    deps: Deps,
-    owner: String,
-    page: u32,
-    page_size: u32,
-) -> StdResult<Binary> {
-    // Notice that if query_all_allowances_given() was called by a viewing-key call,
-    // the address of 'owner' has already been validated.
-    // The addresses of 'owner' should not be validated if query_all_allowances_given() was
-    // called by a permit call, for compatibility with non-Secret addresses.
-    let owner = Addr::unchecked(owner);
+fn cancel_queued(deps: DepsMut, env: Env, info: MessageInfo, id: u64) -> StdResult<Response> {
+    let operation = TimelockStore::load(deps.storage, id)?;
+    if operation.sender != info.sender {
+        let constants = ConfigStore::load_constants(deps.storage)?;
+        check_if_admin(&constants.admin, &info.sender)?;
+    }
 
-    let all_allowances =
-        AllowancesStore::all_allowances(deps.storage, &owner, page, page_size).unwrap_or_default();
+    TimelockStore::remove(deps.storage, id)?;
 
-    let allowances_result = all_allowances
-        .into_iter()
-        .map(|(spender, allowance)| AllowanceGivenResult {
-            spender,
-            allowance: Uint128::from(allowance.amount),
-            expiration: allowance.expiration,
-        })
-        .collect();
+    AdminHistoryStore::record(
+        deps.storage,
+        &env,
+        &info.sender,
+        AdminAction::CancelQueued {
+            id,
+            queued_by: operation.sender,
+        },
+    )?;
 
-    let response = QueryAnswer::AllowancesGiven {
-        owner: owner.clone(),
-        allowances: allowances_result,
-        count: AllowancesStore::num_allowances(deps.storage, &owner),
-    };
-    to_binary(&response)
+    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::CancelQueued { status: Success })?))
 }
 
-pub fn query_allowances_received(
-    deps: Deps,
-    spender: String,
-    page: u32,
-    page_size: u32,
-) -> StdResult<Binary> {
-    // Notice that if query_all_allowances_received() was called by a viewing-key call,
-    // the address of 'spender' has already been validated.
-    // The addresses of 'spender' should not be validated if query_all_allowances_received() was
-    // called by a permit call, for compatibility with non-Secret addresses.
-    let spender = Addr::unchecked(spender);
+fn renounce_admin(deps: DepsMut, env: Env, info: MessageInfo) -> StdResult<Response> {
+    let mut constants = ConfigStore::load_constants(deps.storage)?;
+    check_if_admin(&constants.admin, &info.sender)?;
 
-    let all_allowed =
-        AllowancesStore::all_allowed(deps.storage, &spender, page, page_size).unwrap_or_default();
+    RolesStore::revoke_all(deps.storage)?;
+    ADMIN_PROPOSAL.remove(deps.storage);
+    constants.admin = None;
+    ConfigStore::set_constants(deps.storage, &constants)?;
 
-    let allowances = all_allowed
-        .into_iter()
-        .map(|(owner, allowance)| AllowanceReceivedResult {
-            owner,
-            allowance: Uint128::from(allowance.amount),
-            expiration: allowance.expiration,
-        })
-        .collect();
+    AdminHistoryStore::record(
+        deps.storage,
+        &env,
+        &info.sender,
+        AdminAction::RenounceAdmin {},
+    )?;
 
-    let response = QueryAnswer::AllowancesReceived {
-        spender: spender.clone(),
-        allowances,
-        count: AllowancesStore::num_allowed(deps.storage, &spender),
-    };
-    to_binary(&response)
+    Ok(
+        Response::new().set_data(to_binary(&ExecuteAnswer::RenounceAdmin {
+            status: Success,
+        })?),
+    )
 }
 
-fn try_deposit(
+fn grant_role(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    decoys: Option<Vec<Addr>>,
-    account_random_pos: Option<usize>,
+    role: Role,
+    account: String,
 ) -> StdResult<Response> {
     let constants = ConfigStore::load_constants(deps.storage)?;
+    check_if_admin(&constants.admin, &info.sender)?;
 
-    let mut amount = Uint128::zero();
+    let account = deps.api.addr_validate(account.as_str())?;
+    RolesStore::grant(deps.storage, role, &account)?;
 
-    // NOTE - supported_denoms is not part of the config due to migrating from snip24
-    for coin in &info.funds {
-        // if constants.supported_denoms.contains(&coin.denom) {
-        amount += coin.amount
-        // } else {
-        //     return Err(StdError::generic_err(format!(
-        //         "Tried to deposit an unsupported coin {}",
-        //         coin.denom
-        //     )));
-        // }
-    }
+    AdminHistoryStore::record(
+        deps.storage,
+        &env,
+        &info.sender,
+        AdminAction::GrantRole { role, account },
+    )?;
 
-    if amount.is_zero() {
-        return Err(StdError::generic_err("No funds were sent to be deposited"));
-    }
+    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::GrantRole { status: Success })?))
+}
 
-    let mut raw_amount = amount.u128();
+fn revoke_role(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    role: Role,
+    account: String,
+) -> StdResult<Response> {
+    let constants = ConfigStore::load_constants(deps.storage)?;
+    check_if_admin(&constants.admin, &info.sender)?;
 
-    if !constants.deposit_is_enabled {
-        return Err(StdError::generic_err(
-            "Deposit functionality is not enabled.",
-        ));
-    }
+    let account = deps.api.addr_validate(account.as_str())?;
+    RolesStore::revoke(deps.storage, role, &account)?;
 
-    let mut total_supply = ConfigStore::load_total_supply(deps.storage)?;
-    raw_amount = safe_add(&mut total_supply, raw_amount);
-    ConfigStore::set_total_supply(deps.storage, &total_supply)?;
+    AdminHistoryStore::record(
+        deps.storage,
+        &env,
+        &info.sender,
+        AdminAction::RevokeRole { role, account },
+    )?;
 
-    let sender_address = &deps.api.addr_canonicalize(info.sender.as_str())?;
-    let decoys = convert_decoys(&decoys, deps.api)?;
+    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::RevokeRole { status: Success })?))
+}
 
-    BalancesStore::update_balance(
+fn renounce_role(deps: DepsMut, env: Env, info: MessageInfo, role: Role) -> StdResult<Response> {
+    RolesStore::check_role(deps.storage, role, &info.sender)?;
+    RolesStore::revoke(deps.storage, role, &info.sender)?;
+
+    AdminHistoryStore::record(
         deps.storage,
         &env,
-        sender_address,
-        raw_amount,
-        true,
-        "",
-        &decoys,
-        &account_random_pos,
+        &info.sender,
+        AdminAction::RenounceRole { role },
     )?;
 
-    store_deposit(
+    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::RenounceRole { status: Success })?))
+}
+
+/// Set the max supply if there is none yet, or lower it. It can never be raised, nor set below
+/// the current total supply.
+fn set_max_supply_impl(storage: &mut dyn Storage, max_supply: u128) -> StdResult<()> {
+    let mut constants = ConfigStore::load_constants(storage)?;
+    if let Some(current) = constants.max_supply {
+        if max_supply > current {
+            return Err(StdError::generic_err(format!(
+                "The max supply can only be lowered, it is currently {current}"
+            )));
+        }
+    }
+    let total_supply = ConfigStore::load_total_supply(storage)?;
+    if max_supply < total_supply {
+        return Err(StdError::generic_err(format!(
+            "The max supply cannot be below the total supply of {total_supply}"
+        )));
+    }
+
+    constants.max_supply = Some(max_supply);
+    ConfigStore::set_constants(storage, &constants)
+}
+
+fn set_max_supply(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    max_supply: Uint128,
+) -> StdResult<Response> {
+    RolesStore::check_role(deps.storage, Role::MinterManager, &info.sender)?;
+
+    set_max_supply_impl(deps.storage, max_supply.u128())?;
+
+    AdminHistoryStore::record(
         deps.storage,
-        sender_address,
-        Uint128::new(raw_amount),
-        "uscrt".to_string(),
-        &env.block,
-        &decoys,
-        &account_random_pos,
+        &env,
+        &info.sender,
+        AdminAction::SetMaxSupply { max_supply },
     )?;
 
-    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::Deposit { status: Success })?))
+    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::SetMaxSupply { status: Success })?))
 }
 
-fn try_redeem(
+fn add_supported_denoms(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    amount: Uint128,
-    denom: Option<String>,
-    decoys: Option<Vec<Addr>>,
-    account_random_pos: Option<usize>,
+    denoms: Vec<String>,
+    pegged: bool,
 ) -> StdResult<Response> {
-    let constants = ConfigStore::load_constants(deps.storage)?;
-    if !constants.redeem_is_enabled {
-        return Err(StdError::generic_err(
-            "Redeem functionality is not enabled for this token.",
-        ));
-    }
+    let mut config = ConfigStore::load_constants(deps.storage)?;
 
-    // NOTE - supported_denoms is not part of the config due to migrating from snip24
-    // // if denom is none and there is only 1 supported denom then we don't need to check anything
-    // let withdraw_denom = if denom.is_none() && constants.supported_denoms.len() == 1 {
-    //     constants.supported_denoms.first().unwrap().clone()
-    // // if denom is specified make sure it's on the list before trying to withdraw with it
-    // } else if denom.is_some() && constants.supported_denoms.contains(denom.as_ref().unwrap()) {
-    //     denom.unwrap()
-    // // error handling
-    // } else if denom.is_none() {
-    //     return Err(StdError::generic_err(
-    //         "Tried to redeem without specifying denom, but multiple coins are supported",
-    //     ));
-    // } else {
+    RolesStore::check_role(deps.storage, Role::DenomManager, &info.sender)?;
+    // if !config.can_modify_denoms {
     //     return Err(StdError::generic_err(
-    //         "Tried to redeem for an unsupported coin",
+    //         "Cannot modify denoms for this contract",
     //     ));
-    // };
+    // }
 
-    let withdraw_denom = if let Some(denom) = denom {
-        denom
-    } else {
+    for denom in denoms.iter() {
+        if !config.supported_denoms.contains(denom) {
+            config.supported_denoms.push(denom.clone());
+        }
+    }
+    // Every deposit is credited 1:1, so denoms worth different amounts would let depositors of
+    // the cheaper one redeem the others
+    if config.supported_denoms.len() > 1 && !pegged {
         return Err(StdError::generic_err(
-            "Tried to redeem without specifying denom",
+            "Deposits are credited 1:1 whatever their denom, set pegged to confirm that the supported denoms are pegged to each other",
         ));
-    };
-
-    let amount_raw = amount.u128();
+    }
 
-    let sender_address = deps.api.addr_canonicalize(info.sender.as_str())?;
-    let decoys = convert_decoys(&decoys, deps.api)?;
+    ConfigStore::set_constants(deps.storage, &config)?;
 
-    BalancesStore::update_balance(
+    AdminHistoryStore::record(
         deps.storage,
         &env,
-        &sender_address,
-        amount_raw,
-        false,
-        "redeem",
-        &decoys,
-        &account_random_pos,
+        &info.sender,
+        AdminAction::AddSupportedDenoms { denoms },
     )?;
 
-    let total_supply = ConfigStore::load_total_supply(deps.storage)?;
-    if let Some(total_supply) = total_supply.checked_sub(amount_raw) {
-        ConfigStore::set_total_supply(deps.storage, &total_supply)?;
-    } else {
-        return Err(StdError::generic_err(
-            "You are trying to redeem more tokens than what is available in the total supply",
-        ));
-    }
+    Ok(
+        Response::new().set_data(to_binary(&ExecuteAnswer::AddSupportedDenoms {
+            status: Success,
+        })?),
+    )
+}
 
-    let token_reserve = deps
-        .querier
-        .query_balance(&env.contract.address, &withdraw_denom)?
-        .amount;
-    if amount > token_reserve {
-        return Err(StdError::generic_err(format!(
-            "You are trying to redeem for more {withdraw_denom} than the contract has in its reserve",
-        )));
-    }
+fn remove_supported_denoms(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    denoms: Vec<String>,
+) -> StdResult<Response> {
+    let mut config = ConfigStore::load_constants(deps.storage)?;
 
-    let withdrawal_coins: Vec<Coin> = vec![Coin {
-        denom: withdraw_denom,
-        amount,
-    }];
+    RolesStore::check_role(deps.storage, Role::DenomManager, &info.sender)?;
+    // if !config.can_modify_denoms {
+    //     return Err(StdError::generic_err(
+    //         "Cannot modify denoms for this contract",
+    //     ));
+    // }
 
-    store_redeem(
+    for denom in denoms.iter() {
+        config.supported_denoms.retain(|x| x != denom);
+    }
+
+    ConfigStore::set_constants(deps.storage, &config)?;
+
+    AdminHistoryStore::record(
         deps.storage,
-        &sender_address,
-        amount,
-        constants.symbol,
-        &env.block,
-        &decoys,
-        &account_random_pos,
+        &env,
+        &info.sender,
+        AdminAction::RemoveSupportedDenoms { denoms },
     )?;
 
-    let message = CosmosMsg::Bank(BankMsg::Send {
-        to_address: info.sender.clone().into_string(),
-        amount: withdrawal_coins,
-    });
-    let data = to_binary(&ExecuteAnswer::Redeem { status: Success })?;
-    let res = Response::new().add_message(message).set_data(data);
-    Ok(res)
+    Ok(
+        Response::new().set_data(to_binary(&ExecuteAnswer::RemoveSupportedDenoms {
+            status: Success,
+        })?),
+    )
+}
+
+fn enable_checkpoints(deps: DepsMut, env: Env, info: MessageInfo) -> StdResult<Response> {
+    let account = deps.api.addr_canonicalize(info.sender.as_str())?;
+    CheckpointsStore::enable(deps.storage, &account, env.block.height)?;
+
+    Ok(
+        Response::new().set_data(to_binary(&ExecuteAnswer::EnableCheckpoints {
+            status: Success,
+        })?),
+    )
+}
+
+/// Remove all but the last `keep_last` txs from the caller's history, after settling the ones
+/// still waiting in the delayed write buffer.
+fn prune_own_history(deps: DepsMut, info: &MessageInfo, keep_last: u32) -> StdResult<(u32, u32)> {
+    let account = deps.api.addr_canonicalize(info.sender.as_str())?;
+
+    let mut dwb = DelayedWriteBuffer::load(deps.storage)?;
+    dwb.settle_account(deps.storage, &account)?;
+    dwb.save(deps.storage)?;
+
+    prune_history(deps.storage, &account, keep_last)
+}
+
+fn try_prune_history(deps: DepsMut, info: MessageInfo, keep_last: u32) -> StdResult<Response> {
+    let (removed, remaining) = prune_own_history(deps, &info, keep_last)?;
+
+    Ok(
+        Response::new().set_data(to_binary(&ExecuteAnswer::PruneHistory {
+            removed,
+            remaining,
+        })?),
+    )
+}
+
+fn try_clear_history(deps: DepsMut, info: MessageInfo) -> StdResult<Response> {
+    let (removed, remaining) = prune_own_history(deps, &info, 0)?;
+
+    Ok(
+        Response::new().set_data(to_binary(&ExecuteAnswer::ClearHistory {
+            removed,
+            remaining,
+        })?),
+    )
+}
+
+fn try_set_tx_label(
+    deps: DepsMut,
+    info: MessageInfo,
+    tx_id: u64,
+    note: Option<String>,
+    tags: Option<Vec<String>>,
+) -> StdResult<Response> {
+    let account = deps.api.addr_canonicalize(info.sender.as_str())?;
+    let label = TxLabel {
+        note,
+        tags: tags.unwrap_or_default(),
+    };
+    set_tx_label(deps.storage, &account, tx_id, label)?;
+
+    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::SetTxLabel { status: Success })?))
+}
+
+fn regenerate_code(deps: DepsMut, env: Env, info: MessageInfo) -> StdResult<Response> {
+    let account = deps.api.addr_canonicalize(info.sender.as_str())?;
+    let code = OneAmberStore::regenerate_code(deps.storage, &account, &env)?;
+
+    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::RegenerateCode { code })?))
+}
+
+fn backfill_members(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    accounts: Vec<String>,
+) -> StdResult<Response> {
+    RolesStore::check_role(deps.storage, Role::OacManager, &info.sender)?;
+
+    let enrolled = backfill_members_impl(deps, &env, accounts)?;
+
+    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::BackfillMembers { enrolled })?))
+}
+
+fn set_member_tiers(
+    deps: DepsMut,
+    info: MessageInfo,
+    thresholds: Vec<Uint128>,
+) -> StdResult<Response> {
+    RolesStore::check_role(deps.storage, Role::OacManager, &info.sender)?;
+
+    let thresholds = thresholds.iter().map(Uint128::u128).collect();
+    OneAmberStore::save_tiers(deps.storage, thresholds)?;
+
+    Ok(
+        Response::new().set_data(to_binary(&ExecuteAnswer::SetMemberTiers {
+            status: Success,
+        })?),
+    )
+}
+
+fn reevaluate_members(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    accounts: Vec<String>,
+) -> StdResult<Response> {
+    RolesStore::check_role(deps.storage, Role::OacManager, &info.sender)?;
+
+    // Which accounts changed tier is not returned, so the answer does not reveal their balances
+    for account in accounts {
+        let account = deps.api.addr_validate(account.as_str())?;
+        let account = deps.api.addr_canonicalize(account.as_str())?;
+
+        OneAmberStore::reevaluate_member(deps.storage, &account, &env)?;
+    }
+
+    Ok(
+        Response::new().set_data(to_binary(&ExecuteAnswer::ReevaluateMembers {
+            status: Success,
+        })?),
+    )
+}
+
+fn create_invite_code(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    max_uses: u32,
+    expiration_height: Option<u64>,
+    expiration_time: Option<u64>,
+) -> StdResult<Response> {
+    let account = deps.api.addr_canonicalize(info.sender.as_str())?;
+    let code = OneAmberStore::create_invite_code(
+        deps.storage,
+        &account,
+        max_uses,
+        expiration_height,
+        expiration_time,
+        &env,
+    )?;
+
+    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::CreateInviteCode { code })?))
+}
+
+fn consume_invite_code(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    code: String,
+) -> StdResult<Response> {
+    if !OneAmberStore::is_consumer(deps.storage, &info.sender) {
+        return Err(StdError::generic_err(
+            "Invite codes can only be consumed by code consumers",
+        ));
+    }
+
+    let (tier, remaining_uses) =
+        OneAmberStore::consume_invite_code(deps.storage, &env.block, code.as_str())?;
+
+    Ok(
+        Response::new().set_data(to_binary(&ExecuteAnswer::ConsumeInviteCode {
+            tier,
+            remaining_uses,
+        })?),
+    )
+}
+
+fn add_code_consumers(
+    deps: DepsMut,
+    info: MessageInfo,
+    consumers: Vec<String>,
+) -> StdResult<Response> {
+    RolesStore::check_role(deps.storage, Role::OacManager, &info.sender)?;
+
+    let consumers: StdResult<Vec<Addr>> = consumers
+        .iter()
+        .map(|consumer| deps.api.addr_validate(consumer.as_str()))
+        .collect();
+    OneAmberStore::add_consumers(deps.storage, consumers?)?;
+
+    Ok(
+        Response::new().set_data(to_binary(&ExecuteAnswer::AddCodeConsumers {
+            status: Success,
+        })?),
+    )
+}
+
+fn remove_code_consumers(
+    deps: DepsMut,
+    info: MessageInfo,
+    consumers: Vec<String>,
+) -> StdResult<Response> {
+    RolesStore::check_role(deps.storage, Role::OacManager, &info.sender)?;
+
+    let consumers: StdResult<Vec<Addr>> = consumers
+        .iter()
+        .map(|consumer| deps.api.addr_validate(consumer.as_str()))
+        .collect();
+    OneAmberStore::remove_consumers(deps.storage, consumers?)?;
+
+    Ok(
+        Response::new().set_data(to_binary(&ExecuteAnswer::RemoveCodeConsumers {
+            status: Success,
+        })?),
+    )
+}
+
+fn add_membership_hook(
+    deps: DepsMut,
+    info: MessageInfo,
+    contract: String,
+    code_hash: String,
+    on_error: HookErrorPolicy,
+) -> StdResult<Response> {
+    RolesStore::check_role(deps.storage, Role::HookManager, &info.sender)?;
+
+    let contract = deps.api.addr_validate(contract.as_str())?;
+    MembershipHooksStore::add(
+        deps.storage,
+        &contract,
+        &MembershipHook {
+            code_hash,
+            on_error,
+        },
+    )?;
+
+    Ok(
+        Response::new().set_data(to_binary(&ExecuteAnswer::AddMembershipHook {
+            status: Success,
+        })?),
+    )
+}
+
+fn remove_membership_hook(
+    deps: DepsMut,
+    info: MessageInfo,
+    contract: String,
+) -> StdResult<Response> {
+    RolesStore::check_role(deps.storage, Role::HookManager, &info.sender)?;
+
+    let contract = deps.api.addr_validate(contract.as_str())?;
+    MembershipHooksStore::remove(deps.storage, &contract)?;
+
+    Ok(
+        Response::new().set_data(to_binary(&ExecuteAnswer::RemoveMembershipHook {
+            status: Success,
+        })?),
+    )
+}
+
+/// Enroll every qualifying account in the batch, returning how many were newly enrolled.
+/// Accounts that are already members or do not qualify for any tier are skipped, so a batch
+/// can be resubmitted if a previous attempt ran out of gas.
+fn backfill_members_impl(deps: DepsMut, env: &Env, accounts: Vec<String>) -> StdResult<u32> {
+    let mut enrolled: u32 = 0;
+
+    for account in accounts {
+        let account = deps.api.addr_validate(account.as_str())?;
+        let account = deps.api.addr_canonicalize(account.as_str())?;
+
+        if OneAmberStore::backfill_member(deps.storage, &account, env)? {
+            enrolled += 1;
+        }
+    }
+
+    Ok(enrolled)
 }
 
 #[allow(clippy::too_many_arguments)]
-fn try_transfer_impl(
+fn try_mint_impl(
     deps: &mut DepsMut,
     env: &Env,
-    sender: &Addr,
-    recipient: &Addr,
+    minter: Addr,
+    recipient: Addr,
     amount: Uint128,
+    denom: String,
     memo: Option<String>,
     block: &cosmwasm_std::BlockInfo,
     decoys: Option<Vec<Addr>>,
     account_random_pos: Option<usize>,
 ) -> StdResult<()> {
-    let sender = deps.api.addr_canonicalize(sender.as_str())?;
+    let raw_amount = amount.u128();
+    MintersStore::use_quota(deps.storage, &minter, raw_amount, block)?;
+    let minter = deps.api.addr_canonicalize(minter.as_str())?;
     let recipient = deps.api.addr_canonicalize(recipient.as_str())?;
+
     let decoys = convert_decoys(&decoys, deps.api)?;
 
-    perform_transfer(
+    BalancesStore::update_balance(
         deps.storage,
         env,
-        &sender,
         &recipient,
-        amount.u128(),
+        raw_amount,
+        true,
+        "",
         &decoys,
         &account_random_pos,
     )?;
 
-    let symbol = ConfigStore::load_constants(deps.storage)?.symbol;
-    store_transfer(
+    NotificationsStore::notify_received(deps.storage, &recipient, raw_amount, Some(&minter))?;
+
+    store_mint(
         deps.storage,
-        &sender,
-        &sender,
-        &recipient,
+        minter,
+        recipient,
         amount,
-        symbol,
+        denom,
         memo,
         block,
         &decoys,
@@ -1334,8 +2326,18 @@ fn try_transfer_impl(
     Ok(())
 }
 
+/// Refuse to mint or deposit past the max supply, if there is one.
+fn check_max_supply(constants: &Constants, total_supply: u128, operation: &str) -> StdResult<()> {
+    match constants.max_supply {
+        Some(max_supply) if total_supply > max_supply => Err(StdError::generic_err(format!(
+            "{operation} would exceed the max supply of {max_supply}"
+        ))),
+        _ => Ok(()),
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
-fn try_transfer(
+fn try_mint(
     mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
@@ -1347,36 +2349,79 @@ fn try_transfer(
 ) -> StdResult<Response> {
     let recipient = deps.api.addr_validate(recipient.as_str())?;
 
-    try_transfer_impl(
+    let constants = ConfigStore::load_constants(deps.storage)?;
+
+    if !constants.mint_is_enabled {
+        return Err(StdError::generic_err(
+            "Mint functionality is not enabled for this token.",
+        ));
+    }
+
+    let minters = MintersStore::load(deps.storage)?;
+    if !minters.contains(&info.sender) {
+        return Err(StdError::generic_err(
+            "Minting is allowed to minter accounts only",
+        ));
+    }
+
+    let mut total_supply = ConfigStore::load_total_supply(deps.storage)?;
+    let minted_amount = safe_add(&mut total_supply, amount.u128());
+    check_max_supply(&constants, total_supply, "Minting")?;
+    ConfigStore::set_total_supply(deps.storage, &total_supply)?;
+
+    // Note that even when minted_amount is equal to 0 we still want to perform the operations for logic consistency
+    try_mint_impl(
         &mut deps,
         &env,
-        &info.sender,
-        &recipient,
-        amount,
+        info.sender,
+        recipient,
+        Uint128::new(minted_amount),
+        constants.symbol,
         memo,
         &env.block,
         decoys,
         account_random_pos,
     )?;
 
-    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::Transfer { status: Success })?))
+    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::Mint { status: Success })?))
 }
 
-fn try_batch_transfer(
+fn try_batch_mint(
     mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    actions: Vec<batch::TransferAction>,
+    actions: Vec<batch::MintAction>,
     account_random_pos: Option<usize>,
 ) -> StdResult<Response> {
+    let constants = ConfigStore::load_constants(deps.storage)?;
+
+    if !constants.mint_is_enabled {
+        return Err(StdError::generic_err(
+            "Mint functionality is not enabled for this token.",
+        ));
+    }
+
+    let minters = MintersStore::load(deps.storage)?;
+    if !minters.contains(&info.sender) {
+        return Err(StdError::generic_err(
+            "Minting is allowed to minter accounts only",
+        ));
+    }
+
+    let mut total_supply = ConfigStore::load_total_supply(deps.storage)?;
+
+    // Quick loop to check that the total of amounts is valid
     for action in actions {
+        let actual_amount = safe_add(&mut total_supply, action.amount.u128());
+
         let recipient = deps.api.addr_validate(action.recipient.as_str())?;
-        try_transfer_impl(
+        try_mint_impl(
             &mut deps,
             &env,
-            &info.sender,
-            &recipient,
-            action.amount,
+            info.sender.clone(),
+            recipient,
+            Uint128::new(actual_amount),
+            constants.symbol.clone(),
             action.memo,
             &env.block,
             action.decoys,
@@ -1384,235 +2429,386 @@ fn try_batch_transfer(
         )?;
     }
 
+    check_max_supply(&constants, total_supply, "Minting")?;
+    ConfigStore::set_total_supply(deps.storage, &total_supply)?;
+
+    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::BatchMint { status: Success })?))
+}
+
+pub fn try_set_key(deps: DepsMut, info: MessageInfo, key: String) -> StdResult<Response> {
+    let canonical_addr = deps.api.addr_canonicalize(info.sender.as_str())?;
+    ViewingKey::set(deps.storage, canonical_addr.as_slice(), key.as_str());
     Ok(
-        Response::new().set_data(to_binary(&ExecuteAnswer::BatchTransfer {
+        Response::new().set_data(to_binary(&ExecuteAnswer::SetViewingKey {
             status: Success,
         })?),
     )
 }
 
-#[allow(clippy::too_many_arguments)]
-fn try_add_receiver_api_callback(
-    storage: &dyn Storage,
-    messages: &mut Vec<CosmosMsg>,
-    recipient: Addr,
-    recipient_code_hash: Option<String>,
-    msg: Option<Binary>,
-    sender: Addr,
-    from: Addr,
-    amount: Uint128,
-    memo: Option<String>,
-) -> StdResult<()> {
-    if let Some(receiver_hash) = recipient_code_hash {
-        let receiver_msg = Snip20ReceiveMsg::new(sender, from, amount, memo, msg);
-        let callback_msg = receiver_msg.into_cosmos_msg(receiver_hash, recipient)?;
-
-        messages.push(callback_msg);
-        return Ok(());
-    }
-
-    let receiver_hash = ReceiverHashStore::may_load(storage, &recipient)?;
-    if let Some(receiver_hash) = receiver_hash {
-        let receiver_msg = Snip20ReceiveMsg::new(sender, from, amount, memo, msg);
-        let callback_msg = receiver_msg.into_cosmos_msg(receiver_hash, recipient)?;
-
-        messages.push(callback_msg);
-    }
-    Ok(())
-}
-
-#[allow(clippy::too_many_arguments)]
-fn try_send_impl(
-    deps: &mut DepsMut,
-    env: &Env,
-    messages: &mut Vec<CosmosMsg>,
-    sender: Addr,
-    recipient: Addr,
-    recipient_code_hash: Option<String>,
-    amount: Uint128,
-    memo: Option<String>,
-    msg: Option<Binary>,
-    block: &cosmwasm_std::BlockInfo,
-    decoys: Option<Vec<Addr>>,
-    account_random_pos: Option<usize>,
-) -> StdResult<()> {
-    try_transfer_impl(
-        deps,
-        env,
-        &sender,
-        &recipient,
-        amount,
-        memo.clone(),
-        block,
-        decoys,
-        account_random_pos,
-    )?;
-
-    try_add_receiver_api_callback(
+pub fn try_create_key(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    entropy: String,
+) -> StdResult<Response> {
+    let canonical_addr = deps.api.addr_canonicalize(info.sender.as_str())?;
+    let key = ViewingKey::create(
         deps.storage,
-        messages,
-        recipient,
-        recipient_code_hash,
-        msg,
-        sender.clone(),
-        sender,
-        amount,
-        memo,
-    )?;
+        &info,
+        &env,
+        canonical_addr.as_slice(),
+        entropy.as_ref(),
+    );
 
-    Ok(())
+    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::CreateViewingKey { key })?))
 }
 
-#[allow(clippy::too_many_arguments)]
-fn try_send(
-    mut deps: DepsMut,
+fn set_contract_status(
+    deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    recipient: String,
-    recipient_code_hash: Option<String>,
-    amount: Uint128,
-    memo: Option<String>,
-    msg: Option<Binary>,
-    decoys: Option<Vec<Addr>>,
-    account_random_pos: Option<usize>,
+    status_level: ContractStatusLevel,
 ) -> StdResult<Response> {
-    let recipient = deps.api.addr_validate(recipient.as_str())?;
+    RolesStore::check_role(deps.storage, Role::Pauser, &info.sender)?;
 
-    let mut messages = vec![];
-    try_send_impl(
-        &mut deps,
+    ConfigStore::set_contract_status(deps.storage, &status_level)?;
+
+    AdminHistoryStore::record(
+        deps.storage,
         &env,
-        &mut messages,
-        info.sender,
-        recipient,
-        recipient_code_hash,
-        amount,
-        memo,
-        msg,
-        &env.block,
-        decoys,
-        account_random_pos,
+        &info.sender,
+        AdminAction::SetContractStatus {
+            level: status_level,
+        },
     )?;
 
-    Ok(Response::new()
-        .add_messages(messages)
-        .set_data(to_binary(&ExecuteAnswer::Send { status: Success })?))
+    Ok(
+        Response::new().set_data(to_binary(&ExecuteAnswer::SetContractStatus {
+            status: Success,
+        })?),
+    )
 }
 
-fn try_batch_send(
-    mut deps: DepsMut,
+fn set_pause_flags(
+    deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    actions: Vec<batch::SendAction>,
-    account_random_pos: Option<usize>,
+    flags: PauseFlags,
 ) -> StdResult<Response> {
-    let mut messages = vec![];
-    for action in actions {
-        let recipient = deps.api.addr_validate(action.recipient.as_str())?;
-        try_send_impl(
-            &mut deps,
-            &env,
-            &mut messages,
-            info.sender.clone(),
-            recipient,
-            action.recipient_code_hash,
-            action.amount,
-            action.memo,
-            action.msg,
-            &env.block,
-            action.decoys,
-            account_random_pos,
-        )?;
-    }
-
-    Ok(Response::new()
-        .add_messages(messages)
-        .set_data(to_binary(&ExecuteAnswer::BatchSend { status: Success })?))
-}
+    RolesStore::check_role(deps.storage, Role::Pauser, &info.sender)?;
 
-fn try_register_receive(
-    deps: DepsMut,
-    info: MessageInfo,
-    code_hash: String,
-) -> StdResult<Response> {
-    ReceiverHashStore::save(deps.storage, &info.sender, code_hash);
+    ConfigStore::set_pause_flags(deps.storage, &flags)?;
 
-    let data = to_binary(&ExecuteAnswer::RegisterReceive { status: Success })?;
-    Ok(Response::new()
-        .add_attribute("register_status", "success")
-        .set_data(data))
-}
+    AdminHistoryStore::record(
+        deps.storage,
+        &env,
+        &info.sender,
+        AdminAction::SetPauseFlags { flags },
+    )?;
 
-fn insufficient_allowance(allowance: u128, required: u128) -> StdError {
-    StdError::generic_err(format!(
-        "insufficient allowance: allowance={allowance}, required={required}",
-    ))
+    Ok(
+        Response::new().set_data(to_binary(&ExecuteAnswer::SetPauseFlags {
+            status: Success,
+        })?),
+    )
 }
 
-fn use_allowance(
-    storage: &mut dyn Storage,
-    env: &Env,
-    owner: &Addr,
-    spender: &Addr,
-    amount: u128,
-) -> StdResult<()> {
-    let mut allowance = AllowancesStore::load(storage, owner, spender);
-
-    if allowance.is_expired_at(&env.block) {
-        return Err(insufficient_allowance(0, amount));
-    }
-    if let Some(new_allowance) = allowance.amount.checked_sub(amount) {
-        allowance.amount = new_allowance;
-    } else {
-        return Err(insufficient_allowance(allowance.amount, amount));
-    }
+pub fn query_allowance(deps: Deps, owner: String, spender: String) -> StdResult<Binary> {
+    // Notice that if query_allowance() was called by a viewing-key call, the addresses of 'owner'
+    // and 'spender' have already been validated.
+    // The addresses of 'owner' and 'spender' should not be validated if query_allowance() was
+    // called by a permit call, for compatibility with non-Secret addresses.
+    let owner = Addr::unchecked(owner);
+    let spender = Addr::unchecked(spender);
 
-    AllowancesStore::save(storage, owner, spender, &allowance)?;
+    let allowance = AllowancesStore::load(deps.storage, &owner, &spender);
 
-    Ok(())
+    let response = QueryAnswer::Allowance {
+        owner,
+        spender,
+        allowance: Uint128::new(allowance.amount),
+        expiration: allowance.expiration,
+    };
+    to_binary(&response)
 }
 
-#[allow(clippy::too_many_arguments)]
-fn try_transfer_from_impl(
-    deps: &mut DepsMut,
-    env: &Env,
-    spender: &Addr,
-    owner: &Addr,
+pub fn query_allowances_given(
+    deps: Deps,
+    owner: String,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    // Notice that if query_all_allowances_given() was called by a viewing-key call,
+    // the address of 'owner' has already been validated.
+    // The addresses of 'owner' should not be validated if query_all_allowances_given() was
+    // called by a permit call, for compatibility with non-Secret addresses.
+    let owner = Addr::unchecked(owner);
+
+    let all_allowances =
+        AllowancesStore::all_allowances(deps.storage, &owner, page, page_size).unwrap_or_default();
+
+    let allowances_result = all_allowances
+        .into_iter()
+        .map(|(spender, allowance)| AllowanceGivenResult {
+            spender,
+            allowance: Uint128::from(allowance.amount),
+            expiration: allowance.expiration,
+        })
+        .collect();
+
+    let response = QueryAnswer::AllowancesGiven {
+        owner: owner.clone(),
+        allowances: allowances_result,
+        count: AllowancesStore::num_allowances(deps.storage, &owner),
+    };
+    to_binary(&response)
+}
+
+pub fn query_allowances_received(
+    deps: Deps,
+    spender: String,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    // Notice that if query_all_allowances_received() was called by a viewing-key call,
+    // the address of 'spender' has already been validated.
+    // The addresses of 'spender' should not be validated if query_all_allowances_received() was
+    // called by a permit call, for compatibility with non-Secret addresses.
+    let spender = Addr::unchecked(spender);
+
+    let all_allowed =
+        AllowancesStore::all_allowed(deps.storage, &spender, page, page_size).unwrap_or_default();
+
+    let allowances = all_allowed
+        .into_iter()
+        .map(|(owner, allowance)| AllowanceReceivedResult {
+            owner,
+            allowance: Uint128::from(allowance.amount),
+            expiration: allowance.expiration,
+        })
+        .collect();
+
+    let response = QueryAnswer::AllowancesReceived {
+        spender: spender.clone(),
+        allowances,
+        count: AllowancesStore::num_allowed(deps.storage, &spender),
+    };
+    to_binary(&response)
+}
+
+fn try_deposit(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    decoys: Option<Vec<Addr>>,
+    account_random_pos: Option<usize>,
+) -> StdResult<Response> {
+    let constants = ConfigStore::load_constants(deps.storage)?;
+
+    let mut amount = Uint128::zero();
+
+    for coin in &info.funds {
+        if constants.supported_denoms.contains(&coin.denom) {
+            amount += coin.amount
+        } else {
+            return Err(StdError::generic_err(format!(
+                "Tried to deposit an unsupported coin {}",
+                coin.denom
+            )));
+        }
+    }
+
+    if amount.is_zero() {
+        return Err(StdError::generic_err("No funds were sent to be deposited"));
+    }
+
+    let mut raw_amount = amount.u128();
+
+    if !constants.deposit_is_enabled {
+        return Err(StdError::generic_err(
+            "Deposit functionality is not enabled.",
+        ));
+    }
+
+    let mut total_supply = ConfigStore::load_total_supply(deps.storage)?;
+    raw_amount = safe_add(&mut total_supply, raw_amount);
+    check_max_supply(&constants, total_supply, "Depositing")?;
+    ConfigStore::set_total_supply(deps.storage, &total_supply)?;
+
+    let sender_address = &deps.api.addr_canonicalize(info.sender.as_str())?;
+    let decoys = convert_decoys(&decoys, deps.api)?;
+
+    BalancesStore::update_balance(
+        deps.storage,
+        &env,
+        sender_address,
+        raw_amount,
+        true,
+        "",
+        &decoys,
+        &account_random_pos,
+    )?;
+
+    NotificationsStore::notify_received(deps.storage, sender_address, raw_amount, None)?;
+
+    // One tx per coin, so each records the denom that was actually deposited. When the total
+    // supply capped the credited amount, the txs add up to what was credited.
+    let mut uncredited = raw_amount;
+    for coin in info.funds.iter().filter(|coin| !coin.amount.is_zero()) {
+        let credited = coin.amount.u128().min(uncredited);
+        uncredited -= credited;
+        store_deposit(
+            deps.storage,
+            sender_address,
+            Uint128::new(credited),
+            coin.denom.clone(),
+            &env.block,
+            &decoys,
+            &account_random_pos,
+        )?;
+    }
+
+    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::Deposit { status: Success })?))
+}
+
+fn try_redeem(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    amount: Uint128,
+    denom: Option<String>,
+    decoys: Option<Vec<Addr>>,
+    account_random_pos: Option<usize>,
+) -> StdResult<Response> {
+    let constants = ConfigStore::load_constants(deps.storage)?;
+    if !constants.redeem_is_enabled {
+        return Err(StdError::generic_err(
+            "Redeem functionality is not enabled for this token.",
+        ));
+    }
+
+    let withdraw_denom = match denom {
+        Some(denom) if constants.supported_denoms.contains(&denom) => denom,
+        Some(denom) => {
+            return Err(StdError::generic_err(format!(
+                "Tried to redeem for an unsupported coin {denom}"
+            )));
+        }
+        // With a single supported denom there is nothing to choose
+        None => match constants.supported_denoms.as_slice() {
+            [only] => only.clone(),
+            [] => {
+                return Err(StdError::generic_err(
+                    "Tried to redeem, but no coins are supported",
+                ));
+            }
+            _ => {
+                return Err(StdError::generic_err(
+                    "Tried to redeem without specifying denom, but multiple coins are supported",
+                ));
+            }
+        },
+    };
+
+    let amount_raw = amount.u128();
+
+    let sender_address = deps.api.addr_canonicalize(info.sender.as_str())?;
+    let decoys = convert_decoys(&decoys, deps.api)?;
+
+    BalancesStore::update_balance(
+        deps.storage,
+        &env,
+        &sender_address,
+        amount_raw,
+        false,
+        "redeem",
+        &decoys,
+        &account_random_pos,
+    )?;
+
+    notify_removed(deps.storage, &sender_address, amount_raw)?;
+
+    let total_supply = ConfigStore::load_total_supply(deps.storage)?;
+    if let Some(total_supply) = total_supply.checked_sub(amount_raw) {
+        ConfigStore::set_total_supply(deps.storage, &total_supply)?;
+    } else {
+        return Err(StdError::generic_err(
+            "You are trying to redeem more tokens than what is available in the total supply",
+        ));
+    }
+
+    let token_reserve = deps
+        .querier
+        .query_balance(&env.contract.address, &withdraw_denom)?
+        .amount;
+    if amount > token_reserve {
+        return Err(StdError::generic_err(format!(
+            "You are trying to redeem for more {withdraw_denom} than the contract has in its reserve",
+        )));
+    }
+
+    let withdrawal_coins: Vec<Coin> = vec![Coin {
+        denom: withdraw_denom,
+        amount,
+    }];
+
+    store_redeem(
+        deps.storage,
+        &sender_address,
+        amount,
+        constants.symbol,
+        &env.block,
+        &decoys,
+        &account_random_pos,
+    )?;
+
+    let message = CosmosMsg::Bank(BankMsg::Send {
+        to_address: info.sender.clone().into_string(),
+        amount: withdrawal_coins,
+    });
+    let data = to_binary(&ExecuteAnswer::Redeem { status: Success })?;
+    let res = Response::new().add_message(message).set_data(data);
+    Ok(res)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn try_transfer_impl(
+    deps: &mut DepsMut,
+    env: &Env,
+    sender: &Addr,
     recipient: &Addr,
     amount: Uint128,
     memo: Option<String>,
+    block: &cosmwasm_std::BlockInfo,
     decoys: Option<Vec<Addr>>,
     account_random_pos: Option<usize>,
 ) -> StdResult<()> {
-    let raw_amount = amount.u128();
-
-    use_allowance(deps.storage, env, owner, spender, raw_amount)?;
-
-    let spender = &deps.api.addr_canonicalize(spender.as_str())?;
-    let owner = &deps.api.addr_canonicalize(owner.as_str())?;
-    let recipient = &deps.api.addr_canonicalize(recipient.as_str())?;
+    let sender = deps.api.addr_canonicalize(sender.as_str())?;
+    let recipient = deps.api.addr_canonicalize(recipient.as_str())?;
     let decoys = convert_decoys(&decoys, deps.api)?;
 
     perform_transfer(
         deps.storage,
         env,
-        owner,
-        recipient,
-        raw_amount,
+        &sender,
+        &recipient,
+        amount.u128(),
         &decoys,
         &account_random_pos,
     )?;
 
+    notify_transfer(deps.storage, &sender, &recipient, amount.u128())?;
+
     let symbol = ConfigStore::load_constants(deps.storage)?.symbol;
     store_transfer(
         deps.storage,
-        owner,
-        spender,
-        recipient,
+        &sender,
+        &sender,
+        &recipient,
         amount,
         symbol,
         memo,
-        &env.block,
+        block,
         &decoys,
         &account_random_pos,
     )?;
@@ -1621,88 +2817,115 @@ fn try_transfer_from_impl(
 }
 
 #[allow(clippy::too_many_arguments)]
-fn try_transfer_from(
+fn try_transfer(
     mut deps: DepsMut,
-    env: &Env,
+    env: Env,
     info: MessageInfo,
-    owner: String,
     recipient: String,
     amount: Uint128,
     memo: Option<String>,
     decoys: Option<Vec<Addr>>,
     account_random_pos: Option<usize>,
 ) -> StdResult<Response> {
-    let owner = deps.api.addr_validate(owner.as_str())?;
     let recipient = deps.api.addr_validate(recipient.as_str())?;
-    try_transfer_from_impl(
+
+    try_transfer_impl(
         &mut deps,
-        env,
+        &env,
         &info.sender,
-        &owner,
         &recipient,
         amount,
         memo,
+        &env.block,
         decoys,
         account_random_pos,
     )?;
 
-    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::TransferFrom { status: Success })?))
+    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::Transfer { status: Success })?))
 }
 
-fn try_batch_transfer_from(
+fn try_batch_transfer(
     mut deps: DepsMut,
-    env: &Env,
+    env: Env,
     info: MessageInfo,
-    actions: Vec<batch::TransferFromAction>,
+    actions: Vec<batch::TransferAction>,
     account_random_pos: Option<usize>,
 ) -> StdResult<Response> {
     for action in actions {
-        let owner = deps.api.addr_validate(action.owner.as_str())?;
         let recipient = deps.api.addr_validate(action.recipient.as_str())?;
-        try_transfer_from_impl(
+        try_transfer_impl(
             &mut deps,
-            env,
+            &env,
             &info.sender,
-            &owner,
             &recipient,
             action.amount,
             action.memo,
+            &env.block,
             action.decoys,
             account_random_pos,
         )?;
     }
 
     Ok(
-        Response::new().set_data(to_binary(&ExecuteAnswer::BatchTransferFrom {
+        Response::new().set_data(to_binary(&ExecuteAnswer::BatchTransfer {
             status: Success,
         })?),
     )
 }
 
 #[allow(clippy::too_many_arguments)]
-fn try_send_from_impl(
+fn try_add_receiver_api_callback(
+    storage: &dyn Storage,
+    messages: &mut Vec<CosmosMsg>,
+    recipient: Addr,
+    recipient_code_hash: Option<String>,
+    msg: Option<Binary>,
+    sender: Addr,
+    from: Addr,
+    amount: Uint128,
+    memo: Option<String>,
+) -> StdResult<()> {
+    if let Some(receiver_hash) = recipient_code_hash {
+        let receiver_msg = Snip20ReceiveMsg::new(sender, from, amount, memo, msg);
+        let callback_msg = receiver_msg.into_cosmos_msg(receiver_hash, recipient)?;
+
+        messages.push(callback_msg);
+        return Ok(());
+    }
+
+    let receiver_hash = ReceiverHashStore::may_load(storage, &recipient)?;
+    if let Some(receiver_hash) = receiver_hash {
+        let receiver_msg = Snip20ReceiveMsg::new(sender, from, amount, memo, msg);
+        let callback_msg = receiver_msg.into_cosmos_msg(receiver_hash, recipient)?;
+
+        messages.push(callback_msg);
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn try_send_impl(
     deps: &mut DepsMut,
-    env: Env,
-    info: &MessageInfo,
+    env: &Env,
     messages: &mut Vec<CosmosMsg>,
-    owner: Addr,
+    sender: Addr,
     recipient: Addr,
     recipient_code_hash: Option<String>,
     amount: Uint128,
     memo: Option<String>,
     msg: Option<Binary>,
+    block: &cosmwasm_std::BlockInfo,
     decoys: Option<Vec<Addr>>,
     account_random_pos: Option<usize>,
 ) -> StdResult<()> {
-    let spender = info.sender.clone();
-    try_transfer_from_impl(
+    try_transfer_impl(
         deps,
-        &env,
-        &spender,
-        &owner,
+        env,
+        &sender,
         &recipient,
         amount,
         memo.clone(),
+        block,
         decoys,
         account_random_pos,
     )?;
@@ -1713,8 +2936,8 @@ fn try_send_from_impl(
         recipient,
         recipient_code_hash,
         msg,
-        info.sender.clone(),
-        owner,
+        sender.clone(),
+        sender,
         amount,
         memo,
     )?;
@@ -1723,11 +2946,10 @@ fn try_send_from_impl(
 }
 
 #[allow(clippy::too_many_arguments)]
-fn try_send_from(
+fn try_send(
     mut deps: DepsMut,
     env: Env,
-    info: &MessageInfo,
-    owner: String,
+    info: MessageInfo,
     recipient: String,
     recipient_code_hash: Option<String>,
     amount: Uint128,
@@ -1736,52 +2958,50 @@ fn try_send_from(
     decoys: Option<Vec<Addr>>,
     account_random_pos: Option<usize>,
 ) -> StdResult<Response> {
-    let owner = deps.api.addr_validate(owner.as_str())?;
     let recipient = deps.api.addr_validate(recipient.as_str())?;
+
     let mut messages = vec![];
-    try_send_from_impl(
+    try_send_impl(
         &mut deps,
-        env,
-        info,
+        &env,
         &mut messages,
-        owner,
+        info.sender,
         recipient,
         recipient_code_hash,
         amount,
         memo,
         msg,
+        &env.block,
         decoys,
         account_random_pos,
     )?;
 
     Ok(Response::new()
         .add_messages(messages)
-        .set_data(to_binary(&ExecuteAnswer::SendFrom { status: Success })?))
+        .set_data(to_binary(&ExecuteAnswer::Send { status: Success })?))
 }
 
-fn try_batch_send_from(
+fn try_batch_send(
     mut deps: DepsMut,
     env: Env,
-    info: &MessageInfo,
-    actions: Vec<batch::SendFromAction>,
+    info: MessageInfo,
+    actions: Vec<batch::SendAction>,
     account_random_pos: Option<usize>,
 ) -> StdResult<Response> {
     let mut messages = vec![];
-
     for action in actions {
-        let owner = deps.api.addr_validate(action.owner.as_str())?;
         let recipient = deps.api.addr_validate(action.recipient.as_str())?;
-        try_send_from_impl(
+        try_send_impl(
             &mut deps,
-            env.clone(),
-            info,
+            &env,
             &mut messages,
-            owner,
+            info.sender.clone(),
             recipient,
             action.recipient_code_hash,
             action.amount,
             action.memo,
             action.msg,
+            &env.block,
             action.decoys,
             account_random_pos,
         )?;
@@ -1789,113 +3009,381 @@ fn try_batch_send_from(
 
     Ok(Response::new()
         .add_messages(messages)
-        .set_data(to_binary(&ExecuteAnswer::BatchSendFrom {
-            status: Success,
-        })?))
+        .set_data(to_binary(&ExecuteAnswer::BatchSend { status: Success })?))
 }
 
-#[allow(clippy::too_many_arguments)]
-fn try_burn_from(
+fn try_register_receive(
     deps: DepsMut,
-    env: &Env,
     info: MessageInfo,
-    owner: String,
+    code_hash: String,
+) -> StdResult<Response> {
+    ReceiverHashStore::save(deps.storage, &info.sender, code_hash);
+
+    let data = to_binary(&ExecuteAnswer::RegisterReceive { status: Success })?;
+    Ok(Response::new()
+        .add_attribute("register_status", "success")
+        .set_data(data))
+}
+
+fn insufficient_allowance(allowance: u128, required: u128) -> StdError {
+    StdError::generic_err(format!(
+        "insufficient allowance: allowance={allowance}, required={required}",
+    ))
+}
+
+fn use_allowance(
+    storage: &mut dyn Storage,
+    env: &Env,
+    owner: &Addr,
+    spender: &Addr,
+    amount: u128,
+) -> StdResult<()> {
+    let mut allowance = AllowancesStore::load(storage, owner, spender);
+
+    if allowance.is_expired_at(&env.block) {
+        return Err(insufficient_allowance(0, amount));
+    }
+    if let Some(new_allowance) = allowance.amount.checked_sub(amount) {
+        allowance.amount = new_allowance;
+    } else {
+        return Err(insufficient_allowance(allowance.amount, amount));
+    }
+
+    AllowancesStore::save(storage, owner, spender, &allowance)?;
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn try_transfer_from_impl(
+    deps: &mut DepsMut,
+    env: &Env,
+    spender: &Addr,
+    owner: &Addr,
+    recipient: &Addr,
     amount: Uint128,
     memo: Option<String>,
     decoys: Option<Vec<Addr>>,
     account_random_pos: Option<usize>,
-) -> StdResult<Response> {
-    let owner = deps.api.addr_validate(owner.as_str())?;
-    let constants = ConfigStore::load_constants(deps.storage)?;
-    if !constants.burn_is_enabled {
-        return Err(StdError::generic_err(
-            "Burn functionality is not enabled for this token.",
-        ));
-    }
-
+) -> StdResult<()> {
     let raw_amount = amount.u128();
-    use_allowance(deps.storage, env, &owner, &info.sender, raw_amount)?;
 
-    let sender = deps.api.addr_canonicalize(info.sender.as_str())?;
-    let owner = deps.api.addr_canonicalize(owner.as_str())?;
+    use_allowance(deps.storage, env, owner, spender, raw_amount)?;
+
+    let spender = &deps.api.addr_canonicalize(spender.as_str())?;
+    let owner = &deps.api.addr_canonicalize(owner.as_str())?;
+    let recipient = &deps.api.addr_canonicalize(recipient.as_str())?;
     let decoys = convert_decoys(&decoys, deps.api)?;
 
-    BalancesStore::update_balance(
+    perform_transfer(
         deps.storage,
         env,
-        &owner,
+        owner,
+        recipient,
         raw_amount,
-        false,
-        "burn",
         &decoys,
         &account_random_pos,
     )?;
 
-    // remove from supply
-    let mut total_supply = ConfigStore::load_total_supply(deps.storage)?;
-
-    if let Some(new_total_supply) = total_supply.checked_sub(raw_amount) {
-        total_supply = new_total_supply;
-    } else {
-        return Err(StdError::generic_err(
-            "You're trying to burn more than is available in the total supply",
-        ));
-    }
-    ConfigStore::set_total_supply(deps.storage, &total_supply)?;
+    notify_transfer(deps.storage, owner, recipient, raw_amount)?;
 
-    store_burn(
+    let symbol = ConfigStore::load_constants(deps.storage)?.symbol;
+    store_transfer(
         deps.storage,
         owner,
-        sender,
+        spender,
+        recipient,
         amount,
-        constants.symbol,
+        symbol,
         memo,
         &env.block,
         &decoys,
         &account_random_pos,
     )?;
 
-    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::BurnFrom { status: Success })?))
+    Ok(())
 }
 
-fn try_batch_burn_from(
-    deps: DepsMut,
+#[allow(clippy::too_many_arguments)]
+fn try_transfer_from(
+    mut deps: DepsMut,
     env: &Env,
     info: MessageInfo,
-    actions: Vec<batch::BurnFromAction>,
+    owner: String,
+    recipient: String,
+    amount: Uint128,
+    memo: Option<String>,
+    decoys: Option<Vec<Addr>>,
     account_random_pos: Option<usize>,
 ) -> StdResult<Response> {
-    let constants = ConfigStore::load_constants(deps.storage)?;
-    if !constants.burn_is_enabled {
-        return Err(StdError::generic_err(
-            "Burn functionality is not enabled for this token.",
-        ));
-    }
+    let owner = deps.api.addr_validate(owner.as_str())?;
+    let recipient = deps.api.addr_validate(recipient.as_str())?;
+    try_transfer_from_impl(
+        &mut deps,
+        env,
+        &info.sender,
+        &owner,
+        &recipient,
+        amount,
+        memo,
+        decoys,
+        account_random_pos,
+    )?;
 
-    let spender = info.sender;
-    let mut total_supply = ConfigStore::load_total_supply(deps.storage)?;
+    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::TransferFrom { status: Success })?))
+}
 
+fn try_batch_transfer_from(
+    mut deps: DepsMut,
+    env: &Env,
+    info: MessageInfo,
+    actions: Vec<batch::TransferFromAction>,
+    account_random_pos: Option<usize>,
+) -> StdResult<Response> {
     for action in actions {
         let owner = deps.api.addr_validate(action.owner.as_str())?;
-        let amount = action.amount.u128();
-        use_allowance(deps.storage, env, &owner, &spender, amount)?;
-
-        let burner = &spender;
-        let burner = deps.api.addr_canonicalize(burner.as_str())?;
-        let owner = deps.api.addr_canonicalize(owner.as_str())?;
-        let decoys = convert_decoys(&action.decoys, deps.api)?;
-
-        BalancesStore::update_balance(
-            deps.storage,
+        let recipient = deps.api.addr_validate(action.recipient.as_str())?;
+        try_transfer_from_impl(
+            &mut deps,
             env,
+            &info.sender,
             &owner,
-            amount,
+            &recipient,
+            action.amount,
+            action.memo,
+            action.decoys,
+            account_random_pos,
+        )?;
+    }
+
+    Ok(
+        Response::new().set_data(to_binary(&ExecuteAnswer::BatchTransferFrom {
+            status: Success,
+        })?),
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn try_send_from_impl(
+    deps: &mut DepsMut,
+    env: Env,
+    info: &MessageInfo,
+    messages: &mut Vec<CosmosMsg>,
+    owner: Addr,
+    recipient: Addr,
+    recipient_code_hash: Option<String>,
+    amount: Uint128,
+    memo: Option<String>,
+    msg: Option<Binary>,
+    decoys: Option<Vec<Addr>>,
+    account_random_pos: Option<usize>,
+) -> StdResult<()> {
+    let spender = info.sender.clone();
+    try_transfer_from_impl(
+        deps,
+        &env,
+        &spender,
+        &owner,
+        &recipient,
+        amount,
+        memo.clone(),
+        decoys,
+        account_random_pos,
+    )?;
+
+    try_add_receiver_api_callback(
+        deps.storage,
+        messages,
+        recipient,
+        recipient_code_hash,
+        msg,
+        info.sender.clone(),
+        owner,
+        amount,
+        memo,
+    )?;
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn try_send_from(
+    mut deps: DepsMut,
+    env: Env,
+    info: &MessageInfo,
+    owner: String,
+    recipient: String,
+    recipient_code_hash: Option<String>,
+    amount: Uint128,
+    memo: Option<String>,
+    msg: Option<Binary>,
+    decoys: Option<Vec<Addr>>,
+    account_random_pos: Option<usize>,
+) -> StdResult<Response> {
+    let owner = deps.api.addr_validate(owner.as_str())?;
+    let recipient = deps.api.addr_validate(recipient.as_str())?;
+    let mut messages = vec![];
+    try_send_from_impl(
+        &mut deps,
+        env,
+        info,
+        &mut messages,
+        owner,
+        recipient,
+        recipient_code_hash,
+        amount,
+        memo,
+        msg,
+        decoys,
+        account_random_pos,
+    )?;
+
+    Ok(Response::new()
+        .add_messages(messages)
+        .set_data(to_binary(&ExecuteAnswer::SendFrom { status: Success })?))
+}
+
+fn try_batch_send_from(
+    mut deps: DepsMut,
+    env: Env,
+    info: &MessageInfo,
+    actions: Vec<batch::SendFromAction>,
+    account_random_pos: Option<usize>,
+) -> StdResult<Response> {
+    let mut messages = vec![];
+
+    for action in actions {
+        let owner = deps.api.addr_validate(action.owner.as_str())?;
+        let recipient = deps.api.addr_validate(action.recipient.as_str())?;
+        try_send_from_impl(
+            &mut deps,
+            env.clone(),
+            info,
+            &mut messages,
+            owner,
+            recipient,
+            action.recipient_code_hash,
+            action.amount,
+            action.memo,
+            action.msg,
+            action.decoys,
+            account_random_pos,
+        )?;
+    }
+
+    Ok(Response::new()
+        .add_messages(messages)
+        .set_data(to_binary(&ExecuteAnswer::BatchSendFrom {
+            status: Success,
+        })?))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn try_burn_from(
+    deps: DepsMut,
+    env: &Env,
+    info: MessageInfo,
+    owner: String,
+    amount: Uint128,
+    memo: Option<String>,
+    decoys: Option<Vec<Addr>>,
+    account_random_pos: Option<usize>,
+) -> StdResult<Response> {
+    let owner = deps.api.addr_validate(owner.as_str())?;
+    let constants = ConfigStore::load_constants(deps.storage)?;
+    if !constants.burn_is_enabled {
+        return Err(StdError::generic_err(
+            "Burn functionality is not enabled for this token.",
+        ));
+    }
+
+    let raw_amount = amount.u128();
+    use_allowance(deps.storage, env, &owner, &info.sender, raw_amount)?;
+
+    let sender = deps.api.addr_canonicalize(info.sender.as_str())?;
+    let owner = deps.api.addr_canonicalize(owner.as_str())?;
+    let decoys = convert_decoys(&decoys, deps.api)?;
+
+    BalancesStore::update_balance(
+        deps.storage,
+        env,
+        &owner,
+        raw_amount,
+        false,
+        "burn",
+        &decoys,
+        &account_random_pos,
+    )?;
+
+    notify_removed(deps.storage, &owner, raw_amount)?;
+
+    // remove from supply
+    let mut total_supply = ConfigStore::load_total_supply(deps.storage)?;
+
+    if let Some(new_total_supply) = total_supply.checked_sub(raw_amount) {
+        total_supply = new_total_supply;
+    } else {
+        return Err(StdError::generic_err(
+            "You're trying to burn more than is available in the total supply",
+        ));
+    }
+    ConfigStore::set_total_supply(deps.storage, &total_supply)?;
+
+    store_burn(
+        deps.storage,
+        owner,
+        sender,
+        amount,
+        constants.symbol,
+        memo,
+        &env.block,
+        &decoys,
+        &account_random_pos,
+    )?;
+
+    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::BurnFrom { status: Success })?))
+}
+
+fn try_batch_burn_from(
+    deps: DepsMut,
+    env: &Env,
+    info: MessageInfo,
+    actions: Vec<batch::BurnFromAction>,
+    account_random_pos: Option<usize>,
+) -> StdResult<Response> {
+    let constants = ConfigStore::load_constants(deps.storage)?;
+    if !constants.burn_is_enabled {
+        return Err(StdError::generic_err(
+            "Burn functionality is not enabled for this token.",
+        ));
+    }
+
+    let spender = info.sender;
+    let mut total_supply = ConfigStore::load_total_supply(deps.storage)?;
+
+    for action in actions {
+        let owner = deps.api.addr_validate(action.owner.as_str())?;
+        let amount = action.amount.u128();
+        use_allowance(deps.storage, env, &owner, &spender, amount)?;
+
+        let burner = &spender;
+        let burner = deps.api.addr_canonicalize(burner.as_str())?;
+        let owner = deps.api.addr_canonicalize(owner.as_str())?;
+        let decoys = convert_decoys(&action.decoys, deps.api)?;
+
+        BalancesStore::update_balance(
+            deps.storage,
+            env,
+            &owner,
+            amount,
             false,
             "burn",
             &decoys,
             &account_random_pos,
         )?;
 
+        notify_removed(deps.storage, &owner, amount)?;
+
         // remove from supply
         if let Some(new_total_supply) = total_supply.checked_sub(amount) {
             total_supply = new_total_supply;
@@ -2001,6 +3489,7 @@ fn try_decrease_allowance(
 
 fn add_minters(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     minters_to_add: Vec<String>,
 ) -> StdResult<Response> {
@@ -2011,19 +3500,29 @@ fn add_minters(
         ));
     }
 
-    check_if_admin(&constants.admin, &info.sender)?;
+    RolesStore::check_role(deps.storage, Role::MinterManager, &info.sender)?;
 
     let minters_to_add: Vec<Addr> = minters_to_add
         .iter()
         .map(|minter| deps.api.addr_validate(minter.as_str()).unwrap())
         .collect();
-    MintersStore::add_minters(deps.storage, minters_to_add)?;
+    MintersStore::add_minters(deps.storage, minters_to_add.clone())?;
+
+    AdminHistoryStore::record(
+        deps.storage,
+        &env,
+        &info.sender,
+        AdminAction::AddMinters {
+            minters: minters_to_add,
+        },
+    )?;
 
     Ok(Response::new().set_data(to_binary(&ExecuteAnswer::AddMinters { status: Success })?))
 }
 
 fn remove_minters(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     minters_to_remove: Vec<String>,
 ) -> StdResult<Response> {
@@ -2034,13 +3533,22 @@ fn remove_minters(
         ));
     }
 
-    check_if_admin(&constants.admin, &info.sender)?;
+    RolesStore::check_role(deps.storage, Role::MinterManager, &info.sender)?;
 
-    let minters_to_remove: StdResult<Vec<Addr>> = minters_to_remove
+    let minters_to_remove: Vec<Addr> = minters_to_remove
         .iter()
         .map(|minter| deps.api.addr_validate(minter.as_str()))
-        .collect();
-    MintersStore::remove_minters(deps.storage, minters_to_remove?)?;
+        .collect::<StdResult<_>>()?;
+    MintersStore::remove_minters(deps.storage, minters_to_remove.clone())?;
+
+    AdminHistoryStore::record(
+        deps.storage,
+        &env,
+        &info.sender,
+        AdminAction::RemoveMinters {
+            minters: minters_to_remove,
+        },
+    )?;
 
     Ok(
         Response::new().set_data(to_binary(&ExecuteAnswer::RemoveMinters {
@@ -2051,6 +3559,7 @@ fn remove_minters(
 
 fn set_minters(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     minters_to_set: Vec<String>,
 ) -> StdResult<Response> {
@@ -2061,20 +3570,83 @@ fn set_minters(
         ));
     }
 
-    check_if_admin(&constants.admin, &info.sender)?;
+    RolesStore::check_role(deps.storage, Role::MinterManager, &info.sender)?;
 
     let minters_to_set: Vec<Addr> = minters_to_set
         .iter()
         .map(|minter| deps.api.addr_validate(minter.as_str()).unwrap())
         .collect();
-    MintersStore::save(deps.storage, minters_to_set)?;
+    MintersStore::save(deps.storage, minters_to_set.clone())?;
+
+    AdminHistoryStore::record(
+        deps.storage,
+        &env,
+        &info.sender,
+        AdminAction::SetMinters {
+            minters: minters_to_set,
+        },
+    )?;
 
     Ok(Response::new().set_data(to_binary(&ExecuteAnswer::SetMinters { status: Success })?))
 }
 
-/// Burn tokens
-///
-/// Remove `amount` tokens from the system irreversibly, from signer account
+fn set_minter_quota(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    minter: String,
+    allowance: Option<Uint128>,
+    rate: Option<MintRate>,
+) -> StdResult<Response> {
+    let constants = ConfigStore::load_constants(deps.storage)?;
+    if !constants.mint_is_enabled {
+        return Err(StdError::generic_err(
+            "Mint functionality is not enabled for this token.",
+        ));
+    }
+
+    RolesStore::check_role(deps.storage, Role::MinterManager, &info.sender)?;
+
+    let minter = deps.api.addr_validate(minter.as_str())?;
+    if !MintersStore::load(deps.storage)?.contains(&minter) {
+        return Err(StdError::generic_err(format!("{minter} is not a minter")));
+    }
+    if let Some(rate) = &rate {
+        if matches!(rate.period, MintPeriod::Blocks(0) | MintPeriod::Seconds(0)) {
+            return Err(StdError::generic_err(
+                "The rate period must be longer than 0",
+            ));
+        }
+    }
+
+    MintersStore::set_quota(
+        deps.storage,
+        &minter,
+        allowance.map(|allowance| allowance.u128()),
+        rate.clone(),
+    )?;
+
+    AdminHistoryStore::record(
+        deps.storage,
+        &env,
+        &info.sender,
+        AdminAction::SetMinterQuota {
+            minter,
+            allowance,
+            rate,
+        },
+    )?;
+
+    Ok(
+        Response::new().set_data(to_binary(&ExecuteAnswer::SetMinterQuota {
+            status: Success,
+        })?),
+    )
+}
+
+/// Burn tokens
+///
+/// Remove `amount` tokens from the system irreversibly, from signer account
 ///
 /// @param amount the amount of money to burn
 fn try_burn(
@@ -2109,6 +3681,8 @@ fn try_burn(
         &account_random_pos,
     )?;
 
+    notify_removed(deps.storage, &sender, raw_amount)?;
+
     let mut total_supply = ConfigStore::load_total_supply(deps.storage)?;
     if let Some(new_total_supply) = total_supply.checked_sub(raw_amount) {
         total_supply = new_total_supply;
@@ -2158,6 +3732,24 @@ fn perform_transfer(
     Ok(())
 }
 
+/// Let the recipient know what they received, and the owner what they spent.
+fn notify_transfer(
+    store: &mut dyn Storage,
+    owner: &CanonicalAddr,
+    recipient: &CanonicalAddr,
+    amount: u128,
+) -> StdResult<()> {
+    NotificationsStore::notify_received(store, recipient, amount, Some(owner))?;
+    let balance = BalancesStore::load(store, owner)?;
+    NotificationsStore::notify_spent(store, owner, amount, Some(recipient), balance)
+}
+
+/// Let the owner know what left their balance without going to another account.
+fn notify_removed(store: &mut dyn Storage, owner: &CanonicalAddr, amount: u128) -> StdResult<()> {
+    let balance = BalancesStore::load(store, owner)?;
+    NotificationsStore::notify_spent(store, owner, amount, None, balance)
+}
+
 fn revoke_permit(deps: DepsMut, info: MessageInfo, permit_name: String) -> StdResult<Response> {
     RevokedPermits::revoke_permit(
         deps.storage,
@@ -2169,7 +3761,15 @@ fn revoke_permit(deps: DepsMut, info: MessageInfo, permit_name: String) -> StdRe
     Ok(Response::new().set_data(to_binary(&ExecuteAnswer::RevokePermit { status: Success })?))
 }
 
-fn check_if_admin(config_admin: &Addr, account: &Addr) -> StdResult<()> {
+fn check_if_admin(config_admin: &Option<Addr>, account: &Addr) -> StdResult<()> {
+    let config_admin = match config_admin {
+        Some(config_admin) => config_admin,
+        None => {
+            return Err(StdError::generic_err(
+                "This token has no admin, so admin commands are disabled",
+            ))
+        }
+    };
     if config_admin != account {
         return Err(StdError::generic_err(
             "This is an admin command. Admin commands can only be run from admin address",
@@ -2225,13 +3825,23 @@ mod tests {
 
     use cosmwasm_std::testing::*;
     use cosmwasm_std::{
-        from_binary, BlockInfo, ContractInfo, MessageInfo, OwnedDeps, QueryResponse, ReplyOn,
-        SubMsg, Timestamp, TransactionInfo, WasmMsg,
+        from_binary, BlockInfo, ContractInfo, MessageInfo, Order, OwnedDeps, QuerierWrapper,
+        QueryResponse, Record, ReplyOn, SubMsg, Timestamp, TransactionInfo, WasmMsg,
     };
+    use cosmwasm_storage::{prefixed, prefixed_read};
     use secret_toolkit::permit::{PermitParams, PermitSignature, PubKey};
 
+    use crate::amber::{OAC_INVITE_CODES, OAC_MEMBERS, OAC_MEMBER_CODES, OAC_MEMBER_TIERS};
+    use crate::anonymity_set::{ANONYMITY_SET_LEN, ANONYMITY_SET_SLOTS};
+    use crate::btbe;
+    use crate::dwb::{DWB_LEN, DWB_MAX_TX_NODES};
+    use crate::hooks::{MembershipChange, MembershipChangedMsg};
     use crate::msg::ResponseStatus;
-    use crate::msg::{InitConfig, InitialBalance};
+    use crate::msg::{AdminHistoryEntry, InitConfig, InitialBalance};
+    use crate::state::{
+        KEY_CONSTANTS, KEY_CONTRACT_STATUS, KEY_PAUSE_FLAGS, PREFIX_BALANCES, PREFIX_CONFIG,
+    };
+    use crate::timelock::TIMELOCK_GRACE_PERIOD;
 
     use super::*;
 
@@ -2343,10 +3953,30 @@ mod tests {
             | ExecuteAnswer::BurnFrom { status }
             | ExecuteAnswer::Mint { status }
             | ExecuteAnswer::ChangeAdmin { status }
+            | ExecuteAnswer::ProposeAdmin { status }
+            | ExecuteAnswer::AcceptAdmin { status }
+            | ExecuteAnswer::CancelAdminProposal { status }
+            | ExecuteAnswer::RenounceAdmin { status }
+            | ExecuteAnswer::SetTimelock { status }
+            | ExecuteAnswer::CancelQueued { status }
+            | ExecuteAnswer::GrantRole { status }
+            | ExecuteAnswer::RevokeRole { status }
+            | ExecuteAnswer::RenounceRole { status }
             | ExecuteAnswer::SetContractStatus { status }
+            | ExecuteAnswer::SetPauseFlags { status }
+            | ExecuteAnswer::ReevaluateMembers { status }
+            | ExecuteAnswer::SetMaxSupply { status }
+            | ExecuteAnswer::SetMemberTiers { status }
+            | ExecuteAnswer::AddCodeConsumers { status }
+            | ExecuteAnswer::AddMembershipHook { status }
+            | ExecuteAnswer::EnableCheckpoints { status }
+            | ExecuteAnswer::SetTxLabel { status }
             | ExecuteAnswer::SetMinters { status }
+            | ExecuteAnswer::SetMinterQuota { status }
             | ExecuteAnswer::AddMinters { status }
-            | ExecuteAnswer::RemoveMinters { status } => {
+            | ExecuteAnswer::RemoveMinters { status }
+            | ExecuteAnswer::AddSupportedDenoms { status }
+            | ExecuteAnswer::RemoveSupportedDenoms { status } => {
                 matches!(status, ResponseStatus::Success { .. })
             }
             _ => panic!(
@@ -2401,7 +4031,7 @@ mod tests {
             ContractStatusLevel::NormalRun
         );
         assert_eq!(constants.name, "sec-sec".to_string());
-        assert_eq!(constants.admin, Addr::unchecked("admin".to_string()));
+        assert_eq!(constants.admin, Some(Addr::unchecked("admin".to_string())));
         assert_eq!(constants.symbol, "SECSEC".to_string());
         assert_eq!(constants.decimals, 8);
         assert_eq!(constants.total_supply_is_public, false);
@@ -2438,7 +4068,7 @@ mod tests {
             ContractStatusLevel::NormalRun
         );
         assert_eq!(constants.name, "sec-sec".to_string());
-        assert_eq!(constants.admin, Addr::unchecked("admin".to_string()));
+        assert_eq!(constants.admin, Some(Addr::unchecked("admin".to_string())));
         assert_eq!(constants.symbol, "SECSEC".to_string());
         assert_eq!(constants.decimals, 8);
         assert_eq!(constants.total_supply_is_public, false);
@@ -2483,272 +4113,2771 @@ mod tests {
             error,
             "The sum of all initial balances exceeds the maximum possible total supply"
         );
-    }
-
-    // Handle tests
+    }
+
+    // Handle tests
+
+    #[test]
+    fn test_execute_transfer() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: "bob".to_string(),
+            amount: Uint128::new(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = ExecuteMsg::Transfer {
+            recipient: "alice".to_string(),
+            amount: Uint128::new(1000),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("bob", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        let result = handle_result.unwrap();
+        assert!(ensure_success(result));
+        let bob_addr = Addr::unchecked("bob".to_string());
+        let alice_addr = Addr::unchecked("alice".to_string());
+
+        let bob_canonical = deps.api.addr_canonicalize(bob_addr.as_str()).unwrap();
+        let alice_canonical = deps.api.addr_canonicalize(alice_addr.as_str()).unwrap();
+
+        assert_eq!(
+            5000 - 1000,
+            BalancesStore::load(&deps.storage, &bob_canonical).unwrap()
+        );
+        assert_eq!(
+            1000,
+            BalancesStore::load(&deps.storage, &alice_canonical).unwrap()
+        );
+
+        let handle_msg = ExecuteMsg::Transfer {
+            recipient: "alice".to_string(),
+            amount: Uint128::new(10000),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("bob", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient funds"));
+    }
+
+    #[test]
+    fn test_decoys_balance_stays_on_transfer() {
+        let (init_result, mut deps) = init_helper(vec![
+            InitialBalance {
+                address: "bob".to_string(),
+                amount: Uint128::new(5000),
+            },
+            InitialBalance {
+                address: "lior".to_string(),
+                amount: Uint128::new(7000),
+            },
+        ]);
+
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let bob_addr = Addr::unchecked("bob".to_string());
+        let alice_addr = Addr::unchecked("alice".to_string());
+        let lior_addr = Addr::unchecked("lior".to_string());
+        let jhon_addr = Addr::unchecked("jhon".to_string());
+
+        let bob_canonical = deps.api.addr_canonicalize(bob_addr.as_str()).unwrap();
+        let alice_canonical = deps.api.addr_canonicalize(alice_addr.as_str()).unwrap();
+        let lior_canonical = deps.api.addr_canonicalize(lior_addr.as_str()).unwrap();
+        let jhon_canonical = deps.api.addr_canonicalize(jhon_addr.as_str()).unwrap();
+
+        let bob_balance = BalancesStore::load(&deps.storage, &bob_canonical).unwrap();
+        let alice_balance = BalancesStore::load(&deps.storage, &alice_canonical).unwrap();
+        let lior_balance = BalancesStore::load(&deps.storage, &lior_canonical).unwrap();
+        let jhon_balance = BalancesStore::load(&deps.storage, &jhon_canonical).unwrap();
+
+        let handle_msg = ExecuteMsg::Transfer {
+            recipient: "alice".to_string(),
+            amount: Uint128::new(1000),
+            memo: None,
+            decoys: Some(vec![lior_addr.clone(), jhon_addr.clone()]),
+            decoy_count: None,
+            entropy: Some(Binary::from_base64("VEVTVFRFU1RURVNUQ0hFQ0tDSEVDSw==").unwrap()),
+            padding: None,
+            gas_target: None,
+        };
+
+        let info = mock_info("bob", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        let result = handle_result.unwrap();
+        assert!(ensure_success(result));
+
+        assert_eq!(
+            bob_balance - 1000,
+            BalancesStore::load(&deps.storage, &bob_canonical).unwrap()
+        );
+        assert_eq!(
+            alice_balance + 1000,
+            BalancesStore::load(&deps.storage, &alice_canonical).unwrap()
+        );
+        assert_eq!(
+            lior_balance,
+            BalancesStore::load(&deps.storage, &lior_canonical).unwrap()
+        );
+        assert_eq!(
+            jhon_balance,
+            BalancesStore::load(&deps.storage, &jhon_canonical).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_handle_send() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: "bob".to_string(),
+            amount: Uint128::new(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = ExecuteMsg::RegisterReceive {
+            code_hash: "this_is_a_hash_of_a_code".to_string(),
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("contract", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        let result = handle_result.unwrap();
+        assert!(ensure_success(result));
+
+        let handle_msg = ExecuteMsg::Send {
+            recipient: "contract".to_string(),
+            recipient_code_hash: None,
+            amount: Uint128::new(100),
+            memo: Some("my memo".to_string()),
+            padding: None,
+            gas_target: None,
+            msg: Some(to_binary("hey hey you you").unwrap()),
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+        };
+        let info = mock_info("bob", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        let result = handle_result.unwrap();
+        assert!(ensure_success(result.clone()));
+        let id = 0;
+        assert!(result.messages.contains(&SubMsg {
+            id,
+            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "contract".to_string(),
+                code_hash: "this_is_a_hash_of_a_code".to_string(),
+                msg: Snip20ReceiveMsg::new(
+                    Addr::unchecked("bob".to_string()),
+                    Addr::unchecked("bob".to_string()),
+                    Uint128::new(100),
+                    Some("my memo".to_string()),
+                    Some(to_binary("hey hey you you").unwrap())
+                )
+                .into_binary()
+                .unwrap(),
+                funds: vec![],
+            })
+            .into(),
+            reply_on: match id {
+                0 => ReplyOn::Never,
+                _ => ReplyOn::Always,
+            },
+            gas_limit: None,
+        }));
+    }
+
+    #[test]
+    fn test_handle_register_receive() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: "bob".to_string(),
+            amount: Uint128::new(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = ExecuteMsg::RegisterReceive {
+            code_hash: "this_is_a_hash_of_a_code".to_string(),
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("contract", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        let result = handle_result.unwrap();
+        assert!(ensure_success(result));
+
+        let hash =
+            ReceiverHashStore::may_load(&deps.storage, &Addr::unchecked("contract".to_string()))
+                .unwrap()
+                .unwrap();
+        assert_eq!(hash, "this_is_a_hash_of_a_code".to_string());
+    }
+
+    #[test]
+    fn test_handle_create_viewing_key() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: "bob".to_string(),
+            amount: Uint128::new(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = ExecuteMsg::CreateViewingKey {
+            entropy: "".to_string(),
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("bob", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+        let answer: ExecuteAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+
+        let key = match answer {
+            ExecuteAnswer::CreateViewingKey { key } => key,
+            _ => panic!("NOPE"),
+        };
+        let bob_canonical = deps.as_mut().api.addr_canonicalize("bob").unwrap();
+
+        let result = ViewingKey::check(&deps.storage, bob_canonical.as_slice(), key.as_str());
+        assert!(result.is_ok());
+
+        // let saved_vk = read_viewing_key(&deps.storage, &bob_canonical).unwrap();
+        // assert!(key.check_viewing_key(saved_vk.as_slice()));
+    }
+
+    #[test]
+    fn test_handle_set_viewing_key() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: "bob".to_string(),
+            amount: Uint128::new(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        // Set VK
+        let handle_msg = ExecuteMsg::SetViewingKey {
+            key: "hi lol".to_string(),
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("bob", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        let unwrapped_result: ExecuteAnswer =
+            from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        assert_eq!(
+            to_binary(&unwrapped_result).unwrap(),
+            to_binary(&ExecuteAnswer::SetViewingKey {
+                status: ResponseStatus::Success
+            })
+            .unwrap(),
+        );
+
+        // Set valid VK
+        let actual_vk = "x".to_string().repeat(VIEWING_KEY_SIZE);
+        let handle_msg = ExecuteMsg::SetViewingKey {
+            key: actual_vk.clone(),
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("bob", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        let unwrapped_result: ExecuteAnswer =
+            from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        assert_eq!(
+            to_binary(&unwrapped_result).unwrap(),
+            to_binary(&ExecuteAnswer::SetViewingKey { status: Success }).unwrap(),
+        );
+
+        let bob_canonical = deps.as_mut().api.addr_canonicalize("bob").unwrap();
+
+        let result = ViewingKey::check(&deps.storage, bob_canonical.as_slice(), actual_vk.as_str());
+        assert!(result.is_ok());
+    }
+
+    fn revoke_permit(
+        permit_name: &str,
+        user_address: &str,
+        deps: &mut OwnedDeps<cosmwasm_std::MemoryStorage, MockApi, MockQuerier>,
+    ) -> Result<Response, StdError> {
+        let handle_msg = ExecuteMsg::RevokePermit {
+            permit_name: permit_name.to_string(),
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info(user_address, &[]);
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        handle_result
+    }
+
+    fn get_balance_with_permit_qry_msg(
+        permit_name: &str,
+        chain_id: &str,
+        pub_key_value: &str,
+        signature: &str,
+    ) -> QueryMsg {
+        let permit = gen_permit_obj(
+            permit_name,
+            chain_id,
+            pub_key_value,
+            signature,
+            AmberPermissions::Balance,
+        );
+
+        QueryMsg::WithPermit {
+            permit,
+            query: QueryWithPermit::Balance {},
+        }
+    }
+
+    fn gen_permit_obj(
+        permit_name: &str,
+        chain_id: &str,
+        pub_key_value: &str,
+        signature: &str,
+        permit_type: AmberPermissions,
+    ) -> Permit<AmberPermissions> {
+        let permit: Permit<AmberPermissions> = Permit {
+            params: PermitParams {
+                allowed_tokens: vec![MOCK_CONTRACT_ADDR.to_string()],
+                permit_name: permit_name.to_string(),
+                chain_id: chain_id.to_string(),
+                permissions: vec![permit_type],
+            },
+            signature: PermitSignature {
+                pub_key: PubKey {
+                    r#type: "tendermint/PubKeySecp256k1".to_string(),
+                    value: Binary::from_base64(pub_key_value).unwrap(),
+                },
+                signature: Binary::from_base64(signature).unwrap(),
+            },
+        };
+        permit
+    }
+
+    fn get_allowances_given_permit(
+        permit_name: &str,
+        chain_id: &str,
+        pub_key_value: &str,
+        signature: &str,
+        spender: String,
+    ) -> QueryMsg {
+        let permit = gen_permit_obj(
+            permit_name,
+            chain_id,
+            pub_key_value,
+            signature,
+            AmberPermissions::Owner,
+        );
+
+        QueryMsg::WithPermit {
+            permit,
+            query: QueryWithPermit::AllowancesReceived {
+                spender,
+                page: None,
+                page_size: 0,
+            },
+        }
+    }
+
+    #[test]
+    fn test_permit_query_allowances_given_should_fail() {
+        let user_address = "secret18mdrja40gfuftt5yx6tgj0fn5lurplezyp894y";
+        let permit_name = "default";
+        let chain_id = "secretdev-1";
+        let pub_key = "AkZqxdKMtPq2w0kGDGwWGejTAed0H7azPMHtrCX0XYZG";
+        let signature = "ZXyFMlAy6guMG9Gj05rFvcMi5/JGfClRtJpVTHiDtQY3GtSfBHncY70kmYiTXkKIxSxdnh/kS8oXa+GSX5su6Q==";
+
+        // Init the contract
+        let (init_result, deps) = init_helper(vec![InitialBalance {
+            address: user_address.to_string(),
+            amount: Uint128::new(50000000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let msg = get_allowances_given_permit(
+            permit_name,
+            chain_id,
+            pub_key,
+            signature,
+            "secret1kmgdagt5efcz2kku0ak9ezfgntg29g2vr88q0e".to_string(),
+        );
+        let query_result = query(deps.as_ref(), mock_env(), msg);
+
+        assert_eq!(query_result.is_err(), true);
+    }
+
+    #[test]
+    fn test_permit_query_allowances_given() {
+        let user_address = "secret18mdrja40gfuftt5yx6tgj0fn5lurplezyp894y";
+        let permit_name = "default";
+        let chain_id = "secretdev-1";
+        let pub_key = "AkZqxdKMtPq2w0kGDGwWGejTAed0H7azPMHtrCX0XYZG";
+        let signature = "ZXyFMlAy6guMG9Gj05rFvcMi5/JGfClRtJpVTHiDtQY3GtSfBHncY70kmYiTXkKIxSxdnh/kS8oXa+GSX5su6Q==";
+
+        // Init the contract
+        let (init_result, deps) = init_helper(vec![InitialBalance {
+            address: user_address.to_string(),
+            amount: Uint128::new(50000000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let msg = get_allowances_given_permit(
+            permit_name,
+            chain_id,
+            pub_key,
+            signature,
+            "secret18mdrja40gfuftt5yx6tgj0fn5lurplezyp894y".to_string(),
+        );
+        let query_result = query(deps.as_ref(), mock_env(), msg);
+
+        assert_eq!(query_result.is_ok(), true);
+    }
+
+    #[test]
+    fn test_permit_revoke() {
+        let user_address = "secret1kmgdagt5efcz2kku0ak9ezfgntg29g2vr88q0e";
+        let permit_name = "to_be_revoked";
+        let chain_id = "blabla";
+
+        // Note that 'signature'was generated with the specific values of the above:
+        // user_address, permit_name, chain_id, pub_key_value
+        let pub_key_value = "Ahlb7vwjo4aTY6dqfgpPmPYF7XhTAIReVwncQwlq8Sct";
+        let signature = "VS13F7iv1qxKABxrCAvZQPy2IruLQsIyfTewy/PIhNtybtq417lr3FxsWjV/i9YTqCUxg7weoZwHmYs0YgYX4w==";
+
+        // Init the contract
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: user_address.to_string(),
+            amount: Uint128::new(50000000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        // Query the account's balance
+        let balance_with_permit_msg =
+            get_balance_with_permit_qry_msg(permit_name, chain_id, pub_key_value, signature);
+        let query_result = query(deps.as_ref(), mock_env(), balance_with_permit_msg);
+        let balance = match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::Balance { amount } => amount,
+            _ => panic!("Unexpected result from query"),
+        };
+        assert_eq!(balance.u128(), 50000000);
+
+        // Revoke the Balance permit
+        let handle_result = revoke_permit(permit_name, user_address, &mut deps);
+        let status = match from_binary(&handle_result.unwrap().data.unwrap()).unwrap() {
+            ExecuteAnswer::RevokePermit { status } => status,
+            _ => panic!("NOPE"),
+        };
+        assert_eq!(status, ResponseStatus::Success);
+
+        // Try to query the balance with permit and fail because the permit is now revoked
+        let balance_with_permit_msg =
+            get_balance_with_permit_qry_msg(permit_name, chain_id, pub_key_value, signature);
+        let query_result = query(deps.as_ref(), mock_env(), balance_with_permit_msg);
+        let error = extract_error_msg(query_result);
+        assert!(
+            error.contains(format!("Permit \"{}\" was revoked by account", permit_name).as_str())
+        );
+    }
+
+    #[test]
+    fn test_query_has_at_least() {
+        let user_address = "secret1kmgdagt5efcz2kku0ak9ezfgntg29g2vr88q0e";
+        let permit_name = "to_be_revoked";
+        let chain_id = "blabla";
+        let pub_key_value = "Ahlb7vwjo4aTY6dqfgpPmPYF7XhTAIReVwncQwlq8Sct";
+        let signature = "VS13F7iv1qxKABxrCAvZQPy2IruLQsIyfTewy/PIhNtybtq417lr3FxsWjV/i9YTqCUxg7weoZwHmYs0YgYX4w==";
+
+        let (init_result, deps) = init_helper(vec![InitialBalance {
+            address: user_address.to_string(),
+            amount: Uint128::new(50000000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        // A balance permit covers the threshold query too
+        let permit = gen_permit_obj(
+            permit_name,
+            chain_id,
+            pub_key_value,
+            signature,
+            AmberPermissions::Balance,
+        );
+        for (amount, expected) in [(50000000, true), (50000001, false)] {
+            let query_msg = QueryMsg::WithPermit {
+                permit: permit.clone(),
+                query: QueryWithPermit::HasAtLeast {
+                    amount: Uint128::new(amount),
+                    attest: None,
+                },
+            };
+            let query_result = query(deps.as_ref(), mock_env(), query_msg).unwrap();
+            match from_binary(&query_result).unwrap() {
+                QueryAnswer::HasAtLeast {
+                    result,
+                    attestation,
+                    ..
+                } => {
+                    assert_eq!(result, expected);
+                    assert!(attestation.is_none());
+                }
+                other => panic!("Unexpected: {:?}", other),
+            }
+        }
+
+        // A threshold permit is signed for one amount, and only covers that one
+        let permission = AmberPermissions::HasAtLeast {
+            amount: Uint128::new(1000),
+        };
+        assert_eq!(
+            to_binary(&permission).unwrap(),
+            Binary::from(br#"{"has_at_least":{"amount":"1000"}}"#.as_slice())
+        );
+        let permit = gen_permit_obj(permit_name, chain_id, pub_key_value, signature, permission);
+        assert!(permits_has_at_least(&permit, Uint128::new(1000)));
+        assert!(!permits_has_at_least(&permit, Uint128::new(999)));
+        assert!(!permits_has_at_least(&permit, Uint128::new(1001)));
+        let permit = gen_permit_obj(
+            permit_name,
+            chain_id,
+            pub_key_value,
+            signature,
+            AmberPermissions::Balance,
+        );
+        assert!(permits_has_at_least(&permit, Uint128::new(1)));
+        let permit = gen_permit_obj(
+            permit_name,
+            chain_id,
+            pub_key_value,
+            signature,
+            AmberPermissions::History,
+        );
+        assert!(!permits_has_at_least(&permit, Uint128::new(1)));
+
+        let query_result = query(deps.as_ref(), mock_env(), QueryMsg::AttestationKey {}).unwrap();
+        let pubkey = match from_binary(&query_result).unwrap() {
+            QueryAnswer::AttestationKey { pubkey } => pubkey,
+            other => panic!("Unexpected: {:?}", other),
+        };
+
+        let env = mock_env();
+        let result = query_has_at_least(
+            deps.as_ref(),
+            &env,
+            "bob".to_string(),
+            Uint128::new(1),
+            true,
+        )
+        .unwrap();
+        match from_binary(&result).unwrap() {
+            QueryAnswer::HasAtLeast {
+                result,
+                attestation: Some(attestation),
+                data: Some(data),
+                signature: Some(signature),
+            } => {
+                assert!(!result);
+                assert!(!attestation.has_at_least);
+                assert_eq!(attestation.amount, Uint128::new(1));
+                assert_eq!(
+                    attestation.address_hash,
+                    Binary::from(sha_256(b"bob").to_vec())
+                );
+                assert_eq!(data, to_binary(&attestation).unwrap());
+
+                let verified = deps
+                    .api
+                    .secp256k1_verify(&sha_256(data.as_slice()), signature.as_slice(), &pubkey)
+                    .unwrap();
+                assert!(verified);
+            }
+            other => panic!("Unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_execute_transfer_from() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: "bob".to_string(),
+            amount: Uint128::new(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        // Transfer before allowance
+        let handle_msg = ExecuteMsg::TransferFrom {
+            owner: "bob".to_string(),
+            recipient: "alice".to_string(),
+            amount: Uint128::new(2500),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("alice", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient allowance"));
+
+        // Transfer more than allowance
+        let handle_msg = ExecuteMsg::IncreaseAllowance {
+            spender: "alice".to_string(),
+            amount: Uint128::new(2000),
+            padding: None,
+            gas_target: None,
+            expiration: Some(1_571_797_420),
+        };
+        let info = mock_info("bob", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+        let handle_msg = ExecuteMsg::TransferFrom {
+            owner: "bob".to_string(),
+            recipient: "alice".to_string(),
+            amount: Uint128::new(2500),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("alice", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient allowance"));
+
+        // Transfer after allowance expired
+        let handle_msg = ExecuteMsg::TransferFrom {
+            owner: "bob".to_string(),
+            recipient: "alice".to_string(),
+            amount: Uint128::new(2000),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+
+        let info = MessageInfo {
+            sender: Addr::unchecked("bob".to_string()),
+            funds: vec![],
+        };
+
+        let handle_result = execute(
+            deps.as_mut(),
+            Env {
+                block: BlockInfo {
+                    height: 12_345,
+                    time: Timestamp::from_seconds(1_571_797_420),
+                    chain_id: "cosmos-testnet-14002".to_string(),
+                    random: None,
+                },
+                transaction: Some(TransactionInfo {
+                    index: 3,
+                    hash: "lol".to_string(),
+                }),
+                contract: ContractInfo {
+                    address: Addr::unchecked(MOCK_CONTRACT_ADDR.to_string()),
+                    code_hash: "".to_string(),
+                },
+            },
+            info,
+            handle_msg,
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient allowance"));
+
+        // Sanity check
+        let handle_msg = ExecuteMsg::TransferFrom {
+            owner: "bob".to_string(),
+            recipient: "alice".to_string(),
+            amount: Uint128::new(2000),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("alice", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+        let bob_addr = Addr::unchecked("bob".to_string());
+        let alice_addr = Addr::unchecked("alice".to_string());
+
+        let bob_canonical = deps.api.addr_canonicalize(bob_addr.as_str()).unwrap();
+        let alice_canonical = deps.api.addr_canonicalize(alice_addr.as_str()).unwrap();
+
+        let bob_balance = BalancesStore::load(&deps.storage, &bob_canonical).unwrap();
+        let alice_balance = BalancesStore::load(&deps.storage, &alice_canonical).unwrap();
+        assert_eq!(bob_balance, 5000 - 2000);
+        assert_eq!(alice_balance, 2000);
+        let total_supply = ConfigStore::load_total_supply(&deps.storage).unwrap();
+        assert_eq!(total_supply, 5000);
+
+        // Second send more than allowance
+        let handle_msg = ExecuteMsg::TransferFrom {
+            owner: "bob".to_string(),
+            recipient: "alice".to_string(),
+            amount: Uint128::new(1),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("alice", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient allowance"));
+    }
+
+    #[test]
+    fn test_handle_send_from() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: "bob".to_string(),
+            amount: Uint128::new(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        // Send before allowance
+        let handle_msg = ExecuteMsg::SendFrom {
+            owner: "bob".to_string(),
+            recipient: "alice".to_string(),
+            recipient_code_hash: None,
+            amount: Uint128::new(2500),
+            memo: None,
+            msg: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("alice", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient allowance"));
+
+        // Send more than allowance
+        let handle_msg = ExecuteMsg::IncreaseAllowance {
+            spender: "alice".to_string(),
+            amount: Uint128::new(2000),
+            padding: None,
+            gas_target: None,
+            expiration: None,
+        };
+        let info = mock_info("bob", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+        let handle_msg = ExecuteMsg::SendFrom {
+            owner: "bob".to_string(),
+            recipient: "alice".to_string(),
+            recipient_code_hash: None,
+            amount: Uint128::new(2500),
+            memo: None,
+            msg: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("alice", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient allowance"));
+
+        // Sanity check
+        let handle_msg = ExecuteMsg::RegisterReceive {
+            code_hash: "lolz".to_string(),
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("contract", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+        let send_msg = Binary::from(r#"{ "some_msg": { "some_key": "some_val" } }"#.as_bytes());
+        let snip20_msg = Snip20ReceiveMsg::new(
+            Addr::unchecked("alice".to_string()),
+            Addr::unchecked("bob".to_string()),
+            Uint128::new(2000),
+            Some("my memo".to_string()),
+            Some(send_msg.clone()),
+        );
+        let handle_msg = ExecuteMsg::SendFrom {
+            owner: "bob".to_string(),
+            recipient: "contract".to_string(),
+            recipient_code_hash: None,
+            amount: Uint128::new(2000),
+            memo: Some("my memo".to_string()),
+            msg: Some(send_msg),
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("alice", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+        assert!(handle_result.unwrap().messages.contains(
+            &into_cosmos_submsg(
+                snip20_msg,
+                "lolz".to_string(),
+                Addr::unchecked("contract".to_string()),
+                0
+            )
+            .unwrap()
+        ));
+
+        let bob_addr = Addr::unchecked("bob".to_string());
+        let bob_canonical = deps.api.addr_canonicalize(bob_addr.as_str()).unwrap();
+
+        let contract_addr = Addr::unchecked("contract".to_string());
+        let canon_contract_addr = deps.api.addr_canonicalize(contract_addr.as_str()).unwrap();
+
+        let bob_balance = BalancesStore::load(&deps.storage, &bob_canonical).unwrap();
+        let contract_balance = BalancesStore::load(&deps.storage, &canon_contract_addr).unwrap();
+        assert_eq!(bob_balance, 5000 - 2000);
+        assert_eq!(contract_balance, 2000);
+        let total_supply = ConfigStore::load_total_supply(&deps.storage).unwrap();
+        assert_eq!(total_supply, 5000);
+
+        // Second send more than allowance
+        let handle_msg = ExecuteMsg::SendFrom {
+            owner: "bob".to_string(),
+            recipient: "alice".to_string(),
+            recipient_code_hash: None,
+            amount: Uint128::new(1),
+            memo: None,
+            msg: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("alice", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient allowance"));
+    }
+
+    #[test]
+    fn test_handle_burn_from() {
+        let (init_result, mut deps) = init_helper_with_config(
+            vec![InitialBalance {
+                address: "bob".to_string(),
+                amount: Uint128::new(10000),
+            }],
+            false,
+            false,
+            false,
+            true,
+            0,
+            vec![],
+        );
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let (init_result_for_failure, mut deps_for_failure) = init_helper(vec![InitialBalance {
+            address: "bob".to_string(),
+            amount: Uint128::new(10000),
+        }]);
+        assert!(
+            init_result_for_failure.is_ok(),
+            "Init failed: {}",
+            init_result_for_failure.err().unwrap()
+        );
+        // test when burn disabled
+        let handle_msg = ExecuteMsg::BurnFrom {
+            owner: "bob".to_string(),
+            amount: Uint128::new(2500),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("alice", &[]);
+
+        let handle_result = execute(deps_for_failure.as_mut(), mock_env(), info, handle_msg);
+
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Burn functionality is not enabled for this token."));
+
+        // Burn before allowance
+        let handle_msg = ExecuteMsg::BurnFrom {
+            owner: "bob".to_string(),
+            amount: Uint128::new(2500),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("alice", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient allowance"));
+
+        // Burn more than allowance
+        let handle_msg = ExecuteMsg::IncreaseAllowance {
+            spender: "alice".to_string(),
+            amount: Uint128::new(2000),
+            padding: None,
+            gas_target: None,
+            expiration: None,
+        };
+        let info = mock_info("bob", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+        let handle_msg = ExecuteMsg::BurnFrom {
+            owner: "bob".to_string(),
+            amount: Uint128::new(2500),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("alice", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient allowance"));
+
+        // Sanity check
+        let handle_msg = ExecuteMsg::BurnFrom {
+            owner: "bob".to_string(),
+            amount: Uint128::new(2000),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("alice", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+        let bob_addr = Addr::unchecked("bob".to_string());
+        let bob_canonical = deps.api.addr_canonicalize(bob_addr.as_str()).unwrap();
+        let bob_balance = BalancesStore::load(&deps.storage, &bob_canonical).unwrap();
+        assert_eq!(bob_balance, 10000 - 2000);
+        let total_supply = ConfigStore::load_total_supply(&deps.storage).unwrap();
+        assert_eq!(total_supply, 10000 - 2000);
+
+        // Second burn more than allowance
+        let handle_msg = ExecuteMsg::BurnFrom {
+            owner: "bob".to_string(),
+            amount: Uint128::new(1),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("alice", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient allowance"));
+    }
+
+    #[test]
+    fn test_handle_batch_burn_from() {
+        let (init_result, mut deps) = init_helper_with_config(
+            vec![
+                InitialBalance {
+                    address: "bob".to_string(),
+                    amount: Uint128::new(10000),
+                },
+                InitialBalance {
+                    address: "jerry".to_string(),
+                    amount: Uint128::new(10000),
+                },
+                InitialBalance {
+                    address: "mike".to_string(),
+                    amount: Uint128::new(10000),
+                },
+            ],
+            false,
+            false,
+            false,
+            true,
+            0,
+            vec![],
+        );
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let (init_result_for_failure, mut deps_for_failure) = init_helper(vec![InitialBalance {
+            address: "bob".to_string(),
+            amount: Uint128::new(10000),
+        }]);
+        assert!(
+            init_result_for_failure.is_ok(),
+            "Init failed: {}",
+            init_result_for_failure.err().unwrap()
+        );
+        // test when burn disabled
+        let actions: Vec<_> = ["bob", "jerry", "mike"]
+            .iter()
+            .map(|name| batch::BurnFromAction {
+                owner: name.to_string(),
+                amount: Uint128::new(2500),
+                memo: None,
+                decoys: None,
+            })
+            .collect();
+        let handle_msg = ExecuteMsg::BatchBurnFrom {
+            actions,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("alice", &[]);
+        let handle_result = execute(
+            deps_for_failure.as_mut(),
+            mock_env(),
+            info,
+            handle_msg.clone(),
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Burn functionality is not enabled for this token."));
+
+        // Burn before allowance
+        let info = mock_info("alice", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient allowance"));
+
+        // Burn more than allowance
+        let allowance_size = 2000;
+        for name in &["bob", "jerry", "mike"] {
+            let handle_msg = ExecuteMsg::IncreaseAllowance {
+                spender: "alice".to_string(),
+                amount: Uint128::new(allowance_size),
+                padding: None,
+                gas_target: None,
+                expiration: None,
+            };
+            let info = mock_info(*name, &[]);
+            let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+            assert!(
+                handle_result.is_ok(),
+                "handle() failed: {}",
+                handle_result.err().unwrap()
+            );
+            let handle_msg = ExecuteMsg::BurnFrom {
+                owner: "name".to_string(),
+                amount: Uint128::new(2500),
+                memo: None,
+                decoys: None,
+                decoy_count: None,
+                entropy: None,
+                padding: None,
+                gas_target: None,
+            };
+            let info = mock_info("alice", &[]);
+
+            let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+            let error = extract_error_msg(handle_result);
+            assert!(error.contains("insufficient allowance"));
+        }
+
+        // Burn some of the allowance
+        let actions: Vec<_> = [("bob", 200_u128), ("jerry", 300), ("mike", 400)]
+            .iter()
+            .map(|(name, amount)| batch::BurnFromAction {
+                owner: name.to_string(),
+                amount: Uint128::new(*amount),
+                memo: None,
+                decoys: None,
+            })
+            .collect();
+
+        let handle_msg = ExecuteMsg::BatchBurnFrom {
+            actions,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("alice", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+        for (name, amount) in &[("bob", 200_u128), ("jerry", 300), ("mike", 400)] {
+            let name_addr = Addr::unchecked(name.to_string());
+            let name_canonical = deps.api.addr_canonicalize(name_addr.as_str()).unwrap();
+            let balance = BalancesStore::load(&deps.storage, &name_canonical).unwrap();
+            assert_eq!(balance, 10000 - amount);
+        }
+        let total_supply = ConfigStore::load_total_supply(&deps.storage).unwrap();
+        assert_eq!(total_supply, 10000 * 3 - (200 + 300 + 400));
+
+        // Burn the rest of the allowance
+        let actions: Vec<_> = [("bob", 200_u128), ("jerry", 300), ("mike", 400)]
+            .iter()
+            .map(|(name, amount)| batch::BurnFromAction {
+                owner: name.to_string(),
+                amount: Uint128::new(allowance_size - *amount),
+                memo: None,
+                decoys: None,
+            })
+            .collect();
+
+        let handle_msg = ExecuteMsg::BatchBurnFrom {
+            actions,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("alice", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+        for name in &["bob", "jerry", "mike"] {
+            let name_addr = Addr::unchecked(name.to_string());
+            let name_canonical = deps.api.addr_canonicalize(name_addr.as_str()).unwrap();
+            let balance = BalancesStore::load(&deps.storage, &name_canonical).unwrap();
+            assert_eq!(balance, 10000 - allowance_size);
+        }
+        let total_supply = ConfigStore::load_total_supply(&deps.storage).unwrap();
+        assert_eq!(total_supply, 3 * (10000 - allowance_size));
+
+        // Second burn more than allowance
+        let actions: Vec<_> = ["bob", "jerry", "mike"]
+            .iter()
+            .map(|name| batch::BurnFromAction {
+                owner: name.to_string(),
+                amount: Uint128::new(1),
+                memo: None,
+                decoys: None,
+            })
+            .collect();
+        let handle_msg = ExecuteMsg::BatchBurnFrom {
+            actions,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("alice", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient allowance"));
+    }
+
+    #[test]
+    fn test_handle_decrease_allowance() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: "bob".to_string(),
+            amount: Uint128::new(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = ExecuteMsg::DecreaseAllowance {
+            spender: "alice".to_string(),
+            amount: Uint128::new(2000),
+            padding: None,
+            gas_target: None,
+            expiration: None,
+        };
+        let info = mock_info("bob", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+
+        let bob_addr = Addr::unchecked("bob".to_string());
+        let alice_addr = Addr::unchecked("alice".to_string());
+
+        let _bob_canonical = deps.api.addr_canonicalize(bob_addr.as_str()).unwrap();
+        let _alice_canonical = deps.api.addr_canonicalize(alice_addr.as_str()).unwrap();
+
+        let allowance = AllowancesStore::load(&deps.storage, &bob_addr, &alice_addr);
+        assert_eq!(
+            allowance,
+            crate::state::Allowance {
+                amount: 0,
+                expiration: None
+            }
+        );
+
+        let handle_msg = ExecuteMsg::IncreaseAllowance {
+            spender: "alice".to_string(),
+            amount: Uint128::new(2000),
+            padding: None,
+            gas_target: None,
+            expiration: None,
+        };
+        let info = mock_info("bob", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+
+        let handle_msg = ExecuteMsg::DecreaseAllowance {
+            spender: "alice".to_string(),
+            amount: Uint128::new(50),
+            padding: None,
+            gas_target: None,
+            expiration: None,
+        };
+        let info = mock_info("bob", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+
+        let allowance = AllowancesStore::load(&deps.storage, &bob_addr, &alice_addr);
+        assert_eq!(
+            allowance,
+            crate::state::Allowance {
+                amount: 1950,
+                expiration: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_handle_increase_allowance() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: "bob".to_string(),
+            amount: Uint128::new(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = ExecuteMsg::IncreaseAllowance {
+            spender: "alice".to_string(),
+            amount: Uint128::new(2000),
+            padding: None,
+            gas_target: None,
+            expiration: None,
+        };
+        let info = mock_info("bob", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+
+        let bob_canonical = Addr::unchecked("bob".to_string());
+        let alice_canonical = Addr::unchecked("alice".to_string());
+
+        let allowance = AllowancesStore::load(&deps.storage, &bob_canonical, &alice_canonical);
+        assert_eq!(
+            allowance,
+            crate::state::Allowance {
+                amount: 2000,
+                expiration: None
+            }
+        );
+
+        let handle_msg = ExecuteMsg::IncreaseAllowance {
+            spender: "alice".to_string(),
+            amount: Uint128::new(2000),
+            padding: None,
+            gas_target: None,
+            expiration: None,
+        };
+        let info = mock_info("bob", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+
+        let allowance = AllowancesStore::load(&deps.storage, &bob_canonical, &alice_canonical);
+        assert_eq!(
+            allowance,
+            crate::state::Allowance {
+                amount: 4000,
+                expiration: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_handle_change_admin() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: "bob".to_string(),
+            amount: Uint128::new(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = ExecuteMsg::ChangeAdmin {
+            address: "bob".to_string(),
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("admin", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+
+        // Nothing changes until the new admin accepts
+        let admin = ConfigStore::load_constants(&deps.storage).unwrap().admin;
+        assert_eq!(admin, Some(Addr::unchecked("admin".to_string())));
+
+        let handle_msg = ExecuteMsg::AcceptAdmin {
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let admin = ConfigStore::load_constants(&deps.storage).unwrap().admin;
+        assert_eq!(admin, Some(Addr::unchecked("bob".to_string())));
+    }
+
+    #[test]
+    fn test_admin_proposals() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: "bob".to_string(),
+            amount: Uint128::new(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let accept_msg = ExecuteMsg::AcceptAdmin {
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &[]),
+            accept_msg.clone(),
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("There is no pending admin proposal for this address"));
+
+        let expiration = mock_env().block.time.seconds() + 100;
+        let propose_msg = ExecuteMsg::ProposeAdmin {
+            address: "bob".to_string(),
+            expiration: Some(expiration),
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            propose_msg.clone(),
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let query_result = query(deps.as_ref(), mock_env(), QueryMsg::AdminProposal {});
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::AdminProposal {
+                admin,
+                proposed_admin,
+                expiration: proposal_expiration,
+            } => {
+                assert_eq!(admin, Some(Addr::unchecked("admin")));
+                assert_eq!(proposed_admin, Some(Addr::unchecked("bob")));
+                assert_eq!(proposal_expiration, Some(expiration));
+            }
+            _ => panic!("unexpected"),
+        }
+
+        // Only the proposed address can accept, and only before the proposal expires
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("lebron", &[]),
+            accept_msg.clone(),
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("There is no pending admin proposal for this address"));
+
+        let mut env = mock_env();
+        env.block.time = Timestamp::from_seconds(expiration);
+        let handle_result = execute(
+            deps.as_mut(),
+            env,
+            mock_info("bob", &[]),
+            accept_msg.clone(),
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The admin proposal has expired"));
+
+        let cancel_msg = ExecuteMsg::CancelAdminProposal {
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            cancel_msg.clone(),
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            cancel_msg,
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("There is no pending admin proposal"));
+
+        // Renouncing leaves no admin and no role holders
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            propose_msg.clone(),
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        let renounce_msg = ExecuteMsg::RenounceAdmin {
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            renounce_msg,
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let query_result = query(deps.as_ref(), mock_env(), QueryMsg::RoleHolders {});
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::RoleHolders { admin, roles } => {
+                assert_eq!(admin, None);
+                assert!(roles.iter().all(|role| role.holders.is_empty()));
+            }
+            _ => panic!("unexpected"),
+        }
+
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), accept_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("There is no pending admin proposal for this address"));
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            propose_msg,
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This token has no admin"));
+    }
+
+    #[test]
+    fn test_timelock() {
+        let (init_result, mut deps) = init_helper_with_config(
+            vec![InitialBalance {
+                address: "bob".to_string(),
+                amount: Uint128::new(5000),
+            }],
+            false,
+            false,
+            true,
+            false,
+            0,
+            vec![],
+        );
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+        let env_at = |height: u64| {
+            let mut env = mock_env();
+            env.block.height = height;
+            env
+        };
+
+        let handle_msg = ExecuteMsg::SetTimelock {
+            delay: 100,
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &[]),
+            handle_msg.clone(),
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This is an admin command"));
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            ExecuteMsg::SetTimelock {
+                delay: MAX_TIMELOCK_DELAY + 1,
+                padding: None,
+                gas_target: None,
+            },
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The timelock delay cannot be more than 100800 blocks"));
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            handle_msg,
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+
+        // Adding a minter is queued instead of executed, and only by a minter manager
+        let add_minters_msg = ExecuteMsg::AddMinters {
+            minters: vec!["bob".to_string()],
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &[]),
+            add_minters_msg.clone(),
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This command requires the minter_manager role"));
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            add_minters_msg,
+        );
+        let answer: ExecuteAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        let id = match answer {
+            ExecuteAnswer::Queued { id, not_before } => {
+                assert_eq!(not_before, 12445);
+                id
+            }
+            _ => panic!("unexpected"),
+        };
+        assert!(!MintersStore::load(&deps.storage)
+            .unwrap()
+            .contains(&Addr::unchecked("bob")));
+
+        let queued = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>, page: u32| {
+            let query_msg = QueryMsg::QueuedOperations {
+                page: Some(page),
+                page_size: 1,
+            };
+            let query_result = query(deps.as_ref(), mock_env(), query_msg);
+            match from_binary(&query_result.unwrap()).unwrap() {
+                QueryAnswer::QueuedOperations {
+                    delay,
+                    operations,
+                    total,
+                } => (delay, operations, total),
+                _ => panic!("unexpected"),
+            }
+        };
+        let (delay, operations, total) = queued(&deps, 0);
+        assert_eq!(delay, 100);
+        assert_eq!(total, 1);
+        assert_eq!(operations.len(), 1);
+        assert_eq!(operations[0].id, id);
+        assert_eq!(operations[0].sender, Addr::unchecked("admin"));
+        assert!(matches!(operations[0].msg, ExecuteMsg::AddMinters { .. }));
+        assert_eq!(operations[0].not_before, 12445);
+        assert_eq!(operations[0].expires_at, 12445 + TIMELOCK_GRACE_PERIOD);
+        let (_, operations, total) = queued(&deps, 1);
+        assert!(operations.is_empty());
+        assert_eq!(total, 1);
+
+        let execute_msg = ExecuteMsg::ExecuteQueued {
+            id,
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            env_at(12444),
+            mock_info("admin", &[]),
+            execute_msg.clone(),
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("cannot be executed before height 12445"));
+        let handle_result = execute(
+            deps.as_mut(),
+            env_at(12445),
+            mock_info("bob", &[]),
+            execute_msg.clone(),
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Only the account that queued an operation can execute it"));
+        let handle_result = execute(
+            deps.as_mut(),
+            env_at(12445),
+            mock_info("admin", &[]),
+            execute_msg.clone(),
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        assert!(MintersStore::load(&deps.storage)
+            .unwrap()
+            .contains(&Addr::unchecked("bob")));
+        let handle_result = execute(
+            deps.as_mut(),
+            env_at(12445),
+            mock_info("admin", &[]),
+            execute_msg,
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains(&format!("There is no queued operation {id}")));
+
+        // Stopping the contract takes effect right away, resuming it waits out the delay
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            ExecuteMsg::SetContractStatus {
+                level: ContractStatusLevel::StopAll,
+                padding: None,
+                gas_target: None,
+            },
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            ExecuteMsg::SetContractStatus {
+                level: ContractStatusLevel::NormalRun,
+                padding: None,
+                gas_target: None,
+            },
+        );
+        let answer: ExecuteAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        let id = match answer {
+            ExecuteAnswer::Queued { id, .. } => id,
+            _ => panic!("unexpected"),
+        };
+        assert_eq!(
+            ConfigStore::load_contract_status(&deps.storage).unwrap(),
+            ContractStatusLevel::StopAll
+        );
+        let handle_result = execute(
+            deps.as_mut(),
+            env_at(12445),
+            mock_info("admin", &[]),
+            ExecuteMsg::ExecuteQueued {
+                id,
+                padding: None,
+                gas_target: None,
+            },
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        assert_eq!(
+            ConfigStore::load_contract_status(&deps.storage).unwrap(),
+            ContractStatusLevel::NormalRun
+        );
+
+        // The admin can cancel an operation queued by a role holder
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            ExecuteMsg::GrantRole {
+                role: Role::HookManager,
+                account: "bob".to_string(),
+                padding: None,
+                gas_target: None,
+            },
+        );
+        let answer: ExecuteAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        let id = match answer {
+            ExecuteAnswer::Queued { id, .. } => id,
+            _ => panic!("unexpected"),
+        };
+        // Once the grace period is over, the operation can no longer be executed
+        let handle_result = execute(
+            deps.as_mut(),
+            env_at(12445 + TIMELOCK_GRACE_PERIOD),
+            mock_info("admin", &[]),
+            ExecuteMsg::ExecuteQueued {
+                id,
+                padding: None,
+                gas_target: None,
+            },
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains(&format!(
+            "Operation {id} expired at height {}",
+            12445 + TIMELOCK_GRACE_PERIOD
+        )));
+
+        let cancel_msg = ExecuteMsg::CancelQueued {
+            id,
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &[]),
+            cancel_msg.clone(),
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This is an admin command"));
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            cancel_msg,
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        assert!(TimelockStore::load_all(&deps.storage).unwrap().is_empty());
+
+        // New code consumers can use up invite codes, so adding them waits too
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            ExecuteMsg::AddCodeConsumers {
+                consumers: vec!["bob".to_string()],
+                padding: None,
+                gas_target: None,
+            },
+        );
+        let answer: ExecuteAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        assert!(matches!(answer, ExecuteAnswer::Queued { .. }));
+    }
+
+    #[test]
+    fn test_admin_history() {
+        let (init_result, mut deps) = init_helper_with_config(
+            vec![InitialBalance {
+                address: "bob".to_string(),
+                amount: Uint128::new(5000),
+            }],
+            false,
+            false,
+            true,
+            false,
+            0,
+            vec![],
+        );
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msgs = vec![
+            ExecuteMsg::AddMinters {
+                minters: vec!["bob".to_string()],
+                padding: None,
+                gas_target: None,
+            },
+            ExecuteMsg::AddSupportedDenoms {
+                denoms: vec!["uatom".to_string()],
+                pegged: None,
+            },
+            ExecuteMsg::SetContractStatus {
+                level: ContractStatusLevel::StopAll,
+                padding: None,
+                gas_target: None,
+            },
+            ExecuteMsg::SetContractStatus {
+                level: ContractStatusLevel::NormalRun,
+                padding: None,
+                gas_target: None,
+            },
+        ];
+        for handle_msg in handle_msgs {
+            let handle_result = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("admin", &[]),
+                handle_msg,
+            );
+            assert!(ensure_success(handle_result.unwrap()));
+        }
+
+        // Rejected actions are not recorded
+        let handle_msg = ExecuteMsg::RemoveMinters {
+            minters: vec!["admin".to_string()],
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This command requires the minter_manager role"));
+
+        let query_msg = QueryMsg::AdminHistory {
+            page: None,
+            page_size: 2,
+        };
+        let query_result = query(deps.as_ref(), mock_env(), query_msg);
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::AdminHistory { entries, total } => {
+                assert_eq!(total, 4);
+                assert_eq!(
+                    entries,
+                    vec![
+                        AdminHistoryEntry {
+                            actor: Addr::unchecked("admin"),
+                            action: AdminAction::SetContractStatus {
+                                level: ContractStatusLevel::NormalRun,
+                            },
+                            block_time: mock_env().block.time.seconds(),
+                            block_height: 12345,
+                        },
+                        AdminHistoryEntry {
+                            actor: Addr::unchecked("admin"),
+                            action: AdminAction::SetContractStatus {
+                                level: ContractStatusLevel::StopAll,
+                            },
+                            block_time: mock_env().block.time.seconds(),
+                            block_height: 12345,
+                        },
+                    ]
+                );
+            }
+            _ => panic!("unexpected"),
+        }
+
+        let query_msg = QueryMsg::AdminHistory {
+            page: Some(1),
+            page_size: 2,
+        };
+        let query_result = query(deps.as_ref(), mock_env(), query_msg);
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::AdminHistory { entries, total } => {
+                assert_eq!(total, 4);
+                let actions: Vec<AdminAction> =
+                    entries.into_iter().map(|entry| entry.action).collect();
+                assert_eq!(
+                    actions,
+                    vec![
+                        AdminAction::AddSupportedDenoms {
+                            denoms: vec!["uatom".to_string()],
+                        },
+                        AdminAction::AddMinters {
+                            minters: vec![Addr::unchecked("bob")],
+                        },
+                    ]
+                );
+            }
+            _ => panic!("unexpected"),
+        }
+
+        // Role, quota, proposal, max supply and timelock changes are recorded too
+        let rate = MintRate {
+            amount: Uint128::new(100),
+            period: MintPeriod::Blocks(10),
+        };
+        let handle_msgs = vec![
+            ExecuteMsg::GrantRole {
+                role: Role::Pauser,
+                account: "bob".to_string(),
+                padding: None,
+                gas_target: None,
+            },
+            ExecuteMsg::RevokeRole {
+                role: Role::Pauser,
+                account: "bob".to_string(),
+                padding: None,
+                gas_target: None,
+            },
+            ExecuteMsg::SetMinterQuota {
+                minter: "bob".to_string(),
+                allowance: Some(Uint128::new(1000)),
+                rate: Some(rate.clone()),
+                padding: None,
+                gas_target: None,
+            },
+            ExecuteMsg::ProposeAdmin {
+                address: "bob".to_string(),
+                expiration: None,
+                padding: None,
+                gas_target: None,
+            },
+            ExecuteMsg::CancelAdminProposal {
+                padding: None,
+                gas_target: None,
+            },
+            ExecuteMsg::RenounceRole {
+                role: Role::HookManager,
+                padding: None,
+                gas_target: None,
+            },
+            ExecuteMsg::SetMaxSupply {
+                max_supply: Uint128::new(10000),
+                padding: None,
+                gas_target: None,
+            },
+            ExecuteMsg::SetTimelock {
+                delay: 100,
+                padding: None,
+                gas_target: None,
+            },
+        ];
+        for handle_msg in handle_msgs {
+            let handle_result = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("admin", &[]),
+                handle_msg,
+            );
+            assert!(ensure_success(handle_result.unwrap()));
+        }
+
+        // Queueing an operation is not recorded, cancelling it is
+        let handle_msg = ExecuteMsg::GrantRole {
+            role: Role::Pauser,
+            account: "bob".to_string(),
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            handle_msg,
+        );
+        let answer: ExecuteAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        let id = match answer {
+            ExecuteAnswer::Queued { id, .. } => id,
+            _ => panic!("unexpected"),
+        };
+        let handle_msg = ExecuteMsg::CancelQueued {
+            id,
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            handle_msg,
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let query_msg = QueryMsg::AdminHistory {
+            page: None,
+            page_size: 9,
+        };
+        let query_result = query(deps.as_ref(), mock_env(), query_msg);
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::AdminHistory { entries, total } => {
+                assert_eq!(total, 13);
+                let actions: Vec<AdminAction> =
+                    entries.into_iter().map(|entry| entry.action).collect();
+                assert_eq!(
+                    actions,
+                    vec![
+                        AdminAction::CancelQueued {
+                            id,
+                            queued_by: Addr::unchecked("admin"),
+                        },
+                        AdminAction::SetTimelock { delay: 100 },
+                        AdminAction::SetMaxSupply {
+                            max_supply: Uint128::new(10000),
+                        },
+                        AdminAction::RenounceRole {
+                            role: Role::HookManager,
+                        },
+                        AdminAction::CancelAdminProposal {},
+                        AdminAction::ProposeAdmin {
+                            address: Addr::unchecked("bob"),
+                            expiration: None,
+                        },
+                        AdminAction::SetMinterQuota {
+                            minter: Addr::unchecked("bob"),
+                            allowance: Some(Uint128::new(1000)),
+                            rate: Some(rate),
+                        },
+                        AdminAction::RevokeRole {
+                            role: Role::Pauser,
+                            account: Addr::unchecked("bob"),
+                        },
+                        AdminAction::GrantRole {
+                            role: Role::Pauser,
+                            account: Addr::unchecked("bob"),
+                        },
+                    ]
+                );
+            }
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn test_roles() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: "bob".to_string(),
+            amount: Uint128::new(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let query_result = query(deps.as_ref(), mock_env(), QueryMsg::RoleHolders {});
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::RoleHolders { admin, roles } => {
+                assert_eq!(admin, Some(Addr::unchecked("admin")));
+                assert_eq!(roles.len(), ROLES.len());
+                for role in roles {
+                    assert_eq!(role.holders, vec![Addr::unchecked("admin")]);
+                }
+            }
+            _ => panic!("unexpected"),
+        }
+
+        let grant_msg = ExecuteMsg::GrantRole {
+            role: Role::Pauser,
+            account: "guardian".to_string(),
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("guardian", &[]),
+            grant_msg.clone(),
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This is an admin command"));
+
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            grant_msg,
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+
+        // A role only unlocks its own commands
+        let denoms_msg = ExecuteMsg::AddSupportedDenoms {
+            denoms: vec!["uatom".to_string()],
+            pegged: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("guardian", &[]),
+            denoms_msg,
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This command requires the denom_manager role"));
+
+        for level in [ContractStatusLevel::StopAll, ContractStatusLevel::NormalRun] {
+            let status_msg = ExecuteMsg::SetContractStatus {
+                level,
+                padding: None,
+                gas_target: None,
+            };
+            let handle_result = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("guardian", &[]),
+                status_msg,
+            );
+            assert!(ensure_success(handle_result.unwrap()));
+        }
+
+        let renounce_msg = ExecuteMsg::RenounceRole {
+            role: Role::Pauser,
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("guardian", &[]),
+            renounce_msg.clone(),
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("guardian", &[]),
+            renounce_msg,
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This command requires the pauser role"));
+
+        // Without the role, even the admin cannot pause the contract
+        let revoke_msg = ExecuteMsg::RevokeRole {
+            role: Role::Pauser,
+            account: "admin".to_string(),
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            revoke_msg,
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        let pause_msg = ExecuteMsg::SetContractStatus {
+            level: ContractStatusLevel::StopAll,
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            pause_msg,
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This command requires the pauser role"));
 
-    #[test]
-    fn test_execute_transfer() {
-        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+        // A new admin takes over the roles the old one held
+        let change_admin_msg = ExecuteMsg::ChangeAdmin {
             address: "bob".to_string(),
-            amount: Uint128::new(5000),
-        }]);
-        assert!(
-            init_result.is_ok(),
-            "Init failed: {}",
-            init_result.err().unwrap()
-        );
-
-        let handle_msg = ExecuteMsg::Transfer {
-            recipient: "alice".to_string(),
-            amount: Uint128::new(1000),
-            memo: None,
-            decoys: None,
-            entropy: None,
             padding: None,
+            gas_target: None,
         };
-        let info = mock_info("bob", &[]);
-
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
-
-        let result = handle_result.unwrap();
-        assert!(ensure_success(result));
-        let bob_addr = Addr::unchecked("bob".to_string());
-        let alice_addr = Addr::unchecked("alice".to_string());
-
-        let bob_canonical = deps.api.addr_canonicalize(bob_addr.as_str()).unwrap();
-        let alice_canonical = deps.api.addr_canonicalize(alice_addr.as_str()).unwrap();
-
-        assert_eq!(
-            5000 - 1000,
-            BalancesStore::load(&deps.storage, &bob_canonical)
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            change_admin_msg,
         );
-        assert_eq!(1000, BalancesStore::load(&deps.storage, &alice_canonical));
-
-        let handle_msg = ExecuteMsg::Transfer {
-            recipient: "alice".to_string(),
-            amount: Uint128::new(10000),
-            memo: None,
-            decoys: None,
-            entropy: None,
+        assert!(ensure_success(handle_result.unwrap()));
+        let accept_msg = ExecuteMsg::AcceptAdmin {
             padding: None,
+            gas_target: None,
         };
-        let info = mock_info("bob", &[]);
-
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), accept_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+        let bob = Addr::unchecked("bob");
+        let admin = Addr::unchecked("admin");
+        assert!(RolesStore::has_role(
+            &deps.storage,
+            Role::DenomManager,
+            &bob
+        ));
+        assert!(!RolesStore::has_role(
+            &deps.storage,
+            Role::DenomManager,
+            &admin
+        ));
+        assert!(!RolesStore::has_role(&deps.storage, Role::Pauser, &bob));
 
-        let error = extract_error_msg(handle_result);
-        assert!(error.contains("insufficient funds"));
+        // Migrating from before roles existed grants them all to the admin
+        let older = ContractVersion {
+            schema: 5,
+            ..current_version()
+        };
+        ContractVersionStore::save(&mut deps.storage, &older).unwrap();
+        let migrate_msg = MigrateMsg::Migrate {
+            accounts: None,
+            max_supply: None,
+        };
+        let migrate_result = migrate(deps.as_mut(), mock_env(), migrate_msg);
+        assert!(
+            migrate_result.is_ok(),
+            "migrate() failed: {}",
+            migrate_result.err().unwrap()
+        );
+        for role in ROLES {
+            assert!(RolesStore::has_role(&deps.storage, role, &bob));
+        }
     }
 
     #[test]
-    fn test_decoys_balance_stays_on_transfer() {
+    fn test_handle_backfill_members() {
         let (init_result, mut deps) = init_helper(vec![
             InitialBalance {
                 address: "bob".to_string(),
-                amount: Uint128::new(5000),
+                amount: Uint128::new(5_000_000),
             },
             InitialBalance {
-                address: "lior".to_string(),
-                amount: Uint128::new(7000),
+                address: "lebron".to_string(),
+                amount: Uint128::new(5000),
             },
         ]);
-
         assert!(
             init_result.is_ok(),
             "Init failed: {}",
             init_result.err().unwrap()
         );
 
-        let bob_addr = Addr::unchecked("bob".to_string());
-        let alice_addr = Addr::unchecked("alice".to_string());
-        let lior_addr = Addr::unchecked("lior".to_string());
-        let jhon_addr = Addr::unchecked("jhon".to_string());
-
-        let bob_canonical = deps.api.addr_canonicalize(bob_addr.as_str()).unwrap();
-        let alice_canonical = deps.api.addr_canonicalize(alice_addr.as_str()).unwrap();
-        let lior_canonical = deps.api.addr_canonicalize(lior_addr.as_str()).unwrap();
-        let jhon_canonical = deps.api.addr_canonicalize(jhon_addr.as_str()).unwrap();
-
-        let bob_balance = BalancesStore::load(&deps.storage, &bob_canonical);
-        let alice_balance = BalancesStore::load(&deps.storage, &alice_canonical);
-        let lior_balance = BalancesStore::load(&deps.storage, &lior_canonical);
-        let jhon_balance = BalancesStore::load(&deps.storage, &jhon_canonical);
+        // Simulate a holder from before the membership sets existed
+        let bob = deps.api.addr_canonicalize("bob").unwrap();
+        let code = OAC_MEMBER_CODES.get(&deps.storage, &bob).unwrap();
+        OAC_INVITE_CODES.remove(&mut deps.storage, &code).unwrap();
+        OAC_MEMBER_CODES.remove(&mut deps.storage, &bob).unwrap();
+        OAC_MEMBERS.remove(&mut deps.storage, &bob).unwrap();
+        OAC_MEMBER_TIERS.remove(&mut deps.storage, &bob).unwrap();
 
-        let handle_msg = ExecuteMsg::Transfer {
-            recipient: "alice".to_string(),
-            amount: Uint128::new(1000),
-            memo: None,
-            decoys: Some(vec![lior_addr.clone(), jhon_addr.clone()]),
-            entropy: Some(Binary::from_base64("VEVTVFRFU1RURVNUQ0hFQ0tDSEVDSw==").unwrap()),
+        let handle_msg = ExecuteMsg::BackfillMembers {
+            accounts: vec!["bob".to_string(), "lebron".to_string()],
             padding: None,
+            gas_target: None,
         };
-
         let info = mock_info("bob", &[]);
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg.clone());
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This command requires the oac_manager role"));
 
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
-
-        let result = handle_result.unwrap();
-        assert!(ensure_success(result));
+        let info = mock_info("admin", &[]);
+        let handle_result = execute(deps.as_mut(), mock_env(), info.clone(), handle_msg.clone());
+        let answer: ExecuteAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        assert!(matches!(
+            answer,
+            ExecuteAnswer::BackfillMembers { enrolled: 1 }
+        ));
+        assert!(OAC_MEMBERS.contains(&deps.storage, &bob));
+        assert!(!OneAmberStore::get_code(&deps.storage, &bob).is_empty());
 
-        assert_eq!(
-            bob_balance - 1000,
-            BalancesStore::load(&deps.storage, &bob_canonical)
-        );
-        assert_eq!(
-            alice_balance + 1000,
-            BalancesStore::load(&deps.storage, &alice_canonical)
-        );
-        assert_eq!(
-            lior_balance,
-            BalancesStore::load(&deps.storage, &lior_canonical)
-        );
-        assert_eq!(
-            jhon_balance,
-            BalancesStore::load(&deps.storage, &jhon_canonical)
-        );
+        // Resubmitting the same batch is a no-op
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        let answer: ExecuteAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        assert!(matches!(
+            answer,
+            ExecuteAnswer::BackfillMembers { enrolled: 0 }
+        ));
     }
 
     #[test]
-    fn test_handle_send() {
-        let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: "bob".to_string(),
-            amount: Uint128::new(5000),
-        }]);
+    fn test_migrate_contract_version() {
+        let (init_result, mut deps) = init_helper(vec![]);
         assert!(
             init_result.is_ok(),
             "Init failed: {}",
             init_result.err().unwrap()
         );
 
-        let handle_msg = ExecuteMsg::RegisterReceive {
-            code_hash: "this_is_a_hash_of_a_code".to_string(),
-            padding: None,
-        };
-        let info = mock_info("contract", &[]);
+        let query_msg = QueryMsg::ContractVersion {};
+        let query_result = query(deps.as_ref(), mock_env(), query_msg.clone()).unwrap();
+        match from_binary(&query_result).unwrap() {
+            QueryAnswer::ContractVersion { version } => {
+                assert_eq!(version, Some(current_version()));
+            }
+            _ => panic!("unexpected"),
+        }
 
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        let migrate_msg = MigrateMsg::Migrate {
+            accounts: None,
+            max_supply: None,
+        };
 
-        let result = handle_result.unwrap();
-        assert!(ensure_success(result));
+        // Refuse to go back to an older storage layout
+        let newer_schema = ContractVersion {
+            schema: STORAGE_SCHEMA + 1,
+            ..current_version()
+        };
+        ContractVersionStore::save(&mut deps.storage, &newer_schema).unwrap();
+        let migrate_result = migrate(deps.as_mut(), mock_env(), migrate_msg.clone());
+        let error = extract_error_msg(migrate_result);
+        assert!(error.contains("to older schema"));
 
-        let handle_msg = ExecuteMsg::Send {
-            recipient: "contract".to_string(),
-            recipient_code_hash: None,
-            amount: Uint128::new(100),
-            memo: Some("my memo".to_string()),
-            padding: None,
-            msg: Some(to_binary("hey hey you you").unwrap()),
-            decoys: None,
-            entropy: None,
+        // Refuse to go back to older code
+        let newer_code = ContractVersion {
+            version: "99.0.0".to_string(),
+            ..current_version()
         };
-        let info = mock_info("bob", &[]);
+        ContractVersionStore::save(&mut deps.storage, &newer_code).unwrap();
+        let migrate_result = migrate(deps.as_mut(), mock_env(), migrate_msg.clone());
+        let error = extract_error_msg(migrate_result);
+        assert!(error.contains("to older version"));
 
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        let older = ContractVersion {
+            version: "1.0.0".to_string(),
+            schema: 0,
+            ..current_version()
+        };
+        ContractVersionStore::save(&mut deps.storage, &older).unwrap();
+        let migrate_result = migrate(deps.as_mut(), mock_env(), migrate_msg);
+        assert!(
+            migrate_result.is_ok(),
+            "migrate() failed: {}",
+            migrate_result.err().unwrap()
+        );
 
-        let result = handle_result.unwrap();
-        assert!(ensure_success(result.clone()));
-        let id = 0;
-        assert!(result.messages.contains(&SubMsg {
-            id,
-            msg: CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: "contract".to_string(),
-                code_hash: "this_is_a_hash_of_a_code".to_string(),
-                msg: Snip20ReceiveMsg::new(
-                    Addr::unchecked("bob".to_string()),
-                    Addr::unchecked("bob".to_string()),
-                    Uint128::new(100),
-                    Some("my memo".to_string()),
-                    Some(to_binary("hey hey you you").unwrap())
-                )
-                .into_binary()
-                .unwrap(),
-                funds: vec![],
-            })
-            .into(),
-            reply_on: match id {
-                0 => ReplyOn::Never,
-                _ => ReplyOn::Always,
-            },
-            gas_limit: None,
-        }));
+        let query_result = query(deps.as_ref(), mock_env(), query_msg).unwrap();
+        match from_binary(&query_result).unwrap() {
+            QueryAnswer::ContractVersion { version } => {
+                assert_eq!(version, Some(current_version()));
+            }
+            _ => panic!("unexpected"),
+        }
     }
 
     #[test]
-    fn test_handle_register_receive() {
-        let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: "bob".to_string(),
-            amount: Uint128::new(5000),
-        }]);
+    fn test_migrate_supported_denoms() {
+        let (init_result, mut deps) = init_helper_with_config(
+            vec![InitialBalance {
+                address: "lebron".to_string(),
+                amount: Uint128::new(5000),
+            }],
+            true,
+            true,
+            false,
+            false,
+            0,
+            vec![],
+        );
         assert!(
             init_result.is_ok(),
             "Init failed: {}",
             init_result.err().unwrap()
         );
 
-        let handle_msg = ExecuteMsg::RegisterReceive {
-            code_hash: "this_is_a_hash_of_a_code".to_string(),
-            padding: None,
+        let before_denoms = ContractVersion {
+            schema: 8,
+            ..current_version()
         };
-        let info = mock_info("contract", &[]);
-
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
-
-        let result = handle_result.unwrap();
-        assert!(ensure_success(result));
+        ContractVersionStore::save(&mut deps.storage, &before_denoms).unwrap();
+        let migrate_msg = MigrateMsg::Migrate {
+            accounts: None,
+            max_supply: None,
+        };
+        let migrate_result = migrate(deps.as_mut(), mock_env(), migrate_msg);
+        assert!(
+            migrate_result.is_ok(),
+            "migrate() failed: {}",
+            migrate_result.err().unwrap()
+        );
 
-        let hash =
-            ReceiverHashStore::may_load(&deps.storage, &Addr::unchecked("contract".to_string()))
-                .unwrap()
-                .unwrap();
-        assert_eq!(hash, "this_is_a_hash_of_a_code".to_string());
+        let constants = ConfigStore::load_constants(&deps.storage).unwrap();
+        assert_eq!(constants.supported_denoms, vec!["uscrt".to_string()]);
     }
 
     #[test]
-    fn test_handle_create_viewing_key() {
-        let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: "bob".to_string(),
-            amount: Uint128::new(5000),
-        }]);
+    fn test_migrate_contract_status() {
+        let (init_result, mut deps) = init_helper(vec![]);
         assert!(
             init_result.is_ok(),
             "Init failed: {}",
             init_result.err().unwrap()
         );
 
-        let handle_msg = ExecuteMsg::CreateViewingKey {
-            entropy: "".to_string(),
-            padding: None,
-        };
-        let info = mock_info("bob", &[]);
+        // The status level was stored as a single byte before schema 8
+        for (stored_level, expected) in [
+            (2u8, PauseFlags::all()),
+            (1u8, ContractStatusLevel::StopAllButRedeems.flags()),
+            (0u8, PauseFlags::default()),
+        ] {
+            let mut config_store = prefixed(&mut deps.storage, PREFIX_CONFIG);
+            config_store.remove(KEY_PAUSE_FLAGS);
+            config_store.set(KEY_CONTRACT_STATUS, &[stored_level]);
+            let before_flags = ContractVersion {
+                schema: 7,
+                ..current_version()
+            };
+            ContractVersionStore::save(&mut deps.storage, &before_flags).unwrap();
+            let migrate_msg = MigrateMsg::Migrate {
+                accounts: None,
+                max_supply: None,
+            };
+            let migrate_result = migrate(deps.as_mut(), mock_env(), migrate_msg);
+            assert!(
+                migrate_result.is_ok(),
+                "migrate() failed: {}",
+                migrate_result.err().unwrap()
+            );
 
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+            assert_eq!(
+                ConfigStore::load_pause_flags(&deps.storage).unwrap(),
+                expected
+            );
+            assert!(prefixed_read(&deps.storage, PREFIX_CONFIG)
+                .get(KEY_CONTRACT_STATUS)
+                .is_none());
+        }
+        assert_eq!(
+            ConfigStore::load_contract_status(&deps.storage).unwrap(),
+            ContractStatusLevel::NormalRun
+        );
+
+        // Storage that already has pause flags keeps them
+        ConfigStore::set_contract_status(&mut deps.storage, &ContractStatusLevel::StopAll).unwrap();
+        ConfigStore::upgrade_contract_status(&mut deps.storage).unwrap();
+        assert_eq!(
+            ConfigStore::load_pause_flags(&deps.storage).unwrap(),
+            PauseFlags::all()
+        );
+    }
 
+    #[test]
+    fn test_migrate_anonymity_set() {
+        /// The anonymity set as stored before schema 11
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct LegacyAnonymitySet {
+            accounts: Vec<CanonicalAddr>,
+            next: u32,
+        }
+        let legacy_set: secret_toolkit::storage::Item<LegacyAnonymitySet> =
+            secret_toolkit::storage::Item::new(b"anonymity_set");
+
+        let (init_result, mut deps) = init_helper(vec![]);
         assert!(
-            handle_result.is_ok(),
-            "handle() failed: {}",
-            handle_result.err().unwrap()
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
         );
-        let answer: ExecuteAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
 
-        let key = match answer {
-            ExecuteAnswer::CreateViewingKey { key } => key,
-            _ => panic!("NOPE"),
+        // A full set whose oldest account is in slot 5
+        let accounts: Vec<CanonicalAddr> = (0..ANONYMITY_SET_LEN)
+            .map(|i| CanonicalAddr::from(vec![i as u8 + 1; 20]))
+            .collect();
+        let legacy = LegacyAnonymitySet {
+            accounts: accounts.clone(),
+            next: 5,
         };
-        let bob_canonical = deps.as_mut().api.addr_canonicalize("bob").unwrap();
+        legacy_set.save(&mut deps.storage, &legacy).unwrap();
+
+        let before_slots = ContractVersion {
+            schema: 10,
+            ..current_version()
+        };
+        ContractVersionStore::save(&mut deps.storage, &before_slots).unwrap();
+        let migrate_msg = MigrateMsg::Migrate {
+            accounts: None,
+            max_supply: None,
+        };
+        let migrate_result = migrate(deps.as_mut(), mock_env(), migrate_msg);
+        assert!(
+            migrate_result.is_ok(),
+            "migrate() failed: {}",
+            migrate_result.err().unwrap()
+        );
+
+        assert!(legacy_set.may_load(&deps.storage).unwrap().is_none());
+        let slot = |slot: u32| ANONYMITY_SET_SLOTS.get(&deps.storage, &slot);
+        assert_eq!(slot(0), Some(accounts[5].clone()));
+        assert_eq!(slot(ANONYMITY_SET_LEN - 1), Some(accounts[4].clone()));
+
+        // Recording an account already in the set keeps it in its slot
+        AnonymitySet::record(&mut deps.storage, &accounts[4]).unwrap();
+        let slot = |slot: u32| ANONYMITY_SET_SLOTS.get(&deps.storage, &slot);
+        assert_eq!(slot(0), Some(accounts[5].clone()));
+        assert_eq!(slot(ANONYMITY_SET_LEN - 1), Some(accounts[4].clone()));
+
+        // A new account replaces the oldest one
+        let newcomer = CanonicalAddr::from(vec![0; 20]);
+        AnonymitySet::record(&mut deps.storage, &newcomer).unwrap();
+        assert_eq!(ANONYMITY_SET_SLOTS.get(&deps.storage, &0), Some(newcomer));
+    }
+
+    #[test]
+    fn test_migrate_constants_layouts() {
+        /// `Constants` as stored before schema 5
+        #[derive(serde::Serialize)]
+        struct LegacyConstants {
+            name: String,
+            admin: Addr,
+            symbol: String,
+            decimals: u8,
+            prng_seed: Vec<u8>,
+            total_supply_is_public: bool,
+            deposit_is_enabled: bool,
+            redeem_is_enabled: bool,
+            mint_is_enabled: bool,
+            burn_is_enabled: bool,
+            contract_address: Addr,
+            supported_denoms: Vec<String>,
+        }
+
+        /// `Constants` as stored from schema 5 until schema 7
+        #[derive(serde::Serialize)]
+        struct ConstantsV5 {
+            name: String,
+            admin: Addr,
+            symbol: String,
+            decimals: u8,
+            prng_seed: Vec<u8>,
+            total_supply_is_public: bool,
+            deposit_is_enabled: bool,
+            redeem_is_enabled: bool,
+            mint_is_enabled: bool,
+            burn_is_enabled: bool,
+            contract_address: Addr,
+            supported_denoms: Vec<String>,
+            max_supply: Option<u128>,
+        }
 
-        let result = ViewingKey::check(&deps.storage, bob_canonical.as_slice(), key.as_str());
-        assert!(result.is_ok());
+        let (init_result, mut deps) = init_helper(vec![]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+        let constants = ConfigStore::load_constants(&deps.storage).unwrap();
 
-        // let saved_vk = read_viewing_key(&deps.storage, &bob_canonical).unwrap();
-        // assert!(key.check_viewing_key(saved_vk.as_slice()));
+        let migrate_from = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+                            schema: u32,
+                            stored: Vec<u8>| {
+            prefixed(&mut deps.storage, PREFIX_CONFIG).set(KEY_CONSTANTS, &stored);
+            let older = ContractVersion {
+                schema,
+                ..current_version()
+            };
+            ContractVersionStore::save(&mut deps.storage, &older).unwrap();
+            let migrate_msg = MigrateMsg::Migrate {
+                accounts: None,
+                max_supply: None,
+            };
+            let migrate_result = migrate(deps.as_mut(), mock_env(), migrate_msg);
+            assert!(
+                migrate_result.is_ok(),
+                "migrate() failed: {}",
+                migrate_result.err().unwrap()
+            );
+            ConfigStore::load_constants(&deps.storage).unwrap()
+        };
+
+        // Constants from before schema 5 get no max supply
+        let legacy = LegacyConstants {
+            name: constants.name.clone(),
+            admin: Addr::unchecked("admin"),
+            symbol: constants.symbol.clone(),
+            decimals: constants.decimals,
+            prng_seed: constants.prng_seed.clone(),
+            total_supply_is_public: constants.total_supply_is_public,
+            deposit_is_enabled: constants.deposit_is_enabled,
+            redeem_is_enabled: constants.redeem_is_enabled,
+            mint_is_enabled: constants.mint_is_enabled,
+            burn_is_enabled: constants.burn_is_enabled,
+            contract_address: constants.contract_address.clone(),
+            supported_denoms: constants.supported_denoms.clone(),
+        };
+        let migrated = migrate_from(&mut deps, 4, bincode2::serialize(&legacy).unwrap());
+        assert_eq!(migrated, constants);
+
+        // Constants written by schemas 5 and 6 keep their max supply
+        let v5 = ConstantsV5 {
+            name: constants.name.clone(),
+            admin: Addr::unchecked("admin"),
+            symbol: constants.symbol.clone(),
+            decimals: constants.decimals,
+            prng_seed: constants.prng_seed.clone(),
+            total_supply_is_public: constants.total_supply_is_public,
+            deposit_is_enabled: constants.deposit_is_enabled,
+            redeem_is_enabled: constants.redeem_is_enabled,
+            mint_is_enabled: constants.mint_is_enabled,
+            burn_is_enabled: constants.burn_is_enabled,
+            contract_address: constants.contract_address.clone(),
+            supported_denoms: constants.supported_denoms.clone(),
+            max_supply: Some(1_000_000),
+        };
+        for schema in [5, 6] {
+            let migrated = migrate_from(&mut deps, schema, bincode2::serialize(&v5).unwrap());
+            assert_eq!(
+                migrated,
+                Constants {
+                    max_supply: Some(1_000_000),
+                    ..constants.clone()
+                }
+            );
+        }
     }
 
     #[test]
-    fn test_handle_set_viewing_key() {
+    fn test_handle_member_tiers() {
         let (init_result, mut deps) = init_helper(vec![InitialBalance {
             address: "bob".to_string(),
-            amount: Uint128::new(5000),
+            amount: Uint128::new(50_000_000),
         }]);
         assert!(
             init_result.is_ok(),
@@ -2756,209 +6885,299 @@ mod tests {
             init_result.err().unwrap()
         );
 
-        // Set VK
-        let handle_msg = ExecuteMsg::SetViewingKey {
-            key: "hi lol".to_string(),
+        let bob = deps.api.addr_canonicalize("bob").unwrap();
+        assert_eq!(OneAmberStore::get_tier(&deps.storage, &bob), Some(0));
+
+        let handle_msg = ExecuteMsg::SetMemberTiers {
+            thresholds: (1..=crate::amber::MAX_TIERS as u128 + 1)
+                .map(|tier| Uint128::new(tier * 1_000_000))
+                .collect(),
             padding: None,
+            gas_target: None,
         };
-        let info = mock_info("bob", &[]);
+        let info = mock_info("admin", &[]);
+        let handle_result = execute(deps.as_mut(), mock_env(), info.clone(), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Number of tiers must be between 1 and"));
+
+        let handle_msg = ExecuteMsg::SetMemberTiers {
+            thresholds: vec![Uint128::new(10_000_000), Uint128::new(1_000_000)],
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(deps.as_mut(), mock_env(), info.clone(), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("strictly ascending"));
 
+        let handle_msg = ExecuteMsg::SetMemberTiers {
+            thresholds: vec![
+                Uint128::new(1_000_000),
+                Uint128::new(10_000_000),
+                Uint128::new(100_000_000),
+            ],
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(deps.as_mut(), mock_env(), info.clone(), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        // Existing members are not moved until re-evaluated
+        assert_eq!(OneAmberStore::get_tier(&deps.storage, &bob), Some(0));
+
+        let handle_msg = ExecuteMsg::ReevaluateMembers {
+            accounts: vec!["bob".to_string()],
+            padding: None,
+            gas_target: None,
+        };
         let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+        assert_eq!(OneAmberStore::get_tier(&deps.storage, &bob), Some(1));
 
-        let unwrapped_result: ExecuteAnswer =
-            from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
-        assert_eq!(
-            to_binary(&unwrapped_result).unwrap(),
-            to_binary(&ExecuteAnswer::SetViewingKey {
-                status: ResponseStatus::Success
-            })
-            .unwrap(),
-        );
+        let code = OneAmberStore::get_code(&deps.storage, &bob);
+        let query_msg = QueryMsg::ValidCodes {
+            codes: vec![code.clone()],
+        };
+        let query_result = query(deps.as_ref(), mock_env(), query_msg).unwrap();
+        match from_binary(&query_result).unwrap() {
+            QueryAnswer::ValidCodes { codes } => {
+                assert_eq!(codes, vec![ValidCode { code, tier: 1 }]);
+            }
+            _ => panic!("unexpected"),
+        }
 
-        // Set valid VK
-        let actual_vk = "x".to_string().repeat(VIEWING_KEY_SIZE);
-        let handle_msg = ExecuteMsg::SetViewingKey {
-            key: actual_vk.clone(),
+        // Dropping below a threshold moves the member down on their next balance change
+        let handle_msg = ExecuteMsg::Transfer {
+            recipient: "alice".to_string(),
+            amount: Uint128::new(45_000_000),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
-
         let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+        assert_eq!(OneAmberStore::get_tier(&deps.storage, &bob), Some(0));
 
-        let unwrapped_result: ExecuteAnswer =
-            from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
-        assert_eq!(
-            to_binary(&unwrapped_result).unwrap(),
-            to_binary(&ExecuteAnswer::SetViewingKey { status: Success }).unwrap(),
-        );
+        let alice = deps.api.addr_canonicalize("alice").unwrap();
+        assert_eq!(OneAmberStore::get_tier(&deps.storage, &alice), Some(1));
 
-        let bob_canonical = deps.as_mut().api.addr_canonicalize("bob").unwrap();
+        // Codes in a removed tier stay valid until their member is moved
+        let handle_msg = ExecuteMsg::SetMemberTiers {
+            thresholds: vec![Uint128::new(1_000_000)],
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("admin", &[]);
+        let handle_result = execute(deps.as_mut(), mock_env(), info.clone(), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
 
-        let result = ViewingKey::check(&deps.storage, bob_canonical.as_slice(), actual_vk.as_str());
-        assert!(result.is_ok());
-    }
+        let old_code = OneAmberStore::get_code(&deps.storage, &alice);
+        let query_msg = QueryMsg::ValidCodes {
+            codes: vec![old_code.clone()],
+        };
+        let query_result = query(deps.as_ref(), mock_env(), query_msg).unwrap();
+        match from_binary(&query_result).unwrap() {
+            QueryAnswer::ValidCodes { codes } => {
+                assert_eq!(
+                    codes,
+                    vec![ValidCode {
+                        code: old_code.clone(),
+                        tier: 1
+                    }]
+                );
+            }
+            _ => panic!("unexpected"),
+        }
 
-    fn revoke_permit(
-        permit_name: &str,
-        user_address: &str,
-        deps: &mut OwnedDeps<cosmwasm_std::MemoryStorage, MockApi, MockQuerier>,
-    ) -> Result<Response, StdError> {
-        let handle_msg = ExecuteMsg::RevokePermit {
-            permit_name: permit_name.to_string(),
+        let handle_msg = ExecuteMsg::ReevaluateMembers {
+            accounts: vec!["alice".to_string()],
             padding: None,
+            gas_target: None,
         };
-        let info = mock_info(user_address, &[]);
         let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
-        handle_result
-    }
-
-    fn get_balance_with_permit_qry_msg(
-        permit_name: &str,
-        chain_id: &str,
-        pub_key_value: &str,
-        signature: &str,
-    ) -> QueryMsg {
-        let permit = gen_permit_obj(
-            permit_name,
-            chain_id,
-            pub_key_value,
-            signature,
-            TokenPermissions::Balance,
-        );
+        assert!(ensure_success(handle_result.unwrap()));
+        assert_eq!(OneAmberStore::get_tier(&deps.storage, &alice), Some(0));
 
-        QueryMsg::WithPermit {
-            permit,
-            query: QueryWithPermit::Balance {},
+        let new_code = OneAmberStore::get_code(&deps.storage, &alice);
+        let query_msg = QueryMsg::ValidCodes {
+            codes: vec![old_code, new_code.clone()],
+        };
+        let query_result = query(deps.as_ref(), mock_env(), query_msg).unwrap();
+        match from_binary(&query_result).unwrap() {
+            QueryAnswer::ValidCodes { codes } => {
+                assert_eq!(
+                    codes,
+                    vec![ValidCode {
+                        code: new_code,
+                        tier: 0
+                    }]
+                );
+            }
+            _ => panic!("unexpected"),
         }
     }
 
-    fn gen_permit_obj(
-        permit_name: &str,
-        chain_id: &str,
-        pub_key_value: &str,
-        signature: &str,
-        permit_type: TokenPermissions,
-    ) -> Permit {
-        let permit: Permit = Permit {
-            params: PermitParams {
-                allowed_tokens: vec![MOCK_CONTRACT_ADDR.to_string()],
-                permit_name: permit_name.to_string(),
-                chain_id: chain_id.to_string(),
-                permissions: vec![permit_type],
+    #[test]
+    fn test_query_membership_attestation() {
+        let (init_result, deps) = init_helper(vec![
+            InitialBalance {
+                address: "bob".to_string(),
+                amount: Uint128::new(5_000_000),
             },
-            signature: PermitSignature {
-                pub_key: PubKey {
-                    r#type: "tendermint/PubKeySecp256k1".to_string(),
-                    value: Binary::from_base64(pub_key_value).unwrap(),
-                },
-                signature: Binary::from_base64(signature).unwrap(),
+            InitialBalance {
+                address: "lebron".to_string(),
+                amount: Uint128::new(5000),
             },
+        ]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let query_result = query(deps.as_ref(), mock_env(), QueryMsg::AttestationKey {}).unwrap();
+        let pubkey = match from_binary(&query_result).unwrap() {
+            QueryAnswer::AttestationKey { pubkey } => pubkey,
+            _ => panic!("unexpected"),
         };
-        permit
-    }
+        assert_eq!(pubkey.len(), 33);
 
-    fn get_allowances_given_permit(
-        permit_name: &str,
-        chain_id: &str,
-        pub_key_value: &str,
-        signature: &str,
-        spender: String,
-    ) -> QueryMsg {
-        let permit = gen_permit_obj(
-            permit_name,
-            chain_id,
-            pub_key_value,
-            signature,
-            TokenPermissions::Owner,
-        );
+        let env = mock_env();
+        let result = query_membership_attestation(deps.as_ref(), &env, "lebron".to_string());
+        let error = extract_error_msg(result);
+        assert!(error.contains("not an OAC member"));
+
+        let result = query_membership_attestation(deps.as_ref(), &env, "bob".to_string()).unwrap();
+        match from_binary(&result).unwrap() {
+            QueryAnswer::MembershipAttestation {
+                attestation,
+                data,
+                signature,
+            } => {
+                assert_eq!(attestation.tier, 0);
+                assert_eq!(attestation.block_height, env.block.height);
+                assert_eq!(
+                    attestation.address_hash,
+                    Binary::from(sha_256(b"bob").to_vec())
+                );
+                assert_eq!(data, to_binary(&attestation).unwrap());
 
-        QueryMsg::WithPermit {
-            permit,
-            query: QueryWithPermit::AllowancesReceived {
-                spender,
-                page: None,
-                page_size: 0,
-            },
+                let verified = deps
+                    .api
+                    .secp256k1_verify(&sha_256(data.as_slice()), signature.as_slice(), &pubkey)
+                    .unwrap();
+                assert!(verified);
+            }
+            _ => panic!("unexpected"),
         }
     }
 
     #[test]
-    fn test_permit_query_allowances_given_should_fail() {
-        let user_address = "secret18mdrja40gfuftt5yx6tgj0fn5lurplezyp894y";
-        let permit_name = "default";
-        let chain_id = "secretdev-1";
-        let pub_key = "AkZqxdKMtPq2w0kGDGwWGejTAed0H7azPMHtrCX0XYZG";
-        let signature = "ZXyFMlAy6guMG9Gj05rFvcMi5/JGfClRtJpVTHiDtQY3GtSfBHncY70kmYiTXkKIxSxdnh/kS8oXa+GSX5su6Q==";
-
-        // Init the contract
-        let (init_result, deps) = init_helper(vec![InitialBalance {
-            address: user_address.to_string(),
-            amount: Uint128::new(50000000),
-        }]);
+    fn test_handle_invite_codes() {
+        let (init_result, mut deps) = init_helper(vec![
+            InitialBalance {
+                address: "bob".to_string(),
+                amount: Uint128::new(5_000_000),
+            },
+            InitialBalance {
+                address: "lebron".to_string(),
+                amount: Uint128::new(5000),
+            },
+        ]);
         assert!(
             init_result.is_ok(),
             "Init failed: {}",
             init_result.err().unwrap()
         );
 
-        let msg = get_allowances_given_permit(
-            permit_name,
-            chain_id,
-            pub_key,
-            signature,
-            "secret1kmgdagt5efcz2kku0ak9ezfgntg29g2vr88q0e".to_string(),
-        );
-        let query_result = query(deps.as_ref(), mock_env(), msg);
+        let create_msg = ExecuteMsg::CreateInviteCode {
+            max_uses: 2,
+            expiration_height: Some(mock_env().block.height + 100),
+            expiration_time: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("lebron", &[]);
+        let handle_result = execute(deps.as_mut(), mock_env(), info, create_msg.clone());
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Only OAC members"));
 
-        assert_eq!(query_result.is_err(), true);
-    }
+        let info = mock_info("bob", &[]);
+        let handle_result = execute(deps.as_mut(), mock_env(), info, create_msg);
+        let code = match from_binary(&handle_result.unwrap().data.unwrap()).unwrap() {
+            ExecuteAnswer::CreateInviteCode { code } => code,
+            _ => panic!("unexpected"),
+        };
 
-    #[test]
-    fn test_permit_query_allowances_given() {
-        let user_address = "secret18mdrja40gfuftt5yx6tgj0fn5lurplezyp894y";
-        let permit_name = "default";
-        let chain_id = "secretdev-1";
-        let pub_key = "AkZqxdKMtPq2w0kGDGwWGejTAed0H7azPMHtrCX0XYZG";
-        let signature = "ZXyFMlAy6guMG9Gj05rFvcMi5/JGfClRtJpVTHiDtQY3GtSfBHncY70kmYiTXkKIxSxdnh/kS8oXa+GSX5su6Q==";
+        let query_msg = QueryMsg::ValidCodes {
+            codes: vec![code.clone()],
+        };
+        let query_result = query(deps.as_ref(), mock_env(), query_msg.clone()).unwrap();
+        match from_binary(&query_result).unwrap() {
+            QueryAnswer::ValidCodes { codes } => assert_eq!(codes.len(), 1),
+            _ => panic!("unexpected"),
+        }
 
-        // Init the contract
-        let (init_result, deps) = init_helper(vec![InitialBalance {
-            address: user_address.to_string(),
-            amount: Uint128::new(50000000),
-        }]);
-        assert!(
-            init_result.is_ok(),
-            "Init failed: {}",
-            init_result.err().unwrap()
-        );
+        let consume_msg = ExecuteMsg::ConsumeInviteCode {
+            code: code.clone(),
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("oac-bot", &[]);
+        let handle_result = execute(deps.as_mut(), mock_env(), info.clone(), consume_msg.clone());
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("only be consumed by code consumers"));
 
-        let msg = get_allowances_given_permit(
-            permit_name,
-            chain_id,
-            pub_key,
-            signature,
-            "secret18mdrja40gfuftt5yx6tgj0fn5lurplezyp894y".to_string(),
-        );
-        let query_result = query(deps.as_ref(), mock_env(), msg);
+        let add_msg = ExecuteMsg::AddCodeConsumers {
+            consumers: vec!["oac-bot".to_string()],
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), add_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info.clone(), consume_msg.clone());
+        let answer: ExecuteAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        assert!(matches!(
+            answer,
+            ExecuteAnswer::ConsumeInviteCode {
+                tier: 0,
+                remaining_uses: 1
+            }
+        ));
+
+        let bob = deps.api.addr_canonicalize("bob").unwrap();
+        let outstanding =
+            OneAmberStore::invite_codes(&deps.storage, &bob, &mock_env().block).unwrap();
+        assert_eq!(outstanding.len(), 1);
+        assert_eq!(outstanding[0].1.remaining_uses, 1);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info.clone(), consume_msg.clone());
+        assert!(handle_result.is_ok());
+
+        // All uses are spent
+        let handle_result = execute(deps.as_mut(), mock_env(), info, consume_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Invalid invite code"));
 
-        assert_eq!(query_result.is_ok(), true);
+        let query_result = query(deps.as_ref(), mock_env(), query_msg).unwrap();
+        match from_binary(&query_result).unwrap() {
+            QueryAnswer::ValidCodes { codes } => assert!(codes.is_empty()),
+            _ => panic!("unexpected"),
+        }
+        let outstanding =
+            OneAmberStore::invite_codes(&deps.storage, &bob, &mock_env().block).unwrap();
+        assert!(outstanding.is_empty());
     }
 
     #[test]
-    fn test_permit_revoke() {
-        let user_address = "secret1kmgdagt5efcz2kku0ak9ezfgntg29g2vr88q0e";
-        let permit_name = "to_be_revoked";
-        let chain_id = "blabla";
-
-        // Note that 'signature'was generated with the specific values of the above:
-        // user_address, permit_name, chain_id, pub_key_value
-        let pub_key_value = "Ahlb7vwjo4aTY6dqfgpPmPYF7XhTAIReVwncQwlq8Sct";
-        let signature = "VS13F7iv1qxKABxrCAvZQPy2IruLQsIyfTewy/PIhNtybtq417lr3FxsWjV/i9YTqCUxg7weoZwHmYs0YgYX4w==";
-
-        // Init the contract
+    fn test_handle_membership_hooks() {
         let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: user_address.to_string(),
-            amount: Uint128::new(50000000),
+            address: "bob".to_string(),
+            amount: Uint128::new(5_000_000),
         }]);
         assert!(
             init_result.is_ok(),
@@ -2966,39 +7185,88 @@ mod tests {
             init_result.err().unwrap()
         );
 
-        // Query the account's balance
-        let balance_with_permit_msg =
-            get_balance_with_permit_qry_msg(permit_name, chain_id, pub_key_value, signature);
-        let query_result = query(deps.as_ref(), mock_env(), balance_with_permit_msg);
-        let balance = match from_binary(&query_result.unwrap()).unwrap() {
-            QueryAnswer::Balance { amount } => amount,
-            _ => panic!("Unexpected result from query"),
+        let handle_msg = ExecuteMsg::AddMembershipHook {
+            contract: "dao".to_string(),
+            code_hash: "dao_hash".to_string(),
+            on_error: HookErrorPolicy::Revert,
+            padding: None,
+            gas_target: None,
         };
-        assert_eq!(balance.u128(), 50000000);
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            handle_msg,
+        );
+        assert!(ensure_success(handle_result.unwrap()));
 
-        // Revoke the Balance permit
-        let handle_result = revoke_permit(permit_name, user_address, &mut deps);
-        let status = match from_binary(&handle_result.unwrap().data.unwrap()).unwrap() {
-            ExecuteAnswer::RevokePermit { status } => status,
-            _ => panic!("NOPE"),
+        let handle_msg = ExecuteMsg::AddMembershipHook {
+            contract: "gated".to_string(),
+            code_hash: "gated_hash".to_string(),
+            on_error: HookErrorPolicy::Ignore,
+            padding: None,
+            gas_target: None,
         };
-        assert_eq!(status, ResponseStatus::Success);
-
-        // Try to query the balance with permit and fail because the permit is now revoked
-        let balance_with_permit_msg =
-            get_balance_with_permit_qry_msg(permit_name, chain_id, pub_key_value, signature);
-        let query_result = query(deps.as_ref(), mock_env(), balance_with_permit_msg);
-        let error = extract_error_msg(query_result);
-        assert!(
-            error.contains(format!("Permit \"{}\" was revoked by account", permit_name).as_str())
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            handle_msg,
         );
+        assert!(ensure_success(handle_result.unwrap()));
+
+        // Bob drops out of the tier and Alice joins it
+        let handle_msg = ExecuteMsg::Transfer {
+            recipient: "alice".to_string(),
+            amount: Uint128::new(4_500_000),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+        let result = handle_result.unwrap();
+        assert_eq!(result.messages.len(), 4);
+
+        let left = MembershipChangedMsg {
+            account: Addr::unchecked("bob"),
+            change: MembershipChange::Left,
+            tier: 0,
+        };
+        let joined = MembershipChangedMsg {
+            account: Addr::unchecked("alice"),
+            change: MembershipChange::Joined,
+            tier: 0,
+        };
+        assert!(result.messages.contains(&SubMsg::new(
+            left.clone()
+                .into_cosmos_msg("dao_hash".to_string(), Addr::unchecked("dao"))
+                .unwrap()
+        )));
+        assert!(result.messages.contains(&SubMsg::reply_on_error(
+            joined
+                .into_cosmos_msg("gated_hash".to_string(), Addr::unchecked("gated"))
+                .unwrap(),
+            MEMBERSHIP_HOOK_REPLY_ID
+        )));
+
+        // Changes are only sent once
+        let handle_msg = ExecuteMsg::SetViewingKey {
+            key: "key".to_string(),
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+        assert!(handle_result.unwrap().messages.is_empty());
     }
 
     #[test]
-    fn test_execute_transfer_from() {
+    fn test_query_checkpoints() {
         let (init_result, mut deps) = init_helper(vec![InitialBalance {
             address: "bob".to_string(),
-            amount: Uint128::new(5000),
+            amount: Uint128::new(5_000_000),
         }]);
         assert!(
             init_result.is_ok(),
@@ -3006,308 +7274,521 @@ mod tests {
             init_result.err().unwrap()
         );
 
-        // Transfer before allowance
-        let handle_msg = ExecuteMsg::TransferFrom {
-            owner: "bob".to_string(),
-            recipient: "alice".to_string(),
-            amount: Uint128::new(2500),
-            memo: None,
-            decoys: None,
-            entropy: None,
+        let enabled_height = mock_env().block.height;
+        let handle_msg = ExecuteMsg::EnableCheckpoints {
             padding: None,
+            gas_target: None,
         };
-        let info = mock_info("alice", &[]);
-
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
-
-        let error = extract_error_msg(handle_result);
-        assert!(error.contains("insufficient allowance"));
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
 
-        // Transfer more than allowance
-        let handle_msg = ExecuteMsg::IncreaseAllowance {
-            spender: "alice".to_string(),
-            amount: Uint128::new(2000),
+        let handle_msg = ExecuteMsg::SetViewingKey {
+            key: "key".to_string(),
             padding: None,
-            expiration: Some(1_571_797_420),
+            gas_target: None,
         };
-        let info = mock_info("bob", &[]);
-
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
 
-        assert!(
-            handle_result.is_ok(),
-            "handle() failed: {}",
-            handle_result.err().unwrap()
-        );
-        let handle_msg = ExecuteMsg::TransferFrom {
-            owner: "bob".to_string(),
+        // Bob drops out of the OAC ten blocks later
+        let mut env = mock_env();
+        env.block.height = enabled_height + 10;
+        let handle_msg = ExecuteMsg::Transfer {
             recipient: "alice".to_string(),
-            amount: Uint128::new(2500),
+            amount: Uint128::new(4_500_000),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
-        let info = mock_info("alice", &[]);
+        let handle_result = execute(deps.as_mut(), env, mock_info("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
 
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        let balance_at = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>, height| {
+            let query_msg = QueryMsg::BalanceAt {
+                address: "bob".to_string(),
+                key: "key".to_string(),
+                height,
+            };
+            query(deps.as_ref(), mock_env(), query_msg)
+        };
 
-        let error = extract_error_msg(handle_result);
-        assert!(error.contains("insufficient allowance"));
+        let query_result = balance_at(&deps, enabled_height + 5).unwrap();
+        match from_binary(&query_result).unwrap() {
+            QueryAnswer::BalanceAt {
+                amount,
+                checkpoint_height,
+            } => {
+                assert_eq!(amount, Uint128::new(5_000_000));
+                assert_eq!(checkpoint_height, enabled_height);
+            }
+            _ => panic!("unexpected"),
+        }
+        let query_result = balance_at(&deps, enabled_height + 10).unwrap();
+        match from_binary(&query_result).unwrap() {
+            QueryAnswer::BalanceAt { amount, .. } => assert_eq!(amount, Uint128::new(500_000)),
+            _ => panic!("unexpected"),
+        }
+        let error = extract_error_msg(balance_at(&deps, enabled_height - 1));
+        assert!(error.contains("No checkpoint"));
 
-        // Transfer after allowance expired
-        let handle_msg = ExecuteMsg::TransferFrom {
-            owner: "bob".to_string(),
-            recipient: "alice".to_string(),
-            amount: Uint128::new(2000),
+        let query_msg = QueryMsg::WasMemberAt {
+            address: "bob".to_string(),
+            key: "key".to_string(),
+            height: enabled_height + 9,
+        };
+        let query_result = query(deps.as_ref(), mock_env(), query_msg).unwrap();
+        match from_binary(&query_result).unwrap() {
+            QueryAnswer::WasMemberAt { member, tier, .. } => {
+                assert!(member);
+                assert_eq!(tier, Some(0));
+            }
+            _ => panic!("unexpected"),
+        }
+        let query_msg = QueryMsg::WasMemberAt {
+            address: "bob".to_string(),
+            key: "key".to_string(),
+            height: enabled_height + 10,
+        };
+        let query_result = query(deps.as_ref(), mock_env(), query_msg).unwrap();
+        match from_binary(&query_result).unwrap() {
+            QueryAnswer::WasMemberAt { member, .. } => assert!(!member),
+            _ => panic!("unexpected"),
+        }
+
+        // Alice never opted in
+        let alice = deps.api.addr_canonicalize("alice").unwrap();
+        assert!(
+            CheckpointsStore::load_at(&deps.storage, &alice, enabled_height + 10)
+                .unwrap()
+                .is_none()
+        );
+
+        // A credit keeps the tier it brought Bob into, even after the thresholds change
+        let mut env = mock_env();
+        env.block.height = enabled_height + 20;
+        let handle_msg = ExecuteMsg::Transfer {
+            recipient: "bob".to_string(),
+            amount: Uint128::new(1_000_000),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
+        let handle_result = execute(deps.as_mut(), env, mock_info("alice", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
 
-        let info = MessageInfo {
-            sender: Addr::unchecked("bob".to_string()),
-            funds: vec![],
+        let handle_msg = ExecuteMsg::SetMemberTiers {
+            thresholds: vec![Uint128::new(2_000_000)],
+            padding: None,
+            gas_target: None,
         };
-
         let handle_result = execute(
             deps.as_mut(),
-            Env {
-                block: BlockInfo {
-                    height: 12_345,
-                    time: Timestamp::from_seconds(1_571_797_420),
-                    chain_id: "cosmos-testnet-14002".to_string(),
-                    random: None,
-                },
-                transaction: Some(TransactionInfo {
-                    index: 3,
-                    hash: "lol".to_string(),
-                }),
-                contract: ContractInfo {
-                    address: Addr::unchecked(MOCK_CONTRACT_ADDR.to_string()),
-                    code_hash: "".to_string(),
-                },
-            },
-            info,
+            mock_env(),
+            mock_info("admin", &[]),
             handle_msg,
         );
-        let error = extract_error_msg(handle_result);
-        assert!(error.contains("insufficient allowance"));
+        assert!(ensure_success(handle_result.unwrap()));
 
-        // Sanity check
-        let handle_msg = ExecuteMsg::TransferFrom {
-            owner: "bob".to_string(),
-            recipient: "alice".to_string(),
-            amount: Uint128::new(2000),
-            memo: None,
-            decoys: None,
-            entropy: None,
-            padding: None,
+        let bob = deps.api.addr_canonicalize("bob").unwrap();
+        let expected = Checkpoint {
+            height: enabled_height + 20,
+            balance: 1_500_000,
+            tier: Some(0),
         };
-        let info = mock_info("alice", &[]);
-
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        let checkpoint = CheckpointsStore::load_at(&deps.storage, &bob, enabled_height + 20);
+        assert_eq!(checkpoint.unwrap(), Some(expected.clone()));
 
-        assert!(
-            handle_result.is_ok(),
-            "handle() failed: {}",
-            handle_result.err().unwrap()
-        );
-        let bob_addr = Addr::unchecked("bob".to_string());
-        let alice_addr = Addr::unchecked("alice".to_string());
+        let mut dwb = DelayedWriteBuffer::load(&deps.storage).unwrap();
+        dwb.settle_account(&mut deps.storage, &bob).unwrap();
+        dwb.save(&mut deps.storage).unwrap();
+        let checkpoint = CheckpointsStore::load_at(&deps.storage, &bob, enabled_height + 20);
+        assert_eq!(checkpoint.unwrap(), Some(expected));
+    }
 
-        let bob_canonical = deps.api.addr_canonicalize(bob_addr.as_str()).unwrap();
-        let alice_canonical = deps.api.addr_canonicalize(alice_addr.as_str()).unwrap();
+    /// Storage that keeps the keys written to it, in order.
+    struct WriteRecorder<'a> {
+        storage: &'a mut MockStorage,
+        keys: Vec<Vec<u8>>,
+    }
 
-        let bob_balance = BalancesStore::load(&deps.storage, &bob_canonical);
-        let alice_balance = BalancesStore::load(&deps.storage, &alice_canonical);
-        assert_eq!(bob_balance, 5000 - 2000);
-        assert_eq!(alice_balance, 2000);
-        let total_supply = ConfigStore::load_total_supply(&deps.storage).unwrap();
-        assert_eq!(total_supply, 5000);
+    impl Storage for WriteRecorder<'_> {
+        fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+            self.storage.get(key)
+        }
 
-        // Second send more than allowance
-        let handle_msg = ExecuteMsg::TransferFrom {
-            owner: "bob".to_string(),
-            recipient: "alice".to_string(),
-            amount: Uint128::new(1),
-            memo: None,
-            decoys: None,
-            entropy: None,
-            padding: None,
-        };
-        let info = mock_info("alice", &[]);
+        fn range<'a>(
+            &'a self,
+            start: Option<&[u8]>,
+            end: Option<&[u8]>,
+            order: Order,
+        ) -> Box<dyn Iterator<Item = Record> + 'a> {
+            self.storage.range(start, end, order)
+        }
 
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        fn set(&mut self, key: &[u8], value: &[u8]) {
+            self.keys.push(key.to_vec());
+            self.storage.set(key, value)
+        }
 
-        let error = extract_error_msg(handle_result);
-        assert!(error.contains("insufficient allowance"));
+        fn remove(&mut self, key: &[u8]) {
+            self.keys.push(key.to_vec());
+            self.storage.remove(key)
+        }
     }
 
     #[test]
-    fn test_handle_send_from() {
-        let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: "bob".to_string(),
-            amount: Uint128::new(5000),
-        }]);
-        assert!(
-            init_result.is_ok(),
-            "Init failed: {}",
-            init_result.err().unwrap()
-        );
+    fn test_checkpoints_write_pattern() {
+        let transfer_to_bob = |opt_in: bool| {
+            let (init_result, mut deps) = init_helper(vec![InitialBalance {
+                address: "alice".to_string(),
+                amount: Uint128::new(5_000),
+            }]);
+            assert!(
+                init_result.is_ok(),
+                "Init failed: {}",
+                init_result.err().unwrap()
+            );
 
-        // Send before allowance
-        let handle_msg = ExecuteMsg::SendFrom {
-            owner: "bob".to_string(),
-            recipient: "alice".to_string(),
-            recipient_code_hash: None,
-            amount: Uint128::new(2500),
-            memo: None,
-            msg: None,
-            decoys: None,
-            entropy: None,
-            padding: None,
+            if opt_in {
+                let handle_msg = ExecuteMsg::EnableCheckpoints {
+                    padding: None,
+                    gas_target: None,
+                };
+                let handle_result =
+                    execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+                assert!(ensure_success(handle_result.unwrap()));
+            }
+
+            let mut storage = WriteRecorder {
+                storage: &mut deps.storage,
+                keys: vec![],
+            };
+            let deps_mut = DepsMut {
+                storage: &mut storage,
+                api: &deps.api,
+                querier: QuerierWrapper::new(&deps.querier),
+            };
+            let handle_msg = ExecuteMsg::Transfer {
+                recipient: "bob".to_string(),
+                amount: Uint128::new(1_000),
+                memo: None,
+                decoys: None,
+                decoy_count: None,
+                entropy: None,
+                padding: None,
+                gas_target: None,
+            };
+            let handle_result = execute(deps_mut, mock_env(), mock_info("alice", &[]), handle_msg);
+            assert!(ensure_success(handle_result.unwrap()));
+
+            let keys = storage.keys;
+            (keys, deps)
         };
-        let info = mock_info("alice", &[]);
 
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        // A credit writes the same keys whether or not the recipient has opted in
+        let (keys_without_opt_in, _) = transfer_to_bob(false);
+        let (keys_with_opt_in, mut deps) = transfer_to_bob(true);
+        assert_eq!(keys_without_opt_in, keys_with_opt_in);
 
-        let error = extract_error_msg(handle_result);
-        assert!(error.contains("insufficient allowance"));
+        // The credit is still in the buffer, but already counts
+        let bob = deps.api.addr_canonicalize("bob").unwrap();
+        let height = mock_env().block.height;
+        let checkpoint = CheckpointsStore::load_at(&deps.storage, &bob, height)
+            .unwrap()
+            .unwrap();
+        assert_eq!(checkpoint.balance, 1_000);
 
-        // Send more than allowance
-        let handle_msg = ExecuteMsg::IncreaseAllowance {
-            spender: "alice".to_string(),
-            amount: Uint128::new(2000),
-            padding: None,
-            expiration: None,
-        };
-        let info = mock_info("bob", &[]);
+        // Once it settles, the same checkpoint is in the account's own history
+        let mut dwb = DelayedWriteBuffer::load(&deps.storage).unwrap();
+        dwb.settle_account(&mut deps.storage, &bob).unwrap();
+        dwb.save(&mut deps.storage).unwrap();
+        assert_eq!(
+            CheckpointsStore::load_at(&deps.storage, &bob, height).unwrap(),
+            Some(checkpoint)
+        );
+    }
 
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+    #[test]
+    fn test_snip52_notifications() {
+        use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+        use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
 
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: "bob".to_string(),
+            amount: Uint128::new(5_000_000),
+        }]);
         assert!(
-            handle_result.is_ok(),
-            "handle() failed: {}",
-            handle_result.err().unwrap()
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
         );
-        let handle_msg = ExecuteMsg::SendFrom {
-            owner: "bob".to_string(),
-            recipient: "alice".to_string(),
-            recipient_code_hash: None,
-            amount: Uint128::new(2500),
-            memo: None,
-            msg: None,
-            decoys: None,
-            entropy: None,
-            padding: None,
-        };
-        let info = mock_info("alice", &[]);
-
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
 
-        let error = extract_error_msg(handle_result);
-        assert!(error.contains("insufficient allowance"));
+        let query_result = query(deps.as_ref(), mock_env(), QueryMsg::ListChannels {}).unwrap();
+        match from_binary(&query_result).unwrap() {
+            QueryAnswer::ListChannels { channels } => {
+                assert_eq!(channels, vec!["recvd", "spent", "oac"])
+            }
+            _ => panic!("unexpected"),
+        }
 
-        // Sanity check
-        let handle_msg = ExecuteMsg::RegisterReceive {
-            code_hash: "lolz".to_string(),
+        let handle_msg = ExecuteMsg::SetViewingKey {
+            key: "key".to_string(),
             padding: None,
+            gas_target: None,
         };
-        let info = mock_info("contract", &[]);
-
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
-
-        assert!(
-            handle_result.is_ok(),
-            "handle() failed: {}",
-            handle_result.err().unwrap()
-        );
-        let send_msg = Binary::from(r#"{ "some_msg": { "some_key": "some_val" } }"#.as_bytes());
-        let snip20_msg = Snip20ReceiveMsg::new(
-            Addr::unchecked("alice".to_string()),
-            Addr::unchecked("bob".to_string()),
-            Uint128::new(2000),
-            Some("my memo".to_string()),
-            Some(send_msg.clone()),
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            handle_msg,
         );
-        let handle_msg = ExecuteMsg::SendFrom {
-            owner: "bob".to_string(),
-            recipient: "contract".to_string(),
-            recipient_code_hash: None,
-            amount: Uint128::new(2000),
-            memo: Some("my memo".to_string()),
-            msg: Some(send_msg),
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let mut env = mock_env();
+        env.transaction = Some(TransactionInfo {
+            index: 0,
+            hash: "9A5F1C2B".to_string(),
+        });
+        let handle_msg = ExecuteMsg::Transfer {
+            recipient: "alice".to_string(),
+            amount: Uint128::new(4_500_000),
+            memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
-        let info = mock_info("alice", &[]);
-
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
-
-        assert!(
-            handle_result.is_ok(),
-            "handle() failed: {}",
-            handle_result.err().unwrap()
+        let handle_result = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bob", &[]),
+            handle_msg,
         );
-        assert!(handle_result.unwrap().messages.contains(
-            &into_cosmos_submsg(
-                snip20_msg,
-                "lolz".to_string(),
-                Addr::unchecked("contract".to_string()),
-                0
+        let response = handle_result.unwrap();
+
+        let notification = |account: &str, channel: &str| {
+            let account = deps.api.addr_canonicalize(account).unwrap();
+            let seed = notification_seed(&deps.storage, &account).unwrap();
+            let id = Binary::from(notification_id(&seed, channel, "9A5F1C2B").unwrap());
+            let key = format!("snip52:{}", id.to_base64());
+            response
+                .attributes
+                .iter()
+                .find(|attribute| attribute.key == key)
+                .map(|attribute| (seed, Binary::from_base64(&attribute.value).unwrap()))
+        };
+
+        // Alice receives and joins, Bob spends and leaves
+        assert!(notification("alice", "spent").is_none());
+        assert!(notification("bob", "recvd").is_none());
+        assert!(notification("bob", "spent").is_some());
+        assert!(notification("bob", "oac").is_some());
+        assert!(notification("alice", "oac").is_some());
+
+        let (seed, data) = notification("alice", "recvd").unwrap();
+        // Padded to whole blocks, plus the authentication tag
+        assert_eq!(data.len() % 64, 16);
+        let channel_bytes = sha_256(b"recvd");
+        let salt_bytes = sha_256(b"9A5F1C2B");
+        let nonce: Vec<u8> = (0..12).map(|i| channel_bytes[i] ^ salt_bytes[i]).collect();
+        let aad = format!("{}:9A5F1C2B", env.block.height);
+        let plaintext = ChaCha20Poly1305::new(Key::from_slice(&seed))
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: data.as_slice(),
+                    aad: aad.as_bytes(),
+                },
             )
-            .unwrap()
-        ));
+            .unwrap();
+        let bob = deps.api.addr_canonicalize("bob").unwrap();
+        let expected = [
+            vec![0x82, 0xc2, 0x50],
+            4_500_000u128.to_be_bytes().to_vec(),
+            match bob.len() {
+                len @ 0..=23 => vec![0x40 | len as u8],
+                len => vec![0x58, len as u8],
+            },
+            bob.as_slice().to_vec(),
+        ]
+        .concat();
+        assert_eq!(&plaintext[..expected.len()], expected.as_slice());
+        assert!(plaintext[expected.len()..].iter().all(|byte| *byte == 0));
+
+        let query_msg = QueryMsg::ChannelInfo {
+            channels: vec!["recvd".to_string()],
+            txhash: Some("9A5F1C2B".to_string()),
+            address: "alice".to_string(),
+            key: "key".to_string(),
+        };
+        let query_result = query(deps.as_ref(), mock_env(), query_msg).unwrap();
+        match from_binary(&query_result).unwrap() {
+            QueryAnswer::ChannelInfo {
+                channels,
+                seed: answer_seed,
+                ..
+            } => {
+                assert_eq!(answer_seed.as_slice(), seed.as_slice());
+                let id = notification_id(&seed, "recvd", "9A5F1C2B").unwrap();
+                assert_eq!(channels[0].answer_id, Some(Binary::from(id).to_base64()));
+                assert_eq!(channels[0].mode, "txhash");
+            }
+            _ => panic!("unexpected"),
+        }
 
-        let bob_addr = Addr::unchecked("bob".to_string());
-        let bob_canonical = deps.api.addr_canonicalize(bob_addr.as_str()).unwrap();
+        let query_msg = QueryMsg::ChannelInfo {
+            channels: vec!["nope".to_string()],
+            txhash: None,
+            address: "alice".to_string(),
+            key: "key".to_string(),
+        };
+        let error = extract_error_msg(query(deps.as_ref(), mock_env(), query_msg));
+        assert!(error.contains("Unknown notification channel"));
+    }
 
-        let contract_addr = Addr::unchecked("contract".to_string());
-        let canon_contract_addr = deps.api.addr_canonicalize(contract_addr.as_str()).unwrap();
+    #[test]
+    fn test_snip52_batch_notifications() {
+        use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+        use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
 
-        let bob_balance = BalancesStore::load(&deps.storage, &bob_canonical);
-        let contract_balance = BalancesStore::load(&deps.storage, &canon_contract_addr);
-        assert_eq!(bob_balance, 5000 - 2000);
-        assert_eq!(contract_balance, 2000);
-        let total_supply = ConfigStore::load_total_supply(&deps.storage).unwrap();
-        assert_eq!(total_supply, 5000);
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: "bob".to_string(),
+            amount: Uint128::new(5_000_000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
 
-        // Second send more than allowance
-        let handle_msg = ExecuteMsg::SendFrom {
-            owner: "bob".to_string(),
-            recipient: "alice".to_string(),
-            recipient_code_hash: None,
-            amount: Uint128::new(1),
-            memo: None,
-            msg: None,
-            decoys: None,
+        let mut env = mock_env();
+        env.transaction = Some(TransactionInfo {
+            index: 0,
+            hash: "9A5F1C2B".to_string(),
+        });
+        let handle_msg = ExecuteMsg::BatchTransfer {
+            actions: [1_000, 2_000]
+                .iter()
+                .map(|amount| batch::TransferAction {
+                    recipient: "alice".to_string(),
+                    amount: Uint128::new(*amount),
+                    memo: None,
+                    decoys: None,
+                })
+                .collect(),
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bob", &[]),
+            handle_msg,
+        );
+        let response = handle_result.unwrap();
+
+        let plaintext = |account: &str, channel: &str| {
+            let account = deps.api.addr_canonicalize(account).unwrap();
+            let seed = notification_seed(&deps.storage, &account).unwrap();
+            let id = Binary::from(notification_id(&seed, channel, "9A5F1C2B").unwrap());
+            let key = format!("snip52:{}", id.to_base64());
+            let mut attributes = response
+                .attributes
+                .iter()
+                .filter(|attribute| attribute.key == key);
+            let data = Binary::from_base64(&attributes.next().unwrap().value).unwrap();
+            assert!(attributes.next().is_none());
+
+            let channel_bytes = sha_256(channel.as_bytes());
+            let salt_bytes = sha_256(b"9A5F1C2B");
+            let nonce: Vec<u8> = (0..12).map(|i| channel_bytes[i] ^ salt_bytes[i]).collect();
+            let aad = format!("{}:9A5F1C2B", env.block.height);
+            ChaCha20Poly1305::new(Key::from_slice(&seed))
+                .decrypt(
+                    Nonce::from_slice(&nonce),
+                    Payload {
+                        msg: data.as_slice(),
+                        aad: aad.as_bytes(),
+                    },
+                )
+                .unwrap()
         };
-        let info = mock_info("alice", &[]);
-
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
 
-        let error = extract_error_msg(handle_result);
-        assert!(error.contains("insufficient allowance"));
+        // One notification per account and channel, with the amounts of both actions
+        let expected = [vec![0x82, 0xc2, 0x50], 3_000u128.to_be_bytes().to_vec()].concat();
+        assert!(plaintext("alice", "recvd").starts_with(&expected));
+        let expected = [vec![0x83, 0xc2, 0x50], 3_000u128.to_be_bytes().to_vec()].concat();
+        let spent = plaintext("bob", "spent");
+        assert!(spent.starts_with(&expected));
+        // The balance is the one after the last action
+        let alice = deps.api.addr_canonicalize("alice").unwrap();
+        let balance = [vec![0xc2, 0x50], 4_997_000u128.to_be_bytes().to_vec()].concat();
+        let balance_at = expected.len() + 1 + alice.len() + usize::from(alice.len() > 23);
+        assert_eq!(
+            &spent[balance_at..balance_at + balance.len()],
+            balance.as_slice()
+        );
     }
 
     #[test]
-    fn test_handle_burn_from() {
+    fn test_snip52_burn_and_deposit_notifications() {
+        use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+        use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+        fn plaintext(
+            deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>,
+            env: &Env,
+            response: &Response,
+            account: &str,
+            channel: &str,
+        ) -> Option<Vec<u8>> {
+            let tx_hash = env.transaction.as_ref().unwrap().hash.as_str();
+            let account = deps.api.addr_canonicalize(account).unwrap();
+            let seed = notification_seed(&deps.storage, &account).unwrap();
+            let id = Binary::from(notification_id(&seed, channel, tx_hash).unwrap());
+            let key = format!("snip52:{}", id.to_base64());
+            let attribute = response
+                .attributes
+                .iter()
+                .find(|attribute| attribute.key == key)?;
+            let data = Binary::from_base64(&attribute.value).unwrap();
+
+            let channel_bytes = sha_256(channel.as_bytes());
+            let salt_bytes = sha_256(tx_hash.as_bytes());
+            let nonce: Vec<u8> = (0..12).map(|i| channel_bytes[i] ^ salt_bytes[i]).collect();
+            let aad = format!("{}:{}", env.block.height, tx_hash);
+            let plaintext = ChaCha20Poly1305::new(Key::from_slice(&seed))
+                .decrypt(
+                    Nonce::from_slice(&nonce),
+                    Payload {
+                        msg: data.as_slice(),
+                        aad: aad.as_bytes(),
+                    },
+                )
+                .unwrap();
+            Some(plaintext)
+        }
+
         let (init_result, mut deps) = init_helper_with_config(
             vec![InitialBalance {
                 address: "bob".to_string(),
-                amount: Uint128::new(10000),
+                amount: Uint128::new(5_000_000),
             }],
-            false,
+            true,
             false,
             false,
             true,
             0,
-            vec![],
+            vec!["uscrt".to_string()],
         );
         assert!(
             init_result.is_ok(),
@@ -3315,137 +7796,313 @@ mod tests {
             init_result.err().unwrap()
         );
 
-        let (init_result_for_failure, mut deps_for_failure) = init_helper(vec![InitialBalance {
-            address: "bob".to_string(),
-            amount: Uint128::new(10000),
-        }]);
-        assert!(
-            init_result_for_failure.is_ok(),
-            "Init failed: {}",
-            init_result_for_failure.err().unwrap()
-        );
-        // test when burn disabled
-        let handle_msg = ExecuteMsg::BurnFrom {
-            owner: "bob".to_string(),
-            amount: Uint128::new(2500),
+        let mut env = mock_env();
+        env.transaction = Some(TransactionInfo {
+            index: 0,
+            hash: "B0A1".to_string(),
+        });
+        let handle_msg = ExecuteMsg::Burn {
+            amount: Uint128::new(1_000),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
-        let info = mock_info("alice", &[]);
-
-        let handle_result = execute(deps_for_failure.as_mut(), mock_env(), info, handle_msg);
-
-        let error = extract_error_msg(handle_result);
-        assert!(error.contains("Burn functionality is not enabled for this token."));
+        let response = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bob", &[]),
+            handle_msg,
+        )
+        .unwrap();
 
-        // Burn before allowance
-        let handle_msg = ExecuteMsg::BurnFrom {
-            owner: "bob".to_string(),
-            amount: Uint128::new(2500),
-            memo: None,
+        // A burn is spent with no recipient, and the tier is sent even though it did not change
+        let expected = [
+            vec![0x83, 0xc2, 0x50],
+            1_000u128.to_be_bytes().to_vec(),
+            vec![0xf6, 0xc2, 0x50],
+            4_999_000u128.to_be_bytes().to_vec(),
+        ]
+        .concat();
+        let spent = plaintext(&deps, &env, &response, "bob", "spent").unwrap();
+        assert_eq!(&spent[..expected.len()], expected.as_slice());
+        assert!(plaintext(&deps, &env, &response, "bob", "oac").is_some());
+        assert!(plaintext(&deps, &env, &response, "bob", "recvd").is_none());
+
+        env.transaction = Some(TransactionInfo {
+            index: 0,
+            hash: "D3P0".to_string(),
+        });
+        let handle_msg = ExecuteMsg::Deposit {
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
-        let info = mock_info("alice", &[]);
-
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
-
-        let error = extract_error_msg(handle_result);
-        assert!(error.contains("insufficient allowance"));
-
-        // Burn more than allowance
-        let handle_msg = ExecuteMsg::IncreaseAllowance {
-            spender: "alice".to_string(),
-            amount: Uint128::new(2000),
-            padding: None,
-            expiration: None,
-        };
-        let info = mock_info("bob", &[]);
-
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        let info = mock_info(
+            "alice",
+            &[Coin {
+                denom: "uscrt".to_string(),
+                amount: Uint128::new(2_000),
+            }],
+        );
+        let response = execute(deps.as_mut(), env.clone(), info, handle_msg).unwrap();
+
+        // A deposit is received from no sender
+        let expected = [
+            vec![0x82, 0xc2, 0x50],
+            2_000u128.to_be_bytes().to_vec(),
+            vec![0xf6],
+        ]
+        .concat();
+        let recvd = plaintext(&deps, &env, &response, "alice", "recvd").unwrap();
+        assert_eq!(&recvd[..expected.len()], expected.as_slice());
+        assert!(recvd[expected.len()..].iter().all(|byte| *byte == 0));
+        assert!(plaintext(&deps, &env, &response, "alice", "oac").is_some());
+        assert!(plaintext(&deps, &env, &response, "alice", "spent").is_none());
+    }
 
+    #[test]
+    fn test_handle_gas_target() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: "bob".to_string(),
+            amount: Uint128::new(5_000_000),
+        }]);
         assert!(
-            handle_result.is_ok(),
-            "handle() failed: {}",
-            handle_result.err().unwrap()
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
         );
-        let handle_msg = ExecuteMsg::BurnFrom {
-            owner: "bob".to_string(),
-            amount: Uint128::new(2500),
-            memo: None,
-            decoys: None,
-            entropy: None,
-            padding: None,
-        };
-        let info = mock_info("alice", &[]);
 
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        let handle_msg: ExecuteMsg = from_binary(&Binary::from(
+            br#"{"transfer":{"recipient":"alice","amount":"1000","gas_target":150000}}"#.as_slice(),
+        ))
+        .unwrap();
+        assert_eq!(handle_msg.get_gas_target(), Some(150_000));
 
-        let error = extract_error_msg(handle_result);
-        assert!(error.contains("insufficient allowance"));
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
 
-        // Sanity check
-        let handle_msg = ExecuteMsg::BurnFrom {
-            owner: "bob".to_string(),
-            amount: Uint128::new(2000),
+        let bob = deps.api.addr_canonicalize("bob").unwrap();
+        assert_eq!(BalancesStore::load(&deps.storage, &bob).unwrap(), 4_999_000);
+
+        // Variants without padding take no gas target
+        let handle_msg = ExecuteMsg::RegenerateCode {};
+        assert_eq!(handle_msg.get_gas_target(), None);
+
+        // Only the gas short of the target is burnt
+        assert_eq!(gas_to_evaporate(100_000, 150_000), 50_000);
+        assert_eq!(gas_to_evaporate(0, 150_000), 150_000);
+        assert_eq!(gas_to_evaporate(150_000, 150_000), 0);
+        assert_eq!(gas_to_evaporate(200_000, 150_000), 0);
+        assert_eq!(gas_to_evaporate(u64::MAX, u32::MAX), 0);
+        assert!(evaporate_to_target(&deps.api, Some(150_000)).is_ok());
+        assert!(evaporate_to_target(&deps.api, None).is_ok());
+
+        // Hooks and notifications are dispatched before the gas is evaporated, so a targeted
+        // message still carries them
+        let handle_msg = ExecuteMsg::AddMembershipHook {
+            contract: "dao".to_string(),
+            code_hash: "dao_hash".to_string(),
+            on_error: HookErrorPolicy::Revert,
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            handle_msg,
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let mut env = mock_env();
+        env.transaction = Some(TransactionInfo {
+            index: 0,
+            hash: "6A5F1C2B".to_string(),
+        });
+        let handle_msg = ExecuteMsg::Transfer {
+            recipient: "alice".to_string(),
+            amount: Uint128::new(4_500_000),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: Some(150_000),
         };
-        let info = mock_info("alice", &[]);
-
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        let response = execute(deps.as_mut(), env, mock_info("bob", &[]), handle_msg).unwrap();
+        // Bob leaves the tier and Alice joins it
+        assert_eq!(response.messages.len(), 2);
+        let notifications = response
+            .attributes
+            .iter()
+            .filter(|attribute| attribute.key.starts_with("snip52:"))
+            .count();
+        // recvd and oac for Alice, spent and oac for Bob
+        assert_eq!(notifications, 4);
+    }
 
+    #[test]
+    fn test_query_transaction_history_cursor() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: "bob".to_string(),
+            amount: Uint128::new(10000),
+        }]);
         assert!(
-            handle_result.is_ok(),
-            "handle() failed: {}",
-            handle_result.err().unwrap()
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
         );
-        let bob_addr = Addr::unchecked("bob".to_string());
-        let bob_canonical = deps.api.addr_canonicalize(bob_addr.as_str()).unwrap();
-        let bob_balance = BalancesStore::load(&deps.storage, &bob_canonical);
-        assert_eq!(bob_balance, 10000 - 2000);
-        let total_supply = ConfigStore::load_total_supply(&deps.storage).unwrap();
-        assert_eq!(total_supply, 10000 - 2000);
 
-        // Second burn more than allowance
-        let handle_msg = ExecuteMsg::BurnFrom {
-            owner: "bob".to_string(),
+        for key in ["key", "lior_key"] {
+            let sender = if key == "key" { "bob" } else { "lior" };
+            let handle_msg = ExecuteMsg::SetViewingKey {
+                key: key.to_string(),
+                padding: None,
+                gas_target: None,
+            };
+            let handle_result = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(sender, &[]),
+                handle_msg,
+            );
+            assert!(ensure_success(handle_result.unwrap()));
+        }
+
+        // Lior is a decoy in the first and third transfers, and the recipient of the others
+        for (i, recipient) in ["alice", "lior", "alice", "lior", "lior"]
+            .iter()
+            .enumerate()
+        {
+            let decoys = match *recipient {
+                "alice" => Some(vec![Addr::unchecked("lior")]),
+                _ => None,
+            };
+            let handle_msg = ExecuteMsg::Transfer {
+                recipient: recipient.to_string(),
+                amount: Uint128::new(10 + i as u128),
+                memo: None,
+                decoys,
+                decoy_count: None,
+                entropy: Some(Binary::from_base64("VEVTVFRFU1RURVNUQ0hFQ0tDSEVDSw==").unwrap()),
+                padding: None,
+                gas_target: None,
+            };
+            let handle_result =
+                execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+            assert!(ensure_success(handle_result.unwrap()));
+        }
+
+        let history = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>,
+                       address: &str,
+                       key: &str,
+                       filter: bool,
+                       start_after_id: Option<u64>,
+                       direction: HistoryDirection| {
+            let query_msg = QueryMsg::TransactionHistory {
+                address: address.to_string(),
+                key: key.to_string(),
+                page: None,
+                page_size: 2,
+                should_filter_decoys: Some(filter),
+                start_after_id,
+                direction: Some(direction),
+                filter: None,
+            };
+            let query_result = query(deps.as_ref(), mock_env(), query_msg).unwrap();
+            match from_binary(&query_result).unwrap() {
+                QueryAnswer::TransactionHistory {
+                    txs, next_cursor, ..
+                } => (
+                    txs.into_iter().map(|tx| tx.id).collect::<Vec<u64>>(),
+                    next_cursor,
+                ),
+                other => panic!("Unexpected: {:?}", other),
+            }
+        };
+
+        // Pages are full even though decoys are filtered out
+        let (ids, cursor) = history(
+            &deps,
+            "lior",
+            "lior_key",
+            true,
+            None,
+            HistoryDirection::Descending,
+        );
+        assert_eq!(ids, vec![6, 5]);
+        assert_eq!(cursor, Some(5));
+
+        // New transactions do not shift the next page
+        let handle_msg = ExecuteMsg::Transfer {
+            recipient: "lior".to_string(),
             amount: Uint128::new(1),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
-        let info = mock_info("alice", &[]);
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
 
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        let (ids, cursor) = history(
+            &deps,
+            "lior",
+            "lior_key",
+            true,
+            cursor,
+            HistoryDirection::Descending,
+        );
+        assert_eq!(ids, vec![3]);
+        assert_eq!(cursor, None);
+
+        // Walking the whole history oldest first
+        let mut all_ids = vec![];
+        let mut cursor = None;
+        loop {
+            let (ids, next_cursor) = history(
+                &deps,
+                "bob",
+                "key",
+                false,
+                cursor,
+                HistoryDirection::Ascending,
+            );
+            all_ids.extend(ids);
+            if next_cursor.is_none() {
+                break;
+            }
+            cursor = next_cursor;
+        }
+        assert_eq!(all_ids, vec![1, 2, 3, 4, 5, 6, 7]);
 
-        let error = extract_error_msg(handle_result);
-        assert!(error.contains("insufficient allowance"));
+        // Newest first from a cursor in the middle of the settled history
+        let (ids, cursor) = history(
+            &deps,
+            "bob",
+            "key",
+            false,
+            Some(5),
+            HistoryDirection::Descending,
+        );
+        assert_eq!(ids, vec![4, 3]);
+        assert_eq!(cursor, Some(3));
     }
 
     #[test]
-    fn test_handle_batch_burn_from() {
+    fn test_query_transaction_history_filter() {
         let (init_result, mut deps) = init_helper_with_config(
-            vec![
-                InitialBalance {
-                    address: "bob".to_string(),
-                    amount: Uint128::new(10000),
-                },
-                InitialBalance {
-                    address: "jerry".to_string(),
-                    amount: Uint128::new(10000),
-                },
-                InitialBalance {
-                    address: "mike".to_string(),
-                    amount: Uint128::new(10000),
-                },
-            ],
+            vec![InitialBalance {
+                address: "bob".to_string(),
+                amount: Uint128::new(10000),
+            }],
             false,
             false,
             false,
@@ -3459,174 +8116,444 @@ mod tests {
             init_result.err().unwrap()
         );
 
-        let (init_result_for_failure, mut deps_for_failure) = init_helper(vec![InitialBalance {
-            address: "bob".to_string(),
-            amount: Uint128::new(10000),
-        }]);
-        assert!(
-            init_result_for_failure.is_ok(),
-            "Init failed: {}",
-            init_result_for_failure.err().unwrap()
+        let handle_msg = ExecuteMsg::SetViewingKey {
+            key: "key".to_string(),
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_msg = ExecuteMsg::Transfer {
+            recipient: "alice".to_string(),
+            amount: Uint128::new(100),
+            memo: Some("rent".to_string()),
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let mut env = mock_env();
+        env.block.height += 100;
+        let handle_msg = ExecuteMsg::Transfer {
+            recipient: "lior".to_string(),
+            amount: Uint128::new(100),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bob", &[]),
+            handle_msg,
         );
-        // test when burn disabled
-        let actions: Vec<_> = ["bob", "jerry", "mike"]
-            .iter()
-            .map(|name| batch::BurnFromAction {
-                owner: name.to_string(),
-                amount: Uint128::new(2500),
-                memo: None,
-                decoys: None,
-            })
-            .collect();
-        let handle_msg = ExecuteMsg::BatchBurnFrom {
-            actions,
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_msg = ExecuteMsg::Burn {
+            amount: Uint128::new(100),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
-        let info = mock_info("alice", &[]);
         let handle_result = execute(
-            deps_for_failure.as_mut(),
-            mock_env(),
-            info,
-            handle_msg.clone(),
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bob", &[]),
+            handle_msg,
         );
-        let error = extract_error_msg(handle_result);
-        assert!(error.contains("Burn functionality is not enabled for this token."));
+        assert!(ensure_success(handle_result.unwrap()));
 
-        // Burn before allowance
-        let info = mock_info("alice", &[]);
+        let history = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>,
+                       page_size: u32,
+                       filter: TxFilter| {
+            let query_msg = QueryMsg::TransactionHistory {
+                address: "bob".to_string(),
+                key: "key".to_string(),
+                page: None,
+                page_size,
+                should_filter_decoys: None,
+                start_after_id: None,
+                direction: None,
+                filter: Some(filter),
+            };
+            let query_result = query(deps.as_ref(), mock_env(), query_msg).unwrap();
+            match from_binary(&query_result).unwrap() {
+                QueryAnswer::TransactionHistory { txs, total, .. } => {
+                    (txs.into_iter().map(|tx| tx.id).collect::<Vec<u64>>(), total)
+                }
+                other => panic!("Unexpected: {:?}", other),
+            }
+        };
 
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        use crate::transaction_history::TxActionKind;
+        let filter = TxFilter {
+            actions: Some(vec![TxActionKind::Mint, TxActionKind::Burn]),
+            ..Default::default()
+        };
+        assert_eq!(history(&deps, 10, filter), (vec![4, 1], Some(2)));
 
-        let error = extract_error_msg(handle_result);
-        assert!(error.contains("insufficient allowance"));
+        let filter = TxFilter {
+            counterparty: Some("alice".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(history(&deps, 10, filter), (vec![2], Some(1)));
 
-        // Burn more than allowance
-        let allowance_size = 2000;
-        for name in &["bob", "jerry", "mike"] {
-            let handle_msg = ExecuteMsg::IncreaseAllowance {
-                spender: "alice".to_string(),
-                amount: Uint128::new(allowance_size),
-                padding: None,
-                expiration: None,
-            };
-            let info = mock_info(*name, &[]);
-            let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        let filter = TxFilter {
+            has_memo: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(history(&deps, 10, filter), (vec![2], Some(1)));
 
-            assert!(
-                handle_result.is_ok(),
-                "handle() failed: {}",
-                handle_result.err().unwrap()
-            );
-            let handle_msg = ExecuteMsg::BurnFrom {
-                owner: "name".to_string(),
-                amount: Uint128::new(2500),
-                memo: None,
-                decoys: None,
-                entropy: None,
-                padding: None,
-            };
-            let info = mock_info("alice", &[]);
+        let filter = TxFilter {
+            min_block_height: Some(env.block.height),
+            actions: Some(vec![TxActionKind::Transfer]),
+            ..Default::default()
+        };
+        assert_eq!(history(&deps, 10, filter), (vec![3], Some(1)));
 
-            let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        // The total is not counted when there are more pages
+        let filter = TxFilter {
+            actions: Some(vec![TxActionKind::Transfer]),
+            ..Default::default()
+        };
+        assert_eq!(history(&deps, 1, filter), (vec![3], None));
+    }
 
-            let error = extract_error_msg(handle_result);
-            assert!(error.contains("insufficient allowance"));
-        }
+    #[test]
+    fn test_transfer_history_from_single_store() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: "bob".to_string(),
+            amount: Uint128::new(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
 
-        // Burn some of the allowance
-        let actions: Vec<_> = [("bob", 200_u128), ("jerry", 300), ("mike", 400)]
-            .iter()
-            .map(|(name, amount)| batch::BurnFromAction {
-                owner: name.to_string(),
-                amount: Uint128::new(*amount),
-                memo: None,
-                decoys: None,
-            })
-            .collect();
+        for (account, key) in [("bob", "key"), ("lior", "lior_key")] {
+            let handle_msg = ExecuteMsg::SetViewingKey {
+                key: key.to_string(),
+                padding: None,
+                gas_target: None,
+            };
+            let handle_result = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(account, &[]),
+                handle_msg,
+            );
+            assert!(ensure_success(handle_result.unwrap()));
+        }
 
-        let handle_msg = ExecuteMsg::BatchBurnFrom {
-            actions,
-            entropy: None,
+        let handle_msg = ExecuteMsg::Transfer {
+            recipient: "alice".to_string(),
+            amount: Uint128::new(100),
+            memo: None,
+            decoys: Some(vec!["lior".to_string()]),
+            decoy_count: None,
+            entropy: Some(Binary::from_base64("VEVTVEVSCg==").unwrap()),
             padding: None,
+            gas_target: None,
         };
-        let info = mock_info("alice", &[]);
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
 
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        let history = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>,
+                       account: &str,
+                       key: &str,
+                       should_filter_decoys: bool| {
+            let query_msg = QueryMsg::TransferHistory {
+                address: account.to_string(),
+                key: key.to_string(),
+                page: None,
+                page_size: 10,
+                should_filter_decoys: Some(should_filter_decoys),
+                start_after_id: None,
+                direction: None,
+                filter: None,
+            };
+            let query_result = query(deps.as_ref(), mock_env(), query_msg).unwrap();
+            match from_binary(&query_result).unwrap() {
+                QueryAnswer::TransferHistory { txs, total, .. } => (txs, total),
+                other => panic!("Unexpected: {:?}", other),
+            }
+        };
+
+        // The initial balance is a mint, so only the transfer is projected
+        let (txs, total) = history(&deps, "bob", "key", false);
+        assert_eq!(total, Some(1));
+        assert_eq!(txs[0].receiver, Addr::unchecked("alice"));
+        assert_eq!(txs[0].block_height, Some(mock_env().block.height));
 
+        // The decoy keeps the transfer's addresses and is still marked with a block height of 0
+        let (txs, total) = history(&deps, "lior", "lior_key", false);
+        assert_eq!(total, Some(1));
+        assert_eq!(txs[0].from, Addr::unchecked("bob"));
+        assert_eq!(txs[0].receiver, Addr::unchecked("lior"));
+        assert_eq!(txs[0].block_height, Some(0));
+        assert_eq!(history(&deps, "lior", "lior_key", true).0.len(), 0);
+
+        // Nothing is written to the legacy transfer store anymore
+        let bob = deps.api.addr_canonicalize("bob").unwrap();
+        let legacy_store = cosmwasm_storage::ReadonlyPrefixedStorage::multilevel(
+            &deps.storage,
+            &[b"transfers", bob.as_slice()],
+        );
+        assert!(legacy_store.get(b"len").is_none());
+    }
+
+    #[test]
+    fn test_prune_history() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: "bob".to_string(),
+            amount: Uint128::new(5000),
+        }]);
         assert!(
-            handle_result.is_ok(),
-            "handle() failed: {}",
-            handle_result.err().unwrap()
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
         );
-        for (name, amount) in &[("bob", 200_u128), ("jerry", 300), ("mike", 400)] {
-            let name_addr = Addr::unchecked(name.to_string());
-            let name_canonical = deps.api.addr_canonicalize(name_addr.as_str()).unwrap();
-            let balance = BalancesStore::load(&deps.storage, &name_canonical);
-            assert_eq!(balance, 10000 - amount);
+
+        for account in ["bob", "alice"] {
+            let handle_msg = ExecuteMsg::SetViewingKey {
+                key: "key".to_string(),
+                padding: None,
+                gas_target: None,
+            };
+            let handle_result = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(account, &[]),
+                handle_msg,
+            );
+            assert!(ensure_success(handle_result.unwrap()));
         }
-        let total_supply = ConfigStore::load_total_supply(&deps.storage).unwrap();
-        assert_eq!(total_supply, 10000 * 3 - (200 + 300 + 400));
 
-        // Burn the rest of the allowance
-        let actions: Vec<_> = [("bob", 200_u128), ("jerry", 300), ("mike", 400)]
-            .iter()
-            .map(|(name, amount)| batch::BurnFromAction {
-                owner: name.to_string(),
-                amount: Uint128::new(allowance_size - *amount),
+        for _ in 0..3 {
+            let handle_msg = ExecuteMsg::Transfer {
+                recipient: "alice".to_string(),
+                amount: Uint128::new(100),
                 memo: None,
                 decoys: None,
-            })
-            .collect();
+                decoy_count: None,
+                entropy: None,
+                padding: None,
+                gas_target: None,
+            };
+            let handle_result =
+                execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+            assert!(ensure_success(handle_result.unwrap()));
+        }
 
-        let handle_msg = ExecuteMsg::BatchBurnFrom {
-            actions,
-            entropy: None,
+        let history = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>, account: &str| {
+            let query_msg = QueryMsg::TransactionHistory {
+                address: account.to_string(),
+                key: "key".to_string(),
+                page: None,
+                page_size: 10,
+                should_filter_decoys: None,
+                start_after_id: None,
+                direction: None,
+                filter: None,
+            };
+            let query_result = query(deps.as_ref(), mock_env(), query_msg).unwrap();
+            match from_binary(&query_result).unwrap() {
+                QueryAnswer::TransactionHistory {
+                    txs,
+                    pruned_through,
+                    ..
+                } => (
+                    txs.into_iter().map(|tx| tx.id).collect::<Vec<u64>>(),
+                    pruned_through,
+                ),
+                other => panic!("Unexpected: {:?}", other),
+            }
+        };
+        assert_eq!(history(&deps, "bob"), (vec![4, 3, 2, 1], None));
+
+        let handle_msg = ExecuteMsg::PruneHistory {
+            keep_last: 2,
             padding: None,
+            gas_target: None,
         };
-        let info = mock_info("alice", &[]);
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+        let answer: ExecuteAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        assert!(matches!(
+            answer,
+            ExecuteAnswer::PruneHistory {
+                removed: 2,
+                remaining: 0
+            }
+        ));
+        assert_eq!(history(&deps, "bob"), (vec![4, 3], Some(2)));
+        assert_eq!(history(&deps, "alice"), (vec![4, 3, 2], None));
 
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        // Alice's txs are still pending in the buffer, and are settled before they are cleared
+        let handle_msg = ExecuteMsg::ClearHistory {
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            handle_msg,
+        );
+        let answer: ExecuteAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        assert!(matches!(
+            answer,
+            ExecuteAnswer::ClearHistory {
+                removed: 3,
+                remaining: 0
+            }
+        ));
+        assert_eq!(history(&deps, "alice"), (vec![], Some(4)));
+        assert_eq!(history(&deps, "bob"), (vec![4, 3], Some(2)));
+
+        let alice = deps.api.addr_canonicalize("alice").unwrap();
+        assert_eq!(BalancesStore::load(&deps.storage, &alice).unwrap(), 300);
+    }
+
+    #[test]
+    fn test_prune_history_in_batches() {
+        use crate::legacy_support::AppendStoreMut;
+        use crate::transaction_history::MAX_PRUNED_PER_MSG;
+
+        /// `StoredLegacyTransfer`, as stored under `PREFIX_TRANSFERS` before schema 4
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct LegacyTransfer {
+            id: u64,
+            from: CanonicalAddr,
+            sender: CanonicalAddr,
+            receiver: CanonicalAddr,
+            coins: Coin,
+            memo: Option<String>,
+            block_time: u64,
+            block_height: u64,
+        }
 
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: "bob".to_string(),
+            amount: Uint128::new(5000),
+        }]);
         assert!(
-            handle_result.is_ok(),
-            "handle() failed: {}",
-            handle_result.err().unwrap()
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
         );
-        for name in &["bob", "jerry", "mike"] {
-            let name_addr = Addr::unchecked(name.to_string());
-            let name_canonical = deps.api.addr_canonicalize(name_addr.as_str()).unwrap();
-            let balance = BalancesStore::load(&deps.storage, &name_canonical);
-            assert_eq!(balance, 10000 - allowance_size);
-        }
-        let total_supply = ConfigStore::load_total_supply(&deps.storage).unwrap();
-        assert_eq!(total_supply, 3 * (10000 - allowance_size));
 
-        // Second burn more than allowance
-        let actions: Vec<_> = ["bob", "jerry", "mike"]
-            .iter()
-            .map(|name| batch::BurnFromAction {
-                owner: name.to_string(),
+        // With the initial mint, Bob has 301 txs
+        for _ in 0..300 {
+            let handle_msg = ExecuteMsg::Transfer {
+                recipient: "alice".to_string(),
                 amount: Uint128::new(1),
                 memo: None,
                 decoys: None,
-            })
-            .collect();
-        let handle_msg = ExecuteMsg::BatchBurnFrom {
-            actions,
-            entropy: None,
-            padding: None,
+                decoy_count: None,
+                entropy: None,
+                padding: None,
+                gas_target: None,
+            };
+            let handle_result =
+                execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+            assert!(ensure_success(handle_result.unwrap()));
+        }
+
+        let prune = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+                     account: &str,
+                     keep_last: Option<u32>| {
+            let handle_msg = match keep_last {
+                Some(keep_last) => ExecuteMsg::PruneHistory {
+                    keep_last,
+                    padding: None,
+                    gas_target: None,
+                },
+                None => ExecuteMsg::ClearHistory {
+                    padding: None,
+                    gas_target: None,
+                },
+            };
+            let handle_result = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(account, &[]),
+                handle_msg,
+            );
+            match from_binary(&handle_result.unwrap().data.unwrap()).unwrap() {
+                ExecuteAnswer::PruneHistory { removed, remaining } => (removed, remaining),
+                ExecuteAnswer::ClearHistory { removed, remaining } => (removed, remaining),
+                other => panic!("Unexpected: {:?}", other),
+            }
         };
-        let info = mock_info("alice", &[]);
 
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        // Each message removes a bounded number of txs, and the next one carries on
+        let bob = deps.api.addr_canonicalize("bob").unwrap();
+        assert_eq!(
+            prune(&mut deps, "bob", Some(10)),
+            (MAX_PRUNED_PER_MSG, 291 - MAX_PRUNED_PER_MSG)
+        );
+        assert_eq!(
+            history_pruned_through(&deps.storage, &bob),
+            Some(MAX_PRUNED_PER_MSG as u64)
+        );
+        assert_eq!(
+            prune(&mut deps, "bob", Some(10)),
+            (291 - MAX_PRUNED_PER_MSG, 0)
+        );
+        assert_eq!(history_pruned_through(&deps.storage, &bob), Some(291));
+        assert_eq!(prune(&mut deps, "bob", Some(10)), (0, 0));
+
+        // Carol only has transfers from before schema 4
+        let carol = deps.api.addr_canonicalize("carol").unwrap();
+        {
+            let mut legacy_store = cosmwasm_storage::PrefixedStorage::multilevel(
+                &mut deps.storage,
+                &[b"transfers", carol.as_slice()],
+            );
+            let mut legacy_store =
+                AppendStoreMut::<LegacyTransfer, _>::attach_or_create(&mut legacy_store).unwrap();
+            for id in 1..=3 {
+                let transfer = LegacyTransfer {
+                    id,
+                    from: bob.clone(),
+                    sender: bob.clone(),
+                    receiver: carol.clone(),
+                    coins: Coin {
+                        denom: "SECSEC".to_string(),
+                        amount: Uint128::new(1),
+                    },
+                    memo: None,
+                    block_time: 0,
+                    block_height: 1,
+                };
+                legacy_store.push(&transfer).unwrap();
+            }
+        }
 
-        let error = extract_error_msg(handle_result);
-        assert!(error.contains("insufficient allowance"));
+        // Clearing them is recorded even though there were no txs to clear
+        assert_eq!(prune(&mut deps, "carol", None), (3, 0));
+        assert_eq!(history_pruned_through(&deps.storage, &carol), Some(3));
+        let legacy_store = cosmwasm_storage::ReadonlyPrefixedStorage::multilevel(
+            &deps.storage,
+            &[b"transfers", carol.as_slice()],
+        );
+        assert_eq!(legacy_store.get(b"len"), Some(0u32.to_be_bytes().to_vec()));
     }
 
     #[test]
-    fn test_handle_decrease_allowance() {
+    fn test_tx_labels() {
         let (init_result, mut deps) = init_helper(vec![InitialBalance {
             address: "bob".to_string(),
             amount: Uint128::new(5000),
@@ -3637,149 +8564,414 @@ mod tests {
             init_result.err().unwrap()
         );
 
-        let handle_msg = ExecuteMsg::DecreaseAllowance {
-            spender: "alice".to_string(),
-            amount: Uint128::new(2000),
+        for account in ["bob", "alice"] {
+            let handle_msg = ExecuteMsg::SetViewingKey {
+                key: "key".to_string(),
+                padding: None,
+                gas_target: None,
+            };
+            let handle_result = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(account, &[]),
+                handle_msg,
+            );
+            assert!(ensure_success(handle_result.unwrap()));
+        }
+
+        let handle_msg = ExecuteMsg::Transfer {
+            recipient: "alice".to_string(),
+            amount: Uint128::new(100),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
             padding: None,
-            expiration: None,
+            gas_target: None,
         };
-        let info = mock_info("bob", &[]);
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
 
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        let set_label = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+                         account: &str,
+                         tx_id: u64,
+                         note: Option<&str>,
+                         tags: Option<Vec<&str>>| {
+            let handle_msg = ExecuteMsg::SetTxLabel {
+                tx_id,
+                note: note.map(|note| note.to_string()),
+                tags: tags.map(|tags| tags.into_iter().map(|tag| tag.to_string()).collect()),
+                padding: None,
+                gas_target: None,
+            };
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(account, &[]),
+                handle_msg,
+            )
+        };
+        let label = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>, account: &str| {
+            let query_msg = QueryMsg::TransactionHistory {
+                address: account.to_string(),
+                key: "key".to_string(),
+                page: None,
+                page_size: 1,
+                should_filter_decoys: None,
+                start_after_id: None,
+                direction: None,
+                filter: None,
+            };
+            let query_result = query(deps.as_ref(), mock_env(), query_msg).unwrap();
+            match from_binary(&query_result).unwrap() {
+                QueryAnswer::TransactionHistory { txs, .. } => txs[0].label.clone(),
+                other => panic!("Unexpected: {:?}", other),
+            }
+        };
 
-        assert!(
-            handle_result.is_ok(),
-            "handle() failed: {}",
-            handle_result.err().unwrap()
+        let handle_result = set_label(
+            &mut deps,
+            "bob",
+            2,
+            Some("invoice 4411"),
+            Some(vec!["payroll"]),
         );
+        assert!(ensure_success(handle_result.unwrap()));
+        assert_eq!(
+            label(&deps, "bob"),
+            Some(TxLabel {
+                note: Some("invoice 4411".to_string()),
+                tags: vec!["payroll".to_string()],
+            })
+        );
+        // The counterparty does not see it
+        assert_eq!(label(&deps, "alice"), None);
 
-        let bob_addr = Addr::unchecked("bob".to_string());
-        let alice_addr = Addr::unchecked("alice".to_string());
-
-        let _bob_canonical = deps.api.addr_canonicalize(bob_addr.as_str()).unwrap();
-        let _alice_canonical = deps.api.addr_canonicalize(alice_addr.as_str()).unwrap();
-
-        let allowance = AllowancesStore::load(&deps.storage, &bob_addr, &alice_addr);
+        // Alice's copy is still pending in the buffer
+        let handle_result = set_label(&mut deps, "alice", 2, None, Some(vec!["rent"]));
+        assert!(ensure_success(handle_result.unwrap()));
         assert_eq!(
-            allowance,
-            crate::state::Allowance {
-                amount: 0,
-                expiration: None
-            }
+            label(&deps, "alice").unwrap().tags,
+            vec!["rent".to_string()]
         );
 
-        let handle_msg = ExecuteMsg::IncreaseAllowance {
-            spender: "alice".to_string(),
-            amount: Uint128::new(2000),
-            padding: None,
-            expiration: None,
-        };
-        let info = mock_info("bob", &[]);
+        let handle_result = set_label(&mut deps, "alice", 1, Some("not mine"), None);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Transaction 1 is not in your history"));
 
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        let handle_result = set_label(&mut deps, "bob", 2, None, Some(vec![""]));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Tags must be between"));
+
+        let handle_result = set_label(&mut deps, "bob", 2, None, None);
+        assert!(ensure_success(handle_result.unwrap()));
+        assert_eq!(label(&deps, "bob"), None);
+    }
 
+    #[test]
+    fn test_delayed_write_buffer() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: "bob".to_string(),
+            amount: Uint128::new(5000),
+        }]);
         assert!(
-            handle_result.is_ok(),
-            "handle() failed: {}",
-            handle_result.err().unwrap()
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
         );
 
-        let handle_msg = ExecuteMsg::DecreaseAllowance {
-            spender: "alice".to_string(),
-            amount: Uint128::new(50),
+        let bob = deps.api.addr_canonicalize("bob").unwrap();
+        let alice = deps.api.addr_canonicalize("alice").unwrap();
+
+        // The initial balance is still pending
+        assert_eq!(btbe::stored_balance(&deps.storage, &bob).unwrap(), None);
+        assert_eq!(BalancesStore::load(&deps.storage, &bob).unwrap(), 5000);
+
+        let handle_msg = ExecuteMsg::Transfer {
+            recipient: "alice".to_string(),
+            amount: Uint128::new(1000),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
             padding: None,
-            expiration: None,
+            gas_target: None,
         };
-        let info = mock_info("bob", &[]);
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
 
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        // Spending settled bob, while alice's credit waits in the buffer
+        assert_eq!(
+            btbe::stored_balance(&deps.storage, &bob).unwrap(),
+            Some(4000)
+        );
+        assert_eq!(btbe::stored_balance(&deps.storage, &alice).unwrap(), None);
+        assert_eq!(BalancesStore::load(&deps.storage, &alice).unwrap(), 1000);
+        let (txs, total, _) = StoredExtendedTx::get_txs(
+            &deps.api,
+            &deps.storage,
+            alice.clone(),
+            0,
+            10,
+            false,
+            None,
+            HistoryDirection::Descending,
+            None,
+        )
+        .unwrap();
+        assert_eq!((txs.len(), total), (1, Some(1)));
+        let (txs, total, _) = StoredExtendedTx::get_txs(
+            &deps.api,
+            &deps.storage,
+            bob.clone(),
+            0,
+            10,
+            false,
+            None,
+            HistoryDirection::Descending,
+            None,
+        )
+        .unwrap();
+        assert_eq!((txs.len(), total), (2, Some(2)));
 
-        assert!(
-            handle_result.is_ok(),
-            "handle() failed: {}",
-            handle_result.err().unwrap()
-        );
+        // Filling the buffer evicts entries at random, without losing balances or history
+        for i in 0..DWB_LEN + 5 {
+            let handle_msg = ExecuteMsg::Transfer {
+                recipient: format!("holder{i}"),
+                amount: Uint128::new(10),
+                memo: None,
+                decoys: None,
+                decoy_count: None,
+                entropy: None,
+                padding: None,
+                gas_target: None,
+            };
+            let handle_result =
+                execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+            assert!(ensure_success(handle_result.unwrap()));
+        }
+        let mut settled = 0;
+        for i in 0..DWB_LEN + 5 {
+            let holder = deps.api.addr_canonicalize(&format!("holder{i}")).unwrap();
+            assert_eq!(BalancesStore::load(&deps.storage, &holder).unwrap(), 10);
+            let (_, total, _) = StoredExtendedTx::get_txs(
+                &deps.api,
+                &deps.storage,
+                holder.clone(),
+                0,
+                10,
+                false,
+                None,
+                HistoryDirection::Descending,
+                None,
+            )
+            .unwrap();
+            assert_eq!(total, Some(1));
+            if btbe::stored_balance(&deps.storage, &holder).unwrap() == Some(10) {
+                settled += 1;
+            }
+        }
+        assert!(settled >= 5);
 
-        let allowance = AllowancesStore::load(&deps.storage, &bob_addr, &alice_addr);
+        // Balances from before the trie move over when the account settles
+        let charlie = deps.api.addr_canonicalize("charlie").unwrap();
+        prefixed(&mut deps.storage, PREFIX_BALANCES)
+            .set(charlie.as_slice(), &700u128.to_be_bytes());
+        assert_eq!(BalancesStore::load(&deps.storage, &charlie).unwrap(), 700);
+
+        let handle_msg = ExecuteMsg::Transfer {
+            recipient: "bob".to_string(),
+            amount: Uint128::new(200),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("charlie", &[]),
+            handle_msg,
+        );
+        assert!(ensure_success(handle_result.unwrap()));
         assert_eq!(
-            allowance,
-            crate::state::Allowance {
-                amount: 1950,
-                expiration: None
-            }
+            btbe::stored_balance(&deps.storage, &charlie).unwrap(),
+            Some(500)
+        );
+        assert!(prefixed_read(&deps.storage, PREFIX_BALANCES)
+            .get(charlie.as_slice())
+            .is_none());
+
+        // An entry settles once it holds as many txs as it can
+        let transfer_to_dave = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>| {
+            let handle_msg = ExecuteMsg::Transfer {
+                recipient: "dave".to_string(),
+                amount: Uint128::new(10),
+                memo: None,
+                decoys: None,
+                decoy_count: None,
+                entropy: None,
+                padding: None,
+                gas_target: None,
+            };
+            let handle_result =
+                execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+            assert!(ensure_success(handle_result.unwrap()));
+        };
+        transfer_to_dave(&mut deps);
+
+        let dave = deps.api.addr_canonicalize("dave").unwrap();
+        let mut dwb = DelayedWriteBuffer::load(&deps.storage).unwrap();
+        let entry = dwb
+            .entries
+            .iter_mut()
+            .find(|entry| entry.recipient == dave)
+            .unwrap();
+        assert_eq!(entry.list_len, 1);
+        entry.list_len = DWB_MAX_TX_NODES - 1;
+        dwb.save(&mut deps.storage).unwrap();
+
+        transfer_to_dave(&mut deps);
+        let dwb = DelayedWriteBuffer::load(&deps.storage).unwrap();
+        assert_eq!(dwb.pending_amount(&dave), 0);
+        assert_eq!(
+            btbe::stored_balance(&deps.storage, &dave).unwrap(),
+            Some(20)
         );
+        let (_, total, _) = StoredExtendedTx::get_txs(
+            &deps.api,
+            &deps.storage,
+            dave,
+            0,
+            10,
+            false,
+            None,
+            HistoryDirection::Descending,
+            None,
+        )
+        .unwrap();
+        assert_eq!(total, Some(2));
     }
 
     #[test]
-    fn test_handle_increase_allowance() {
-        let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: "bob".to_string(),
-            amount: Uint128::new(5000),
-        }]);
+    fn test_handle_contract_decoys() {
+        let (init_result, mut deps) = init_helper(vec![
+            InitialBalance {
+                address: "bob".to_string(),
+                amount: Uint128::new(5000),
+            },
+            InitialBalance {
+                address: "alice".to_string(),
+                amount: Uint128::new(10),
+            },
+            InitialBalance {
+                address: "lior".to_string(),
+                amount: Uint128::new(10),
+            },
+            InitialBalance {
+                address: "jhon".to_string(),
+                amount: Uint128::new(10),
+            },
+        ]);
         assert!(
             init_result.is_ok(),
             "Init failed: {}",
             init_result.err().unwrap()
         );
 
-        let handle_msg = ExecuteMsg::IncreaseAllowance {
-            spender: "alice".to_string(),
-            amount: Uint128::new(2000),
+        let transfer = |decoys: Option<Vec<Addr>>, decoy_count: Option<u8>| ExecuteMsg::Transfer {
+            recipient: "alice".to_string(),
+            amount: Uint128::new(1000),
+            memo: None,
+            decoys,
+            decoy_count,
+            entropy: None,
             padding: None,
-            expiration: None,
+            gas_target: None,
         };
-        let info = mock_info("bob", &[]);
-
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
 
-        assert!(
-            handle_result.is_ok(),
-            "handle() failed: {}",
-            handle_result.err().unwrap()
-        );
+        let handle_msg = transfer(None, Some(MAX_DECOYS + 1));
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Too many decoys"));
 
-        let bob_canonical = Addr::unchecked("bob".to_string());
-        let alice_canonical = Addr::unchecked("alice".to_string());
+        let handle_msg = transfer(Some(vec![Addr::unchecked("lior")]), Some(2));
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("either decoys or decoy_count"));
 
-        let allowance = AllowancesStore::load(&deps.storage, &bob_canonical, &alice_canonical);
+        // Only lior and jhon can stand in for bob and alice, so asking for more fails
+        let handle_msg = transfer(None, Some(3));
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
         assert_eq!(
-            allowance,
-            crate::state::Allowance {
-                amount: 2000,
-                expiration: None
-            }
+            error,
+            "Only 2 recently active accounts can be decoys, fewer than the 3 asked for"
         );
 
-        let handle_msg = ExecuteMsg::IncreaseAllowance {
-            spender: "alice".to_string(),
-            amount: Uint128::new(2000),
-            padding: None,
-            expiration: None,
+        let decoy_txs = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>, name| {
+            let account = deps.api.addr_canonicalize(name).unwrap();
+            let (txs, total, _) = StoredExtendedTx::get_txs(
+                &deps.api,
+                &deps.storage,
+                account,
+                0,
+                10,
+                true,
+                None,
+                HistoryDirection::Descending,
+                None,
+            )
+            .unwrap();
+            total.unwrap() as usize - txs.len()
         };
-        let info = mock_info("bob", &[]);
 
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        // The decoys come from the accounts that have been active so far, never the sender or
+        // the recipient
+        let handle_msg = transfer(None, Some(2));
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
 
-        assert!(
-            handle_result.is_ok(),
-            "handle() failed: {}",
-            handle_result.err().unwrap()
-        );
+        assert_eq!(decoy_txs(&deps, "alice"), 0);
+        assert_eq!(decoy_txs(&deps, "bob"), 0);
+        assert_eq!(decoy_txs(&deps, "lior"), 1);
+        assert_eq!(decoy_txs(&deps, "jhon"), 1);
+
+        let alice = deps.api.addr_canonicalize("alice").unwrap();
+        let lior = deps.api.addr_canonicalize("lior").unwrap();
+        assert_eq!(BalancesStore::load(&deps.storage, &alice).unwrap(), 1010);
+        assert_eq!(BalancesStore::load(&deps.storage, &lior).unwrap(), 10);
+
+        // In a batch, no action's recipient is a decoy for another action
+        let handle_msg = ExecuteMsg::BatchTransfer {
+            actions: ["lior", "jhon"]
+                .iter()
+                .map(|recipient| batch::TransferAction {
+                    recipient: recipient.to_string(),
+                    amount: Uint128::new(10),
+                    memo: None,
+                    decoys: None,
+                })
+                .collect(),
+            decoy_count: Some(1),
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
 
-        let allowance = AllowancesStore::load(&deps.storage, &bob_canonical, &alice_canonical);
-        assert_eq!(
-            allowance,
-            crate::state::Allowance {
-                amount: 4000,
-                expiration: None
-            }
-        );
+        assert_eq!(decoy_txs(&deps, "alice"), 2);
+        assert_eq!(decoy_txs(&deps, "lior"), 1);
+        assert_eq!(decoy_txs(&deps, "jhon"), 1);
     }
 
     #[test]
-    fn test_handle_change_admin() {
+    fn test_handle_set_contract_status() {
         let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: "bob".to_string(),
+            address: "admin".to_string(),
             amount: Uint128::new(5000),
         }]);
         assert!(
@@ -3788,9 +8980,10 @@ mod tests {
             init_result.err().unwrap()
         );
 
-        let handle_msg = ExecuteMsg::ChangeAdmin {
-            address: "bob".to_string(),
+        let handle_msg = ExecuteMsg::SetContractStatus {
+            level: ContractStatusLevel::StopAll,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("admin", &[]);
 
@@ -3802,41 +8995,136 @@ mod tests {
             handle_result.err().unwrap()
         );
 
-        let admin = ConfigStore::load_constants(&deps.storage).unwrap().admin;
-        assert_eq!(admin, Addr::unchecked("bob".to_string()));
+        let contract_status = ConfigStore::load_contract_status(&deps.storage).unwrap();
+        assert!(matches!(
+            contract_status,
+            ContractStatusLevel::StopAll { .. }
+        ));
     }
 
     #[test]
-    fn test_handle_set_contract_status() {
-        let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: "admin".to_string(),
-            amount: Uint128::new(5000),
-        }]);
+    fn test_handle_set_pause_flags() {
+        let (init_result, mut deps) = init_helper_with_config(
+            vec![InitialBalance {
+                address: "bob".to_string(),
+                amount: Uint128::new(5000),
+            }],
+            false,
+            false,
+            true,
+            false,
+            0,
+            vec![],
+        );
         assert!(
             init_result.is_ok(),
             "Init failed: {}",
             init_result.err().unwrap()
         );
 
-        let handle_msg = ExecuteMsg::SetContractStatus {
-            level: ContractStatusLevel::StopAll,
+        let pause_mints_msg = ExecuteMsg::SetPauseFlags {
+            flags: PauseFlags {
+                mints: true,
+                ..PauseFlags::default()
+            },
             padding: None,
+            gas_target: None,
         };
-        let info = mock_info("admin", &[]);
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &[]),
+            pause_mints_msg.clone(),
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This command requires the pauser role"));
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            pause_mints_msg,
+        );
+        assert!(ensure_success(handle_result.unwrap()));
 
-        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        // Minting is frozen while payments keep working
+        let mint_msg = ExecuteMsg::Mint {
+            recipient: "lebron".to_string(),
+            amount: Uint128::new(100),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            mint_msg.clone(),
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This contract is stopped and this action is not allowed"));
+        let handle_msg = ExecuteMsg::Transfer {
+            recipient: "alice".to_string(),
+            amount: Uint128::new(1000),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
 
-        assert!(
-            handle_result.is_ok(),
-            "handle() failed: {}",
-            handle_result.err().unwrap()
+        let query_result = query(deps.as_ref(), mock_env(), QueryMsg::ContractStatus {});
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::ContractStatus { status, flags } => {
+                assert_eq!(status, ContractStatusLevel::NormalRun);
+                assert!(flags.mints);
+                assert!(!flags.transfers);
+            }
+            _ => panic!("unexpected"),
+        }
+
+        // The levels are presets for the flags
+        let handle_msg = ExecuteMsg::SetContractStatus {
+            level: ContractStatusLevel::StopAllButRedeems,
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            handle_msg,
         );
+        assert!(ensure_success(handle_result.unwrap()));
+        let query_result = query(deps.as_ref(), mock_env(), QueryMsg::ContractStatus {});
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::ContractStatus { status, flags } => {
+                assert_eq!(status, ContractStatusLevel::StopAllButRedeems);
+                assert_eq!(flags, ContractStatusLevel::StopAllButRedeems.flags());
+                assert!(!flags.redeems);
+            }
+            _ => panic!("unexpected"),
+        }
 
-        let contract_status = ConfigStore::load_contract_status(&deps.storage).unwrap();
-        assert!(matches!(
-            contract_status,
-            ContractStatusLevel::StopAll { .. }
-        ));
+        let handle_msg = ExecuteMsg::SetPauseFlags {
+            flags: PauseFlags::default(),
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            handle_msg,
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_result = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), mint_msg);
+        assert!(ensure_success(handle_result.unwrap()));
     }
 
     #[test]
@@ -3891,8 +9179,10 @@ mod tests {
             amount: Uint128::new(1000),
             denom: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("butler", &[]);
 
@@ -3906,8 +9196,10 @@ mod tests {
             amount: Uint128::new(1000),
             denom: Option::from("uscrt".to_string()),
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("butler", &[]);
 
@@ -3919,27 +9211,102 @@ mod tests {
             "You are trying to redeem for more uscrt than the contract has in its reserve"
         );
 
-        // test without denom
+        // test without denom, which redeems for the only supported one
+        let handle_msg = ExecuteMsg::Redeem {
+            amount: Uint128::new(1000),
+            denom: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("butler", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        let result = handle_result.unwrap();
+        assert!(ensure_success(result.clone()));
+        assert_eq!(
+            result.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "butler".to_string(),
+                amount: vec![Coin {
+                    denom: "uscrt".to_string(),
+                    amount: Uint128::new(1000),
+                }],
+            })
+        );
+
+        // A second denom is only supported once it is confirmed to be pegged
+        let handle_msg = ExecuteMsg::AddSupportedDenoms {
+            denoms: vec!["uluna".to_string()],
+            pegged: None,
+        };
+        let info = mock_info("admin", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("set pegged to confirm"));
+        let constants = ConfigStore::load_constants(&deps.storage).unwrap();
+        assert_eq!(constants.supported_denoms, vec!["uscrt".to_string()]);
+
+        // test without denom when multiple coins are supported
+        let handle_msg = ExecuteMsg::AddSupportedDenoms {
+            denoms: vec!["uluna".to_string()],
+            pegged: Some(true),
+        };
+        let info = mock_info("admin", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        assert!(ensure_success(handle_result.unwrap()));
+
         let handle_msg = ExecuteMsg::Redeem {
             amount: Uint128::new(1000),
             denom: None,
             decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info("butler", &[]);
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+
+        let error = extract_error_msg(handle_result);
+        assert_eq!(
+            error,
+            "Tried to redeem without specifying denom, but multiple coins are supported"
+        );
+
+        // test with an unsupported denom
+        let handle_msg = ExecuteMsg::Redeem {
+            amount: Uint128::new(1000),
+            denom: Option::from("uatom".to_string()),
+            decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("butler", &[]);
 
         let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
 
-        assert!(handle_result.is_err(), "handle() was supposed to fail");
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Tried to redeem for an unsupported coin uatom"));
 
         // test with denom specified
         let handle_msg = ExecuteMsg::Redeem {
             amount: Uint128::new(1000),
             denom: Option::from("uscrt".to_string()),
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("butler", &[]);
 
@@ -3953,7 +9320,10 @@ mod tests {
 
         let addr = Addr::unchecked("butler".to_string());
         let canonical = deps.api.addr_canonicalize(addr.as_str()).unwrap();
-        assert_eq!(BalancesStore::load(&deps.storage, &canonical), 4000)
+        assert_eq!(
+            BalancesStore::load(&deps.storage, &canonical).unwrap(),
+            3000
+        )
     }
 
     #[test]
@@ -3988,8 +9358,10 @@ mod tests {
         // test when deposit disabled
         let handle_msg = ExecuteMsg::Deposit {
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info(
             "lebron",
@@ -3999,14 +9371,41 @@ mod tests {
             }],
         );
 
-        let _handle_result = execute(deps_for_failure.as_mut(), mock_env(), info, handle_msg);
-        // let error = extract_error_msg(handle_result);
-        // assert!(error.contains("Tried to deposit an unsupported coin uscrt"));
+        let handle_result = execute(deps_for_failure.as_mut(), mock_env(), info, handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Tried to deposit an unsupported coin uscrt"));
+
+        let handle_msg = ExecuteMsg::Deposit {
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info(
+            "lebron",
+            &[
+                Coin {
+                    denom: "uscrt".to_string(),
+                    amount: Uint128::new(1000),
+                },
+                Coin {
+                    denom: "uatom".to_string(),
+                    amount: Uint128::new(1000),
+                },
+            ],
+        );
+
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Tried to deposit an unsupported coin uatom"));
 
         let handle_msg = ExecuteMsg::Deposit {
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
 
         let info = mock_info(
@@ -4023,10 +9422,49 @@ mod tests {
             "handle() failed: {}",
             handle_result.err().unwrap()
         );
-
-        let addr = Addr::unchecked("lebron".to_string());
-        let canonical = deps.api.addr_canonicalize(addr.as_str()).unwrap();
-        assert_eq!(BalancesStore::load(&deps.storage, &canonical), 6000)
+
+        let addr = Addr::unchecked("lebron".to_string());
+        let canonical = deps.api.addr_canonicalize(addr.as_str()).unwrap();
+        assert_eq!(
+            BalancesStore::load(&deps.storage, &canonical).unwrap(),
+            6000
+        );
+
+        // When the total supply caps the credited amount, the deposit tx records what was credited
+        ConfigStore::set_total_supply(&mut deps.storage, &(u128::MAX - 400)).unwrap();
+        let handle_msg = ExecuteMsg::Deposit {
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info(
+            "lebron",
+            &[Coin {
+                denom: "uscrt".to_string(),
+                amount: Uint128::new(1000),
+            }],
+        );
+        let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+        assert_eq!(
+            BalancesStore::load(&deps.storage, &canonical).unwrap(),
+            6400
+        );
+        let (txs, _, _) = StoredExtendedTx::get_txs(
+            &deps.api,
+            &deps.storage,
+            canonical,
+            0,
+            1,
+            false,
+            None,
+            HistoryDirection::Descending,
+            None,
+        )
+        .unwrap();
+        assert_eq!(txs[0].coins.amount, Uint128::new(400));
     }
 
     #[test]
@@ -4063,8 +9501,10 @@ mod tests {
             amount: Uint128::new(100),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("lebron", &[]);
 
@@ -4079,8 +9519,10 @@ mod tests {
             amount: Uint128::new(burn_amount),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("lebron", &[]);
 
@@ -4131,8 +9573,10 @@ mod tests {
             amount: Uint128::new(mint_amount),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("admin", &[]);
 
@@ -4148,8 +9592,10 @@ mod tests {
             amount: Uint128::new(mint_amount),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("admin", &[]);
 
@@ -4165,6 +9611,334 @@ mod tests {
         assert_eq!(new_supply, supply + mint_amount);
     }
 
+    #[test]
+    fn test_max_supply() {
+        let init_config: InitConfig = from_binary(&Binary::from(
+            r#"{ "enable_mint": true, "enable_deposit": true, "max_supply": "6000" }"#.as_bytes(),
+        ))
+        .unwrap();
+        let mut deps = mock_dependencies_with_balance(&[]);
+        let init_msg = InstantiateMsg {
+            name: "sec-sec".to_string(),
+            admin: Some("admin".to_string()),
+            symbol: "SECSEC".to_string(),
+            decimals: 8,
+            initial_balances: Some(vec![InitialBalance {
+                address: "lebron".to_string(),
+                amount: Uint128::new(5000),
+            }]),
+            prng_seed: Binary::from("lolz fun yay".as_bytes()),
+            config: Some(init_config),
+            supported_denoms: Some(vec!["uscrt".to_string()]),
+        };
+        let init_result = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("instantiator", &[]),
+            init_msg,
+        );
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let mint = |amount: u128| ExecuteMsg::Mint {
+            recipient: "lebron".to_string(),
+            amount: Uint128::new(amount),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            mint(1001),
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Minting would exceed the max supply of 6000"));
+
+        // Deposits raise the supply too, so they are capped the same way
+        let deposit = ExecuteMsg::Deposit {
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let info = mock_info(
+            "lebron",
+            &[Coin {
+                denom: "uscrt".to_string(),
+                amount: Uint128::new(1001),
+            }],
+        );
+        let handle_result = execute(deps.as_mut(), mock_env(), info, deposit);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Depositing would exceed the max supply of 6000"));
+
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            mint(1000),
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        assert_eq!(ConfigStore::load_total_supply(&deps.storage).unwrap(), 6000);
+
+        // Only a minter manager may change the cap, and only downwards
+        let set_max_supply = |max_supply: u128| ExecuteMsg::SetMaxSupply {
+            max_supply: Uint128::new(max_supply),
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("lebron", &[]),
+            set_max_supply(6000),
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This command requires the minter_manager role"));
+
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            set_max_supply(7000),
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The max supply can only be lowered"));
+
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            set_max_supply(5999),
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The max supply cannot be below the total supply of 6000"));
+
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            set_max_supply(6000),
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let query_result = query(deps.as_ref(), mock_env(), QueryMsg::TokenInfo {});
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::TokenInfo { max_supply, .. } => {
+                assert_eq!(max_supply, Some(Uint128::new(6000)));
+            }
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn test_minter_quotas() {
+        let (init_result, mut deps) = init_helper_with_config(
+            vec![InitialBalance {
+                address: "lebron".to_string(),
+                amount: Uint128::new(5000),
+            }],
+            false,
+            false,
+            true,
+            false,
+            0,
+            vec![],
+        );
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = ExecuteMsg::AddMinters {
+            minters: vec!["rewards".to_string()],
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            handle_msg,
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let set_quota = ExecuteMsg::SetMinterQuota {
+            minter: "rewards".to_string(),
+            allowance: Some(Uint128::new(1000)),
+            rate: Some(MintRate {
+                amount: Uint128::new(300),
+                period: MintPeriod::Blocks(100),
+            }),
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("rewards", &[]),
+            set_quota.clone(),
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This command requires the minter_manager role"));
+
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            set_quota,
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let mint = |amount: u128| ExecuteMsg::Mint {
+            recipient: "lebron".to_string(),
+            amount: Uint128::new(amount),
+            memo: None,
+            decoys: None,
+            decoy_count: None,
+            entropy: None,
+            padding: None,
+            gas_target: None,
+        };
+        let env_at = |height: u64| {
+            let mut env = mock_env();
+            env.block.height = height;
+            env
+        };
+
+        // mock_env is at height 12345, so the current period is 12300..12400
+        let handle_result = execute(
+            deps.as_mut(),
+            env_at(12345),
+            mock_info("rewards", &[]),
+            mint(300),
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_result = execute(
+            deps.as_mut(),
+            env_at(12399),
+            mock_info("rewards", &[]),
+            mint(1),
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("would exceed the minter's rate limit, 0 is left"));
+
+        let query_result = query(deps.as_ref(), env_at(12399), QueryMsg::Minters {});
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::Minters { minters, quotas } => {
+                assert_eq!(
+                    minters,
+                    vec![Addr::unchecked("admin"), Addr::unchecked("rewards")]
+                );
+                assert_eq!(quotas[0].remaining, None);
+                assert_eq!(quotas[0].remaining_in_period, None);
+                assert_eq!(
+                    quotas[1],
+                    MinterQuotaInfo {
+                        minter: Addr::unchecked("rewards"),
+                        allowance: Some(Uint128::new(1000)),
+                        minted: Uint128::new(300),
+                        remaining: Some(Uint128::new(700)),
+                        rate: Some(MintRate {
+                            amount: Uint128::new(300),
+                            period: MintPeriod::Blocks(100),
+                        }),
+                        remaining_in_period: Some(Uint128::new(0)),
+                        next_period_at: Some(12400),
+                    }
+                );
+            }
+            _ => panic!("unexpected"),
+        }
+
+        let handle_result = execute(
+            deps.as_mut(),
+            env_at(12400),
+            mock_info("rewards", &[]),
+            mint(300),
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_result = execute(
+            deps.as_mut(),
+            env_at(12500),
+            mock_info("rewards", &[]),
+            mint(300),
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_result = execute(
+            deps.as_mut(),
+            env_at(12600),
+            mock_info("rewards", &[]),
+            mint(101),
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("would exceed the minter's remaining allowance of 100"));
+        let handle_result = execute(
+            deps.as_mut(),
+            env_at(12600),
+            mock_info("rewards", &[]),
+            mint(100),
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+
+        // Minters without a quota are not limited
+        let handle_result = execute(
+            deps.as_mut(),
+            env_at(12600),
+            mock_info("admin", &[]),
+            mint(5000),
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+
+        // Removing a minter keeps its quota, so adding it back does not lift its limits
+        let handle_msg = ExecuteMsg::RemoveMinters {
+            minters: vec!["rewards".to_string()],
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            handle_msg,
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        let quota = MintersStore::load_quota(&deps.storage, &Addr::unchecked("rewards")).unwrap();
+        assert_eq!(quota.minted, 1000);
+
+        let handle_msg = ExecuteMsg::AddMinters {
+            minters: vec!["rewards".to_string()],
+            padding: None,
+            gas_target: None,
+        };
+        let handle_result = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            handle_msg,
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_result = execute(
+            deps.as_mut(),
+            env_at(12700),
+            mock_info("rewards", &[]),
+            mint(1),
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("would exceed the minter's remaining allowance of 0"));
+    }
+
     #[test]
     fn test_handle_admin_commands() {
         let admin_err = "Admin commands can only be run from admin address".to_string();
@@ -4189,50 +9963,55 @@ mod tests {
         let pause_msg = ExecuteMsg::SetContractStatus {
             level: ContractStatusLevel::StopAllButRedeems,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("not_admin", &[]);
 
         let handle_result = execute(deps.as_mut(), mock_env(), info, pause_msg);
 
         let error = extract_error_msg(handle_result);
-        assert!(error.contains(&admin_err.clone()));
+        assert!(error.contains("This command requires the pauser role"));
 
         let mint_msg = ExecuteMsg::AddMinters {
             minters: vec!["not_admin".to_string()],
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("not_admin", &[]);
 
         let handle_result = execute(deps.as_mut(), mock_env(), info, mint_msg);
 
         let error = extract_error_msg(handle_result);
-        assert!(error.contains(&admin_err.clone()));
+        assert!(error.contains("This command requires the minter_manager role"));
 
         let mint_msg = ExecuteMsg::RemoveMinters {
             minters: vec!["admin".to_string()],
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("not_admin", &[]);
 
         let handle_result = execute(deps.as_mut(), mock_env(), info, mint_msg);
 
         let error = extract_error_msg(handle_result);
-        assert!(error.contains(&admin_err.clone()));
+        assert!(error.contains("This command requires the minter_manager role"));
 
         let mint_msg = ExecuteMsg::SetMinters {
             minters: vec!["not_admin".to_string()],
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("not_admin", &[]);
 
         let handle_result = execute(deps.as_mut(), mock_env(), info, mint_msg);
 
         let error = extract_error_msg(handle_result);
-        assert!(error.contains(&admin_err.clone()));
+        assert!(error.contains("This command requires the minter_manager role"));
 
         let change_admin_msg = ExecuteMsg::ChangeAdmin {
             address: "not_admin".to_string(),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("not_admin", &[]);
 
@@ -4265,6 +10044,7 @@ mod tests {
         let pause_msg = ExecuteMsg::SetContractStatus {
             level: ContractStatusLevel::StopAllButRedeems,
             padding: None,
+            gas_target: None,
         };
 
         let info = mock_info("admin", &[]);
@@ -4282,8 +10062,10 @@ mod tests {
             amount: Uint128::new(123),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("admin", &[]);
 
@@ -4299,8 +10081,10 @@ mod tests {
             amount: Uint128::new(5000),
             denom: Option::from("uscrt".to_string()),
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("lebron", &[]);
 
@@ -4328,6 +10112,7 @@ mod tests {
         let pause_msg = ExecuteMsg::SetContractStatus {
             level: ContractStatusLevel::StopAll,
             padding: None,
+            gas_target: None,
         };
 
         let info = mock_info("admin", &[]);
@@ -4345,8 +10130,10 @@ mod tests {
             amount: Uint128::new(123),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("admin", &[]);
 
@@ -4362,8 +10149,10 @@ mod tests {
             amount: Uint128::new(5000),
             denom: Option::from("uscrt".to_string()),
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("lebron", &[]);
 
@@ -4408,6 +10197,7 @@ mod tests {
         let handle_msg = ExecuteMsg::SetMinters {
             minters: vec!["bob".to_string()],
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("admin", &[]);
 
@@ -4419,17 +10209,19 @@ mod tests {
         let handle_msg = ExecuteMsg::SetMinters {
             minters: vec!["bob".to_string()],
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
         let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
 
         let error = extract_error_msg(handle_result);
-        assert!(error.contains("Admin commands can only be run from admin address"));
+        assert!(error.contains("This command requires the minter_manager role"));
 
         let handle_msg = ExecuteMsg::SetMinters {
             minters: vec!["bob".to_string()],
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("admin", &[]);
 
@@ -4442,8 +10234,10 @@ mod tests {
             amount: Uint128::new(100),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -4456,8 +10250,10 @@ mod tests {
             amount: Uint128::new(100),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("admin", &[]);
 
@@ -4499,6 +10295,7 @@ mod tests {
         let handle_msg = ExecuteMsg::AddMinters {
             minters: vec!["bob".to_string()],
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("admin", &[]);
 
@@ -4510,17 +10307,19 @@ mod tests {
         let handle_msg = ExecuteMsg::AddMinters {
             minters: vec!["bob".to_string()],
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
         let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
 
         let error = extract_error_msg(handle_result);
-        assert!(error.contains("Admin commands can only be run from admin address"));
+        assert!(error.contains("This command requires the minter_manager role"));
 
         let handle_msg = ExecuteMsg::AddMinters {
             minters: vec!["bob".to_string()],
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("admin", &[]);
 
@@ -4533,8 +10332,10 @@ mod tests {
             amount: Uint128::new(100),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -4547,8 +10348,10 @@ mod tests {
             amount: Uint128::new(100),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("admin", &[]);
 
@@ -4589,6 +10392,7 @@ mod tests {
         let handle_msg = ExecuteMsg::RemoveMinters {
             minters: vec!["bob".to_string()],
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("admin", &[]);
 
@@ -4600,17 +10404,19 @@ mod tests {
         let handle_msg = ExecuteMsg::RemoveMinters {
             minters: vec!["admin".to_string()],
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
         let handle_result = execute(deps.as_mut(), mock_env(), info, handle_msg);
 
         let error = extract_error_msg(handle_result);
-        assert!(error.contains("Admin commands can only be run from admin address"));
+        assert!(error.contains("This command requires the minter_manager role"));
 
         let handle_msg = ExecuteMsg::RemoveMinters {
             minters: vec!["admin".to_string()],
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("admin", &[]);
 
@@ -4623,8 +10429,10 @@ mod tests {
             amount: Uint128::new(100),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -4638,8 +10446,10 @@ mod tests {
             amount: Uint128::new(100),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("admin", &[]);
 
@@ -4652,6 +10462,7 @@ mod tests {
         let handle_msg = ExecuteMsg::RemoveMinters {
             minters: vec!["admin".to_string()],
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("admin", &[]);
 
@@ -4664,8 +10475,10 @@ mod tests {
             amount: Uint128::new(100),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -4679,8 +10492,10 @@ mod tests {
             amount: Uint128::new(100),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("admin", &[]);
 
@@ -4718,6 +10533,7 @@ mod tests {
         let create_vk_msg = ExecuteMsg::CreateViewingKey {
             entropy: "34".to_string(),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("giannis", &[]);
         let handle_response = execute(deps.as_mut(), mock_env(), info, create_vk_msg).unwrap();
@@ -4799,11 +10615,13 @@ mod tests {
                 symbol,
                 decimals,
                 total_supply,
+                max_supply,
             } => {
                 assert_eq!(name, init_name);
                 assert_eq!(symbol, init_symbol);
                 assert_eq!(decimals, init_decimals);
                 assert_eq!(total_supply, Some(Uint128::new(5000)));
+                assert_eq!(max_supply, None);
             }
             _ => panic!("unexpected"),
         }
@@ -4869,6 +10687,9 @@ mod tests {
                 mint_enabled,
                 burn_enabled,
                 supported_denoms,
+                max_decoys,
+                gas_evaporation,
+                max_supply,
             } => {
                 assert_eq!(public_total_supply, true);
                 assert_eq!(deposit_enabled, false);
@@ -4876,6 +10697,9 @@ mod tests {
                 assert_eq!(mint_enabled, true);
                 assert_eq!(burn_enabled, false);
                 assert_eq!(supported_denoms.len(), 0);
+                assert_eq!(max_decoys, MAX_DECOYS);
+                assert!(gas_evaporation);
+                assert_eq!(max_supply, None);
             }
             _ => panic!("unexpected"),
         }
@@ -5124,6 +10948,7 @@ mod tests {
             spender: "lebron".to_string(),
             amount: Uint128::new(2000),
             padding: None,
+            gas_target: None,
             expiration: None,
         };
         let info = mock_info("giannis", &[]);
@@ -5156,6 +10981,7 @@ mod tests {
         let handle_msg = ExecuteMsg::SetViewingKey {
             key: vk1.clone(),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("lebron", &[]);
 
@@ -5174,6 +11000,7 @@ mod tests {
         let handle_msg = ExecuteMsg::SetViewingKey {
             key: vk2.clone(),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("giannis", &[]);
 
@@ -5249,6 +11076,7 @@ mod tests {
             let handle_msg = ExecuteMsg::SetViewingKey {
                 key: vk.clone(),
                 padding: None,
+                gas_target: None,
             };
             let info = mock_info(format!("owner{}", i).as_str(), &[]);
 
@@ -5271,6 +11099,7 @@ mod tests {
                     spender: format!("spender{}", j),
                     amount: Uint128::new(50),
                     padding: None,
+                    gas_target: None,
                     expiration: None,
                 };
                 let info = mock_info(format!("owner{}", i).as_str(), &[]);
@@ -5285,6 +11114,7 @@ mod tests {
                 let handle_msg = ExecuteMsg::SetViewingKey {
                     key: vk.clone(),
                     padding: None,
+                    gas_target: None,
                 };
                 let info = mock_info(format!("spender{}", j).as_str(), &[]);
 
@@ -5470,6 +11300,7 @@ mod tests {
         let handle_msg = ExecuteMsg::SetViewingKey {
             key: "key".to_string(),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -5507,6 +11338,8 @@ mod tests {
 
     #[test]
     fn test_query_transfer_history() {
+        use crate::transaction_history::TxActionKind;
+
         let (init_result, mut deps) = init_helper(vec![InitialBalance {
             address: "bob".to_string(),
             amount: Uint128::new(5000),
@@ -5520,6 +11353,7 @@ mod tests {
         let handle_msg = ExecuteMsg::SetViewingKey {
             key: "key".to_string(),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -5532,8 +11366,10 @@ mod tests {
             amount: Uint128::new(1000),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -5546,8 +11382,10 @@ mod tests {
             amount: Uint128::new(500),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -5560,8 +11398,10 @@ mod tests {
             amount: Uint128::new(2500),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -5576,15 +11416,73 @@ mod tests {
             page: None,
             page_size: 0,
             should_filter_decoys: Some(false),
+            start_after_id: None,
+            direction: None,
+            filter: None,
         };
         let query_result = query(deps.as_ref(), mock_env(), query_msg);
         // let a: QueryAnswer = from_binary(&query_result.unwrap()).unwrap();
         // println!("{:?}", a);
+        let (transfers, total) = match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::TransferHistory { txs, total, .. } => (txs, total),
+            _ => panic!("Unexpected"),
+        };
+        assert!(transfers.is_empty());
+        // An empty page still counts every transfer
+        assert_eq!(total, Some(3));
+
+        // Filters apply to transfers as they do to txs
+        let query_msg = QueryMsg::TransferHistory {
+            address: "bob".to_string(),
+            key: "key".to_string(),
+            page: None,
+            page_size: 0,
+            should_filter_decoys: Some(false),
+            start_after_id: None,
+            direction: None,
+            filter: Some(TxFilter {
+                actions: None,
+                min_block_height: None,
+                max_block_height: None,
+                min_block_time: None,
+                max_block_time: None,
+                counterparty: Some("banana".to_string()),
+                has_memo: None,
+            }),
+        };
+        let query_result = query(deps.as_ref(), mock_env(), query_msg);
+        let total = match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::TransferHistory { total, .. } => total,
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(total, Some(1));
+
+        let query_msg = QueryMsg::TransferHistory {
+            address: "bob".to_string(),
+            key: "key".to_string(),
+            page: None,
+            page_size: 10,
+            should_filter_decoys: Some(false),
+            start_after_id: None,
+            direction: None,
+            filter: Some(TxFilter {
+                actions: Some(vec![TxActionKind::Transfer]),
+                min_block_height: None,
+                max_block_height: None,
+                min_block_time: None,
+                max_block_time: None,
+                counterparty: Some("mango".to_string()),
+                has_memo: Some(false),
+            }),
+        };
+        let query_result = query(deps.as_ref(), mock_env(), query_msg);
         let transfers = match from_binary(&query_result.unwrap()).unwrap() {
             QueryAnswer::TransferHistory { txs, .. } => txs,
             _ => panic!("Unexpected"),
         };
-        assert!(transfers.is_empty());
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].receiver, Addr::unchecked("mango"));
+        assert_eq!(transfers[0].coins.amount, Uint128::new(2500));
 
         let query_msg = QueryMsg::TransferHistory {
             address: "bob".to_string(),
@@ -5592,6 +11490,9 @@ mod tests {
             page: None,
             page_size: 10,
             should_filter_decoys: Some(false),
+            start_after_id: None,
+            direction: None,
+            filter: None,
         };
         let query_result = query(deps.as_ref(), mock_env(), query_msg);
         let transfers = match from_binary(&query_result.unwrap()).unwrap() {
@@ -5606,13 +11507,41 @@ mod tests {
             page: None,
             page_size: 2,
             should_filter_decoys: Some(false),
+            start_after_id: None,
+            direction: None,
+            filter: None,
         };
         let query_result = query(deps.as_ref(), mock_env(), query_msg);
-        let transfers = match from_binary(&query_result.unwrap()).unwrap() {
-            QueryAnswer::TransferHistory { txs, .. } => txs,
+        let (transfers, total, next_cursor) = match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::TransferHistory {
+                txs,
+                total,
+                next_cursor,
+                ..
+            } => (txs, total, next_cursor),
             _ => panic!("Unexpected"),
         };
         assert_eq!(transfers.len(), 2);
+        // The total is reported even though the page stops short of the end
+        assert_eq!(total, Some(3));
+
+        let query_msg = QueryMsg::TransferHistory {
+            address: "bob".to_string(),
+            key: "key".to_string(),
+            page: None,
+            page_size: 1,
+            should_filter_decoys: Some(false),
+            start_after_id: next_cursor,
+            direction: None,
+            filter: None,
+        };
+        let query_result = query(deps.as_ref(), mock_env(), query_msg);
+        let (transfers, total) = match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::TransferHistory { txs, total, .. } => (txs, total),
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(total, Some(3));
 
         let query_msg = QueryMsg::TransferHistory {
             address: "bob".to_string(),
@@ -5620,6 +11549,9 @@ mod tests {
             page: Some(1),
             page_size: 2,
             should_filter_decoys: Some(false),
+            start_after_id: None,
+            direction: None,
+            filter: None,
         };
         let query_result = query(deps.as_ref(), mock_env(), query_msg);
         let transfers = match from_binary(&query_result.unwrap()).unwrap() {
@@ -5650,6 +11582,7 @@ mod tests {
         let handle_msg = ExecuteMsg::SetViewingKey {
             key: "key".to_string(),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -5659,6 +11592,7 @@ mod tests {
         let handle_msg = ExecuteMsg::SetViewingKey {
             key: "alice_key".to_string(),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("alice", &[]);
 
@@ -5668,6 +11602,7 @@ mod tests {
         let handle_msg = ExecuteMsg::SetViewingKey {
             key: "lior_key".to_string(),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("lior", &[]);
 
@@ -5677,6 +11612,7 @@ mod tests {
         let handle_msg = ExecuteMsg::SetViewingKey {
             key: "banana_key".to_string(),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("banana", &[]);
 
@@ -5698,8 +11634,10 @@ mod tests {
                 alice_addr.clone(),
             ]),
 
+            decoy_count: None,
             entropy: Some(Binary::from_base64("VEVTVFRFU1RURVNUQ0hFQ0tDSEVDSw==").unwrap()),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -5712,8 +11650,10 @@ mod tests {
             amount: Uint128::new(500),
             memo: None,
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -5728,6 +11668,9 @@ mod tests {
             page: None,
             page_size: 10,
             should_filter_decoys: Some(true),
+            start_after_id: None,
+            direction: None,
+            filter: None,
         };
         let query_result = query(deps.as_ref(), mock_env(), query_msg);
         let transfers = match from_binary(&query_result.unwrap()).unwrap() {
@@ -5742,6 +11685,9 @@ mod tests {
             page: None,
             page_size: 10,
             should_filter_decoys: Some(false),
+            start_after_id: None,
+            direction: None,
+            filter: None,
         };
         let query_result = query(deps.as_ref(), mock_env(), query_msg);
         let transfers = match from_binary(&query_result.unwrap()).unwrap() {
@@ -5756,6 +11702,9 @@ mod tests {
             page: None,
             page_size: 10,
             should_filter_decoys: Some(true),
+            start_after_id: None,
+            direction: None,
+            filter: None,
         };
         let query_result = query(deps.as_ref(), mock_env(), query_msg);
         let transfers = match from_binary(&query_result.unwrap()).unwrap() {
@@ -5770,6 +11719,9 @@ mod tests {
             page: None,
             page_size: 10,
             should_filter_decoys: Some(true),
+            start_after_id: None,
+            direction: None,
+            filter: None,
         };
         let query_result = query(deps.as_ref(), mock_env(), query_msg);
         let transfers = match from_binary(&query_result.unwrap()).unwrap() {
@@ -5784,6 +11736,9 @@ mod tests {
             page: None,
             page_size: 10,
             should_filter_decoys: Some(true),
+            start_after_id: None,
+            direction: None,
+            filter: None,
         };
         let query_result = query(deps.as_ref(), mock_env(), query_msg);
         let transfers = match from_binary(&query_result.unwrap()).unwrap() {
@@ -5860,6 +11815,7 @@ mod tests {
         let handle_msg = ExecuteMsg::SetViewingKey {
             key: "key".to_string(),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -5871,8 +11827,10 @@ mod tests {
             amount: Uint128::new(1),
             memo: Some("my burn message".to_string()),
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -5888,8 +11846,10 @@ mod tests {
             amount: Uint128::new(1000),
             denom: Option::from("uscrt".to_string()),
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -5906,8 +11866,10 @@ mod tests {
             amount: Uint128::new(100),
             memo: Some("my mint message".to_string()),
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("admin", &[]);
 
@@ -5917,8 +11879,10 @@ mod tests {
 
         let handle_msg = ExecuteMsg::Deposit {
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info(
             "bob",
@@ -5940,8 +11904,10 @@ mod tests {
             amount: Uint128::new(1000),
             memo: Some("my transfer message #1".to_string()),
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -5955,8 +11921,10 @@ mod tests {
             amount: Uint128::new(500),
             memo: Some("my transfer message #2".to_string()),
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -5970,8 +11938,10 @@ mod tests {
             amount: Uint128::new(2500),
             memo: Some("my transfer message #3".to_string()),
             decoys: None,
+            decoy_count: None,
             entropy: None,
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -5986,6 +11956,9 @@ mod tests {
             page: None,
             page_size: 10,
             should_filter_decoys: Some(false),
+            start_after_id: None,
+            direction: None,
+            filter: None,
         };
         let query_result = query(deps.as_ref(), mock_env(), query_msg);
         let transfers = match from_binary(&query_result.unwrap()).unwrap() {
@@ -6000,6 +11973,9 @@ mod tests {
             page: None,
             page_size: 10,
             should_filter_decoys: Some(false),
+            start_after_id: None,
+            direction: None,
+            filter: None,
         };
         let query_result = query(deps.as_ref(), mock_env(), query_msg);
         let transfers = match from_binary(&query_result.unwrap()).unwrap() {
@@ -6023,6 +11999,7 @@ mod tests {
                 memo: Some("my transfer message #3".to_string()),
                 block_time: 1571797419,
                 block_height: 12345,
+                label: None,
             },
             ExtendedTx {
                 id: 7,
@@ -6038,6 +12015,7 @@ mod tests {
                 memo: Some("my transfer message #2".to_string()),
                 block_time: 1571797419,
                 block_height: 12345,
+                label: None,
             },
             ExtendedTx {
                 id: 6,
@@ -6053,6 +12031,7 @@ mod tests {
                 memo: Some("my transfer message #1".to_string()),
                 block_time: 1571797419,
                 block_height: 12345,
+                label: None,
             },
             ExtendedTx {
                 id: 5,
@@ -6064,6 +12043,7 @@ mod tests {
                 memo: None,
                 block_time: 1571797419,
                 block_height: 12345,
+                label: None,
             },
             ExtendedTx {
                 id: 4,
@@ -6078,6 +12058,7 @@ mod tests {
                 memo: Some("my mint message".to_string()),
                 block_time: 1571797419,
                 block_height: 12345,
+                label: None,
             },
             ExtendedTx {
                 id: 3,
@@ -6089,6 +12070,7 @@ mod tests {
                 memo: None,
                 block_time: 1571797419,
                 block_height: 12345,
+                label: None,
             },
             ExtendedTx {
                 id: 2,
@@ -6103,6 +12085,7 @@ mod tests {
                 memo: Some("my burn message".to_string()),
                 block_time: 1571797419,
                 block_height: 12345,
+                label: None,
             },
             ExtendedTx {
                 id: 1,
@@ -6118,6 +12101,7 @@ mod tests {
                 memo: Some("Initial Balance".to_string()),
                 block_time: 1571797419,
                 block_height: 12345,
+                label: None,
             },
         ];
 
@@ -6154,6 +12138,7 @@ mod tests {
         let handle_msg = ExecuteMsg::SetViewingKey {
             key: "key".to_string(),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -6163,6 +12148,7 @@ mod tests {
         let handle_msg = ExecuteMsg::SetViewingKey {
             key: "alice_key".to_string(),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("alice", &[]);
 
@@ -6172,6 +12158,7 @@ mod tests {
         let handle_msg = ExecuteMsg::SetViewingKey {
             key: "lior_key".to_string(),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("lior", &[]);
 
@@ -6181,6 +12168,7 @@ mod tests {
         let handle_msg = ExecuteMsg::SetViewingKey {
             key: "jhon_key".to_string(),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("jhon", &[]);
 
@@ -6200,8 +12188,10 @@ mod tests {
                 jhon_addr.clone(),
                 alice_addr.clone(),
             ]),
+            decoy_count: None,
             entropy: Some(Binary::from_base64("VEVTVFRFU1RURVNUQ0hFQ0tDSEVDSw==").unwrap()),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -6221,8 +12211,10 @@ mod tests {
                 jhon_addr.clone(),
                 alice_addr.clone(),
             ]),
+            decoy_count: None,
             entropy: Some(Binary::from_base64("VEVTVFRFU1RURVNUQ0hFQ0tDSEVDSw==").unwrap()),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -6243,8 +12235,10 @@ mod tests {
                 jhon_addr.clone(),
                 alice_addr.clone(),
             ]),
+            decoy_count: None,
             entropy: Some(Binary::from_base64("VEVTVFRFU1RURVNUQ0hFQ0tDSEVDSw==").unwrap()),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("admin", &[]);
 
@@ -6258,8 +12252,10 @@ mod tests {
                 jhon_addr.clone(),
                 alice_addr.clone(),
             ]),
+            decoy_count: None,
             entropy: Some(Binary::from_base64("VEVTVFRFU1RURVNUQ0hFQ0tDSEVDSw==").unwrap()),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info(
             "bob",
@@ -6285,8 +12281,10 @@ mod tests {
                 jhon_addr.clone(),
                 alice_addr.clone(),
             ]),
+            decoy_count: None,
             entropy: Some(Binary::from_base64("VEVTVFRFU1RURVNUQ0hFQ0tDSEVDSw==").unwrap()),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -6304,8 +12302,10 @@ mod tests {
                 jhon_addr.clone(),
                 alice_addr.clone(),
             ]),
+            decoy_count: None,
             entropy: Some(Binary::from_base64("VEVTVFRFU1RURVNUQ0hFQ0tDSEVDSw==").unwrap()),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -6323,8 +12323,10 @@ mod tests {
                 jhon_addr.clone(),
                 alice_addr.clone(),
             ]),
+            decoy_count: None,
             entropy: Some(Binary::from_base64("VEVTVFRFU1RURVNUQ0hFQ0tDSEVDSw==").unwrap()),
             padding: None,
+            gas_target: None,
         };
         let info = mock_info("bob", &[]);
 
@@ -6339,6 +12341,9 @@ mod tests {
             page: None,
             page_size: 10,
             should_filter_decoys: Some(true),
+            start_after_id: None,
+            direction: None,
+            filter: None,
         };
         let query_result = query(deps.as_ref(), mock_env(), query_msg);
         let transactions = match from_binary(&query_result.unwrap()).unwrap() {
@@ -6354,6 +12359,9 @@ mod tests {
             page: None,
             page_size: 10,
             should_filter_decoys: Some(false),
+            start_after_id: None,
+            direction: None,
+            filter: None,
         };
         let query_result = query(deps.as_ref(), mock_env(), query_msg);
         let transactions = match from_binary(&query_result.unwrap()).unwrap() {
@@ -6369,6 +12377,9 @@ mod tests {
             page: None,
             page_size: 10,
             should_filter_decoys: Some(true),
+            start_after_id: None,
+            direction: None,
+            filter: None,
         };
         let query_result = query(deps.as_ref(), mock_env(), query_msg);
         let transactions = match from_binary(&query_result.unwrap()).unwrap() {
@@ -6384,6 +12395,9 @@ mod tests {
             page: None,
             page_size: 10,
             should_filter_decoys: Some(true),
+            start_after_id: None,
+            direction: None,
+            filter: None,
         };
         let query_result = query(deps.as_ref(), mock_env(), query_msg);
         let transactions = match from_binary(&query_result.unwrap()).unwrap() {
@@ -6399,6 +12413,9 @@ mod tests {
             page: None,
             page_size: 10,
             should_filter_decoys: Some(true),
+            start_after_id: None,
+            direction: None,
+            filter: None,
         };
         let query_result = query(deps.as_ref(), mock_env(), query_msg);
         let transactions = match from_binary(&query_result.unwrap()).unwrap() {
@@ -6422,6 +12439,7 @@ mod tests {
                 memo: Some("my transfer message #3".to_string()),
                 block_time: 1571797419,
                 block_height: 12345,
+                label: None,
             },
             ExtendedTx {
                 id: 8,
@@ -6437,6 +12455,7 @@ mod tests {
                 memo: Some("my transfer message #2".to_string()),
                 block_time: 1571797419,
                 block_height: 12345,
+                label: None,
             },
             ExtendedTx {
                 id: 7,
@@ -6452,6 +12471,7 @@ mod tests {
                 memo: Some("my transfer message #1".to_string()),
                 block_time: 1571797419,
                 block_height: 12345,
+                label: None,
             },
             ExtendedTx {
                 id: 6,
@@ -6463,6 +12483,7 @@ mod tests {
                 memo: None,
                 block_time: 1571797419,
                 block_height: 12345,
+                label: None,
             },
             ExtendedTx {
                 id: 5,
@@ -6477,6 +12498,7 @@ mod tests {
                 memo: Some("my mint message".to_string()),
                 block_time: 1571797419,
                 block_height: 12345,
+                label: None,
             },
             ExtendedTx {
                 id: 4,
@@ -6488,6 +12510,7 @@ mod tests {
                 memo: None,
                 block_time: 1571797419,
                 block_height: 12345,
+                label: None,
             },
             ExtendedTx {
                 id: 3,
@@ -6502,6 +12525,7 @@ mod tests {
                 memo: Some("my burn message".to_string()),
                 block_time: 1571797419,
                 block_height: 12345,
+                label: None,
             },
             ExtendedTx {
                 id: 1,
@@ -6517,6 +12541,7 @@ mod tests {
                 memo: Some("Initial Balance".to_string()),
                 block_time: 1571797419,
                 block_height: 12345,
+                label: None,
             },
         ];
 
@@ -0,0 +1,347 @@
+//! Delayed write buffer (DWB) for incoming balance changes.
+//!
+//! Credits are not written to the recipient's balance right away. They accumulate in a single
+//! fixed-size buffer that is rewritten in full on every update, together with the recipient's
+//! new transactions. An entry settles into the balance trie and the account's history when the
+//! account spends, or when the buffer is full and its slot is picked at random to make room.
+//! Storage writes from a credit therefore no longer point at the recipient.
+
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{CanonicalAddr, StdError, StdResult, Storage};
+use secret_toolkit::crypto::ContractPrng;
+use secret_toolkit::storage::{Item, Keymap};
+
+use crate::amber::OneAmberStore;
+use crate::state::{safe_add, BalancesStore, CheckpointsStore};
+use crate::transaction_history::{append_settled_tx, StoredExtendedTx, StoredLegacyTransfer};
+
+/// The number of accounts that can have a pending balance at the same time.
+pub const DWB_LEN: usize = 64;
+/// The most transactions an entry holds. Its entry settles once it has this many, so the count
+/// cannot overflow and settling never has to walk an unbounded list.
+pub const DWB_MAX_TX_NODES: u16 = 256;
+
+pub static DWB: Item<DelayedWriteBuffer> = Item::new(b"dwb");
+/// Transactions waiting in the buffer for their recipient to settle, keyed by node id.
+pub static TX_NODES: Keymap<u64, TxNode> = Keymap::new(b"tx_nodes");
+pub static TX_NODE_COUNT: Item<u64> = Item::new(b"tx_node_count");
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TxNode {
+    pub tx: StoredExtendedTx,
+    /// The recipient's OAC tier right after the tx, recorded in its checkpoint when it settles
+    pub tier: Option<u8>,
+    /// The next (older) node in the list, or 0 at the end
+    pub next: u64,
+}
+
+/// A node as stored by schemas 4 to 9, before it kept the recipient's tier.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct UntieredTxNode {
+    tx: StoredExtendedTx,
+    next: u64,
+}
+
+/// The same nodes as `TX_NODES`, read in their format from schemas 4 to 9.
+static UNTIERED_TX_NODES: Keymap<u64, UntieredTxNode> = Keymap::new(b"tx_nodes");
+
+/// A node as stored before schema 4, when transfers were also kept in a history of their own.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct LegacyTxNode {
+    tx: StoredExtendedTx,
+    transfer: Option<StoredLegacyTransfer>,
+    next: u64,
+}
+
+/// The same nodes as `TX_NODES`, read in their format from before schema 4.
+static LEGACY_TX_NODES: Keymap<u64, LegacyTxNode> = Keymap::new(b"tx_nodes");
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DelayedWriteBufferEntry {
+    /// Empty for an unused slot
+    pub recipient: CanonicalAddr,
+    pub amount: u128,
+    /// The newest pending transaction for the recipient, or 0 if there are none
+    pub head_node: u64,
+    pub list_len: u16,
+}
+
+impl DelayedWriteBufferEntry {
+    fn is_empty(&self) -> bool {
+        self.recipient.as_slice().is_empty()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DelayedWriteBuffer {
+    pub entries: Vec<DelayedWriteBufferEntry>,
+}
+
+impl Default for DelayedWriteBuffer {
+    fn default() -> Self {
+        Self {
+            entries: vec![DelayedWriteBufferEntry::default(); DWB_LEN],
+        }
+    }
+}
+
+/// Create an empty buffer, unless one already exists.
+pub fn initialize_dwb(storage: &mut dyn Storage) -> StdResult<()> {
+    if DWB.may_load(storage)?.is_some() {
+        return Ok(());
+    }
+
+    DWB.save(storage, &DelayedWriteBuffer::default())
+}
+
+impl DelayedWriteBuffer {
+    pub fn load(storage: &dyn Storage) -> StdResult<Self> {
+        DWB.load(storage)
+    }
+
+    pub fn save(&self, storage: &mut dyn Storage) -> StdResult<()> {
+        DWB.save(storage, self)
+    }
+
+    fn position(&self, address: &CanonicalAddr) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|entry| !entry.is_empty() && &entry.recipient == address)
+    }
+
+    /// The amount credited to the account that has not settled yet.
+    pub fn pending_amount(&self, address: &CanonicalAddr) -> u128 {
+        self.position(address)
+            .map_or(0, |pos| self.entries[pos].amount)
+    }
+
+    /// Settle the account's entry, if it has one, so its whole balance is in the trie.
+    pub fn settle_account(
+        &mut self,
+        storage: &mut dyn Storage,
+        address: &CanonicalAddr,
+    ) -> StdResult<()> {
+        if let Some(pos) = self.position(address) {
+            let entry = std::mem::take(&mut self.entries[pos]);
+            settle_entry(storage, entry)?;
+        }
+
+        Ok(())
+    }
+
+    /// Add `amount` to the account's pending balance, giving it a slot if it has none.
+    /// When the buffer is full, a random entry is settled to make room.
+    pub fn add_recipient(
+        &mut self,
+        storage: &mut dyn Storage,
+        rng: &mut ContractPrng,
+        address: &CanonicalAddr,
+        amount: u128,
+    ) -> StdResult<()> {
+        let pos = match self.position(address) {
+            Some(pos) => pos,
+            None => {
+                let pos = match self.entries.iter().position(|entry| entry.is_empty()) {
+                    Some(pos) => pos,
+                    None => {
+                        let bytes = rng.rand_bytes();
+                        let pos =
+                            u64::from_be_bytes(bytes[..8].try_into().unwrap()) as usize % DWB_LEN;
+                        let entry = std::mem::take(&mut self.entries[pos]);
+                        settle_entry(storage, entry)?;
+                        pos
+                    }
+                };
+                self.entries[pos].recipient = address.clone();
+                pos
+            }
+        };
+
+        safe_add(&mut self.entries[pos].amount, amount);
+        Ok(())
+    }
+
+    /// Queue a transaction for the account, along with the tier it is in after the tx.
+    /// Returns `false` without doing anything if the account has no entry. An entry that reaches
+    /// `DWB_MAX_TX_NODES` transactions settles right away.
+    pub fn push_tx(
+        &mut self,
+        storage: &mut dyn Storage,
+        address: &CanonicalAddr,
+        tx: &StoredExtendedTx,
+    ) -> StdResult<bool> {
+        let pos = match self.position(address) {
+            Some(pos) => pos,
+            None => return Ok(false),
+        };
+        let entry = &mut self.entries[pos];
+
+        let node_id = TX_NODE_COUNT.may_load(storage)?.unwrap_or_default() + 1;
+        TX_NODES.insert(
+            storage,
+            &node_id,
+            &TxNode {
+                tx: tx.clone(),
+                tier: OneAmberStore::get_tier(storage, address),
+                next: entry.head_node,
+            },
+        )?;
+        TX_NODE_COUNT.save(storage, &node_id)?;
+
+        entry.head_node = node_id;
+        entry.list_len += 1;
+        if entry.list_len >= DWB_MAX_TX_NODES {
+            let entry = std::mem::take(entry);
+            settle_entry(storage, entry)?;
+        }
+
+        Ok(true)
+    }
+
+    /// The account's transactions that have not settled yet, newest first.
+    pub fn pending_txs(
+        &self,
+        storage: &dyn Storage,
+        address: &CanonicalAddr,
+    ) -> StdResult<Vec<TxNode>> {
+        let mut nodes = vec![];
+        if let Some(pos) = self.position(address) {
+            let mut node_id = self.entries[pos].head_node;
+            while node_id != 0 {
+                let node = load_node(storage, node_id)?;
+                node_id = node.next;
+                nodes.push(node);
+            }
+        }
+
+        Ok(nodes)
+    }
+}
+
+fn load_node(storage: &dyn Storage, node_id: u64) -> StdResult<TxNode> {
+    TX_NODES
+        .get(storage, &node_id)
+        .ok_or_else(|| StdError::generic_err("Pending transaction not found"))
+}
+
+fn settle_balance(storage: &mut dyn Storage, entry: &DelayedWriteBufferEntry) -> StdResult<()> {
+    let mut balance = BalancesStore::load_settled(storage, &entry.recipient)?;
+    safe_add(&mut balance, entry.amount);
+    BalancesStore::save_settled(storage, &entry.recipient, balance)
+}
+
+/// Move an entry's amount into the balance trie and its transactions into the account's history.
+fn settle_entry(storage: &mut dyn Storage, entry: DelayedWriteBufferEntry) -> StdResult<()> {
+    if entry.is_empty() {
+        return Ok(());
+    }
+
+    let settled_balance = BalancesStore::load_settled(storage, &entry.recipient)?;
+    settle_balance(storage, &entry)?;
+
+    let mut nodes = Vec::with_capacity(entry.list_len as usize);
+    let mut node_id = entry.head_node;
+    while node_id != 0 {
+        let node = load_node(storage, node_id)?;
+        TX_NODES.remove(storage, &node_id)?;
+        node_id = node.next;
+        nodes.push(node);
+    }
+
+    append_settled_nodes(storage, &entry.recipient, settled_balance, nodes)
+}
+
+/// Append settled nodes (newest first) to the account's history and checkpoints.
+fn append_settled_nodes(
+    storage: &mut dyn Storage,
+    recipient: &CanonicalAddr,
+    settled_balance: u128,
+    mut nodes: Vec<TxNode>,
+) -> StdResult<()> {
+    // The list is newest first, but the history is appended oldest first
+    nodes.reverse();
+    for node in &nodes {
+        append_settled_tx(storage, &node.tx, recipient)?;
+    }
+
+    // The account's own storage is being written anyway, so its checkpoints can be too
+    CheckpointsStore::record_settled_credits(storage, recipient, settled_balance, &nodes)
+}
+
+/// Settle every entry of a buffer written by schemas 4 to 9, so no node is left without a tier.
+/// Their checkpoints get the recipient's current tier, the latest one known.
+pub fn settle_untiered_buffer(storage: &mut dyn Storage) -> StdResult<()> {
+    let mut dwb = match DWB.may_load(storage)? {
+        Some(dwb) => dwb,
+        None => return Ok(()),
+    };
+
+    for entry in dwb.entries.iter_mut() {
+        let entry = std::mem::take(entry);
+        if entry.is_empty() {
+            continue;
+        }
+
+        let tier = OneAmberStore::get_tier(storage, &entry.recipient);
+        let settled_balance = BalancesStore::load_settled(storage, &entry.recipient)?;
+        settle_balance(storage, &entry)?;
+
+        let mut nodes = Vec::with_capacity(entry.list_len as usize);
+        let mut node_id = entry.head_node;
+        while node_id != 0 {
+            let node = UNTIERED_TX_NODES
+                .get(storage, &node_id)
+                .ok_or_else(|| StdError::generic_err("Pending transaction not found"))?;
+            UNTIERED_TX_NODES.remove(storage, &node_id)?;
+            node_id = node.next;
+            nodes.push(TxNode {
+                tx: node.tx,
+                tier,
+                next: node.next,
+            });
+        }
+
+        append_settled_nodes(storage, &entry.recipient, settled_balance, nodes)?;
+    }
+
+    dwb.save(storage)
+}
+
+/// Settle every entry of a buffer written before schema 4, so no node is left in the old format.
+/// Their transfers go to the legacy transfer history, like the ones that settled before them.
+pub fn settle_legacy_buffer(storage: &mut dyn Storage) -> StdResult<()> {
+    let mut dwb = match DWB.may_load(storage)? {
+        Some(dwb) => dwb,
+        None => return Ok(()),
+    };
+
+    for entry in dwb.entries.iter_mut() {
+        let entry = std::mem::take(entry);
+        if entry.is_empty() {
+            continue;
+        }
+
+        settle_balance(storage, &entry)?;
+
+        let mut nodes = Vec::with_capacity(entry.list_len as usize);
+        let mut node_id = entry.head_node;
+        while node_id != 0 {
+            let node = LEGACY_TX_NODES
+                .get(storage, &node_id)
+                .ok_or_else(|| StdError::generic_err("Pending transaction not found"))?;
+            LEGACY_TX_NODES.remove(storage, &node_id)?;
+            node_id = node.next;
+            nodes.push(node);
+        }
+
+        for node in nodes.into_iter().rev() {
+            append_settled_tx(storage, &node.tx, &entry.recipient)?;
+            if let Some(transfer) = node.transfer {
+                StoredLegacyTransfer::append_transfer(storage, &transfer, &entry.recipient)?;
+            }
+        }
+    }
+
+    dwb.save(storage)
+}
@@ -0,0 +1,165 @@
+//! Callbacks to contracts that track OAC membership
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{
+    to_binary, Addr, Api, Binary, CanonicalAddr, CosmosMsg, Response, StdResult, Storage, SubMsg,
+    WasmMsg,
+};
+use secret_toolkit::storage::{Item, Keymap};
+use secret_toolkit::utils::space_pad;
+
+use crate::contract::RESPONSE_BLOCK_SIZE;
+
+/// Reply id for hook callbacks whose failures are ignored.
+pub const MEMBERSHIP_HOOK_REPLY_ID: u64 = 1;
+
+/// A map of registered hook contracts to their code hash and error policy.
+pub static MEMBERSHIP_HOOKS: Keymap<Addr, MembershipHook> = Keymap::new(b"membership_hooks");
+/// Membership changes made during the current execution, waiting to be sent to hooks.
+pub static PENDING_CHANGES: Item<Vec<PendingChange>> = Item::new(b"pending_changes");
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum HookErrorPolicy {
+    /// A failing hook reverts the whole transaction
+    Revert,
+    /// A failing hook is dispatched as a reply-on-error submessage and ignored
+    Ignore,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema, Debug)]
+pub struct MembershipHook {
+    pub code_hash: String,
+    pub on_error: HookErrorPolicy,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum MembershipChange {
+    Joined,
+    Left,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PendingChange {
+    account: CanonicalAddr,
+    change: MembershipChange,
+    tier: u8,
+}
+
+/// MembershipChangedMsg should be de/serialized under `MembershipChanged()` variant in a HandleMsg
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct MembershipChangedMsg {
+    pub account: Addr,
+    pub change: MembershipChange,
+    pub tier: u8,
+}
+
+impl MembershipChangedMsg {
+    /// serializes the message, and pads it to 256 bytes
+    pub fn into_binary(self) -> StdResult<Binary> {
+        let msg = HookHandleMsg::MembershipChanged(self);
+        let mut data = to_binary(&msg)?;
+        space_pad(&mut data.0, RESPONSE_BLOCK_SIZE);
+        Ok(data)
+    }
+
+    /// creates a cosmos_msg sending this struct to the named contract
+    pub fn into_cosmos_msg(self, code_hash: String, contract_addr: Addr) -> StdResult<CosmosMsg> {
+        let msg = self.into_binary()?;
+        let execute = WasmMsg::Execute {
+            msg,
+            code_hash,
+            contract_addr: contract_addr.into_string(),
+            funds: vec![],
+        };
+        Ok(execute.into())
+    }
+}
+
+// This is just a helper to properly serialize the above message
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+enum HookHandleMsg {
+    MembershipChanged(MembershipChangedMsg),
+}
+
+pub struct MembershipHooksStore {}
+impl MembershipHooksStore {
+    pub fn add(storage: &mut dyn Storage, contract: &Addr, hook: &MembershipHook) -> StdResult<()> {
+        MEMBERSHIP_HOOKS.insert(storage, contract, hook)
+    }
+
+    pub fn remove(storage: &mut dyn Storage, contract: &Addr) -> StdResult<()> {
+        if MEMBERSHIP_HOOKS.contains(storage, contract) {
+            MEMBERSHIP_HOOKS.remove(storage, contract)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn load_all(storage: &dyn Storage) -> StdResult<Vec<(Addr, MembershipHook)>> {
+        MEMBERSHIP_HOOKS.iter(storage)?.collect()
+    }
+
+    /// Queue a membership change to be sent to hooks at the end of the execution.
+    /// Nothing is stored if no hooks are registered.
+    pub fn record(
+        storage: &mut dyn Storage,
+        account: &CanonicalAddr,
+        change: MembershipChange,
+        tier: u8,
+    ) -> StdResult<()> {
+        if MEMBERSHIP_HOOKS.is_empty(storage)? {
+            return Ok(());
+        }
+
+        let mut pending = PENDING_CHANGES.may_load(storage)?.unwrap_or_default();
+        pending.push(PendingChange {
+            account: account.clone(),
+            change,
+            tier,
+        });
+        PENDING_CHANGES.save(storage, &pending)
+    }
+
+    /// Drain the queued membership changes and add a callback to every hook for each of them.
+    pub fn dispatch(
+        storage: &mut dyn Storage,
+        api: &dyn Api,
+        response: Response,
+    ) -> StdResult<Response> {
+        let pending = match PENDING_CHANGES.may_load(storage)? {
+            Some(pending) => pending,
+            None => return Ok(response),
+        };
+        PENDING_CHANGES.remove(storage);
+
+        let hooks = Self::load_all(storage)?;
+
+        let mut messages = vec![];
+        for pending_change in pending {
+            let account = api.addr_humanize(&pending_change.account)?;
+            for (contract, hook) in hooks.iter() {
+                let msg = MembershipChangedMsg {
+                    account: account.clone(),
+                    change: pending_change.change,
+                    tier: pending_change.tier,
+                }
+                .into_cosmos_msg(hook.code_hash.clone(), contract.clone())?;
+
+                messages.push(match hook.on_error {
+                    HookErrorPolicy::Revert => SubMsg::new(msg),
+                    HookErrorPolicy::Ignore => {
+                        SubMsg::reply_on_error(msg, MEMBERSHIP_HOOK_REPLY_ID)
+                    }
+                });
+            }
+        }
+
+        Ok(response.add_submessages(messages))
+    }
+}
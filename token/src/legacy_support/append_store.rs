@@ -2,7 +2,8 @@
 //! from a list of items in storage.
 //!
 //! This is achieved by storing each item in a separate storage entry. A special key is reserved
-//! for storing the length of the collection so far.
+//! for storing the length of the collection so far, and another for the storage position of the
+//! first item, once items have been dropped from the front.
 use std::convert::TryInto;
 use std::marker::PhantomData;
 
@@ -13,6 +14,21 @@ use cosmwasm_std::{StdError, StdResult, Storage};
 use secret_toolkit::serialization::{Bincode2, Serde};
 
 const LEN_KEY: &[u8] = b"len";
+const OFFSET_KEY: &[u8] = b"offset";
+
+/// The storage position of the first item, which stays 0 until `retain_last` drops items.
+fn load_offset(storage: &dyn Storage) -> StdResult<u32> {
+    match storage.get(OFFSET_KEY) {
+        Some(offset_vec) => {
+            let offset_array = offset_vec
+                .as_slice()
+                .try_into()
+                .map_err(|err| StdError::parse_err("u32", err))?;
+            Ok(u32::from_be_bytes(offset_array))
+        }
+        None => Ok(0),
+    }
+}
 
 // Mutable append-store
 
@@ -26,6 +42,7 @@ where
     item_type: PhantomData<*const T>,
     serialization_type: PhantomData<*const Ser>,
     len: u32,
+    offset: u32,
 }
 
 impl<'a, T> AppendStoreMut<'a, T, Bincode2>
@@ -89,12 +106,14 @@ where
             .try_into()
             .map_err(|err| StdError::parse_err("u32", err))?;
         let len = u32::from_be_bytes(len_array);
+        let offset = load_offset(storage)?;
 
         Ok(Self {
             storage,
             item_type: PhantomData,
             serialization_type: PhantomData,
             len,
+            offset,
         })
     }
 
@@ -144,7 +163,8 @@ where
 
     fn set_at_unchecked(&mut self, pos: u32, item: &T) -> StdResult<()> {
         let serialized = Ser::serialize(item)?;
-        self.storage.set(&pos.to_be_bytes(), &serialized);
+        self.storage
+            .set(&(self.offset + pos).to_be_bytes(), &serialized);
         Ok(())
     }
 
@@ -173,12 +193,39 @@ where
         self.set_length(0);
     }
 
+    /// Keep only the last `keep` items, which then start at position 0.
+    /// Unlike `clear`, the items that are dropped are removed from storage. The kept items are not
+    /// moved, so the cost only depends on the number of items dropped.
+    pub fn retain_last(&mut self, keep: u32) -> StdResult<()> {
+        let keep = keep.min(self.len);
+        let dropped = self.len - keep;
+        if dropped == 0 {
+            return Ok(());
+        }
+
+        for pos in 0..dropped {
+            self.storage.remove(&(self.offset + pos).to_be_bytes());
+        }
+
+        // An emptied collection starts over from the first storage position
+        let offset = if keep == 0 { 0 } else { self.offset + dropped };
+        self.set_offset(offset);
+        self.set_length(keep);
+        Ok(())
+    }
+
     /// Set the length of the collection
     fn set_length(&mut self, len: u32) {
         self.storage.set(LEN_KEY, &len.to_be_bytes());
         self.len = len;
     }
 
+    /// Set the storage position of the first item
+    fn set_offset(&mut self, offset: u32) {
+        self.storage.set(OFFSET_KEY, &offset.to_be_bytes());
+        self.offset = offset;
+    }
+
     /// Gain access to the implementation of the immutable methods
     fn as_readonly(&self) -> AppendStore<T, Ser> {
         AppendStore {
@@ -186,6 +233,7 @@ where
             item_type: self.item_type,
             serialization_type: self.serialization_type,
             len: self.len,
+            offset: self.offset,
         }
     }
 }
@@ -223,6 +271,7 @@ where
     item_type: PhantomData<*const T>,
     serialization_type: PhantomData<*const Ser>,
     len: u32,
+    offset: u32,
 }
 
 impl<'a, T> AppendStore<'a, T, Bincode2>
@@ -262,12 +311,14 @@ where
             .try_into()
             .map_err(|err| StdError::parse_err("u32", err))?;
         let len = u32::from_be_bytes(len_array);
+        let offset = load_offset(storage)?;
 
         Ok(Self {
             storage,
             item_type: PhantomData,
             serialization_type: PhantomData,
             len,
+            offset,
         })
     }
 
@@ -304,9 +355,12 @@ where
     }
 
     fn get_at_unchecked(&self, pos: u32) -> StdResult<T> {
-        let serialized = self.storage.get(&pos.to_be_bytes()).ok_or_else(|| {
-            StdError::generic_err(format!("No item in AppendStorage at position {}", pos))
-        })?;
+        let serialized = self
+            .storage
+            .get(&(self.offset + pos).to_be_bytes())
+            .ok_or_else(|| {
+                StdError::generic_err(format!("No item in AppendStorage at position {}", pos))
+            })?;
         Ser::deserialize(&serialized)
     }
 }
@@ -341,6 +395,7 @@ where
             item_type: self.item_type,
             serialization_type: self.serialization_type,
             len: self.len,
+            offset: self.offset,
         }
     }
 }
@@ -452,6 +507,50 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_retain_last() -> StdResult<()> {
+        let mut storage = MockStorage::new();
+        let mut append_store = AppendStoreMut::attach_or_create(&mut storage)?;
+        append_store.push(&1234)?;
+        append_store.push(&2143)?;
+        append_store.push(&3412)?;
+        append_store.push(&4321)?;
+
+        append_store.retain_last(2)?;
+        assert_eq!(append_store.len(), 2);
+        assert_eq!(append_store.get_at(0), Ok(3412));
+        assert_eq!(append_store.get_at(1), Ok(4321));
+
+        append_store.retain_last(5)?;
+        assert_eq!(append_store.len(), 2);
+
+        // New items go after the kept ones, also once the store is attached again
+        append_store.push(&1111)?;
+        let append_store = AppendStore::<i32, _>::attach(&storage).unwrap()?;
+        assert_eq!(
+            append_store.iter().collect::<StdResult<Vec<_>>>()?,
+            vec![3412, 4321, 1111]
+        );
+        // The kept items were left where they were
+        assert_eq!(storage.get(&0_u32.to_be_bytes()), None);
+        assert_eq!(storage.get(&1_u32.to_be_bytes()), None);
+        assert!(storage.get(&2_u32.to_be_bytes()).is_some());
+
+        let mut append_store = AppendStoreMut::<i32, _>::attach(&mut storage).unwrap()?;
+        append_store.retain_last(0)?;
+        assert!(append_store.is_empty());
+        for pos in 0..5_u32 {
+            assert_eq!(storage.get(&pos.to_be_bytes()), None);
+        }
+
+        let mut append_store = AppendStoreMut::<i32, _>::attach(&mut storage).unwrap()?;
+        append_store.push(&2222)?;
+        assert_eq!(append_store.get_at(0), Ok(2222));
+        assert!(storage.get(&0_u32.to_be_bytes()).is_some());
+
+        Ok(())
+    }
+
     #[test]
     fn test_iterator() -> StdResult<()> {
         let mut storage = MockStorage::new();
@@ -1,10 +1,18 @@
 #![allow(clippy::too_many_arguments)]
 
 mod amber;
+mod anonymity_set;
+mod audit;
 mod batch;
+mod btbe;
 pub mod contract;
+mod dwb;
+pub mod hooks;
 mod legacy_support;
 pub mod msg;
+mod notifications;
 pub mod receiver;
+pub mod roles;
 pub mod state;
+mod timelock;
 mod transaction_history;
@@ -5,8 +5,11 @@ use serde::{Deserialize, Serialize};
 
 use crate::batch;
 use crate::batch::HasDecoy;
-use crate::transaction_history::{ExtendedTx, Tx};
-use cosmwasm_std::{Addr, Api, Binary, StdError, StdResult, Uint128};
+use crate::hooks::HookErrorPolicy;
+use crate::roles::Role;
+use crate::state::ContractVersion;
+use crate::transaction_history::{ExtendedTx, HistoryDirection, Tx, TxFilter};
+use cosmwasm_std::{Addr, Api, Binary, StdError, StdResult, Uint128, Uint64};
 use secret_toolkit::permit::Permit;
 
 #[cfg_attr(test, derive(Eq, PartialEq))]
@@ -20,13 +23,22 @@ pub struct InitialBalance {
 #[derive(Serialize, Deserialize, Clone, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum MigrateMsg {
-    Migrate {},
+    Migrate {
+        /// Existing holders to enroll as OAC members during the migration.
+        /// Any remaining accounts can be enrolled afterwards with `BackfillMembers`.
+        accounts: Option<Vec<String>>,
+        /// Cap the total supply at this amount. Once set, it can only be lowered.
+        max_supply: Option<Uint128>,
+    },
 }
 
 #[derive(Serialize, Deserialize, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum MigrateAnswer {
-    Migrate { status: ResponseStatus },
+    Migrate {
+        status: ResponseStatus,
+        enrolled: u32,
+    },
 }
 
 #[derive(Serialize, Deserialize, JsonSchema)]
@@ -71,6 +83,9 @@ pub struct InitConfig {
     /// Indicated whether an admin can modify supported denoms
     /// default: False
     can_modify_denoms: Option<bool>,
+    /// The most the total supply may reach by minting. Can be lowered later, but not raised.
+    /// default: None (uncapped)
+    max_supply: Option<Uint128>,
 }
 
 impl InitConfig {
@@ -97,6 +112,10 @@ impl InitConfig {
     pub fn can_modify_denoms(&self) -> bool {
         self.can_modify_denoms.unwrap_or(false)
     }
+
+    pub fn max_supply(&self) -> Option<u128> {
+        self.max_supply.map(|max_supply| max_supply.u128())
+    }
 }
 
 #[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
@@ -107,13 +126,17 @@ pub enum ExecuteMsg {
         amount: Uint128,
         denom: Option<String>,
         decoys: Option<Vec<Addr>>,
+        decoy_count: Option<u8>,
         entropy: Option<Binary>,
         padding: Option<String>,
+        gas_target: Option<u32>,
     },
     Deposit {
         decoys: Option<Vec<Addr>>,
+        decoy_count: Option<u8>,
         entropy: Option<Binary>,
         padding: Option<String>,
+        gas_target: Option<u32>,
     },
 
     // Base ERC-20 stuff
@@ -122,8 +145,10 @@ pub enum ExecuteMsg {
         amount: Uint128,
         memo: Option<String>,
         decoys: Option<Vec<Addr>>,
+        decoy_count: Option<u8>,
         entropy: Option<Binary>,
         padding: Option<String>,
+        gas_target: Option<u32>,
     },
     Send {
         recipient: String,
@@ -132,37 +157,48 @@ pub enum ExecuteMsg {
         msg: Option<Binary>,
         memo: Option<String>,
         decoys: Option<Vec<Addr>>,
+        decoy_count: Option<u8>,
         entropy: Option<Binary>,
         padding: Option<String>,
+        gas_target: Option<u32>,
     },
     BatchTransfer {
         actions: Vec<batch::TransferAction>,
+        decoy_count: Option<u8>,
         entropy: Option<Binary>,
         padding: Option<String>,
+        gas_target: Option<u32>,
     },
     BatchSend {
         actions: Vec<batch::SendAction>,
+        decoy_count: Option<u8>,
         entropy: Option<Binary>,
         padding: Option<String>,
+        gas_target: Option<u32>,
     },
     Burn {
         amount: Uint128,
         memo: Option<String>,
         decoys: Option<Vec<Addr>>,
+        decoy_count: Option<u8>,
         entropy: Option<Binary>,
         padding: Option<String>,
+        gas_target: Option<u32>,
     },
     RegisterReceive {
         code_hash: String,
         padding: Option<String>,
+        gas_target: Option<u32>,
     },
     CreateViewingKey {
         entropy: String,
         padding: Option<String>,
+        gas_target: Option<u32>,
     },
     SetViewingKey {
         key: String,
         padding: Option<String>,
+        gas_target: Option<u32>,
     },
 
     // Allowance
@@ -171,12 +207,14 @@ pub enum ExecuteMsg {
         amount: Uint128,
         expiration: Option<u64>,
         padding: Option<String>,
+        gas_target: Option<u32>,
     },
     DecreaseAllowance {
         spender: String,
         amount: Uint128,
         expiration: Option<u64>,
         padding: Option<String>,
+        gas_target: Option<u32>,
     },
     TransferFrom {
         owner: String,
@@ -184,8 +222,10 @@ pub enum ExecuteMsg {
         amount: Uint128,
         memo: Option<String>,
         decoys: Option<Vec<Addr>>,
+        decoy_count: Option<u8>,
         entropy: Option<Binary>,
         padding: Option<String>,
+        gas_target: Option<u32>,
     },
     SendFrom {
         owner: String,
@@ -195,31 +235,41 @@ pub enum ExecuteMsg {
         msg: Option<Binary>,
         memo: Option<String>,
         decoys: Option<Vec<Addr>>,
+        decoy_count: Option<u8>,
         entropy: Option<Binary>,
         padding: Option<String>,
+        gas_target: Option<u32>,
     },
     BatchTransferFrom {
         actions: Vec<batch::TransferFromAction>,
+        decoy_count: Option<u8>,
         entropy: Option<Binary>,
         padding: Option<String>,
+        gas_target: Option<u32>,
     },
     BatchSendFrom {
         actions: Vec<batch::SendFromAction>,
+        decoy_count: Option<u8>,
         entropy: Option<Binary>,
         padding: Option<String>,
+        gas_target: Option<u32>,
     },
     BurnFrom {
         owner: String,
         amount: Uint128,
         memo: Option<String>,
         decoys: Option<Vec<Addr>>,
+        decoy_count: Option<u8>,
         entropy: Option<Binary>,
         padding: Option<String>,
+        gas_target: Option<u32>,
     },
     BatchBurnFrom {
         actions: Vec<batch::BurnFromAction>,
+        decoy_count: Option<u8>,
         entropy: Option<Binary>,
         padding: Option<String>,
+        gas_target: Option<u32>,
     },
 
     // Mint
@@ -228,39 +278,134 @@ pub enum ExecuteMsg {
         amount: Uint128,
         memo: Option<String>,
         decoys: Option<Vec<Addr>>,
+        decoy_count: Option<u8>,
         entropy: Option<Binary>,
         padding: Option<String>,
+        gas_target: Option<u32>,
     },
     BatchMint {
         actions: Vec<batch::MintAction>,
+        decoy_count: Option<u8>,
         entropy: Option<Binary>,
         padding: Option<String>,
+        gas_target: Option<u32>,
     },
     AddMinters {
         minters: Vec<String>,
         padding: Option<String>,
+        gas_target: Option<u32>,
     },
     RemoveMinters {
         minters: Vec<String>,
         padding: Option<String>,
+        gas_target: Option<u32>,
     },
     SetMinters {
         minters: Vec<String>,
         padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    /// Limit how much a minter may mint over its lifetime (`allowance`) and per period (`rate`).
+    /// Leaving either unset lifts that limit. What the minter already minted still counts.
+    SetMinterQuota {
+        minter: String,
+        allowance: Option<Uint128>,
+        rate: Option<MintRate>,
+        padding: Option<String>,
+        gas_target: Option<u32>,
     },
 
     // Admin
+    /// Same as `ProposeAdmin` without an expiration. The new admin still has to accept.
     ChangeAdmin {
         address: String,
         padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    /// Propose a new admin, who becomes admin by sending `AcceptAdmin`. A new proposal replaces
+    /// the pending one. `expiration` is a unix time in seconds after which it can't be accepted.
+    ProposeAdmin {
+        address: String,
+        expiration: Option<u64>,
+        padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    AcceptAdmin {
+        padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    CancelAdminProposal {
+        padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    /// Give up the admin and revoke every role, so the token's settings can never change again.
+    RenounceAdmin {
+        padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    /// Delay sensitive admin messages by this many blocks, or 0 to execute them right away.
+    /// While a delay is set, changing it waits out the delay too.
+    SetTimelock {
+        delay: u64,
+        padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    /// Execute an operation queued by the sender, once its delay has passed.
+    ExecuteQueued {
+        id: u64,
+        padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    /// Drop a queued operation. The account that queued it and the admin can cancel it.
+    CancelQueued {
+        id: u64,
+        padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    /// Give an account a role. Only the admin can grant roles.
+    GrantRole {
+        role: Role,
+        account: String,
+        padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    /// Take a role away from an account. Only the admin can revoke roles.
+    RevokeRole {
+        role: Role,
+        account: String,
+        padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    /// Give up a role held by the sender.
+    RenounceRole {
+        role: Role,
+        padding: Option<String>,
+        gas_target: Option<u32>,
     },
     SetContractStatus {
         level: ContractStatusLevel,
         padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    /// Pause or resume each class of operation independently. `SetContractStatus` sets these
+    /// flags to one of its presets.
+    SetPauseFlags {
+        flags: PauseFlags,
+        padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    /// Cap the total supply, or lower the existing cap. The cap can never be raised.
+    SetMaxSupply {
+        max_supply: Uint128,
+        padding: Option<String>,
+        gas_target: Option<u32>,
     },
     /// Add deposit/redeem support for these coin denoms
     AddSupportedDenoms {
         denoms: Vec<String>,
+        /// Deposits of every denom are credited 1:1, so supporting more than one requires
+        /// confirming that they are pegged to each other
+        pegged: Option<bool>,
     },
     /// Remove deposit/redeem support for these coin denoms
     RemoveSupportedDenoms {
@@ -271,15 +416,176 @@ pub enum ExecuteMsg {
     RevokePermit {
         permit_name: String,
         padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+
+    // Checkpoints
+    /// Start recording this account's balance and membership at each height it changes.
+    /// Cannot be turned off, so snapshots taken by other contracts stay valid.
+    EnableCheckpoints {
+        padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+
+    // History
+    /// Remove all but the last `keep_last` txs from the caller's own history.
+    /// Counterparties keep their copies. A long history takes several messages.
+    PruneHistory {
+        keep_last: u32,
+        padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    /// Remove every tx from the caller's own history. Counterparties keep their copies.
+    ClearHistory {
+        padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    /// Attach a private note and tags to a tx in the caller's own history, returned with it in
+    /// `TransactionHistory`. Leaving both empty clears the label.
+    SetTxLabel {
+        tx_id: u64,
+        note: Option<String>,
+        tags: Option<Vec<String>>,
+        padding: Option<String>,
+        gas_target: Option<u32>,
     },
 
     // Amber
     RegenerateCode {},
+    /// Enroll existing holders with 1+ AMBER who have not transacted since the migration
+    BackfillMembers {
+        accounts: Vec<String>,
+        padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    /// Set the minimum balance for each membership tier, in ascending order, up to `MAX_TIERS`.
+    /// Existing members move to their new tier on their next balance change.
+    SetMemberTiers {
+        thresholds: Vec<Uint128>,
+        padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    /// Move these accounts to the tier their current balance qualifies for.
+    /// Membership hooks are told about every tier change, so re-evaluating an account shows the
+    /// hook contracts which tier band its balance is in.
+    ReevaluateMembers {
+        accounts: Vec<String>,
+        padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    /// Mint an additional invite code that expires after `max_uses` uses, or at the given
+    /// height or time, whichever comes first
+    CreateInviteCode {
+        max_uses: u32,
+        expiration_height: Option<u64>,
+        expiration_time: Option<u64>,
+        padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    /// Use up one use of an additional invite code. Only callable by code consumers.
+    ConsumeInviteCode {
+        code: String,
+        padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    AddCodeConsumers {
+        consumers: Vec<String>,
+        padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    RemoveCodeConsumers {
+        consumers: Vec<String>,
+        padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    /// Register a contract to be called whenever an account joins or leaves a tier
+    AddMembershipHook {
+        contract: String,
+        code_hash: String,
+        on_error: HookErrorPolicy,
+        padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+    RemoveMembershipHook {
+        contract: String,
+        padding: Option<String>,
+        gas_target: Option<u32>,
+    },
+}
+
+impl ExecuteMsg {
+    /// The amount of gas the sender wants the execution to use up, see SNIP-50.
+    pub fn get_gas_target(&self) -> Option<u32> {
+        match self {
+            ExecuteMsg::Redeem { gas_target, .. }
+            | ExecuteMsg::Deposit { gas_target, .. }
+            | ExecuteMsg::Transfer { gas_target, .. }
+            | ExecuteMsg::Send { gas_target, .. }
+            | ExecuteMsg::BatchTransfer { gas_target, .. }
+            | ExecuteMsg::BatchSend { gas_target, .. }
+            | ExecuteMsg::Burn { gas_target, .. }
+            | ExecuteMsg::RegisterReceive { gas_target, .. }
+            | ExecuteMsg::CreateViewingKey { gas_target, .. }
+            | ExecuteMsg::SetViewingKey { gas_target, .. }
+            | ExecuteMsg::IncreaseAllowance { gas_target, .. }
+            | ExecuteMsg::DecreaseAllowance { gas_target, .. }
+            | ExecuteMsg::TransferFrom { gas_target, .. }
+            | ExecuteMsg::SendFrom { gas_target, .. }
+            | ExecuteMsg::BatchTransferFrom { gas_target, .. }
+            | ExecuteMsg::BatchSendFrom { gas_target, .. }
+            | ExecuteMsg::BurnFrom { gas_target, .. }
+            | ExecuteMsg::BatchBurnFrom { gas_target, .. }
+            | ExecuteMsg::Mint { gas_target, .. }
+            | ExecuteMsg::BatchMint { gas_target, .. }
+            | ExecuteMsg::AddMinters { gas_target, .. }
+            | ExecuteMsg::RemoveMinters { gas_target, .. }
+            | ExecuteMsg::SetMinters { gas_target, .. }
+            | ExecuteMsg::SetMinterQuota { gas_target, .. }
+            | ExecuteMsg::ChangeAdmin { gas_target, .. }
+            | ExecuteMsg::ProposeAdmin { gas_target, .. }
+            | ExecuteMsg::AcceptAdmin { gas_target, .. }
+            | ExecuteMsg::CancelAdminProposal { gas_target, .. }
+            | ExecuteMsg::RenounceAdmin { gas_target, .. }
+            | ExecuteMsg::SetTimelock { gas_target, .. }
+            | ExecuteMsg::ExecuteQueued { gas_target, .. }
+            | ExecuteMsg::CancelQueued { gas_target, .. }
+            | ExecuteMsg::GrantRole { gas_target, .. }
+            | ExecuteMsg::RevokeRole { gas_target, .. }
+            | ExecuteMsg::RenounceRole { gas_target, .. }
+            | ExecuteMsg::SetContractStatus { gas_target, .. }
+            | ExecuteMsg::SetPauseFlags { gas_target, .. }
+            | ExecuteMsg::SetMaxSupply { gas_target, .. }
+            | ExecuteMsg::RevokePermit { gas_target, .. }
+            | ExecuteMsg::EnableCheckpoints { gas_target, .. }
+            | ExecuteMsg::PruneHistory { gas_target, .. }
+            | ExecuteMsg::ClearHistory { gas_target, .. }
+            | ExecuteMsg::SetTxLabel { gas_target, .. }
+            | ExecuteMsg::BackfillMembers { gas_target, .. }
+            | ExecuteMsg::SetMemberTiers { gas_target, .. }
+            | ExecuteMsg::ReevaluateMembers { gas_target, .. }
+            | ExecuteMsg::CreateInviteCode { gas_target, .. }
+            | ExecuteMsg::ConsumeInviteCode { gas_target, .. }
+            | ExecuteMsg::AddCodeConsumers { gas_target, .. }
+            | ExecuteMsg::RemoveCodeConsumers { gas_target, .. }
+            | ExecuteMsg::AddMembershipHook { gas_target, .. }
+            | ExecuteMsg::RemoveMembershipHook { gas_target, .. } => *gas_target,
+            _ => None,
+        }
+    }
 }
 
 pub trait Decoyable {
     fn get_minimal_decoys_size(&self) -> usize;
+    fn get_maximal_decoys_size(&self) -> usize;
     fn get_entropy(self) -> Option<Binary>;
+    /// The number of decoys the contract should pick for this message, if any.
+    fn get_decoy_count(&self) -> Option<u8>;
+    /// The accounts besides the sender whose balances the message changes, in every action.
+    fn get_accounts(&self) -> Vec<&str>;
+    /// Set the message's decoys, or those of every batch action without any, to the output of `pick`.
+    fn fill_decoys<F>(&mut self, pick: F) -> StdResult<()>
+    where
+        F: FnMut() -> StdResult<Vec<Addr>>;
 }
 
 impl Decoyable for ExecuteMsg {
@@ -310,6 +616,27 @@ impl Decoyable for ExecuteMsg {
         }
     }
 
+    fn get_maximal_decoys_size(&self) -> usize {
+        match self {
+            ExecuteMsg::Deposit { decoys, .. }
+            | ExecuteMsg::Redeem { decoys, .. }
+            | ExecuteMsg::Transfer { decoys, .. }
+            | ExecuteMsg::Send { decoys, .. }
+            | ExecuteMsg::Burn { decoys, .. }
+            | ExecuteMsg::Mint { decoys, .. }
+            | ExecuteMsg::TransferFrom { decoys, .. }
+            | ExecuteMsg::SendFrom { decoys, .. }
+            | ExecuteMsg::BurnFrom { decoys, .. } => decoys.as_ref().map_or(0, Vec::len),
+            ExecuteMsg::BatchSendFrom { actions, .. } => get_max_decoys_count(actions),
+            ExecuteMsg::BatchTransferFrom { actions, .. } => get_max_decoys_count(actions),
+            ExecuteMsg::BatchTransfer { actions, .. } => get_max_decoys_count(actions),
+            ExecuteMsg::BatchSend { actions, .. } => get_max_decoys_count(actions),
+            ExecuteMsg::BatchBurnFrom { actions, .. } => get_max_decoys_count(actions),
+            ExecuteMsg::BatchMint { actions, .. } => get_max_decoys_count(actions),
+            _ => 0,
+        }
+    }
+
     fn get_entropy(self) -> Option<Binary> {
         match self {
             ExecuteMsg::Deposit { entropy, .. }
@@ -330,6 +657,119 @@ impl Decoyable for ExecuteMsg {
             _ => None,
         }
     }
+
+    fn get_decoy_count(&self) -> Option<u8> {
+        match self {
+            ExecuteMsg::Deposit { decoy_count, .. }
+            | ExecuteMsg::Redeem { decoy_count, .. }
+            | ExecuteMsg::Transfer { decoy_count, .. }
+            | ExecuteMsg::Send { decoy_count, .. }
+            | ExecuteMsg::Burn { decoy_count, .. }
+            | ExecuteMsg::Mint { decoy_count, .. }
+            | ExecuteMsg::TransferFrom { decoy_count, .. }
+            | ExecuteMsg::SendFrom { decoy_count, .. }
+            | ExecuteMsg::BurnFrom { decoy_count, .. }
+            | ExecuteMsg::BatchTransferFrom { decoy_count, .. }
+            | ExecuteMsg::BatchSendFrom { decoy_count, .. }
+            | ExecuteMsg::BatchTransfer { decoy_count, .. }
+            | ExecuteMsg::BatchSend { decoy_count, .. }
+            | ExecuteMsg::BatchBurnFrom { decoy_count, .. }
+            | ExecuteMsg::BatchMint { decoy_count, .. } => *decoy_count,
+            _ => None,
+        }
+    }
+
+    fn get_accounts(&self) -> Vec<&str> {
+        match self {
+            ExecuteMsg::Transfer { recipient, .. }
+            | ExecuteMsg::Send { recipient, .. }
+            | ExecuteMsg::Mint { recipient, .. } => vec![recipient.as_str()],
+            ExecuteMsg::TransferFrom {
+                owner, recipient, ..
+            }
+            | ExecuteMsg::SendFrom {
+                owner, recipient, ..
+            } => vec![owner.as_str(), recipient.as_str()],
+            ExecuteMsg::BurnFrom { owner, .. } => vec![owner.as_str()],
+            ExecuteMsg::BatchSendFrom { actions, .. } => get_action_accounts(actions),
+            ExecuteMsg::BatchTransferFrom { actions, .. } => get_action_accounts(actions),
+            ExecuteMsg::BatchTransfer { actions, .. } => get_action_accounts(actions),
+            ExecuteMsg::BatchSend { actions, .. } => get_action_accounts(actions),
+            ExecuteMsg::BatchBurnFrom { actions, .. } => get_action_accounts(actions),
+            ExecuteMsg::BatchMint { actions, .. } => get_action_accounts(actions),
+            _ => vec![],
+        }
+    }
+
+    fn fill_decoys<F>(&mut self, mut pick: F) -> StdResult<()>
+    where
+        F: FnMut() -> StdResult<Vec<Addr>>,
+    {
+        match self {
+            ExecuteMsg::Deposit { decoys, .. }
+            | ExecuteMsg::Redeem { decoys, .. }
+            | ExecuteMsg::Transfer { decoys, .. }
+            | ExecuteMsg::Send { decoys, .. }
+            | ExecuteMsg::Burn { decoys, .. }
+            | ExecuteMsg::Mint { decoys, .. }
+            | ExecuteMsg::TransferFrom { decoys, .. }
+            | ExecuteMsg::SendFrom { decoys, .. }
+            | ExecuteMsg::BurnFrom { decoys, .. } => {
+                if decoys.is_some() {
+                    return Err(StdError::generic_err(
+                        "Provide either decoys or decoy_count, not both",
+                    ));
+                }
+                *decoys = picked_decoys(&mut pick)?;
+            }
+            ExecuteMsg::BatchSendFrom { actions, .. } => fill_action_decoys(actions, &mut pick)?,
+            ExecuteMsg::BatchTransferFrom { actions, .. } => {
+                fill_action_decoys(actions, &mut pick)?
+            }
+            ExecuteMsg::BatchTransfer { actions, .. } => fill_action_decoys(actions, &mut pick)?,
+            ExecuteMsg::BatchSend { actions, .. } => fill_action_decoys(actions, &mut pick)?,
+            ExecuteMsg::BatchBurnFrom { actions, .. } => fill_action_decoys(actions, &mut pick)?,
+            ExecuteMsg::BatchMint { actions, .. } => fill_action_decoys(actions, &mut pick)?,
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+fn get_action_accounts<T: HasDecoy>(actions: &[T]) -> Vec<&str> {
+    actions.iter().flat_map(HasDecoy::accounts).collect()
+}
+
+fn get_max_decoys_count<T: HasDecoy>(actions: &[T]) -> usize {
+    actions
+        .iter()
+        .map(|action| action.decoys().as_ref().map_or(0, Vec::len))
+        .max()
+        .unwrap_or(0)
+}
+
+/// An empty pick means no decoys, rather than a decoy list of length 0.
+fn picked_decoys<F>(pick: &mut F) -> StdResult<Option<Vec<Addr>>>
+where
+    F: FnMut() -> StdResult<Vec<Addr>>,
+{
+    Ok(Some(pick()?).filter(|decoys| !decoys.is_empty()))
+}
+
+fn fill_action_decoys<T, F>(actions: &mut [T], pick: &mut F) -> StdResult<()>
+where
+    T: HasDecoy,
+    F: FnMut() -> StdResult<Vec<Addr>>,
+{
+    for action in actions {
+        // Actions with their own decoys keep them
+        if action.decoys().is_none() {
+            *action.decoys_mut() = picked_decoys(pick)?;
+        }
+    }
+
+    Ok(())
 }
 
 fn get_min_decoys_count<T: HasDecoy>(actions: &[T]) -> usize {
@@ -432,14 +872,55 @@ pub enum ExecuteAnswer {
     SetMinters {
         status: ResponseStatus,
     },
+    SetMinterQuota {
+        status: ResponseStatus,
+    },
 
     // Other
     ChangeAdmin {
         status: ResponseStatus,
     },
+    ProposeAdmin {
+        status: ResponseStatus,
+    },
+    AcceptAdmin {
+        status: ResponseStatus,
+    },
+    CancelAdminProposal {
+        status: ResponseStatus,
+    },
+    RenounceAdmin {
+        status: ResponseStatus,
+    },
+    SetTimelock {
+        status: ResponseStatus,
+    },
+    /// Returned instead of the message's own answer when it was queued behind the timelock
+    Queued {
+        id: u64,
+        not_before: u64,
+    },
+    CancelQueued {
+        status: ResponseStatus,
+    },
+    GrantRole {
+        status: ResponseStatus,
+    },
+    RevokeRole {
+        status: ResponseStatus,
+    },
+    RenounceRole {
+        status: ResponseStatus,
+    },
     SetContractStatus {
         status: ResponseStatus,
     },
+    SetPauseFlags {
+        status: ResponseStatus,
+    },
+    SetMaxSupply {
+        status: ResponseStatus,
+    },
     AddSupportedDenoms {
         status: ResponseStatus,
     },
@@ -452,10 +933,57 @@ pub enum ExecuteAnswer {
         status: ResponseStatus,
     },
 
+    // Checkpoints
+    EnableCheckpoints {
+        status: ResponseStatus,
+    },
+
+    // History
+    /// `remaining` records are still to be removed. Send the message again to continue.
+    PruneHistory {
+        removed: u32,
+        remaining: u32,
+    },
+    ClearHistory {
+        removed: u32,
+        remaining: u32,
+    },
+    SetTxLabel {
+        status: ResponseStatus,
+    },
+
     // Amber
     RegenerateCode {
         code: String,
     },
+    BackfillMembers {
+        enrolled: u32,
+    },
+    SetMemberTiers {
+        status: ResponseStatus,
+    },
+    ReevaluateMembers {
+        status: ResponseStatus,
+    },
+    CreateInviteCode {
+        code: String,
+    },
+    ConsumeInviteCode {
+        tier: u8,
+        remaining_uses: u32,
+    },
+    AddCodeConsumers {
+        status: ResponseStatus,
+    },
+    RemoveCodeConsumers {
+        status: ResponseStatus,
+    },
+    AddMembershipHook {
+        status: ResponseStatus,
+    },
+    RemoveMembershipHook {
+        status: ResponseStatus,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
@@ -465,6 +993,7 @@ pub enum QueryMsg {
     TokenInfo {},
     TokenConfig {},
     ContractStatus {},
+    ContractVersion {},
     ExchangeRate {},
     Allowance {
         owner: String,
@@ -494,6 +1023,12 @@ pub enum QueryMsg {
         page_size: u32,
         // I made this optional
         should_filter_decoys: Option<bool>,
+        /// Continue after the tx with this id, as returned in `next_cursor`. Overrides `page`.
+        start_after_id: Option<u64>,
+        /// Defaults to newest first
+        direction: Option<HistoryDirection>,
+        /// Only return transfers that match. `total` is then only reported when it comes free.
+        filter: Option<TxFilter>,
     },
     TransactionHistory {
         address: String,
@@ -502,10 +1037,40 @@ pub enum QueryMsg {
         page_size: u32,
         // I made this optional
         should_filter_decoys: Option<bool>,
+        /// Continue after the tx with this id, as returned in `next_cursor`. Overrides `page`.
+        start_after_id: Option<u64>,
+        /// Defaults to newest first
+        direction: Option<HistoryDirection>,
+        /// Only return txs that match. `total` is then only reported when it comes free.
+        filter: Option<TxFilter>,
+    },
+    BalanceAt {
+        address: String,
+        key: String,
+        height: u64,
+    },
+    WasMemberAt {
+        address: String,
+        key: String,
+        height: u64,
     },
     Minters {},
+    /// The admin and the holders of each role
+    RoleHolders {},
+    /// The admin and the pending proposal for a new one, if any
+    AdminProposal {},
+    /// The timelock delay and the operations waiting for it
+    QueuedOperations {
+        page: Option<u32>,
+        page_size: u32,
+    },
+    /// The log of privileged actions, newest first
+    AdminHistory {
+        page: Option<u32>,
+        page_size: u32,
+    },
     WithPermit {
-        permit: Permit,
+        permit: Permit<AmberPermissions>,
         query: QueryWithPermit,
     },
     MemberCode {
@@ -515,6 +1080,22 @@ pub enum QueryMsg {
     ValidCodes {
         codes: Vec<String>,
     },
+    MemberTiers {},
+    AttestationKey {},
+    InviteCodes {
+        address: String,
+        key: String,
+    },
+    CodeConsumers {},
+    MembershipHooks {},
+    ListChannels {},
+    ChannelInfo {
+        channels: Vec<String>,
+        /// Include the ids of the notifications emitted by this transaction
+        txhash: Option<String>,
+        address: String,
+        key: String,
+    },
 }
 
 impl QueryMsg {
@@ -524,6 +1105,14 @@ impl QueryMsg {
                 let address = api.addr_validate(address.as_str())?;
                 Ok((vec![address], key.clone()))
             }
+            Self::BalanceAt { address, key, .. } => {
+                let address = api.addr_validate(address.as_str())?;
+                Ok((vec![address], key.clone()))
+            }
+            Self::WasMemberAt { address, key, .. } => {
+                let address = api.addr_validate(address.as_str())?;
+                Ok((vec![address], key.clone()))
+            }
             Self::TransferHistory { address, key, .. } => {
                 let address = api.addr_validate(address.as_str())?;
                 Ok((vec![address], key.clone()))
@@ -555,6 +1144,14 @@ impl QueryMsg {
                 let address = api.addr_validate(address.as_str())?;
                 Ok((vec![address], key.clone()))
             }
+            Self::InviteCodes { address, key } => {
+                let address = api.addr_validate(address.as_str())?;
+                Ok((vec![address], key.clone()))
+            }
+            Self::ChannelInfo { address, key, .. } => {
+                let address = api.addr_validate(address.as_str())?;
+                Ok((vec![address], key.clone()))
+            }
             _ => panic!("This query type does not require authentication"),
         }
     }
@@ -579,17 +1176,58 @@ pub enum QueryWithPermit {
         page_size: u32,
     },
     Balance {},
+    BalanceAt {
+        height: u64,
+    },
+    WasMemberAt {
+        height: u64,
+    },
     TransferHistory {
         page: Option<u32>,
         page_size: u32,
         should_filter_decoys: Option<bool>,
+        start_after_id: Option<u64>,
+        direction: Option<HistoryDirection>,
+        filter: Option<TxFilter>,
     },
     TransactionHistory {
         page: Option<u32>,
         page_size: u32,
         should_filter_decoys: Option<bool>,
+        start_after_id: Option<u64>,
+        direction: Option<HistoryDirection>,
+        filter: Option<TxFilter>,
     },
     MemberCode {},
+    MembershipAttestation {},
+    InviteCodes {},
+    ChannelInfo {
+        channels: Vec<String>,
+        txhash: Option<String>,
+    },
+    /// Whether the account holds at least `amount`, without revealing its balance.
+    /// Set `attest` to also get the answer signed by the contract. Needs a `balance` permit, or a
+    /// `has_at_least` one signed for this `amount`.
+    HasAtLeast {
+        amount: Uint128,
+        attest: Option<bool>,
+    },
+}
+
+/// The permissions a permit for this token can grant. The first four are the standard
+/// `TokenPermissions`, and serialize the same way.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AmberPermissions {
+    Allowance,
+    Balance,
+    History,
+    Owner,
+    /// Only allows `HasAtLeast` for this exact `amount`, so the balance itself stays private and
+    /// the querier cannot search for it with other thresholds
+    HasAtLeast {
+        amount: Uint128,
+    },
 }
 
 #[derive(Serialize, Deserialize, JsonSchema, Debug)]
@@ -600,6 +1238,8 @@ pub enum QueryAnswer {
         symbol: String,
         decimals: u8,
         total_supply: Option<Uint128>,
+        /// The most the total supply may reach by minting, if capped
+        max_supply: Option<Uint128>,
     },
     TokenConfig {
         public_total_supply: bool,
@@ -608,9 +1248,20 @@ pub enum QueryAnswer {
         mint_enabled: bool,
         burn_enabled: bool,
         supported_denoms: Vec<String>,
+        /// The most decoys a message, or a batch action, may have
+        max_decoys: u8,
+        /// Whether execute messages accept a SNIP-50 `gas_target`
+        gas_evaporation: bool,
+        max_supply: Option<Uint128>,
     },
     ContractStatus {
+        /// The strictest preset whose operations are all paused
         status: ContractStatusLevel,
+        flags: PauseFlags,
+    },
+    ContractVersion {
+        /// `None` if the instance has not been migrated since code 563
+        version: Option<ContractVersion>,
     },
     ExchangeRate {
         rate: Uint128,
@@ -635,26 +1286,295 @@ pub enum QueryAnswer {
     Balance {
         amount: Uint128,
     },
+    BalanceAt {
+        amount: Uint128,
+        /// Height of the checkpoint the amount was taken from
+        checkpoint_height: u64,
+    },
+    WasMemberAt {
+        member: bool,
+        tier: Option<u8>,
+        /// Height of the checkpoint the membership was taken from
+        checkpoint_height: u64,
+    },
     TransferHistory {
         txs: Vec<Tx>,
         total: Option<u64>,
+        /// Pass as `start_after_id` to read the next page, `None` on the last page
+        next_cursor: Option<u64>,
+        /// Set if the account removed its txs up to this id from its history
+        pruned_through: Option<u64>,
     },
     TransactionHistory {
         txs: Vec<ExtendedTx>,
         total: Option<u64>,
+        /// Pass as `start_after_id` to read the next page, `None` on the last page
+        next_cursor: Option<u64>,
+        /// Set if the account removed its txs up to this id from its history
+        pruned_through: Option<u64>,
     },
     ViewingKeyError {
         msg: String,
     },
     Minters {
         minters: Vec<Addr>,
+        /// The limits of each minter and what is left of them
+        quotas: Vec<MinterQuotaInfo>,
+    },
+    RoleHolders {
+        admin: Option<Addr>,
+        roles: Vec<RoleHoldersInfo>,
+    },
+    AdminProposal {
+        /// None once the admin has renounced
+        admin: Option<Addr>,
+        proposed_admin: Option<Addr>,
+        expiration: Option<u64>,
+    },
+    QueuedOperations {
+        /// In blocks, 0 if admin messages are not delayed
+        delay: u64,
+        operations: Vec<QueuedOperationInfo>,
+        total: u64,
+    },
+    AdminHistory {
+        entries: Vec<AdminHistoryEntry>,
+        total: u64,
     },
     MemberCode {
         code: String,
+        tier: Option<u8>,
     },
     ValidCodes {
-        codes: Vec<String>,
+        codes: Vec<ValidCode>,
+    },
+    MemberTiers {
+        thresholds: Vec<Uint128>,
+    },
+    AttestationKey {
+        /// Compressed secp256k1 public key
+        pubkey: Binary,
+    },
+    MembershipAttestation {
+        attestation: Attestation,
+        /// The exact bytes that were signed
+        data: Binary,
+        /// secp256k1 signature over `sha256(data)`
+        signature: Binary,
+    },
+    HasAtLeast {
+        result: bool,
+        /// Only set if requested with `attest`
+        attestation: Option<BalanceAttestation>,
+        /// The exact bytes that were signed
+        data: Option<Binary>,
+        /// secp256k1 signature over `sha256(data)`
+        signature: Option<Binary>,
+    },
+    InviteCodes {
+        codes: Vec<InviteCodeInfo>,
+    },
+    CodeConsumers {
+        consumers: Vec<Addr>,
     },
+    MembershipHooks {
+        hooks: Vec<MembershipHookInfo>,
+    },
+    ListChannels {
+        channels: Vec<String>,
+    },
+    ChannelInfo {
+        as_of_block: Uint64,
+        channels: Vec<ChannelInfoData>,
+        /// The account's notification seed, shared by all channels
+        seed: Binary,
+    },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub struct ChannelInfoData {
+    pub channel: String,
+    /// Always `"txhash"`: notification ids are derived from the transaction hash
+    pub mode: String,
+    /// The notification id for the queried transaction, base64 encoded
+    pub answer_id: Option<String>,
+    /// Schema of the decrypted notification data
+    pub cddl: Option<String>,
+}
+
+/// The length of a mint rate period.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MintPeriod {
+    Blocks(u64),
+    Seconds(u64),
+}
+
+/// At most `amount` per period. Periods start at multiples of their length, so they line up
+/// with block heights or unix times rather than with the first mint.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+pub struct MintRate {
+    pub amount: Uint128,
+    pub period: MintPeriod,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub struct MinterQuotaInfo {
+    pub minter: Addr,
+    /// The most the minter may mint over its lifetime, if limited
+    pub allowance: Option<Uint128>,
+    pub minted: Uint128,
+    /// What is left of the allowance, if limited
+    pub remaining: Option<Uint128>,
+    pub rate: Option<MintRate>,
+    /// What is left to mint in the current period, if rate limited
+    pub remaining_in_period: Option<Uint128>,
+    /// The block height or unix time at which the next period starts, if rate limited
+    pub next_period_at: Option<u64>,
+}
+
+/// A privileged action recorded in the admin history, with its parameters.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+#[serde(rename_all = "snake_case")]
+pub enum AdminAction {
+    /// Recorded by both `ChangeAdmin` and `ProposeAdmin`
+    ProposeAdmin {
+        address: Addr,
+        expiration: Option<u64>,
+    },
+    AcceptAdmin {
+        previous_admin: Option<Addr>,
+    },
+    RenounceAdmin {},
+    SetContractStatus {
+        level: ContractStatusLevel,
+    },
+    SetPauseFlags {
+        flags: PauseFlags,
+    },
+    AddMinters {
+        minters: Vec<Addr>,
+    },
+    RemoveMinters {
+        minters: Vec<Addr>,
+    },
+    SetMinters {
+        minters: Vec<Addr>,
+    },
+    AddSupportedDenoms {
+        denoms: Vec<String>,
+    },
+    RemoveSupportedDenoms {
+        denoms: Vec<String>,
+    },
+    CancelAdminProposal {},
+    SetTimelock {
+        delay: u64,
+    },
+    GrantRole {
+        role: Role,
+        account: Addr,
+    },
+    RevokeRole {
+        role: Role,
+        account: Addr,
+    },
+    RenounceRole {
+        role: Role,
+    },
+    SetMinterQuota {
+        minter: Addr,
+        allowance: Option<Uint128>,
+        rate: Option<MintRate>,
+    },
+    SetMaxSupply {
+        max_supply: Uint128,
+    },
+    /// A queued operation cancelled before it was executed
+    CancelQueued {
+        id: u64,
+        queued_by: Addr,
+    },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub struct AdminHistoryEntry {
+    pub actor: Addr,
+    pub action: AdminAction,
+    pub block_time: u64,
+    pub block_height: u64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct QueuedOperationInfo {
+    pub id: u64,
+    pub sender: Addr,
+    pub msg: ExecuteMsg,
+    /// The first height at which the operation can be executed
+    pub not_before: u64,
+    /// The first height at which the operation can no longer be executed
+    pub expires_at: u64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub struct RoleHoldersInfo {
+    pub role: Role,
+    pub holders: Vec<Addr>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct MembershipHookInfo {
+    pub contract: Addr,
+    pub code_hash: String,
+    pub on_error: HookErrorPolicy,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub struct InviteCodeInfo {
+    pub code: String,
+    pub remaining_uses: u32,
+    pub expiration_height: Option<u64>,
+    pub expiration_time: Option<u64>,
+}
+
+/// A statement by the contract that an account held a membership tier at a given height.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub struct Attestation {
+    pub contract: Addr,
+    /// sha256 of the member's address
+    pub address_hash: Binary,
+    pub tier: u8,
+    pub block_height: u64,
+    /// Unix time in seconds after which the attestation should be rejected
+    pub expires_at: u64,
+}
+
+/// A statement by the contract of whether an account held at least `amount` at a given height.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub struct BalanceAttestation {
+    pub contract: Addr,
+    /// sha256 of the account's address
+    pub address_hash: Binary,
+    pub amount: Uint128,
+    pub has_at_least: bool,
+    pub block_height: u64,
+    /// Unix time in seconds after which the attestation should be rejected
+    pub expires_at: u64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub struct ValidCode {
+    pub code: String,
+    pub tier: u8,
 }
 
 #[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
@@ -687,11 +1607,136 @@ pub enum ContractStatusLevel {
     StopAll,
 }
 
-pub fn status_level_to_u8(status_level: &ContractStatusLevel) -> u8 {
-    match status_level {
-        ContractStatusLevel::NormalRun => 0,
-        ContractStatusLevel::StopAllButRedeems => 1,
-        ContractStatusLevel::StopAll => 2,
+impl ContractStatusLevel {
+    /// The pause flags this level is a preset for.
+    pub fn flags(&self) -> PauseFlags {
+        match self {
+            ContractStatusLevel::NormalRun => PauseFlags::default(),
+            ContractStatusLevel::StopAllButRedeems => PauseFlags {
+                redeems: false,
+                ..PauseFlags::all()
+            },
+            ContractStatusLevel::StopAll => PauseFlags::all(),
+        }
+    }
+}
+
+/// The classes of operation that can be paused independently. Flags left out are not paused.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, JsonSchema, Debug)]
+#[serde(default)]
+pub struct PauseFlags {
+    /// Transfers and sends, including their batch and `From` forms
+    pub transfers: bool,
+    pub mints: bool,
+    /// Burns, including `BurnFrom` and `BatchBurnFrom`
+    pub burns: bool,
+    pub deposits: bool,
+    pub redeems: bool,
+    /// Increasing and decreasing allowances
+    pub allowances: bool,
+    /// Regenerating OAC invite codes
+    pub code_regeneration: bool,
+    /// Every other message, except those that change the pause flags or run queued operations
+    pub other: bool,
+}
+
+impl PauseFlags {
+    pub fn all() -> Self {
+        PauseFlags {
+            transfers: true,
+            mints: true,
+            burns: true,
+            deposits: true,
+            redeems: true,
+            allowances: true,
+            code_regeneration: true,
+            other: true,
+        }
+    }
+
+    /// The strictest preset whose operations are all paused, for clients that only know the
+    /// `ContractStatusLevel`s.
+    pub fn level(&self) -> ContractStatusLevel {
+        if *self == PauseFlags::all() {
+            ContractStatusLevel::StopAll
+        } else if *self == ContractStatusLevel::StopAllButRedeems.flags() {
+            ContractStatusLevel::StopAllButRedeems
+        } else {
+            ContractStatusLevel::NormalRun
+        }
+    }
+
+    /// Whether changing to `flags` would resume something that is paused now.
+    pub fn resumed_by(&self, flags: &PauseFlags) -> bool {
+        (self.transfers && !flags.transfers)
+            || (self.mints && !flags.mints)
+            || (self.burns && !flags.burns)
+            || (self.deposits && !flags.deposits)
+            || (self.redeems && !flags.redeems)
+            || (self.allowances && !flags.allowances)
+            || (self.code_regeneration && !flags.code_regeneration)
+            || (self.other && !flags.other)
+    }
+
+    /// Whether `msg` belongs to a paused class of operation.
+    pub fn pauses(&self, msg: &ExecuteMsg) -> bool {
+        match msg {
+            ExecuteMsg::SetContractStatus { .. }
+            | ExecuteMsg::SetPauseFlags { .. }
+            | ExecuteMsg::ExecuteQueued { .. }
+            | ExecuteMsg::CancelQueued { .. } => false,
+            ExecuteMsg::Transfer { .. }
+            | ExecuteMsg::Send { .. }
+            | ExecuteMsg::BatchTransfer { .. }
+            | ExecuteMsg::BatchSend { .. }
+            | ExecuteMsg::TransferFrom { .. }
+            | ExecuteMsg::SendFrom { .. }
+            | ExecuteMsg::BatchTransferFrom { .. }
+            | ExecuteMsg::BatchSendFrom { .. } => self.transfers,
+            ExecuteMsg::Mint { .. } | ExecuteMsg::BatchMint { .. } => self.mints,
+            ExecuteMsg::Burn { .. }
+            | ExecuteMsg::BurnFrom { .. }
+            | ExecuteMsg::BatchBurnFrom { .. } => self.burns,
+            ExecuteMsg::Deposit { .. } => self.deposits,
+            ExecuteMsg::Redeem { .. } => self.redeems,
+            ExecuteMsg::IncreaseAllowance { .. } | ExecuteMsg::DecreaseAllowance { .. } => {
+                self.allowances
+            }
+            ExecuteMsg::RegenerateCode {} => self.code_regeneration,
+            ExecuteMsg::RegisterReceive { .. }
+            | ExecuteMsg::CreateViewingKey { .. }
+            | ExecuteMsg::SetViewingKey { .. }
+            | ExecuteMsg::AddMinters { .. }
+            | ExecuteMsg::RemoveMinters { .. }
+            | ExecuteMsg::SetMinters { .. }
+            | ExecuteMsg::SetMinterQuota { .. }
+            | ExecuteMsg::ChangeAdmin { .. }
+            | ExecuteMsg::ProposeAdmin { .. }
+            | ExecuteMsg::AcceptAdmin { .. }
+            | ExecuteMsg::CancelAdminProposal { .. }
+            | ExecuteMsg::RenounceAdmin { .. }
+            | ExecuteMsg::SetTimelock { .. }
+            | ExecuteMsg::GrantRole { .. }
+            | ExecuteMsg::RevokeRole { .. }
+            | ExecuteMsg::RenounceRole { .. }
+            | ExecuteMsg::SetMaxSupply { .. }
+            | ExecuteMsg::RevokePermit { .. }
+            | ExecuteMsg::AddSupportedDenoms { .. }
+            | ExecuteMsg::RemoveSupportedDenoms { .. }
+            | ExecuteMsg::EnableCheckpoints { .. }
+            | ExecuteMsg::PruneHistory { .. }
+            | ExecuteMsg::ClearHistory { .. }
+            | ExecuteMsg::SetTxLabel { .. }
+            | ExecuteMsg::BackfillMembers { .. }
+            | ExecuteMsg::SetMemberTiers { .. }
+            | ExecuteMsg::ReevaluateMembers { .. }
+            | ExecuteMsg::CreateInviteCode { .. }
+            | ExecuteMsg::ConsumeInviteCode { .. }
+            | ExecuteMsg::AddCodeConsumers { .. }
+            | ExecuteMsg::RemoveCodeConsumers { .. }
+            | ExecuteMsg::AddMembershipHook { .. }
+            | ExecuteMsg::RemoveMembershipHook { .. } => self.other,
+        }
     }
 }
 
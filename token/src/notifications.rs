@@ -0,0 +1,342 @@
+//! SNIP-52 private push notifications:
+//! <https://github.com/SecretFoundation/SNIPs/blob/master/SNIP-52.md>
+//!
+//! Every account has a notification seed derived from the contract's internal secret. When an
+//! execution affects an account, the contract emits a plaintext attribute whose key holds an id
+//! that only the account (and the contract) can compute, and whose value is the notification
+//! data encrypted with the seed. Clients learn their seed from the `ChannelInfo` query and watch
+//! for the ids of the next transactions.
+//!
+//! The id is `hmac_sha256(seed, "{channel}:{tx_hash}")`. The data is a CBOR array, padded with
+//! zero bytes to a multiple of `NOTIFICATION_BLOCK_SIZE`, and encrypted with ChaCha20-Poly1305
+//! using the seed as key, `sha256(channel)[..12] ^ sha256(tx_hash)[..12]` as nonce and
+//! `"{block_height}:{tx_hash}"` as associated data.
+
+use base64::{engine::general_purpose, Engine as _};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use cosmwasm_std::{CanonicalAddr, Env, Response, StdError, StdResult, Storage};
+use secret_toolkit::crypto::sha_256;
+use secret_toolkit::storage::Item;
+
+use crate::btbe::INTERNAL_SECRET;
+use crate::state::safe_add;
+
+/// Incoming transfers, mints and deposits
+pub const CHANNEL_RECVD: &str = "recvd";
+/// Outgoing transfers, burns and redeems
+pub const CHANNEL_SPENT: &str = "spent";
+/// OAC membership, sent to every account whose balance is written, decoys included, so that a
+/// notification does not reveal whether the membership changed
+pub const CHANNEL_OAC: &str = "oac";
+pub const CHANNELS: [&str; 3] = [CHANNEL_RECVD, CHANNEL_SPENT, CHANNEL_OAC];
+
+/// Notification data is padded to a multiple of this size, so all channels look alike.
+pub const NOTIFICATION_BLOCK_SIZE: usize = 64;
+
+pub const CDDL_RECVD: &str = "recvd = [\n  amount: biguint,\n  sender: bstr / null,\n]";
+pub const CDDL_SPENT: &str =
+    "spent = [\n  amount: biguint,\n  recipient: bstr / null,\n  balance: biguint,\n]";
+pub const CDDL_OAC: &str = "oac = [\n  tier: uint / null,\n]";
+
+const SEED_SALT: &[u8] = b"snip52_notification_seed";
+
+/// Notifications raised during the current execution, emitted as attributes when it ends.
+pub static PENDING_NOTIFICATIONS: Item<Vec<Notification>> = Item::new(b"pending_notifications");
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Notification {
+    account: CanonicalAddr,
+    data: NotificationData,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+enum NotificationData {
+    /// `sender` is `None` for deposits
+    Recvd {
+        amount: u128,
+        sender: Option<CanonicalAddr>,
+    },
+    /// `recipient` is `None` for burns and redeems
+    Spent {
+        amount: u128,
+        recipient: Option<CanonicalAddr>,
+        balance: u128,
+    },
+    Oac {
+        tier: Option<u8>,
+    },
+}
+
+impl NotificationData {
+    fn channel(&self) -> &'static str {
+        match self {
+            NotificationData::Recvd { .. } => CHANNEL_RECVD,
+            NotificationData::Spent { .. } => CHANNEL_SPENT,
+            NotificationData::Oac { .. } => CHANNEL_OAC,
+        }
+    }
+
+    /// Fold a later notification on the same channel into this one.
+    /// Amounts add up, and everything else is taken from the later notification.
+    fn merge(self, later: NotificationData) -> NotificationData {
+        match (self, later) {
+            (
+                NotificationData::Recvd { mut amount, .. },
+                NotificationData::Recvd {
+                    amount: later_amount,
+                    sender,
+                },
+            ) => {
+                safe_add(&mut amount, later_amount);
+                NotificationData::Recvd { amount, sender }
+            }
+            (
+                NotificationData::Spent { mut amount, .. },
+                NotificationData::Spent {
+                    amount: later_amount,
+                    recipient,
+                    balance,
+                },
+            ) => {
+                safe_add(&mut amount, later_amount);
+                NotificationData::Spent {
+                    amount,
+                    recipient,
+                    balance,
+                }
+            }
+            (_, later) => later,
+        }
+    }
+
+    /// CBOR encoded, before padding
+    fn encode(&self) -> Vec<u8> {
+        match self {
+            NotificationData::Recvd { amount, sender } => {
+                cbor_array(&[cbor_biguint(*amount), cbor_optional_bytes(sender)])
+            }
+            NotificationData::Spent {
+                amount,
+                recipient,
+                balance,
+            } => cbor_array(&[
+                cbor_biguint(*amount),
+                cbor_optional_bytes(recipient),
+                cbor_biguint(*balance),
+            ]),
+            NotificationData::Oac { tier } => {
+                let tier = match tier {
+                    Some(tier) => cbor_uint(*tier as u64),
+                    None => vec![CBOR_NULL],
+                };
+                cbor_array(&[tier])
+            }
+        }
+    }
+}
+
+pub fn cddl(channel: &str) -> Option<&'static str> {
+    match channel {
+        CHANNEL_RECVD => Some(CDDL_RECVD),
+        CHANNEL_SPENT => Some(CDDL_SPENT),
+        CHANNEL_OAC => Some(CDDL_OAC),
+        _ => None,
+    }
+}
+
+/// The account's notification seed.
+pub fn notification_seed(storage: &dyn Storage, account: &CanonicalAddr) -> StdResult<[u8; 32]> {
+    let secret = INTERNAL_SECRET.load(storage)?;
+
+    let mut seed = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(SEED_SALT), &secret)
+        .expand(account.as_slice(), &mut seed)
+        .map_err(|_| StdError::generic_err("Failed to derive notification seed"))?;
+    Ok(seed)
+}
+
+/// The id of the notification on `channel` for the transaction `tx_hash`.
+pub fn notification_id(seed: &[u8; 32], channel: &str, tx_hash: &str) -> StdResult<Vec<u8>> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(seed)
+        .map_err(|_| StdError::generic_err("Failed to derive notification id"))?;
+    mac.update(format!("{channel}:{tx_hash}").as_bytes());
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+fn encrypt_notification_data(
+    seed: &[u8; 32],
+    channel: &str,
+    tx_hash: &str,
+    block_height: u64,
+    data: &[u8],
+) -> StdResult<Vec<u8>> {
+    let mut padded = data.to_vec();
+    let blocks = data.len().div_ceil(NOTIFICATION_BLOCK_SIZE).max(1);
+    padded.resize(blocks * NOTIFICATION_BLOCK_SIZE, 0);
+
+    let channel_bytes = sha_256(channel.as_bytes());
+    let salt_bytes = sha_256(tx_hash.as_bytes());
+    let mut nonce = [0u8; 12];
+    for (i, byte) in nonce.iter_mut().enumerate() {
+        *byte = channel_bytes[i] ^ salt_bytes[i];
+    }
+
+    let aad = format!("{block_height}:{tx_hash}");
+    ChaCha20Poly1305::new(Key::from_slice(seed))
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &padded,
+                aad: aad.as_bytes(),
+            },
+        )
+        .map_err(|_| StdError::generic_err("Failed to encrypt notification"))
+}
+
+fn cbor_head(major: u8, value: u64) -> Vec<u8> {
+    let major = major << 5;
+    match value {
+        0..=23 => vec![major | value as u8],
+        24..=0xff => vec![major | 24, value as u8],
+        0x100..=0xffff => [vec![major | 25], (value as u16).to_be_bytes().to_vec()].concat(),
+        0x1_0000..=0xffff_ffff => {
+            [vec![major | 26], (value as u32).to_be_bytes().to_vec()].concat()
+        }
+        _ => [vec![major | 27], value.to_be_bytes().to_vec()].concat(),
+    }
+}
+
+fn cbor_array(items: &[Vec<u8>]) -> Vec<u8> {
+    [cbor_head(4, items.len() as u64), items.concat()].concat()
+}
+
+fn cbor_uint(value: u64) -> Vec<u8> {
+    cbor_head(0, value)
+}
+
+fn cbor_bytes(bytes: &[u8]) -> Vec<u8> {
+    [cbor_head(2, bytes.len() as u64), bytes.to_vec()].concat()
+}
+
+fn cbor_optional_bytes(address: &Option<CanonicalAddr>) -> Vec<u8> {
+    match address {
+        Some(address) => cbor_bytes(address.as_slice()),
+        None => vec![CBOR_NULL],
+    }
+}
+
+/// Amounts are always encoded as a 16 byte bignum, so their size does not depend on their value.
+fn cbor_biguint(value: u128) -> Vec<u8> {
+    [vec![0xc2], cbor_bytes(&value.to_be_bytes())].concat()
+}
+
+const CBOR_NULL: u8 = 0xf6;
+
+pub struct NotificationsStore {}
+impl NotificationsStore {
+    /// Queue a notification to be emitted at the end of the execution.
+    /// The id only depends on the channel and the transaction, so an account gets at most one
+    /// notification per channel. A later one is merged into the one already queued, so that a
+    /// batch reports the total it moved.
+    fn queue(
+        storage: &mut dyn Storage,
+        account: &CanonicalAddr,
+        data: NotificationData,
+    ) -> StdResult<()> {
+        let mut pending = PENDING_NOTIFICATIONS.may_load(storage)?.unwrap_or_default();
+        let queued = pending.iter_mut().find(|notification| {
+            &notification.account == account && notification.data.channel() == data.channel()
+        });
+        match queued {
+            Some(queued) => queued.data = queued.data.clone().merge(data),
+            None => pending.push(Notification {
+                account: account.clone(),
+                data,
+            }),
+        }
+        PENDING_NOTIFICATIONS.save(storage, &pending)
+    }
+
+    pub fn notify_received(
+        storage: &mut dyn Storage,
+        recipient: &CanonicalAddr,
+        amount: u128,
+        sender: Option<&CanonicalAddr>,
+    ) -> StdResult<()> {
+        let data = NotificationData::Recvd {
+            amount,
+            sender: sender.cloned(),
+        };
+        Self::queue(storage, recipient, data)
+    }
+
+    pub fn notify_spent(
+        storage: &mut dyn Storage,
+        owner: &CanonicalAddr,
+        amount: u128,
+        recipient: Option<&CanonicalAddr>,
+        balance: u128,
+    ) -> StdResult<()> {
+        let data = NotificationData::Spent {
+            amount,
+            recipient: recipient.cloned(),
+            balance,
+        };
+        Self::queue(storage, owner, data)
+    }
+
+    /// `tier` is the account's tier after the change, or `None` if it is no longer a member.
+    pub fn notify_membership(
+        storage: &mut dyn Storage,
+        account: &CanonicalAddr,
+        tier: Option<u8>,
+    ) -> StdResult<()> {
+        Self::queue(storage, account, NotificationData::Oac { tier })
+    }
+
+    /// Drain the queued notifications and add an attribute to the response for each of them.
+    /// Without a transaction hash there is nothing to derive ids from, so they are dropped.
+    pub fn dispatch(
+        storage: &mut dyn Storage,
+        env: &Env,
+        response: Response,
+    ) -> StdResult<Response> {
+        let pending = match PENDING_NOTIFICATIONS.may_load(storage)? {
+            Some(pending) => pending,
+            None => return Ok(response),
+        };
+        PENDING_NOTIFICATIONS.remove(storage);
+
+        let tx_hash = match &env.transaction {
+            Some(transaction) => transaction.hash.clone(),
+            None => return Ok(response),
+        };
+
+        let mut response = response;
+        for notification in pending {
+            let channel = notification.data.channel();
+            let seed = notification_seed(storage, &notification.account)?;
+            let id = notification_id(&seed, channel, &tx_hash)?;
+            let data = encrypt_notification_data(
+                &seed,
+                channel,
+                &tx_hash,
+                env.block.height,
+                &notification.data.encode(),
+            )?;
+
+            response = response.add_attribute_plaintext(
+                format!("snip52:{}", general_purpose::STANDARD.encode(id)),
+                general_purpose::STANDARD.encode(data),
+            );
+        }
+
+        Ok(response)
+    }
+}
@@ -0,0 +1,120 @@
+//! Roles that each gate a group of admin commands.
+//!
+//! The admin is the role admin: it grants and revokes roles, but only holds the ones it was
+//! granted. It is granted all of them when the contract is instantiated, or migrated to schema 6,
+//! so nothing changes until it hands them out.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Addr, StdError, StdResult, Storage};
+use secret_toolkit::storage::Keyset;
+
+/// The holders of a role. Suffixed by the role's name.
+pub static ROLE_HOLDERS: Keyset<Addr> = Keyset::new(b"role_holders");
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Can change the contract status
+    Pauser,
+    /// Can add, remove and limit minters, and lower the max supply
+    MinterManager,
+    /// Can add and remove supported denoms
+    DenomManager,
+    /// Can set the OAC tiers and code consumers, and enroll or re-evaluate members
+    OacManager,
+    /// Can add and remove membership hooks
+    HookManager,
+}
+
+pub const ROLES: [Role; 5] = [
+    Role::Pauser,
+    Role::MinterManager,
+    Role::DenomManager,
+    Role::OacManager,
+    Role::HookManager,
+];
+
+impl Role {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Role::Pauser => "pauser",
+            Role::MinterManager => "minter_manager",
+            Role::DenomManager => "denom_manager",
+            Role::OacManager => "oac_manager",
+            Role::HookManager => "hook_manager",
+        }
+    }
+
+    fn holders(&self) -> Keyset<Addr> {
+        ROLE_HOLDERS.add_suffix(self.name().as_bytes())
+    }
+}
+
+pub struct RolesStore {}
+impl RolesStore {
+    pub fn has_role(storage: &dyn Storage, role: Role, account: &Addr) -> bool {
+        role.holders().contains(storage, account)
+    }
+
+    pub fn check_role(storage: &dyn Storage, role: Role, account: &Addr) -> StdResult<()> {
+        if !Self::has_role(storage, role, account) {
+            return Err(StdError::generic_err(format!(
+                "This command requires the {} role",
+                role.name()
+            )));
+        }
+
+        Ok(())
+    }
+
+    pub fn grant(storage: &mut dyn Storage, role: Role, account: &Addr) -> StdResult<()> {
+        role.holders().insert(storage, account)?;
+        Ok(())
+    }
+
+    pub fn revoke(storage: &mut dyn Storage, role: Role, account: &Addr) -> StdResult<()> {
+        let holders = role.holders();
+        if holders.contains(storage, account) {
+            holders.remove(storage, account)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn grant_all(storage: &mut dyn Storage, account: &Addr) -> StdResult<()> {
+        for role in ROLES {
+            Self::grant(storage, role, account)?;
+        }
+
+        Ok(())
+    }
+
+    /// Move every role `from` holds to `to`.
+    pub fn transfer_all(storage: &mut dyn Storage, from: &Addr, to: &Addr) -> StdResult<()> {
+        for role in ROLES {
+            if Self::has_role(storage, role, from) {
+                Self::revoke(storage, role, from)?;
+                Self::grant(storage, role, to)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Revoke every role from every holder.
+    pub fn revoke_all(storage: &mut dyn Storage) -> StdResult<()> {
+        for role in ROLES {
+            for holder in Self::load_holders(storage, role)? {
+                Self::revoke(storage, role, &holder)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn load_holders(storage: &dyn Storage, role: Role) -> StdResult<Vec<Addr>> {
+        role.holders().iter(storage)?.collect()
+    }
+}
@@ -6,14 +6,16 @@
 //! |   └-- Constants
 //! |-- KEY_TOTAL_SUPPLY
 //! |   └-- u128
-//! |-- KEY_CONTRACT_STATUS
-//! |   └-- u8
+//! |-- KEY_PAUSE_FLAGS
+//! |   └-- PauseFlags
 //! |-- KEY_MINTERS
 //! |   └-- Vec<Addr>
-//! └-- KEY_TX_COUNT
-//!     └-- u64
+//! |-- KEY_TX_COUNT
+//! |   └-- u64
+//! └-- KEY_CONTRACT_VERSION
+//!     └-- ContractVersion
 //!
-//! PREFIX_BALANCES
+//! PREFIX_BALANCES (legacy, emptied as accounts settle into the BTBE)
 //! |-- CanonicalAddr
 //! |   └-- u128
 //! |-- CanonicalAddr
@@ -42,6 +44,12 @@
 //! └-- account: CanonicalAddr
 //!     └-- sha256(key)
 //!
+//! PREFIX_MINTER_QUOTAS
+//! |-- minter: Addr
+//! |   └-- MinterQuota
+//! └-- minter: Addr
+//!     └-- MinterQuota
+//!
 //! PREFIX_RECEIVERS
 //! |-- Addr
 //! |   └-- code_hash
@@ -50,12 +58,21 @@
 //! └-- Addr
 //!     └-- code_hash
 //!
+//! PREFIX_CHECKPOINTS_ENABLED
+//! |-- CanonicalAddr
+//! └-- CanonicalAddr
+//!
+//! [PREFIX_CHECKPOINTS + addr_canonical]
+//! |-- Checkpoint
+//! |-- Checkpoint
+//! └-- Checkpoint
+//!
 //! [PREFIX_TXS + addr_canonical]
 //! |-- StoredExtendedTx
 //! |-- StoredExtendedTx
 //! └-- StoredExtendedTx
 //!
-//! [PREFIX_TRANSFERS + addr_canonical]
+//! [PREFIX_TRANSFERS + addr_canonical] (legacy, no longer written since schema 4)
 //! |-- StoredLegacyTransfer
 //! |-- StoredLegacyTransfer
 //! └-- StoredLegacyTransfer
@@ -66,20 +83,27 @@ use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::any::type_name;
 
-use cosmwasm_std::{Addr, CanonicalAddr, Env, StdError, StdResult, Storage};
+use cosmwasm_std::{Addr, BlockInfo, CanonicalAddr, Env, StdError, StdResult, Storage};
 use cosmwasm_storage::{prefixed, prefixed_read, PrefixedStorage, ReadonlyPrefixedStorage};
 
-use secret_toolkit::crypto::SHA256_HASH_SIZE;
-use secret_toolkit::storage::{Keymap, Keyset};
+use secret_toolkit::crypto::{ContractPrng, SHA256_HASH_SIZE};
+use secret_toolkit::storage::{AppendStore, Item, Keymap, Keyset};
 
 use crate::amber::OneAmberStore;
-use crate::msg::{status_level_to_u8, u8_to_status_level, ContractStatusLevel};
+use crate::anonymity_set::AnonymitySet;
+use crate::btbe;
+use crate::dwb::{DelayedWriteBuffer, TxNode};
+use crate::msg::{u8_to_status_level, ContractStatusLevel, MintPeriod, MintRate, PauseFlags};
+use crate::notifications::NotificationsStore;
 
 pub const KEY_CONSTANTS: &[u8] = b"constants";
 pub const KEY_TOTAL_SUPPLY: &[u8] = b"total_supply";
+/// Held the `ContractStatusLevel` as a u8 until schema 8 replaced it with `KEY_PAUSE_FLAGS`
 pub const KEY_CONTRACT_STATUS: &[u8] = b"contract_status";
+pub const KEY_PAUSE_FLAGS: &[u8] = b"pause_flags";
 pub const KEY_MINTERS: &[u8] = b"minters";
 pub const KEY_TX_COUNT: &[u8] = b"tx-count"; // dash bothers me, but that's how it was
+pub const KEY_CONTRACT_VERSION: &[u8] = b"contract_version";
 
 pub const PREFIX_CONFIG: &[u8] = b"config";
 pub const PREFIX_BALANCES: &[u8] = b"balances";
@@ -87,6 +111,9 @@ pub const PREFIX_ALLOWANCES: &[u8] = b"allowances";
 pub const PREFIX_ALLOWED: &[u8] = b"allowed";
 pub const PREFIX_VIEW_KEY: &[u8] = b"viewingkey";
 pub const PREFIX_RECEIVERS: &[u8] = b"receivers";
+pub const PREFIX_MINTER_QUOTAS: &[u8] = b"minter_quotas";
+pub const PREFIX_CHECKPOINTS_ENABLED: &[u8] = b"checkpoints_enabled";
+pub const PREFIX_CHECKPOINTS: &[u8] = b"checkpoints";
 
 // Config
 
@@ -94,7 +121,8 @@ pub const PREFIX_RECEIVERS: &[u8] = b"receivers";
 #[cfg_attr(test, derive(Eq, PartialEq))]
 pub struct Constants {
     pub name: String,
-    pub admin: Addr,
+    // none once the admin has renounced, leaving the token immutable
+    pub admin: Option<Addr>,
     pub symbol: String,
     pub decimals: u8,
     pub prng_seed: Vec<u8>,
@@ -112,6 +140,83 @@ pub struct Constants {
     pub contract_address: Addr,
     // coin denoms that are supported for deposit/redeem
     pub supported_denoms: Vec<String>,
+    // the most the total supply may reach by minting, if capped
+    pub max_supply: Option<u128>,
+}
+
+/// `Constants` as stored from schema 5 until schema 7, when there always was an admin.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct ConstantsV5 {
+    name: String,
+    admin: Addr,
+    symbol: String,
+    decimals: u8,
+    prng_seed: Vec<u8>,
+    total_supply_is_public: bool,
+    deposit_is_enabled: bool,
+    redeem_is_enabled: bool,
+    mint_is_enabled: bool,
+    burn_is_enabled: bool,
+    contract_address: Addr,
+    supported_denoms: Vec<String>,
+    max_supply: Option<u128>,
+}
+
+impl From<ConstantsV5> for Constants {
+    fn from(v5: ConstantsV5) -> Self {
+        Constants {
+            name: v5.name,
+            admin: Some(v5.admin),
+            symbol: v5.symbol,
+            decimals: v5.decimals,
+            prng_seed: v5.prng_seed,
+            total_supply_is_public: v5.total_supply_is_public,
+            deposit_is_enabled: v5.deposit_is_enabled,
+            redeem_is_enabled: v5.redeem_is_enabled,
+            mint_is_enabled: v5.mint_is_enabled,
+            burn_is_enabled: v5.burn_is_enabled,
+            contract_address: v5.contract_address,
+            supported_denoms: v5.supported_denoms,
+            max_supply: v5.max_supply,
+        }
+    }
+}
+
+/// `Constants` as stored before schema 5, without `max_supply`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct LegacyConstants {
+    name: String,
+    admin: Addr,
+    symbol: String,
+    decimals: u8,
+    prng_seed: Vec<u8>,
+    total_supply_is_public: bool,
+    deposit_is_enabled: bool,
+    redeem_is_enabled: bool,
+    mint_is_enabled: bool,
+    burn_is_enabled: bool,
+    contract_address: Addr,
+    supported_denoms: Vec<String>,
+}
+
+impl From<LegacyConstants> for ConstantsV5 {
+    fn from(legacy: LegacyConstants) -> Self {
+        ConstantsV5 {
+            name: legacy.name,
+            admin: legacy.admin,
+            symbol: legacy.symbol,
+            decimals: legacy.decimals,
+            prng_seed: legacy.prng_seed,
+            total_supply_is_public: legacy.total_supply_is_public,
+            deposit_is_enabled: legacy.deposit_is_enabled,
+            redeem_is_enabled: legacy.redeem_is_enabled,
+            mint_is_enabled: legacy.mint_is_enabled,
+            burn_is_enabled: legacy.burn_is_enabled,
+            contract_address: legacy.contract_address,
+            supported_denoms: legacy.supported_denoms,
+            max_supply: None,
+        }
+    }
 }
 
 impl Constants {
@@ -132,6 +237,43 @@ impl ConfigStore {
             .map_err(|e| StdError::serialize_err(type_name::<Constants>(), e))
     }
 
+    fn load_constants_v5(store: &dyn Storage) -> StdResult<ConstantsV5> {
+        get_bin_data(prefixed_read(store, PREFIX_CONFIG), KEY_CONSTANTS)
+    }
+
+    /// Rewrite constants from before schema 5 in the layout of `ConstantsV5`, with no max supply.
+    /// Constants in that layout or the current one are left as they are. Newer layouts are tried
+    /// first, since bincode ignores the trailing max supply when reading them as an older one.
+    pub fn upgrade_constants_to_v5(store: &mut dyn Storage) -> StdResult<()> {
+        if Self::load_constants(store).is_ok() || Self::load_constants_v5(store).is_ok() {
+            return Ok(());
+        }
+
+        let legacy: LegacyConstants =
+            get_bin_data(prefixed_read(store, PREFIX_CONFIG), KEY_CONSTANTS)?;
+        let mut config_store = prefixed(store, PREFIX_CONFIG);
+        set_bin_data(&mut config_store, KEY_CONSTANTS, &ConstantsV5::from(legacy))
+    }
+
+    /// The admin from constants in the layout of `ConstantsV5` or the current one.
+    pub fn load_admin_v5(store: &dyn Storage) -> StdResult<Option<Addr>> {
+        match Self::load_constants(store) {
+            Ok(constants) => Ok(constants.admin),
+            Err(_) => Ok(Some(Self::load_constants_v5(store)?.admin)),
+        }
+    }
+
+    /// Rewrite constants in the layout of `ConstantsV5` in the current one, where the admin is
+    /// optional. Constants that are already current are left as they are.
+    pub fn upgrade_constants_to_v7(store: &mut dyn Storage) -> StdResult<()> {
+        if Self::load_constants(store).is_ok() {
+            return Ok(());
+        }
+
+        let v5 = Self::load_constants_v5(store)?;
+        Self::set_constants(store, &v5.into())
+    }
+
     pub fn load_total_supply(store: &dyn Storage) -> StdResult<u128> {
         let config_store = prefixed_read(store, PREFIX_CONFIG);
         let supply_bytes = config_store
@@ -141,15 +283,31 @@ impl ConfigStore {
         slice_to_u128(&supply_bytes)
     }
 
+    /// The strictest status level whose operations are all paused.
     pub fn load_contract_status(store: &dyn Storage) -> StdResult<ContractStatusLevel> {
+        Ok(Self::load_pause_flags(store)?.level())
+    }
+
+    pub fn load_pause_flags(store: &dyn Storage) -> StdResult<PauseFlags> {
         let config_store = prefixed_read(store, PREFIX_CONFIG);
-        let supply_bytes = config_store
-            .get(KEY_CONTRACT_STATUS)
-            .expect("no contract status stored in config");
+        get_bin_data(config_store, KEY_PAUSE_FLAGS)
+    }
+
+    /// Replace the status level stored before schema 8 with the pause flags it is a preset for.
+    /// Storage that already has pause flags is left as it is.
+    pub fn upgrade_contract_status(store: &mut dyn Storage) -> StdResult<()> {
+        let config_store = prefixed_read(store, PREFIX_CONFIG);
+        if config_store.get(KEY_PAUSE_FLAGS).is_some() {
+            return Ok(());
+        }
 
-        // These unwraps are ok because we know we stored things correctly
-        let status = slice_to_u8(&supply_bytes).unwrap();
-        u8_to_status_level(status)
+        let level = match config_store.get(KEY_CONTRACT_STATUS) {
+            Some(status_bytes) => u8_to_status_level(slice_to_u8(&status_bytes)?)?,
+            None => ContractStatusLevel::NormalRun,
+        };
+        Self::set_pause_flags(store, &level.flags())?;
+        prefixed(store, PREFIX_CONFIG).remove(KEY_CONTRACT_STATUS);
+        Ok(())
     }
 
     pub fn load_tx_count(store: &dyn Storage) -> u64 {
@@ -168,14 +326,17 @@ impl ConfigStore {
         Ok(())
     }
 
+    /// Set the pause flags to the preset for `status`.
     pub fn set_contract_status(
         store: &mut dyn Storage,
         status: &ContractStatusLevel,
     ) -> StdResult<()> {
+        Self::set_pause_flags(store, &status.flags())
+    }
+
+    pub fn set_pause_flags(store: &mut dyn Storage, flags: &PauseFlags) -> StdResult<()> {
         let mut config_store = prefixed(store, PREFIX_CONFIG);
-        let status_u8 = status_level_to_u8(status);
-        config_store.set(KEY_CONTRACT_STATUS, &status_u8.to_be_bytes());
-        Ok(())
+        set_bin_data(&mut config_store, KEY_PAUSE_FLAGS, flags)
     }
 
     pub fn set_tx_count(store: &mut dyn Storage, count: &u64) -> StdResult<()> {
@@ -184,6 +345,34 @@ impl ConfigStore {
     }
 }
 
+// Contract Version
+
+/// Identifies the code and storage layout of a deployed instance.
+/// Instances migrated from code 563 have no version stored until their first migrate.
+#[derive(Serialize, Debug, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+pub struct ContractVersion {
+    pub name: String,
+    pub version: String,
+    /// Incremented whenever the storage layout changes.
+    pub schema: u32,
+}
+
+pub struct ContractVersionStore {}
+impl ContractVersionStore {
+    pub fn may_load(store: &dyn Storage) -> StdResult<Option<ContractVersion>> {
+        let config_store = prefixed_read(store, PREFIX_CONFIG);
+        config_store
+            .get(KEY_CONTRACT_VERSION)
+            .map(|bytes| deser_bin_data(&bytes))
+            .transpose()
+    }
+
+    pub fn save(store: &mut dyn Storage, version: &ContractVersion) -> StdResult<()> {
+        let mut config_store = prefixed(store, PREFIX_CONFIG);
+        set_bin_data(&mut config_store, KEY_CONTRACT_VERSION, version)
+    }
+}
+
 // old-style functions
 
 fn ser_bin_data<T: Serialize>(obj: &T) -> StdResult<Vec<u8>> {
@@ -214,6 +403,27 @@ fn get_bin_data<T: DeserializeOwned>(storage: ReadonlyPrefixedStorage, key: &[u8
     }
 }
 
+// Admin
+
+/// A new admin proposed by the current one, waiting for it to accept.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AdminProposal {
+    pub address: Addr,
+    /// Unix time in seconds after which the proposal can no longer be accepted
+    pub expiration: Option<u64>,
+}
+
+impl AdminProposal {
+    pub fn is_expired_at(&self, block: &BlockInfo) -> bool {
+        match self.expiration {
+            Some(time) => block.time.seconds() >= time,
+            None => false,
+        }
+    }
+}
+
+pub static ADMIN_PROPOSAL: Item<AdminProposal> = Item::new(b"admin_proposal");
+
 // Prng
 
 // Has a separate interface now, but it still lives inside the Constants struct.
@@ -238,6 +448,53 @@ impl PrngStore {
 
 // Minters
 
+/// A minter's limits and how much of them it has used. Minters without one are unlimited.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct MinterQuota {
+    /// The most the minter may mint over its lifetime
+    pub allowance: Option<u128>,
+    pub minted: u128,
+    pub rate: Option<MintRate>,
+    /// The start of the period that `minted_in_period` counts, in blocks or seconds
+    pub period_start: u64,
+    pub minted_in_period: u128,
+}
+
+/// The start and length of the period that `block` falls in.
+fn current_period(period: MintPeriod, block: &BlockInfo) -> (u64, u64) {
+    let (now, len) = match period {
+        MintPeriod::Blocks(len) => (block.height, len.max(1)),
+        MintPeriod::Seconds(len) => (block.time.seconds(), len.max(1)),
+    };
+    (now - now % len, len)
+}
+
+impl MinterQuota {
+    /// What is left of the allowance, if limited.
+    pub fn remaining(&self) -> Option<u128> {
+        self.allowance
+            .map(|allowance| allowance.saturating_sub(self.minted))
+    }
+
+    /// What is left to mint in the current period and when the next one starts, if rate limited.
+    pub fn remaining_in_period(&self, block: &BlockInfo) -> Option<(u128, u64)> {
+        let rate = self.rate.as_ref()?;
+        let (start, len) = current_period(rate.period, block);
+        let used = if start == self.period_start {
+            self.minted_in_period
+        } else {
+            0
+        };
+        Some((
+            rate.amount.u128().saturating_sub(used),
+            start.saturating_add(len),
+        ))
+    }
+}
+
+/// Kept when a minter is removed, so adding it back does not reset what it has minted.
+pub static MINTER_QUOTAS: Keymap<Addr, MinterQuota> = Keymap::new(PREFIX_MINTER_QUOTAS);
+
 pub struct MintersStore {}
 impl MintersStore {
     pub fn load(store: &dyn Storage) -> StdResult<Vec<Addr>> {
@@ -266,6 +523,63 @@ impl MintersStore {
 
         Self::save(store, loaded_minters)
     }
+
+    pub fn load_quota(store: &dyn Storage, minter: &Addr) -> Option<MinterQuota> {
+        MINTER_QUOTAS.get(store, minter)
+    }
+
+    /// Set a minter's limits. What it already minted keeps counting against the new ones.
+    pub fn set_quota(
+        store: &mut dyn Storage,
+        minter: &Addr,
+        allowance: Option<u128>,
+        rate: Option<MintRate>,
+    ) -> StdResult<()> {
+        let mut quota = Self::load_quota(store, minter).unwrap_or_default();
+        quota.allowance = allowance;
+        quota.rate = rate;
+        MINTER_QUOTAS.insert(store, minter, &quota)
+    }
+
+    /// Count `amount` against the minter's limits, failing if it exceeds either of them.
+    pub fn use_quota(
+        store: &mut dyn Storage,
+        minter: &Addr,
+        amount: u128,
+        block: &BlockInfo,
+    ) -> StdResult<()> {
+        let mut quota = match Self::load_quota(store, minter) {
+            Some(quota) => quota,
+            None => return Ok(()),
+        };
+
+        if let Some(remaining) = quota.remaining() {
+            if amount > remaining {
+                return Err(StdError::generic_err(format!(
+                    "Minting {amount} would exceed the minter's remaining allowance of {remaining}"
+                )));
+            }
+        }
+
+        if let Some(rate) = &quota.rate {
+            let (start, _) = current_period(rate.period, block);
+            if start != quota.period_start {
+                quota.period_start = start;
+                quota.minted_in_period = 0;
+            }
+
+            let remaining = rate.amount.u128().saturating_sub(quota.minted_in_period);
+            if amount > remaining {
+                return Err(StdError::generic_err(format!(
+                    "Minting {amount} would exceed the minter's rate limit, {remaining} is left in the current period"
+                )));
+            }
+            quota.minted_in_period = quota.minted_in_period.saturating_add(amount);
+        }
+
+        quota.minted = quota.minted.saturating_add(amount);
+        MINTER_QUOTAS.insert(store, minter, &quota)
+    }
 }
 
 // Balances
@@ -285,24 +599,53 @@ pub fn safe_add(balance: &mut u128, amount: u128) -> u128 {
 
 pub struct BalancesStore {}
 impl BalancesStore {
-    fn save(store: &mut dyn Storage, account: &CanonicalAddr, amount: u128) {
-        let mut balances = prefixed(store, PREFIX_BALANCES);
-        balances.set(account.as_slice(), &amount.to_be_bytes());
-    }
-
-    pub fn load(store: &dyn Storage, account: &CanonicalAddr) -> u128 {
+    /// Returns the balance written before the balance trie existed, if the account has not
+    /// settled into the trie since.
+    fn load_legacy(store: &dyn Storage, account: &CanonicalAddr) -> StdResult<u128> {
         let balances_store = prefixed_read(store, PREFIX_BALANCES);
 
         let account_bytes = account.as_slice();
         let result = balances_store.get(account_bytes);
 
         match result {
-            // This unwrap is ok because we know we stored things correctly
-            Some(balance_bytes) => slice_to_u128(&balance_bytes).unwrap(),
-            None => 0,
+            Some(balance_bytes) => slice_to_u128(&balance_bytes),
+            None => Ok(0),
+        }
+    }
+
+    /// Returns the account's balance, excluding any credits pending in the delayed write buffer.
+    pub fn load_settled(store: &dyn Storage, account: &CanonicalAddr) -> StdResult<u128> {
+        match btbe::stored_balance(store, account)? {
+            Some(balance) => Ok(balance),
+            None => Self::load_legacy(store, account),
         }
     }
 
+    pub fn save_settled(
+        store: &mut dyn Storage,
+        account: &CanonicalAddr,
+        amount: u128,
+    ) -> StdResult<()> {
+        if btbe::set_balance(store, account, amount)? {
+            // The legacy balance was included in `amount`, so it has now moved into the trie
+            let mut balances = prefixed(store, PREFIX_BALANCES);
+            balances.remove(account.as_slice());
+        }
+
+        Ok(())
+    }
+
+    pub fn load(store: &dyn Storage, account: &CanonicalAddr) -> StdResult<u128> {
+        let mut balance = Self::load_settled(store, account)?;
+        let dwb = DelayedWriteBuffer::load(store)?;
+        safe_add(&mut balance, dwb.pending_amount(account));
+
+        Ok(balance)
+    }
+
+    /// Credits go into the delayed write buffer, so storage writes do not reveal the recipient.
+    /// Debits settle the account's pending credits first and then update the balance trie.
+    /// Decoys go through the same steps with an amount of 0.
     pub fn update_balance(
         store: &mut dyn Storage,
         env: &Env,
@@ -313,81 +656,240 @@ impl BalancesStore {
         decoys: &Option<Vec<CanonicalAddr>>,
         account_random_pos: &Option<usize>,
     ) -> StdResult<()> {
+        let mut accounts_to_be_written: Vec<&CanonicalAddr> = vec![];
         match decoys {
-            None => {
-                let mut balance = Self::load(store, account);
-                let previous_balance = balance;
-
-                balance = match should_add {
-                    true => {
-                        safe_add(&mut balance, amount_to_be_updated);
-                        balance
-                    }
-                    false => {
-                        if let Some(balance) = balance.checked_sub(amount_to_be_updated) {
-                            balance
-                        } else {
-                            return Err(StdError::generic_err(format!(
-                                "insufficient funds to {operation_name}: balance={balance}, required={amount_to_be_updated}",
-                            )));
-                        }
-                    }
-                };
-
-                OneAmberStore::update_member(store, account, previous_balance, balance, env)?;
-
-                Self::save(store, account, balance);
-                Ok(())
-            }
+            None => accounts_to_be_written.push(account),
             Some(decoys_vec) => {
                 // It should always be set when decoys_vec is set
                 let account_pos = account_random_pos.unwrap();
 
-                let mut accounts_to_be_written: Vec<&CanonicalAddr> = vec![];
-
                 let (first_part, second_part) = decoys_vec.split_at(account_pos);
                 accounts_to_be_written.extend(first_part);
                 accounts_to_be_written.push(account);
                 accounts_to_be_written.extend(second_part);
+            }
+        }
 
-                // In a case where the account is also a decoy somehow
-                let mut was_account_updated = false;
-
-                for acc in accounts_to_be_written.iter() {
-                    // Always load account balance to obfuscate the real account
-                    // Please note that decoys are not always present in the DB. In this case it is ok beacuse load will return 0.
-                    let mut acc_balance = Self::load(store, acc);
-                    let mut new_balance = acc_balance;
-
-                    let p_balance = acc_balance;
-
-                    if *acc == account && !was_account_updated {
-                        was_account_updated = true;
-                        new_balance = match should_add {
-                            true => {
-                                safe_add(&mut acc_balance, amount_to_be_updated);
-                                acc_balance
-                            }
-                            false => {
-                                if let Some(balance) = acc_balance.checked_sub(amount_to_be_updated)
-                                {
-                                    balance
-                                } else {
-                                    return Err(StdError::generic_err(format!(
-                                        "insufficient funds to {operation_name}: balance={acc_balance}, required={amount_to_be_updated}",
-                                    )));
-                                }
-                            }
-                        };
-
-                        OneAmberStore::update_member(store, account, p_balance, new_balance, env)?;
-                    }
-                    Self::save(store, acc, new_balance);
+        let mut dwb = DelayedWriteBuffer::load(store)?;
+        // Picks which entry to evict when the buffer is full
+        let seed = env
+            .block
+            .random
+            .as_ref()
+            .ok_or_else(|| StdError::generic_err("Block randomness is unavailable"))?;
+        let mut rng = ContractPrng::new(seed.as_slice(), account.as_slice());
+
+        // In a case where the account is also a decoy somehow
+        let mut was_account_updated = false;
+
+        for acc in accounts_to_be_written {
+            let is_account = acc == account && !was_account_updated;
+            was_account_updated |= is_account;
+            let amount = if is_account { amount_to_be_updated } else { 0 };
+
+            let new_balance = match should_add {
+                true => {
+                    let mut balance = Self::load_settled(store, acc)?;
+                    safe_add(&mut balance, dwb.pending_amount(acc));
+                    safe_add(&mut balance, amount);
+                    dwb.add_recipient(store, &mut rng, acc, amount)?;
+                    balance
                 }
+                false => {
+                    dwb.settle_account(store, acc)?;
+                    let balance = Self::load_settled(store, acc)?;
+                    let new_balance = if let Some(new_balance) = balance.checked_sub(amount) {
+                        new_balance
+                    } else {
+                        return Err(StdError::generic_err(format!(
+                            "insufficient funds to {operation_name}: balance={balance}, required={amount}",
+                        )));
+                    };
+                    Self::save_settled(store, acc, new_balance)?;
+                    new_balance
+                }
+            };
 
-                Ok(())
+            if is_account {
+                OneAmberStore::update_member(store, account, new_balance, env)?;
+                AnonymitySet::record(store, account)?;
+            }
+            // Every account written gets its tier, changed or not, so the notifications do not
+            // tell the account apart from its decoys
+            NotificationsStore::notify_membership(store, acc, OneAmberStore::get_tier(store, acc))?;
+            // A debit writes the account's balance anyway, decoys included. Credits are only
+            // recorded once they settle, so they write nothing tied to the recipient.
+            if !should_add {
+                CheckpointsStore::record(store, acc, new_balance, env.block.height)?;
             }
         }
+
+        dwb.save(store)
+    }
+}
+
+// Checkpoints
+//
+// Opt-in history of an account's balance and OAC tier, one entry per block in which either changed.
+// Lets other contracts ask what an account held at a past height (e.g. a governance snapshot).
+
+#[derive(Serialize, Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub height: u64,
+    /// Balance at the end of `height`.
+    pub balance: u128,
+    /// OAC tier at the end of `height`, if the account was a member.
+    pub tier: Option<u8>,
+}
+
+pub static CHECKPOINTS_ENABLED: Keyset<CanonicalAddr> = Keyset::new(PREFIX_CHECKPOINTS_ENABLED);
+pub static CHECKPOINTS: AppendStore<Checkpoint> = AppendStore::new(PREFIX_CHECKPOINTS);
+pub struct CheckpointsStore {}
+impl CheckpointsStore {
+    pub fn is_enabled(store: &dyn Storage, account: &CanonicalAddr) -> bool {
+        CHECKPOINTS_ENABLED.contains(store, account)
+    }
+
+    /// Starts recording checkpoints for `account`, beginning with its current state.
+    pub fn enable(store: &mut dyn Storage, account: &CanonicalAddr, height: u64) -> StdResult<()> {
+        if Self::is_enabled(store, account) {
+            return Ok(());
+        }
+        CHECKPOINTS_ENABLED.insert(store, account)?;
+        Self::settle_and_record(store, account, height)
+    }
+
+    /// Records the account's state at `height` after a change made outside a balance update.
+    /// Its pending credits settle first, so they are not recorded after a later checkpoint.
+    pub fn settle_and_record(
+        store: &mut dyn Storage,
+        account: &CanonicalAddr,
+        height: u64,
+    ) -> StdResult<()> {
+        if !Self::is_enabled(store, account) {
+            return Ok(());
+        }
+        let mut dwb = DelayedWriteBuffer::load(store)?;
+        dwb.settle_account(store, account)?;
+        dwb.save(store)?;
+
+        let balance = BalancesStore::load_settled(store, account)?;
+        Self::record(store, account, balance, height)
+    }
+
+    /// Records the account's state at `height` if it has opted in.
+    /// Only called where the account's own balance is written anyway, when it spends (decoys
+    /// included) or its pending credits settle. A credit writes nothing tied to the recipient,
+    /// whether or not it has opted in.
+    pub fn record(
+        store: &mut dyn Storage,
+        account: &CanonicalAddr,
+        balance: u128,
+        height: u64,
+    ) -> StdResult<()> {
+        if !Self::is_enabled(store, account) {
+            return Ok(());
+        }
+        let checkpoint = Checkpoint {
+            height,
+            balance,
+            tier: OneAmberStore::get_tier(store, account),
+        };
+        Self::push(store, account, &checkpoint)
+    }
+
+    /// Records a checkpoint for each height the account was credited at, now that the credits
+    /// in `nodes` (oldest first) have settled on top of `settled_balance`.
+    pub fn record_settled_credits(
+        store: &mut dyn Storage,
+        account: &CanonicalAddr,
+        settled_balance: u128,
+        nodes: &[TxNode],
+    ) -> StdResult<()> {
+        if !Self::is_enabled(store, account) {
+            return Ok(());
+        }
+        for checkpoint in Self::credit_checkpoints(account, settled_balance, nodes) {
+            Self::push(store, account, &checkpoint)?;
+        }
+
+        Ok(())
+    }
+
+    /// The checkpoints the credits in `nodes` (oldest first) add on top of `balance`, each with
+    /// the tier the account was in right after it.
+    fn credit_checkpoints(
+        account: &CanonicalAddr,
+        mut balance: u128,
+        nodes: &[TxNode],
+    ) -> Vec<Checkpoint> {
+        let mut checkpoints = vec![];
+        for node in nodes {
+            let credit = node.tx.credited_to(account);
+            if credit == 0 {
+                continue;
+            }
+            safe_add(&mut balance, credit);
+            checkpoints.push(Checkpoint {
+                height: node.tx.block_height(),
+                balance,
+                tier: node.tier,
+            });
+        }
+
+        checkpoints
+    }
+
+    fn push(
+        store: &mut dyn Storage,
+        account: &CanonicalAddr,
+        checkpoint: &Checkpoint,
+    ) -> StdResult<()> {
+        let checkpoints = CHECKPOINTS.add_suffix(account.as_slice());
+        let len = checkpoints.get_len(store)?;
+        // Several updates in the same block collapse into a single checkpoint
+        if len > 0 && checkpoints.get_at(store, len - 1)?.height == checkpoint.height {
+            checkpoints.set_at(store, len - 1, checkpoint)
+        } else {
+            checkpoints.push(store, checkpoint)
+        }
+    }
+
+    /// Returns the latest checkpoint at or before `height`, counting the credits still pending
+    /// in the delayed write buffer.
+    pub fn load_at(
+        store: &dyn Storage,
+        account: &CanonicalAddr,
+        height: u64,
+    ) -> StdResult<Option<Checkpoint>> {
+        if !Self::is_enabled(store, account) {
+            return Ok(None);
+        }
+        let mut nodes = DelayedWriteBuffer::load(store)?.pending_txs(store, account)?;
+        nodes.reverse();
+        let settled_balance = BalancesStore::load_settled(store, account)?;
+        let pending = Self::credit_checkpoints(account, settled_balance, &nodes)
+            .into_iter()
+            .take_while(|checkpoint| checkpoint.height <= height)
+            .last();
+        if pending.is_some() {
+            return Ok(pending);
+        }
+
+        let checkpoints = CHECKPOINTS.add_suffix(account.as_slice());
+        let (mut low, mut high) = (0, checkpoints.get_len(store)?);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if checkpoints.get_at(store, mid)?.height <= height {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        match low {
+            0 => Ok(None),
+            _ => checkpoints.get_at(store, low - 1).map(Some),
+        }
     }
 }
 
@@ -0,0 +1,89 @@
+//! An optional delay on sensitive admin messages.
+//!
+//! While the delay is set, those messages are queued instead of executed. Anyone can see the
+//! queue, and each operation can be executed by the account that queued it once the delay has
+//! passed, or cancelled by it or the admin before then. An operation that is not executed within
+//! the grace period after its delay expires.
+
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Addr, Binary, StdError, StdResult, Storage};
+use secret_toolkit::storage::{Item, Keymap};
+
+/// The longest delay the admin can set, in blocks. About a week of 6 second blocks.
+pub const MAX_TIMELOCK_DELAY: u64 = 100_800;
+/// How many blocks a queued operation can still be executed for once its delay has passed.
+pub const TIMELOCK_GRACE_PERIOD: u64 = 100_800;
+
+/// The delay in blocks, or 0 if messages execute right away.
+pub static TIMELOCK_DELAY: Item<u64> = Item::new(b"timelock_delay");
+pub static QUEUED_OPERATIONS: Keymap<u64, QueuedOperation> = Keymap::new(b"queued_operations");
+pub static QUEUED_OPERATION_COUNT: Item<u64> = Item::new(b"queued_operation_count");
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct QueuedOperation {
+    pub sender: Addr,
+    /// The JSON encoded `ExecuteMsg`, so it still reads the same after new variants are added
+    pub msg: Binary,
+    /// The first height at which the operation can be executed
+    pub not_before: u64,
+}
+
+impl QueuedOperation {
+    /// The first height at which the operation can no longer be executed
+    pub fn expires_at(&self) -> u64 {
+        self.not_before.saturating_add(TIMELOCK_GRACE_PERIOD)
+    }
+}
+
+pub struct TimelockStore {}
+impl TimelockStore {
+    pub fn delay(storage: &dyn Storage) -> StdResult<u64> {
+        Ok(TIMELOCK_DELAY.may_load(storage)?.unwrap_or_default())
+    }
+
+    pub fn set_delay(storage: &mut dyn Storage, delay: u64) -> StdResult<()> {
+        TIMELOCK_DELAY.save(storage, &delay)
+    }
+
+    /// Add an operation to the queue and return its id.
+    pub fn queue(storage: &mut dyn Storage, operation: &QueuedOperation) -> StdResult<u64> {
+        let id = QUEUED_OPERATION_COUNT
+            .may_load(storage)?
+            .unwrap_or_default()
+            + 1;
+        QUEUED_OPERATIONS.insert(storage, &id, operation)?;
+        QUEUED_OPERATION_COUNT.save(storage, &id)?;
+        Ok(id)
+    }
+
+    pub fn load(storage: &dyn Storage, id: u64) -> StdResult<QueuedOperation> {
+        QUEUED_OPERATIONS
+            .get(storage, &id)
+            .ok_or_else(|| StdError::generic_err(format!("There is no queued operation {id}")))
+    }
+
+    pub fn remove(storage: &mut dyn Storage, id: u64) -> StdResult<()> {
+        QUEUED_OPERATIONS.remove(storage, &id)
+    }
+
+    pub fn load_all(storage: &dyn Storage) -> StdResult<Vec<(u64, QueuedOperation)>> {
+        QUEUED_OPERATIONS.iter(storage)?.collect()
+    }
+
+    /// A page of the queue, and the number of operations in it.
+    pub fn load_page(
+        storage: &dyn Storage,
+        page: u32,
+        page_size: u32,
+    ) -> StdResult<(Vec<(u64, QueuedOperation)>, u64)> {
+        let total = QUEUED_OPERATIONS.get_len(storage)?;
+        let operations = QUEUED_OPERATIONS
+            .iter(storage)?
+            .skip((page as usize).saturating_mul(page_size as usize))
+            .take(page_size as usize)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        Ok((operations, total as u64))
+    }
+}
@@ -1,15 +1,27 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::ops::Range;
 
 use cosmwasm_std::{Addr, Api, CanonicalAddr, Coin, StdError, StdResult, Storage, Uint128};
 use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+use secret_toolkit::storage::{Item, Keymap};
 
+use crate::dwb::DelayedWriteBuffer;
 use crate::legacy_support::{AppendStore, AppendStoreMut};
 use crate::state::ConfigStore;
 
 const PREFIX_TXS: &[u8] = b"transactions";
+/// Transfers used to be stored twice, here as well as in `PREFIX_TXS`. Nothing is added to it
+/// since schema 4, and the transfer history is read from `PREFIX_TXS` instead.
 const PREFIX_TRANSFERS: &[u8] = b"transfers";
 
+/// The id of the last tx from before schema 4. Older transfers are read from `PREFIX_TRANSFERS`.
+static LEGACY_TRANSFERS_END: Item<u64> = Item::new(b"legacy_transfers_end");
+/// The id of the newest record each account has removed from its own history.
+static HISTORY_PRUNED_THROUGH: Keymap<CanonicalAddr, u64> = Keymap::new(b"history_pruned_through");
+/// Labels by tx id, suffixed by the account that set them.
+static TX_LABELS: Keymap<u64, TxLabel> = Keymap::new(b"tx_labels");
+
 // Note that id is a globally incrementing counter.
 // Since it's 64 bits long, even at 50 tx/s it would take
 // over 11 billion years for it to rollback. I'm pretty sure
@@ -66,10 +78,203 @@ pub struct ExtendedTx {
     pub memo: Option<String>,
     pub block_time: u64,
     pub block_height: u64,
+    /// Only returned to the account that set it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<TxLabel>,
+}
+
+/// A private note and tags an account attached to a tx in its own history.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TxLabel {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    pub tags: Vec<String>,
+}
+
+pub const MAX_LABEL_NOTE_LEN: usize = 256;
+pub const MAX_LABEL_TAGS: usize = 8;
+pub const MAX_LABEL_TAG_LEN: usize = 32;
+
+impl TxLabel {
+    pub fn validate(&self) -> StdResult<()> {
+        if self
+            .note
+            .as_ref()
+            .map_or(false, |note| note.len() > MAX_LABEL_NOTE_LEN)
+        {
+            return Err(StdError::generic_err(format!(
+                "Label notes are limited to {MAX_LABEL_NOTE_LEN} bytes"
+            )));
+        }
+        if self.tags.len() > MAX_LABEL_TAGS {
+            return Err(StdError::generic_err(format!(
+                "Labels are limited to {MAX_LABEL_TAGS} tags"
+            )));
+        }
+        if self
+            .tags
+            .iter()
+            .any(|tag| tag.is_empty() || tag.len() > MAX_LABEL_TAG_LEN)
+        {
+            return Err(StdError::generic_err(format!(
+                "Tags must be between 1 and {MAX_LABEL_TAG_LEN} bytes long"
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.note.as_ref().map_or(true, |note| note.is_empty()) && self.tags.is_empty()
+    }
+}
+
+/// The kind of a `TxAction`, for filtering history.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TxActionKind {
+    Transfer,
+    Mint,
+    Burn,
+    Deposit,
+    Redeem,
+    Decoy,
+}
+
+impl TxActionKind {
+    fn tx_code(self) -> TxCode {
+        match self {
+            TxActionKind::Transfer => TxCode::Transfer,
+            TxActionKind::Mint => TxCode::Mint,
+            TxActionKind::Burn => TxCode::Burn,
+            TxActionKind::Deposit => TxCode::Deposit,
+            TxActionKind::Redeem => TxCode::Redeem,
+            TxActionKind::Decoy => TxCode::Decoy,
+        }
+    }
+}
+
+/// Conditions a tx must meet to be returned. Unset fields match every tx, and ranges are
+/// inclusive.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TxFilter {
+    /// Match any of these kinds of action
+    pub actions: Option<Vec<TxActionKind>>,
+    pub min_block_height: Option<u64>,
+    pub max_block_height: Option<u64>,
+    /// In seconds since the epoch
+    pub min_block_time: Option<u64>,
+    pub max_block_time: Option<u64>,
+    /// Match txs this address takes part in
+    pub counterparty: Option<String>,
+    /// Match txs with a non-empty memo if `true`, or without one if `false`
+    pub has_memo: Option<bool>,
+}
+
+impl TxFilter {
+    pub fn into_stored(self, api: &dyn Api) -> StdResult<StoredTxFilter> {
+        let counterparty = match self.counterparty {
+            Some(counterparty) => Some(api.addr_canonicalize(counterparty.as_str())?),
+            None => None,
+        };
+
+        Ok(StoredTxFilter {
+            tx_types: self.actions.map(|actions| {
+                actions
+                    .into_iter()
+                    .map(|action| action.tx_code().to_u8())
+                    .collect()
+            }),
+            min_block_height: self.min_block_height,
+            max_block_height: self.max_block_height,
+            min_block_time: self.min_block_time,
+            max_block_time: self.max_block_time,
+            counterparty,
+            has_memo: self.has_memo,
+        })
+    }
+}
+
+/// The order in which history is read.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryDirection {
+    /// Newest first
+    #[default]
+    Descending,
+    /// Oldest first
+    Ascending,
 }
 
 // Stored types:
 
+/// A `TxFilter` with the counterparty canonicalized, ready to match stored txs.
+#[derive(Clone, Debug)]
+pub struct StoredTxFilter {
+    tx_types: Option<Vec<u8>>,
+    min_block_height: Option<u64>,
+    max_block_height: Option<u64>,
+    min_block_time: Option<u64>,
+    max_block_time: Option<u64>,
+    counterparty: Option<CanonicalAddr>,
+    has_memo: Option<bool>,
+}
+
+impl StoredTxFilter {
+    fn matches(&self, tx: &StoredExtendedTx) -> bool {
+        let action = &tx.action;
+        self.matches_fields(
+            action.tx_type,
+            tx.block_height,
+            tx.block_time,
+            [&action.address1, &action.address2, &action.address3]
+                .into_iter()
+                .flatten(),
+            &tx.memo,
+        )
+    }
+
+    /// Transfers are matched as the transfer txs they were recorded from.
+    fn matches_transfer(&self, transfer: &StoredLegacyTransfer) -> bool {
+        let tx_type = match transfer.is_decoy() {
+            true => TxCode::Decoy,
+            false => TxCode::Transfer,
+        };
+        self.matches_fields(
+            tx_type.to_u8(),
+            transfer.block_height,
+            transfer.block_time,
+            [&transfer.from, &transfer.sender, &transfer.receiver].into_iter(),
+            &transfer.memo,
+        )
+    }
+
+    fn matches_fields<'a>(
+        &self,
+        tx_type: u8,
+        block_height: u64,
+        block_time: u64,
+        mut addresses: impl Iterator<Item = &'a CanonicalAddr>,
+        memo: &Option<String>,
+    ) -> bool {
+        let in_range = |value: u64, min: Option<u64>, max: Option<u64>| {
+            min.map_or(true, |min| value >= min) && max.map_or(true, |max| value <= max)
+        };
+
+        self.tx_types
+            .as_ref()
+            .map_or(true, |tx_types| tx_types.contains(&tx_type))
+            && in_range(block_height, self.min_block_height, self.max_block_height)
+            && in_range(block_time, self.min_block_time, self.max_block_time)
+            && self.counterparty.as_ref().map_or(true, |counterparty| {
+                addresses.any(|address| address == counterparty)
+            })
+            && self.has_memo.map_or(true, |has_memo| {
+                has_memo == memo.as_ref().map_or(false, |memo| !memo.is_empty())
+            })
+    }
+}
+
 // REMOVED
 
 /// This type is the stored version of the legacy transfers
@@ -101,7 +306,8 @@ impl StoredLegacyTransfer {
         Ok(tx)
     }
 
-    fn append_transfer(
+    /// Only used to settle transfers that were buffered before schema 4.
+    pub fn append_transfer(
         store: &mut dyn Storage,
         tx: &StoredLegacyTransfer,
         for_address: &CanonicalAddr,
@@ -112,6 +318,8 @@ impl StoredLegacyTransfer {
         store.push(tx)
     }
 
+    /// The transfer history is a projection of the account's txs, after the transfers recorded
+    /// before schema 4 in the legacy store.
     pub fn get_transfers(
         api: &dyn Api,
         storage: &dyn Storage,
@@ -119,46 +327,176 @@ impl StoredLegacyTransfer {
         page: u32,
         page_size: u32,
         should_filter_decoys: bool,
-    ) -> StdResult<(Vec<Tx>, u64)> {
-        let store = ReadonlyPrefixedStorage::multilevel(
+        start_after_id: Option<u64>,
+        direction: HistoryDirection,
+        filter: Option<&StoredTxFilter>,
+    ) -> StdResult<(Vec<Tx>, Option<u64>, Option<u64>)> {
+        let legacy_end = LEGACY_TRANSFERS_END.may_load(storage)?.unwrap_or_default();
+
+        let legacy_store = ReadonlyPrefixedStorage::multilevel(
             storage,
             &[PREFIX_TRANSFERS, for_address.as_slice()],
         );
+        let legacy_store =
+            AppendStore::<StoredLegacyTransfer, _>::attach(&legacy_store).transpose()?;
 
-        // Try to access the storage of transfers for the account.
-        // If it doesn't exist yet, return an empty list of transfers.
-        let store = AppendStore::<StoredLegacyTransfer, _>::attach(&store);
-        let store = if let Some(result) = store {
-            result?
-        } else {
-            return Ok((vec![], 0));
+        // Try to access the storage of txs for the account.
+        // If it doesn't exist yet, the account only has txs pending in the buffer, if any.
+        let tx_store =
+            ReadonlyPrefixedStorage::multilevel(storage, &[PREFIX_TXS, for_address.as_slice()]);
+        let tx_store = AppendStore::<StoredExtendedTx, _>::attach(&tx_store).transpose()?;
+
+        // Txs up to `legacy_end` are already in the legacy store, so skip past them
+        let first_new = match &tx_store {
+            Some(tx_store) if legacy_end > 0 => {
+                first_position_after(tx_store.len(), legacy_end, |pos| {
+                    Ok(tx_store.get_at(pos)?.id)
+                })?
+            }
+            _ => 0,
+        };
+        let project = |positions: Range<u32>| {
+            tx_store
+                .iter()
+                .flat_map(move |tx_store| {
+                    tx_store
+                        .iter()
+                        .skip(positions.start as usize)
+                        .take(positions.len())
+                })
+                .filter_map(|tx| match tx {
+                    Ok(tx) => tx.into_transfer().map(Ok),
+                    Err(err) => Some(Err(err)),
+                })
         };
 
-        // Take `page_size` txs starting from the latest tx, potentially skipping `page * page_size`
-        // txs from the start.
-        let transfer_iter = store
+        // Only the settled entries past the cursor are read
+        let legacy_positions = match &legacy_store {
+            Some(legacy_store) => {
+                positions_after_cursor(legacy_store.len(), start_after_id, direction, |pos| {
+                    Ok(legacy_store.get_at(pos)?.id)
+                })?
+            }
+            None => 0..0,
+        };
+        let projected_positions = match &tx_store {
+            Some(tx_store) => {
+                let positions = positions_after_cursor(
+                    tx_store.len() - first_new,
+                    start_after_id,
+                    direction,
+                    |pos| Ok(tx_store.get_at(first_new + pos)?.id),
+                )?;
+                positions.start + first_new..positions.end + first_new
+            }
+            None => 0..0,
+        };
+
+        // Txs waiting in the delayed write buffer are newer than any settled ones
+        let pending: Vec<StoredLegacyTransfer> = DelayedWriteBuffer::load(storage)?
+            .pending_txs(storage, &for_address)?
+            .into_iter()
+            .filter_map(|node| node.tx.into_transfer())
+            .collect();
+        let is_listed =
+            |transfer: &StoredLegacyTransfer| !(should_filter_decoys && transfer.is_decoy());
+        let pending_total = pending
             .iter()
-            .rev()
-            .skip((page * page_size) as _)
-            .take(page_size as _);
-
-        // The `and_then` here flattens the `StdResult<StdResult<ExtendedTx>>` to an `StdResult<ExtendedTx>`
-        let transfers: StdResult<Vec<Tx>> = if should_filter_decoys {
-            transfer_iter
-                .filter(|transfer| match transfer {
-                    Err(_) => true,
-                    Ok(t) => t.block_height != 0,
+            .filter(|transfer| is_listed(transfer))
+            .count() as u64;
+
+        let history_page = read_page(
+            legacy_store
+                .iter()
+                .flat_map(|legacy_store| {
+                    legacy_store
+                        .iter()
+                        .skip(legacy_positions.start as usize)
+                        .take(legacy_positions.len())
                 })
-                .map(|tx| tx.map(|tx| tx.into_humanized(api)).and_then(|x| x))
-                .collect()
-        } else {
-            transfer_iter
-                .map(|tx| tx.map(|tx| tx.into_humanized(api)).and_then(|x| x))
-                .collect()
+                .chain(project(projected_positions)),
+            pending,
+            page,
+            page_size,
+            |transfer| {
+                is_listed(transfer)
+                    && filter.map_or(true, |filter| filter.matches_transfer(transfer))
+            },
+            start_after_id,
+            direction,
+        )?;
+        let next_cursor = history_page.next_cursor;
+        // Only txs are counted in storage, so transfers are counted by reading them all, unless
+        // the page already did. Filtered totals are only reported when they come free.
+        let total = match (history_page.matching_total, filter) {
+            (Some(total), _) => Some(total),
+            (None, Some(_)) => None,
+            (None, None) => {
+                let settled_len = tx_store.as_ref().map_or(0, |tx_store| tx_store.len());
+                let settled = legacy_store
+                    .iter()
+                    .flat_map(|legacy_store| legacy_store.iter())
+                    .chain(project(first_new..settled_len));
+                let mut total = pending_total;
+                for transfer in settled {
+                    if is_listed(&transfer?) {
+                        total += 1;
+                    }
+                }
+                Some(total)
+            }
         };
 
-        transfers.map(|txs| (txs, store.len() as u64))
+        let transfers: StdResult<Vec<Tx>> = history_page
+            .entries
+            .into_iter()
+            .map(|transfer| transfer.into_humanized(api))
+            .collect();
+
+        transfers.map(|txs| (txs, total, next_cursor))
+    }
+}
+
+/// The position of the first entry with an id above `id`, or `len` if there is none.
+/// Ids only grow along an account's history, so this is a binary search.
+fn first_position_after(
+    len: u32,
+    id: u64,
+    id_at: impl Fn(u32) -> StdResult<u64>,
+) -> StdResult<u32> {
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if id_at(mid)? <= id {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
     }
+
+    Ok(low)
+}
+
+/// The positions of the settled entries that come after `start_after_id` when read in
+/// `direction`. The cursor is found by binary search, so the entries before it are never read.
+fn positions_after_cursor(
+    len: u32,
+    start_after_id: Option<u64>,
+    direction: HistoryDirection,
+    id_at: impl Fn(u32) -> StdResult<u64>,
+) -> StdResult<Range<u32>> {
+    let positions = match (start_after_id, direction) {
+        (None, _) => 0..len,
+        (Some(cursor), HistoryDirection::Descending) => match cursor.checked_sub(1) {
+            Some(id) => 0..first_position_after(len, id, id_at)?,
+            None => 0..0,
+        },
+        (Some(cursor), HistoryDirection::Ascending) => {
+            first_position_after(len, cursor, id_at)?..len
+        }
+    };
+
+    Ok(positions)
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -243,12 +581,20 @@ impl StoredTxAction {
             address3: None,
         }
     }
-    fn decoy(recipient: &CanonicalAddr) -> Self {
+    /// A decoy of a transfer keeps its `from` and `sender`, so that it can appear in the
+    /// transfer history.
+    fn decoy(recipient: &CanonicalAddr, of: &StoredTxAction) -> Self {
+        let (from, sender) = if of.tx_type == TxCode::Transfer.to_u8() {
+            (of.address1.clone(), of.address2.clone())
+        } else {
+            (None, None)
+        };
+
         Self {
             tx_type: TxCode::Decoy.to_u8(),
             address1: Some(recipient.clone()),
-            address2: None,
-            address3: None,
+            address2: from,
+            address3: sender,
         }
     }
 
@@ -339,6 +685,25 @@ impl StoredExtendedTx {
         }
     }
 
+    pub fn block_height(&self) -> u64 {
+        self.block_height
+    }
+
+    /// The amount the tx added to the account's balance, or 0 if it did not credit the account.
+    pub fn credited_to(&self, account: &CanonicalAddr) -> u128 {
+        let recipient = match TxCode::from_u8(self.action.tx_type) {
+            Ok(TxCode::Transfer) => self.action.address3.as_ref(),
+            Ok(TxCode::Mint) => self.action.address2.as_ref(),
+            Ok(TxCode::Deposit) => return self.coins.amount.u128(),
+            _ => None,
+        };
+
+        match recipient {
+            Some(recipient) if recipient == account => self.coins.amount.u128(),
+            _ => 0,
+        }
+    }
+
     fn into_humanized(self, api: &dyn Api) -> StdResult<ExtendedTx> {
         Ok(ExtendedTx {
             id: self.id,
@@ -347,19 +712,35 @@ impl StoredExtendedTx {
             memo: self.memo,
             block_time: self.block_time,
             block_height: self.block_height,
+            label: None,
         })
     }
 
-    fn from_stored_legacy_transfer(transfer: StoredLegacyTransfer) -> Self {
-        let action = StoredTxAction::transfer(transfer.from, transfer.sender, transfer.receiver);
-        Self {
-            id: transfer.id,
-            action,
-            coins: transfer.coins,
-            memo: transfer.memo,
-            block_time: transfer.block_time,
-            block_height: transfer.block_height,
-        }
+    /// The tx as it appears in the transfer history, if it is a transfer or a decoy of one.
+    fn into_transfer(self) -> Option<StoredLegacyTransfer> {
+        let action = self.action;
+        let (from, sender, receiver, block_height) = match TxCode::from_u8(action.tx_type) {
+            Ok(TxCode::Transfer) => (
+                action.address1?,
+                action.address2?,
+                action.address3?,
+                self.block_height,
+            ),
+            // The transfer history has always marked decoys with a block height of 0
+            Ok(TxCode::Decoy) => (action.address2?, action.address3?, action.address1?, 0),
+            _ => return None,
+        };
+
+        Some(StoredLegacyTransfer {
+            id: self.id,
+            from,
+            sender,
+            receiver,
+            coins: self.coins,
+            memo: self.memo,
+            block_time: self.block_time,
+            block_height,
+        })
     }
 
     fn append_tx(
@@ -379,46 +760,178 @@ impl StoredExtendedTx {
         page: u32,
         page_size: u32,
         should_filter_decoys: bool,
-    ) -> StdResult<(Vec<ExtendedTx>, u64)> {
+        start_after_id: Option<u64>,
+        direction: HistoryDirection,
+        filter: Option<&StoredTxFilter>,
+    ) -> StdResult<(Vec<ExtendedTx>, Option<u64>, Option<u64>)> {
         let store =
             ReadonlyPrefixedStorage::multilevel(storage, &[PREFIX_TXS, for_address.as_slice()]);
 
         // Try to access the storage of txs for the account.
-        // If it doesn't exist yet, return an empty list of transfers.
-        let store = AppendStore::<StoredExtendedTx, _>::attach(&store);
-        let store = if let Some(result) = store {
-            result?
-        } else {
-            return Ok((vec![], 0));
+        // If it doesn't exist yet, the account only has txs pending in the buffer, if any.
+        let store = AppendStore::<StoredExtendedTx, _>::attach(&store).transpose()?;
+        let settled_len = store.as_ref().map_or(0, |store| store.len() as u64);
+        // Only the settled txs past the cursor are read
+        let positions = match &store {
+            Some(store) => positions_after_cursor(store.len(), start_after_id, direction, |pos| {
+                Ok(store.get_at(pos)?.id)
+            })?,
+            None => 0..0,
         };
 
-        // Take `page_size` txs starting from the latest tx, potentially skipping `page * page_size`
-        // txs from the start.
-        let tx_iter = store
-            .iter()
-            .rev()
-            .skip((page * page_size) as _)
-            .take(page_size as _);
-
-        // The `and_then` here flattens the `StdResult<StdResult<ExtendedTx>>` to an `StdResult<ExtendedTx>`
-        let txs: StdResult<Vec<ExtendedTx>> = if should_filter_decoys {
-            tx_iter
-                .filter(|tx| match tx {
-                    Err(_) => true,
-                    Ok(t) => t.action.tx_type != TxCode::Decoy.to_u8(),
-                })
-                .map(|tx| tx.map(|tx| tx.into_humanized(api)).and_then(|x| x))
-                .collect()
-        } else {
-            tx_iter
-                .map(|tx| tx.map(|tx| tx.into_humanized(api)).and_then(|x| x))
-                .collect()
+        // Txs waiting in the delayed write buffer are newer than any settled ones
+        let pending: Vec<StoredExtendedTx> = DelayedWriteBuffer::load(storage)?
+            .pending_txs(storage, &for_address)?
+            .into_iter()
+            .map(|node| node.tx)
+            .collect();
+        let total = settled_len + pending.len() as u64;
+
+        let history_page = read_page(
+            store.iter().flat_map(|store| {
+                store
+                    .iter()
+                    .skip(positions.start as usize)
+                    .take(positions.len())
+            }),
+            pending,
+            page,
+            page_size,
+            |tx| {
+                !(should_filter_decoys && tx.is_decoy())
+                    && filter.map_or(true, |filter| filter.matches(tx))
+            },
+            start_after_id,
+            direction,
+        )?;
+        let next_cursor = history_page.next_cursor;
+        // With a filter, the stored total would count txs that were never going to be returned
+        let total = match filter {
+            Some(_) => history_page.matching_total,
+            None => Some(total),
         };
 
-        txs.map(|txs| (txs, store.len() as u64))
+        // The caller is authenticated as the account, so its labels can go along
+        let labels = TX_LABELS.add_suffix(for_address.as_slice());
+        let txs: StdResult<Vec<ExtendedTx>> = history_page
+            .entries
+            .into_iter()
+            .map(|tx| {
+                let mut tx = tx.into_humanized(api)?;
+                tx.label = labels.get(storage, &tx.id);
+                Ok(tx)
+            })
+            .collect();
+
+        txs.map(|txs| (txs, total, next_cursor))
+    }
+}
+
+/// A stored history entry, as far as paging is concerned.
+trait HistoryEntry {
+    fn id(&self) -> u64;
+    fn is_decoy(&self) -> bool;
+}
+
+impl HistoryEntry for StoredLegacyTransfer {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn is_decoy(&self) -> bool {
+        self.block_height == 0
     }
 }
 
+impl HistoryEntry for StoredExtendedTx {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn is_decoy(&self) -> bool {
+        self.action.tx_type == TxCode::Decoy.to_u8()
+    }
+}
+
+/// A page of an account's history.
+struct HistoryPage<T> {
+    entries: Vec<T>,
+    /// The id to continue after, if there are more entries
+    next_cursor: Option<u64>,
+    /// The number of entries that match, if the whole history had to be read anyway
+    matching_total: Option<u64>,
+}
+
+/// Reads a page of an account's history, made of its `settled` entries (oldest first) followed by
+/// its `pending` ones (newest first, as the delayed write buffer returns them).
+///
+/// Ids only grow along an account's history, so `start_after_id` picks up right after the last
+/// entry of the previous page, even if new entries arrived in between. Callers leave the settled
+/// entries before the cursor out with `positions_after_cursor`. Without it, the first
+/// `page * page_size` matching entries are skipped. Entries are matched before the page is cut,
+/// so the page is full unless the history runs out. A `page_size` of 0 reads no entries, only
+/// their count.
+fn read_page<'a, T: HistoryEntry + 'a>(
+    settled: impl DoubleEndedIterator<Item = StdResult<T>> + 'a,
+    pending: Vec<T>,
+    page: u32,
+    page_size: u32,
+    matches: impl Fn(&T) -> bool,
+    start_after_id: Option<u64>,
+    direction: HistoryDirection,
+) -> StdResult<HistoryPage<T>> {
+    let all_entries: Box<dyn Iterator<Item = StdResult<T>> + 'a> = match direction {
+        HistoryDirection::Descending => Box::new(pending.into_iter().map(Ok).chain(settled.rev())),
+        HistoryDirection::Ascending => Box::new(settled.chain(pending.into_iter().rev().map(Ok))),
+    };
+
+    let is_after_cursor = |entry: &T| match (start_after_id, direction) {
+        (None, _) => true,
+        (Some(cursor), HistoryDirection::Descending) => entry.id() < cursor,
+        (Some(cursor), HistoryDirection::Ascending) => entry.id() > cursor,
+    };
+    let skip = match start_after_id {
+        Some(_) => 0,
+        None => page as usize * page_size as usize,
+    };
+
+    let mut skipped = 0;
+    let mut uncollected = 0;
+    let mut entries = Vec::with_capacity(page_size as usize);
+    let mut next_cursor = None;
+    for entry in all_entries {
+        let entry = entry?;
+        if !is_after_cursor(&entry) || !matches(&entry) {
+            continue;
+        }
+        if skipped < skip {
+            skipped += 1;
+            continue;
+        }
+        if page_size == 0 {
+            uncollected += 1;
+            continue;
+        }
+        if entries.len() == page_size as usize {
+            next_cursor = entries.last().map(HistoryEntry::id);
+            break;
+        }
+        entries.push(entry);
+    }
+
+    // Counting from the start to the end of the history is the only time the total comes free
+    let matching_total = match (start_after_id, next_cursor) {
+        (None, None) => Some((skipped + uncollected + entries.len()) as u64),
+        _ => None,
+    };
+
+    Ok(HistoryPage {
+        entries,
+        next_cursor,
+        matching_total,
+    })
+}
+
 // Storage functions:
 
 fn increment_tx_count(store: &mut dyn Storage) -> StdResult<u64> {
@@ -427,6 +940,40 @@ fn increment_tx_count(store: &mut dyn Storage) -> StdResult<u64> {
     Ok(id)
 }
 
+/// Record where the legacy transfer history ends, unless it has been recorded already.
+pub fn record_legacy_transfers_end(store: &mut dyn Storage) -> StdResult<()> {
+    if LEGACY_TRANSFERS_END.may_load(store)?.is_some() {
+        return Ok(());
+    }
+
+    LEGACY_TRANSFERS_END.save(store, &ConfigStore::load_tx_count(store))
+}
+
+/// Appends a settled tx to the account's history.
+pub fn append_settled_tx(
+    store: &mut dyn Storage,
+    tx: &StoredExtendedTx,
+    for_address: &CanonicalAddr,
+) -> StdResult<()> {
+    StoredExtendedTx::append_tx(store, tx, for_address)
+}
+
+/// Adds a tx to the account's history. If the account has a pending balance in the delayed
+/// write buffer, the tx waits there too, so that the write does not point at the account and
+/// its history stays in order.
+fn append_or_buffer_tx(
+    store: &mut dyn Storage,
+    tx: &StoredExtendedTx,
+    for_address: &CanonicalAddr,
+) -> StdResult<()> {
+    let mut dwb = DelayedWriteBuffer::load(store)?;
+    if dwb.push_tx(store, for_address, tx)? {
+        return dwb.save(store);
+    }
+
+    append_settled_tx(store, tx, for_address)
+}
+
 fn store_tx_with_decoys(
     store: &mut dyn Storage,
     tx: &StoredExtendedTx,
@@ -437,20 +984,20 @@ fn store_tx_with_decoys(
 ) -> StdResult<()> {
     let mut index_changer: Option<usize> = None;
     match decoys {
-        None => StoredExtendedTx::append_tx(store, tx, for_address)?,
+        None => append_or_buffer_tx(store, tx, for_address)?,
         Some(user_decoys) => {
             // It should always be set when decoys_vec is set
             let account_pos = account_random_pos.unwrap();
 
             for i in 0..user_decoys.len() + 1 {
                 if i == account_pos {
-                    StoredExtendedTx::append_tx(store, tx, for_address)?;
+                    append_or_buffer_tx(store, tx, for_address)?;
                     index_changer = Some(1);
                     continue;
                 }
 
                 let index = i - index_changer.unwrap_or_default();
-                let decoy_action = StoredTxAction::decoy(&user_decoys[index]);
+                let decoy_action = StoredTxAction::decoy(&user_decoys[index], &tx.action);
                 let decoy_tx = StoredExtendedTx::new(
                     tx.id,
                     decoy_action,
@@ -458,47 +1005,7 @@ fn store_tx_with_decoys(
                     tx.memo.clone(),
                     block,
                 );
-                StoredExtendedTx::append_tx(store, &decoy_tx, &user_decoys[index])?;
-            }
-        }
-    }
-
-    Ok(())
-}
-
-fn store_transfer_tx_with_decoys(
-    store: &mut dyn Storage,
-    transfer: StoredLegacyTransfer,
-    receiver: &CanonicalAddr,
-    decoys: &Option<Vec<CanonicalAddr>>,
-    account_random_pos: &Option<usize>,
-) -> StdResult<()> {
-    let mut index_changer: Option<usize> = None;
-    match decoys {
-        None => StoredLegacyTransfer::append_transfer(store, &transfer, receiver)?,
-        Some(user_decoys) => {
-            // It should always be set when decoys_vec is set
-            let account_pos = account_random_pos.unwrap();
-
-            for i in 0..user_decoys.len() + 1 {
-                if i == account_pos {
-                    StoredLegacyTransfer::append_transfer(store, &transfer, receiver)?;
-                    index_changer = Some(1);
-                    continue;
-                }
-
-                let index = i - index_changer.unwrap_or_default();
-                let decoy_transfer = StoredLegacyTransfer {
-                    id: transfer.id,
-                    from: transfer.from.clone(),
-                    sender: transfer.sender.clone(),
-                    receiver: user_decoys[index].clone(),
-                    coins: transfer.coins.clone(),
-                    memo: transfer.memo.clone(),
-                    block_time: transfer.block_time,
-                    block_height: 0, // To identify the decoy
-                };
-                StoredLegacyTransfer::append_transfer(store, &decoy_transfer, &user_decoys[index])?;
+                append_or_buffer_tx(store, &decoy_tx, &user_decoys[index])?;
             }
         }
     }
@@ -521,35 +1028,23 @@ pub fn store_transfer(
 ) -> StdResult<()> {
     let id = increment_tx_count(store)?;
     let coins = Coin { denom, amount };
-    let transfer = StoredLegacyTransfer {
-        id,
-        from: owner.clone(),
-        sender: sender.clone(),
-        receiver: receiver.clone(),
-        coins,
-        memo,
-        block_time: block.time.seconds(),
-        block_height: block.height,
-    };
-    let tx = StoredExtendedTx::from_stored_legacy_transfer(transfer.clone());
+    let action = StoredTxAction::transfer(owner.clone(), sender.clone(), receiver.clone());
+    let tx = StoredExtendedTx::new(id, action, coins, memo, block);
 
     // Write to the owners history if it's different from the other two addresses
     if owner != sender && owner != receiver {
         // cosmwasm_std::debug_print("saving transaction history for owner");
-        StoredExtendedTx::append_tx(store, &tx, owner)?;
-        StoredLegacyTransfer::append_transfer(store, &transfer, owner)?;
+        append_or_buffer_tx(store, &tx, owner)?;
     }
     // Write to the sender's history if it's different from the receiver
     if sender != receiver {
         // cosmwasm_std::debug_print("saving transaction history for sender");
-        StoredExtendedTx::append_tx(store, &tx, sender)?;
-        StoredLegacyTransfer::append_transfer(store, &transfer, sender)?;
+        append_or_buffer_tx(store, &tx, sender)?;
     }
 
     // Always write to the recipient's history
     // cosmwasm_std::debug_print("saving transaction history for receiver");
     store_tx_with_decoys(store, &tx, receiver, block, decoys, account_random_pos)?;
-    store_transfer_tx_with_decoys(store, transfer, receiver, decoys, account_random_pos)?;
 
     Ok(())
 }
@@ -575,7 +1070,7 @@ pub fn store_mint(
         store_tx_with_decoys(store, &tx, &recipient, block, decoys, account_random_pos)?;
     }
 
-    StoredExtendedTx::append_tx(store, &tx, &minter)?;
+    append_or_buffer_tx(store, &tx, &minter)?;
 
     Ok(())
 }
@@ -601,7 +1096,7 @@ pub fn store_burn(
         store_tx_with_decoys(store, &tx, &owner, block, decoys, account_random_pos)?;
     }
 
-    StoredExtendedTx::append_tx(store, &tx, &burner)?;
+    append_or_buffer_tx(store, &tx, &burner)?;
     Ok(())
 }
 
@@ -638,3 +1133,156 @@ pub fn store_redeem(
 
     store_tx_with_decoys(store, &tx, redeemer, block, decoys, account_random_pos)
 }
+
+/// The most records one `PruneHistory` or `ClearHistory` removes. A longer history takes
+/// several messages, each picking up where the last one stopped.
+pub const MAX_PRUNED_PER_MSG: u32 = 256;
+
+/// Remove all but the last `keep_last` txs from the account's own history, along with the legacy
+/// transfers that are as old. Counterparties keep their copies. At most `MAX_PRUNED_PER_MSG`
+/// records are removed, oldest first. Returns the number removed and the number still to remove.
+///
+/// Txs still pending in the delayed write buffer are not touched, so the caller should settle
+/// the account first.
+pub fn prune_history(
+    store: &mut dyn Storage,
+    for_address: &CanonicalAddr,
+    keep_last: u32,
+) -> StdResult<(u32, u32)> {
+    let mut budget = MAX_PRUNED_PER_MSG;
+    // Legacy transfers left over from a previous message are still due
+    let previous = HISTORY_PRUNED_THROUGH.get(store, for_address);
+    let mut pruned_through = None;
+
+    let mut txs_due = 0;
+    let mut txs_removed = 0;
+    // The newest tx to go once the pruning is complete
+    let mut last_due = None;
+    {
+        let mut tx_store =
+            PrefixedStorage::multilevel(store, &[PREFIX_TXS, for_address.as_slice()]);
+        if let Some(tx_store) = AppendStoreMut::<StoredExtendedTx, _>::attach(&mut tx_store) {
+            let mut tx_store = tx_store?;
+            let len = tx_store.len();
+            txs_due = len.saturating_sub(keep_last);
+            txs_removed = txs_due.min(budget);
+            if txs_due > 0 {
+                last_due = Some(tx_store.get_at(txs_due - 1)?.id);
+            }
+            if txs_removed > 0 {
+                pruned_through = Some(tx_store.get_at(txs_removed - 1)?.id);
+                tx_store.retain_last(len - txs_removed)?;
+                budget -= txs_removed;
+            }
+        }
+    }
+
+    // Drop the legacy transfers that are as old as the txs removed
+    let mut legacy_due = 0;
+    let mut legacy_removed = 0;
+    {
+        let mut legacy_store =
+            PrefixedStorage::multilevel(store, &[PREFIX_TRANSFERS, for_address.as_slice()]);
+        if let Some(legacy_store) =
+            AppendStoreMut::<StoredLegacyTransfer, _>::attach(&mut legacy_store)
+        {
+            let mut legacy_store = legacy_store?;
+            let len = legacy_store.len();
+            let count_through = |id: Option<u64>| match id {
+                Some(id) => first_position_after(len, id, |pos| Ok(legacy_store.get_at(pos)?.id)),
+                None => Ok(0),
+            };
+            // Clearing the history drops every legacy transfer, once the txs are all gone
+            let all_txs_removed = txs_removed == txs_due;
+            legacy_due = match keep_last {
+                0 => len,
+                _ => count_through(last_due.max(previous))?,
+            };
+            let due_now = match (keep_last, all_txs_removed) {
+                (0, true) => len,
+                _ => count_through(pruned_through.max(previous))?,
+            };
+            legacy_removed = due_now.min(budget);
+            if legacy_removed > 0 {
+                let last_removed = legacy_store.get_at(legacy_removed - 1)?.id;
+                pruned_through = pruned_through.max(Some(last_removed));
+                legacy_store.retain_last(len - legacy_removed)?;
+            }
+        }
+    }
+
+    if let Some(pruned_through) = pruned_through {
+        remove_labels_through(store, for_address, pruned_through)?;
+
+        let pruned_through = pruned_through.max(previous.unwrap_or_default());
+        HISTORY_PRUNED_THROUGH.insert(store, for_address, &pruned_through)?;
+    }
+
+    let removed = txs_removed + legacy_removed;
+    let remaining = (txs_due - txs_removed) + (legacy_due - legacy_removed);
+    Ok((removed, remaining))
+}
+
+/// The id of the newest record the account has removed from its own history, if it has pruned it.
+pub fn history_pruned_through(storage: &dyn Storage, for_address: &CanonicalAddr) -> Option<u64> {
+    HISTORY_PRUNED_THROUGH.get(storage, for_address)
+}
+
+/// Whether the tx is in the account's history, settled or pending.
+fn has_tx(storage: &dyn Storage, for_address: &CanonicalAddr, id: u64) -> StdResult<bool> {
+    let store = ReadonlyPrefixedStorage::multilevel(storage, &[PREFIX_TXS, for_address.as_slice()]);
+    if let Some(store) = AppendStore::<StoredExtendedTx, _>::attach(&store) {
+        let store = store?;
+        let pos = first_position_after(store.len(), id, |pos| Ok(store.get_at(pos)?.id))?;
+        if pos > 0 && store.get_at(pos - 1)?.id == id {
+            return Ok(true);
+        }
+    }
+
+    Ok(DelayedWriteBuffer::load(storage)?
+        .pending_txs(storage, for_address)?
+        .iter()
+        .any(|node| node.tx.id == id))
+}
+
+/// Set the account's label for a tx in its history, or clear it if the label is empty.
+pub fn set_tx_label(
+    store: &mut dyn Storage,
+    for_address: &CanonicalAddr,
+    id: u64,
+    label: TxLabel,
+) -> StdResult<()> {
+    let labels = TX_LABELS.add_suffix(for_address.as_slice());
+    if label.is_empty() {
+        if labels.contains(store, &id) {
+            labels.remove(store, &id)?;
+        }
+        return Ok(());
+    }
+
+    label.validate()?;
+    if !has_tx(store, for_address, id)? {
+        return Err(StdError::generic_err(format!(
+            "Transaction {id} is not in your history"
+        )));
+    }
+
+    labels.insert(store, &id, &label)
+}
+
+fn remove_labels_through(
+    store: &mut dyn Storage,
+    for_address: &CanonicalAddr,
+    id: u64,
+) -> StdResult<()> {
+    let labels = TX_LABELS.add_suffix(for_address.as_slice());
+    let pruned: Vec<u64> = labels
+        .iter_keys(store)?
+        .filter(|key| key.as_ref().map_or(true, |key| *key <= id))
+        .collect::<StdResult<_>>()?;
+    for key in pruned {
+        labels.remove(store, &key)?;
+    }
+
+    Ok(())
+}